@@ -62,7 +62,10 @@ use ::std::{
         RefMut,
     },
     mem,
-    net::SocketAddrV4,
+    net::{
+        SocketAddr,
+        SocketAddrV4,
+    },
     os::unix::prelude::RawFd,
     pin::Pin,
     rc::Rc,
@@ -258,7 +261,7 @@ impl CatcollarLibOS {
                         .expect("New qd should have been already allocated");
                     queue.set_addr(addr);
                     queue.set_fd(new_fd);
-                    (qd, OperationResult::Accept((new_qd, addr)))
+                    (qd, OperationResult::Accept((new_qd, SocketAddr::V4(addr))))
                 },
                 Err(e) => {
                     qtable_.free(&new_qd);
@@ -472,7 +475,7 @@ impl CatcollarLibOS {
                         let result: Result<(Option<SocketAddrV4>, DemiBuffer), Fail> = future.await;
                         // Handle the result: if successful, return the addr and buffer.
                         match result {
-                            Ok((addr, buf)) => (qd, OperationResult::Pop(addr, buf)),
+                            Ok((addr, buf)) => (qd, OperationResult::Pop(addr.map(SocketAddr::V4), buf)),
                             Err(e) => (qd, OperationResult::Failed(e)),
                         }
                     });
@@ -540,7 +543,7 @@ fn pack_result(rt: &IoUringRuntime, result: OperationResult, qd: QDesc, qt: u64)
             qr_value: unsafe { mem::zeroed() },
         },
         OperationResult::Accept((new_qd, addr)) => {
-            let saddr: libc::sockaddr = linux::socketaddrv4_to_sockaddr(&addr);
+            let saddr: libc::sockaddr = linux::socketaddr_to_sockaddr(&addr);
             let qr_value: demi_qr_value_t = demi_qr_value_t {
                 ares: demi_accept_result_t {
                     qd: new_qd.into(),
@@ -565,7 +568,7 @@ fn pack_result(rt: &IoUringRuntime, result: OperationResult, qd: QDesc, qt: u64)
         OperationResult::Pop(addr, bytes) => match rt.into_sgarray(bytes) {
             Ok(mut sga) => {
                 if let Some(addr) = addr {
-                    sga.sga_addr = linux::socketaddrv4_to_sockaddr(&addr);
+                    sga.sga_addr = linux::socketaddr_to_sockaddr(&addr);
                 }
                 let qr_value: demi_qr_value_t = demi_qr_value_t { sga };
                 demi_qresult_t {
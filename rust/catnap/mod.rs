@@ -70,6 +70,7 @@ use ::std::{
     mem,
     net::{
         Ipv4Addr,
+        SocketAddr,
         SocketAddrV4,
     },
     os::unix::prelude::RawFd,
@@ -344,7 +345,7 @@ impl CatnapLibOS {
                         };
                         queue.set_socket(&listening_socket);
                     }
-                    (qd, OperationResult::Accept((new_qd, addr)))
+                    (qd, OperationResult::Accept((new_qd, SocketAddr::V4(addr))))
                 },
                 Err(e) => {
                     warn!("accept() listening_qd={:?} new_qd={:?}: {:?}", qd, new_qd, &e);
@@ -670,7 +671,7 @@ impl CatnapLibOS {
                             pop_coroutine(fd, size, yielder).await;
                         // Process result.
                         match result {
-                            Ok((addr, buf)) => (qd, OperationResult::Pop(addr, buf)),
+                            Ok((addr, buf)) => (qd, OperationResult::Pop(addr.map(SocketAddr::V4), buf)),
                             Err(e) => {
                                 warn!("pop() qd={:?}: {:?}", qd, &e);
                                 (qd, OperationResult::Failed(e))
@@ -773,7 +774,7 @@ fn pack_result(rt: &PosixRuntime, result: OperationResult, qd: QDesc, qt: u64) -
             qr_value: unsafe { mem::zeroed() },
         },
         OperationResult::Accept((new_qd, addr)) => {
-            let saddr: libc::sockaddr = linux::socketaddrv4_to_sockaddr(&addr);
+            let saddr: libc::sockaddr = linux::socketaddr_to_sockaddr(&addr);
             let qr_value: demi_qr_value_t = demi_qr_value_t {
                 ares: demi_accept_result_t {
                     qd: new_qd.into(),
@@ -798,7 +799,7 @@ fn pack_result(rt: &PosixRuntime, result: OperationResult, qd: QDesc, qt: u64) -
         OperationResult::Pop(addr, bytes) => match rt.into_sgarray(bytes) {
             Ok(mut sga) => {
                 if let Some(addr) = addr {
-                    sga.sga_addr = linux::socketaddrv4_to_sockaddr(&addr);
+                    sga.sga_addr = linux::socketaddr_to_sockaddr(&addr);
                 }
                 let qr_value: demi_qr_value_t = demi_qr_value_t { sga };
                 demi_qresult_t {
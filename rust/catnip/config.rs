@@ -7,6 +7,10 @@
 
 use crate::{
     demikernel::config::Config,
+    inetstack::protocols::ipv4::{
+        Route,
+        RoutingTable,
+    },
     runtime::network::types::MacAddress,
 };
 use ::anyhow::Error;
@@ -46,6 +50,42 @@ impl Config {
         arp_table
     }
 
+    /// Reads the "netmask", "default gateway" and "routes" parameters from the underlying configuration file.  Without
+    /// a netmask, every destination is on-link.
+    pub fn routing_table(&self) -> RoutingTable {
+        // FIXME: this function should return a Result.
+        let netmask: Ipv4Addr = match self.0["catnip"]["my_ipv4_netmask"].as_str() {
+            Some(netmask) => netmask.parse().unwrap(),
+            None => Ipv4Addr::UNSPECIFIED,
+        };
+        let mut routing_table: RoutingTable = RoutingTable::new(self.local_ipv4_addr(), netmask).unwrap();
+        if let Some(gateway) = self.0["catnip"]["default_gateway"].as_str() {
+            routing_table.set_default_gateway(gateway.parse().unwrap()).unwrap();
+        }
+        if let Some(routes_obj) = self.0["catnip"]["routes"].as_vec() {
+            for route_obj in routes_obj {
+                // Routes look like { destination: "10.1.0.0/16", gateway: "10.0.0.254" }.
+                let destination: &str = route_obj["destination"]
+                    .as_str()
+                    .ok_or_else(|| anyhow::format_err!("Couldn't find route destination in config"))
+                    .unwrap();
+                let (network, prefix_len): (&str, &str) = destination
+                    .split_once('/')
+                    .ok_or_else(|| anyhow::format_err!("Route destination is missing a prefix length"))
+                    .unwrap();
+                let gateway: Ipv4Addr = route_obj["gateway"]
+                    .as_str()
+                    .ok_or_else(|| anyhow::format_err!("Couldn't find route gateway in config"))
+                    .unwrap()
+                    .parse()
+                    .unwrap();
+                let route: Route = Route::new(network.parse().unwrap(), prefix_len.parse().unwrap(), gateway).unwrap();
+                routing_table.add_route(route).unwrap();
+            }
+        }
+        routing_table
+    }
+
     /// Reads the "DPDK EAL" parameter from the underlying configuration file.
     pub fn eal_init_args(&self) -> Vec<CString> {
         // FIXME: this function should return a Result.
@@ -28,9 +28,27 @@ use crate::{
 };
 use ::std::{
     mem,
+    net::SocketAddr,
     sync::Arc,
 };
 
+/// Converts the address of a peer into the one that results carry.  It only has room for IPv4 addresses, so IPv6 ones
+/// come out unspecified, and callers look them up with `demi_getpeername()` instead.
+fn socketaddr_to_sockaddr(addr: &SocketAddr) -> SockAddr {
+    match addr {
+        SocketAddr::V4(addr) => {
+            let saddr: SockAddrIn = SockAddrIn {
+                sin_family: AF_INET,
+                sin_port: addr.port().into(),
+                sin_addr: create_sin_addr(&addr.ip().octets()),
+                sin_zero: create_sin_zero(),
+            };
+            unsafe { mem::transmute::<SockAddrIn, SockAddr>(saddr) }
+        },
+        SocketAddr::V6(_) => unsafe { mem::zeroed() },
+    }
+}
+
 pub fn pack_result(rt: Arc<DPDKRuntime>, result: OperationResult, qd: QDesc, qt: u64) -> demi_qresult_t {
     match result {
         OperationResult::Connect => demi_qresult_t {
@@ -41,18 +59,10 @@ pub fn pack_result(rt: Arc<DPDKRuntime>, result: OperationResult, qd: QDesc, qt:
             qr_value: unsafe { mem::zeroed() },
         },
         OperationResult::Accept((new_qd, addr)) => {
-            let saddr: SockAddrIn = {
-                SockAddrIn {
-                    sin_family: AF_INET,
-                    sin_port: addr.port().into(),
-                    sin_addr: create_sin_addr(&addr.ip().octets()),
-                    sin_zero: create_sin_zero(),
-                }
-            };
             let qr_value: demi_qr_value_t = demi_qr_value_t {
                 ares: demi_accept_result_t {
                     qd: new_qd.into(),
-                    addr: unsafe { mem::transmute::<SockAddrIn, libc::sockaddr>(saddr) },
+                    addr: socketaddr_to_sockaddr(&addr),
                 },
             };
             demi_qresult_t {
@@ -73,15 +83,7 @@ pub fn pack_result(rt: Arc<DPDKRuntime>, result: OperationResult, qd: QDesc, qt:
         OperationResult::Pop(addr, bytes) => match rt.into_sgarray(bytes) {
             Ok(mut sga) => {
                 if let Some(endpoint) = addr {
-                    let saddr: SockAddrIn = {
-                        SockAddrIn {
-                            sin_family: AF_INET,
-                            sin_port: endpoint.port().into(),
-                            sin_addr: create_sin_addr(&endpoint.ip().octets()),
-                            sin_zero: create_sin_zero(),
-                        }
-                    };
-                    sga.sga_addr = unsafe { mem::transmute::<SockAddrIn, SockAddr>(saddr) };
+                    sga.sga_addr = socketaddr_to_sockaddr(&endpoint);
                 }
                 let qr_value = demi_qr_value_t { sga };
                 demi_qresult_t {
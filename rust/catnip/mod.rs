@@ -13,22 +13,20 @@ use self::{
     interop::pack_result,
     runtime::{
         DPDKRuntime,
+        WorkerRequest,
         memory::MemoryManager
     },
 };
 use crate::{
     demikernel::config::Config,
-    collections::ring::RingBuffer,
+    collections::worker_channel::WorkerEnd,
     inetstack::{
         InetStack,
         protocols::tcp::established::ControlBlock
     },
     runtime::{
         fail::Fail,
-        libdpdk::{
-            rte_mbuf,
-            load_mlx_driver,
-        },
+        libdpdk::load_mlx_driver,
         memory::MemoryRuntime,
         network::consts::RECEIVE_BATCH_SIZE,
         types::{
@@ -46,7 +44,7 @@ use crate::{
 };
 use std::{time::Instant, rc::Rc};
 use ::std::{
-    net::SocketAddrV4,
+    net::SocketAddr,
     ops::{
         Deref,
         DerefMut,
@@ -74,7 +72,8 @@ pub struct CatnipLibOS {
 
 /// Associate Functions for Catnip LibOS
 impl CatnipLibOS {
-    pub fn start(tx_queues: u16) -> Result<MemoryManager, Fail> {
+    /// Initializes DPDK and the port, with `rx_queues` RSS queues and `tx_queues` transmit queues.
+    pub fn start(rx_queues: u16, tx_queues: u16) -> Result<MemoryManager, Fail> {
         load_mlx_driver();
 
         // Read in configuration file.
@@ -104,13 +103,14 @@ impl CatnipLibOS {
             config.mtu(), 
             config.tcp_checksum_offload(),
             config.udp_checksum_offload(),
-            tx_queues
+            rx_queues,
+            tx_queues,
         ).unwrap();
 
         Ok(mm)
     }
 
-    pub fn new(config: &Config, queue_id: u16, mm: Arc<MemoryManager>, ring: *mut RingBuffer<(*mut rte_mbuf, *mut ControlBlock<RECEIVE_BATCH_SIZE>)>) -> Self {
+    pub fn new(config: &Config, queue_id: u16, mm: Arc<MemoryManager>, channel: WorkerEnd<WorkerRequest>) -> Self {
         load_mlx_driver();
         let rt: Arc<DPDKRuntime> = Arc::new(DPDKRuntime::new(
             config.local_ipv4_addr(),
@@ -122,7 +122,7 @@ impl CatnipLibOS {
             0u16, 
             queue_id, 
             mm,
-            ring,
+            Some(channel),
         ));
         let now: Instant = Instant::now();
         let clock: TimerRc = TimerRc(Rc::new(Timer::new(now)));
@@ -133,6 +133,8 @@ impl CatnipLibOS {
             clock,
             rt.link_addr,
             rt.ipv4_addr,
+            config.routing_table(),
+            config.local_ipv6_addr(),
             rt.tcp_options.clone(),
         )
         .unwrap();
@@ -155,8 +157,8 @@ impl CatnipLibOS {
         self.scheduler.clone()
     }
 
-    pub fn get_ring(&self) -> *mut RingBuffer<(*mut rte_mbuf, *mut ControlBlock<RECEIVE_BATCH_SIZE>)> {
-        self.rt.ring
+    pub fn get_channel(&self) -> Rc<WorkerEnd<WorkerRequest>> {
+        self.rt.channel.clone().expect("catnip should have a worker channel")
     }
 
     /// Create a push request for Demikernel to asynchronously write data from `sga` to the
@@ -187,7 +189,7 @@ impl CatnipLibOS {
         }
     }
 
-    pub fn pushto(&mut self, qd: QDesc, sga: &demi_sgarray_t, to: SocketAddrV4) -> Result<QToken, Fail> {
+    pub fn pushto(&mut self, qd: QDesc, sga: &demi_sgarray_t, to: SocketAddr) -> Result<QToken, Fail> {
         #[cfg(feature = "profiler")]
         timer!("catnip::pushto");
         trace!("pushto2(): qd={:?}", qd);
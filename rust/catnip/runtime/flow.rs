@@ -0,0 +1,556 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use crate::runtime::{
+    fail::Fail,
+    libdpdk::{
+        rte_flow,
+        rte_flow_action,
+        rte_flow_action_queue,
+        rte_flow_action_type_RTE_FLOW_ACTION_TYPE_END,
+        rte_flow_action_type_RTE_FLOW_ACTION_TYPE_QUEUE,
+        rte_flow_attr,
+        rte_flow_create,
+        rte_flow_error,
+        rte_flow_item,
+        rte_flow_item_type_RTE_FLOW_ITEM_TYPE_END,
+        rte_flow_item_type_RTE_FLOW_ITEM_TYPE_ETH,
+        rte_flow_item_type_RTE_FLOW_ITEM_TYPE_IPV4,
+        rte_flow_item_type_RTE_FLOW_ITEM_TYPE_TCP,
+        rte_flow_item_type_RTE_FLOW_ITEM_TYPE_UDP,
+        rte_flow_validate,
+        rte_tcp_hdr,
+    },
+};
+use ::std::{
+    collections::BTreeMap,
+    ffi::{
+        c_void,
+        CStr,
+    },
+    mem,
+    net::{
+        Ipv4Addr,
+        SocketAddrV4,
+    },
+    ptr::NonNull,
+};
+
+//==============================================================================
+// Foreign Functions
+//==============================================================================
+
+// dpdk-rs does not generate bindings for these, but they live in librte_ethdev, which it already links.
+extern "C" {
+    fn rte_flow_destroy(port_id: u16, flow: *mut rte_flow, error: *mut rte_flow_error) -> libc::c_int;
+    fn rte_flow_flush(port_id: u16, error: *mut rte_flow_error) -> libc::c_int;
+}
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Transport protocol matched by a flow rule.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlowProtocol {
+    Tcp,
+    Udp,
+}
+
+/// Packet pattern matched by a flow rule. Fields left as `None` are wildcards.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FlowPattern {
+    pub protocol: FlowProtocol,
+    pub src_addr: Option<Ipv4Addr>,
+    pub dst_addr: Option<Ipv4Addr>,
+    pub src_port: Option<u16>,
+    pub dst_port: Option<u16>,
+}
+
+/// A flow rule: packets matching `pattern` are delivered to receive queue `queue_id`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FlowRule {
+    pub pattern: FlowPattern,
+    pub queue_id: u16,
+}
+
+/// Identifier of an installed flow rule.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FlowId(u64);
+
+/// Flow Steering
+///
+/// Owns the rte_flow rules installed on a port. Rules are torn down when this is dropped.
+pub struct FlowSteering {
+    port_id: u16,
+    nr_queues: u16,
+    next_id: u64,
+    rules: BTreeMap<FlowId, (FlowRule, NonNull<rte_flow>)>,
+}
+
+/// IPv4 header as laid out by DPDK's `struct rte_ipv4_hdr`, which dpdk-rs does not export.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Default)]
+struct FlowIpv4Hdr {
+    version_ihl: u8,
+    type_of_service: u8,
+    total_length: u16,
+    packet_id: u16,
+    fragment_offset: u16,
+    time_to_live: u8,
+    next_proto_id: u8,
+    hdr_checksum: u16,
+    src_addr: u32,
+    dst_addr: u32,
+}
+
+/// UDP header as laid out by DPDK's `struct rte_udp_hdr`, which dpdk-rs does not export.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Default)]
+struct FlowUdpHdr {
+    src_port: u16,
+    dst_port: u16,
+    dgram_len: u16,
+    dgram_cksum: u16,
+}
+
+/// Spec and mask storage referenced by the rte_flow items of a rule. Must outlive the rte_flow calls.
+struct FlowItems {
+    ipv4: [FlowIpv4Hdr; 2],
+    tcp: [rte_tcp_hdr; 2],
+    udp: [FlowUdpHdr; 2],
+    queue: rte_flow_action_queue,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Flow Patterns
+impl FlowPattern {
+    /// Matches a single connection, identified by its remote and local endpoints.
+    pub fn five_tuple(protocol: FlowProtocol, remote: SocketAddrV4, local: SocketAddrV4) -> Self {
+        Self {
+            protocol,
+            src_addr: Some(*remote.ip()),
+            dst_addr: Some(*local.ip()),
+            src_port: Some(remote.port()),
+            dst_port: Some(local.port()),
+        }
+    }
+
+    /// Matches all packets sent to a local port.
+    pub fn dst_port(protocol: FlowProtocol, port: u16) -> Self {
+        Self {
+            protocol,
+            src_addr: None,
+            dst_addr: None,
+            src_port: None,
+            dst_port: Some(port),
+        }
+    }
+
+    /// Matches all packets sent from a remote port.
+    pub fn src_port(protocol: FlowProtocol, port: u16) -> Self {
+        Self {
+            protocol,
+            src_addr: None,
+            dst_addr: None,
+            src_port: Some(port),
+            dst_port: None,
+        }
+    }
+}
+
+/// Associate Functions for Flow Steering
+impl FlowSteering {
+    /// Creates a flow steering context for a port configured with `nr_queues` receive queues.
+    pub fn new(port_id: u16, nr_queues: u16) -> Self {
+        Self {
+            port_id,
+            nr_queues,
+            next_id: 0,
+            rules: BTreeMap::new(),
+        }
+    }
+
+    /// Checks whether the NIC would accept a rule, without installing it.
+    pub fn validate(&self, rule: &FlowRule) -> Result<(), Fail> {
+        self.check_queue(rule)?;
+        let mut items: FlowItems = FlowItems::new(rule);
+        let (attr, pattern, actions) = items.build(rule);
+        let mut error: rte_flow_error = unsafe { mem::zeroed() };
+        let ret: libc::c_int =
+            unsafe { rte_flow_validate(self.port_id, &attr, pattern.as_ptr(), actions.as_ptr(), &mut error) };
+        if ret != 0 {
+            return Err(flow_error("rte_flow_validate", -ret, &error));
+        }
+        Ok(())
+    }
+
+    /// Validates and installs a rule, returning an identifier that can be used to remove it.
+    pub fn install(&mut self, rule: FlowRule) -> Result<FlowId, Fail> {
+        self.validate(&rule)?;
+        let mut items: FlowItems = FlowItems::new(&rule);
+        let (attr, pattern, actions) = items.build(&rule);
+        let mut error: rte_flow_error = unsafe { mem::zeroed() };
+        let flow: *mut rte_flow =
+            unsafe { rte_flow_create(self.port_id, &attr, pattern.as_ptr(), actions.as_ptr(), &mut error) };
+        let flow: NonNull<rte_flow> = match NonNull::new(flow) {
+            Some(flow) => flow,
+            None => {
+                let errno: libc::c_int = unsafe { dpdk_rs::rte_errno() };
+                return Err(flow_error("rte_flow_create", errno, &error));
+            },
+        };
+        let id: FlowId = FlowId(self.next_id);
+        self.next_id += 1;
+        self.rules.insert(id, (rule, flow));
+        Ok(id)
+    }
+
+    /// Installs a rule if the NIC accepts it. Otherwise, matching packets keep being spread by RSS and `None` is
+    /// returned.
+    pub fn steer(&mut self, rule: FlowRule) -> Option<FlowId> {
+        match self.install(rule) {
+            Ok(id) => Some(id),
+            Err(e) => {
+                warn!("flow rule {:?} rejected, falling back to RSS: {:?}", rule, e);
+                None
+            },
+        }
+    }
+
+    /// Lists installed rules.
+    pub fn list(&self) -> Vec<(FlowId, FlowRule)> {
+        self.rules.iter().map(|(id, (rule, _))| (*id, *rule)).collect()
+    }
+
+    /// Removes an installed rule.
+    pub fn remove(&mut self, id: FlowId) -> Result<FlowRule, Fail> {
+        let (rule, flow): (FlowRule, NonNull<rte_flow>) = match self.rules.remove(&id) {
+            Some(entry) => entry,
+            None => return Err(Fail::new(libc::ENOENT, "flow rule not found")),
+        };
+        let mut error: rte_flow_error = unsafe { mem::zeroed() };
+        let ret: libc::c_int = unsafe { rte_flow_destroy(self.port_id, flow.as_ptr(), &mut error) };
+        if ret != 0 {
+            // Keep track of the rule, so that a later flush still tears it down.
+            self.rules.insert(id, (rule, flow));
+            return Err(flow_error("rte_flow_destroy", -ret, &error));
+        }
+        Ok(rule)
+    }
+
+    /// Removes all rules from the port, including any that were not installed through this context.
+    pub fn flush(&mut self) -> Result<(), Fail> {
+        let mut error: rte_flow_error = unsafe { mem::zeroed() };
+        let ret: libc::c_int = unsafe { rte_flow_flush(self.port_id, &mut error) };
+        if ret != 0 {
+            return Err(flow_error("rte_flow_flush", -ret, &error));
+        }
+        self.rules.clear();
+        Ok(())
+    }
+
+    /// Fails if a rule targets a queue that the port does not have.
+    fn check_queue(&self, rule: &FlowRule) -> Result<(), Fail> {
+        if rule.queue_id >= self.nr_queues {
+            let cause: String = format!("invalid queue (queue_id={:?}, nr_queues={:?})", rule.queue_id, self.nr_queues);
+            return Err(Fail::new(libc::EINVAL, &cause));
+        }
+        Ok(())
+    }
+}
+
+/// Associate Functions for Flow Items
+impl FlowItems {
+    /// Fills in the spec and mask of each header matched by a rule, in network byte order.
+    fn new(rule: &FlowRule) -> Self {
+        let pattern: &FlowPattern = &rule.pattern;
+        let mut items: Self = Self {
+            ipv4: [FlowIpv4Hdr::default(); 2],
+            tcp: unsafe { mem::zeroed() },
+            udp: [FlowUdpHdr::default(); 2],
+            queue: rte_flow_action_queue { index: rule.queue_id },
+        };
+        if let Some(addr) = pattern.src_addr {
+            items.ipv4[0].src_addr = u32::from(addr).to_be();
+            items.ipv4[1].src_addr = u32::MAX;
+        }
+        if let Some(addr) = pattern.dst_addr {
+            items.ipv4[0].dst_addr = u32::from(addr).to_be();
+            items.ipv4[1].dst_addr = u32::MAX;
+        }
+        match pattern.protocol {
+            FlowProtocol::Tcp => {
+                if let Some(port) = pattern.src_port {
+                    items.tcp[0].src_port = port.to_be();
+                    items.tcp[1].src_port = u16::MAX;
+                }
+                if let Some(port) = pattern.dst_port {
+                    items.tcp[0].dst_port = port.to_be();
+                    items.tcp[1].dst_port = u16::MAX;
+                }
+            },
+            FlowProtocol::Udp => {
+                if let Some(port) = pattern.src_port {
+                    items.udp[0].src_port = port.to_be();
+                    items.udp[1].src_port = u16::MAX;
+                }
+                if let Some(port) = pattern.dst_port {
+                    items.udp[0].dst_port = port.to_be();
+                    items.udp[1].dst_port = u16::MAX;
+                }
+            },
+        }
+        items
+    }
+
+    /// Builds the ETH / IPV4 / TCP|UDP pattern and the QUEUE action of a rule.
+    fn build(&mut self, rule: &FlowRule) -> (rte_flow_attr, [rte_flow_item; 4], [rte_flow_action; 2]) {
+        let mut attr: rte_flow_attr = unsafe { mem::zeroed() };
+        attr.set_ingress(1);
+        attr.set_egress(0);
+
+        let mut pattern: [rte_flow_item; 4] = unsafe { mem::zeroed() };
+        pattern[0].type_ = rte_flow_item_type_RTE_FLOW_ITEM_TYPE_ETH;
+        pattern[1].type_ = rte_flow_item_type_RTE_FLOW_ITEM_TYPE_IPV4;
+        pattern[1].spec = &self.ipv4[0] as *const _ as *const c_void;
+        pattern[1].mask = &self.ipv4[1] as *const _ as *const c_void;
+        match rule.pattern.protocol {
+            FlowProtocol::Tcp => {
+                pattern[2].type_ = rte_flow_item_type_RTE_FLOW_ITEM_TYPE_TCP;
+                pattern[2].spec = &self.tcp[0] as *const _ as *const c_void;
+                pattern[2].mask = &self.tcp[1] as *const _ as *const c_void;
+            },
+            FlowProtocol::Udp => {
+                pattern[2].type_ = rte_flow_item_type_RTE_FLOW_ITEM_TYPE_UDP;
+                pattern[2].spec = &self.udp[0] as *const _ as *const c_void;
+                pattern[2].mask = &self.udp[1] as *const _ as *const c_void;
+            },
+        }
+        pattern[3].type_ = rte_flow_item_type_RTE_FLOW_ITEM_TYPE_END;
+
+        let mut actions: [rte_flow_action; 2] = unsafe { mem::zeroed() };
+        actions[0].type_ = rte_flow_action_type_RTE_FLOW_ACTION_TYPE_QUEUE;
+        actions[0].conf = &self.queue as *const _ as *const c_void;
+        actions[1].type_ = rte_flow_action_type_RTE_FLOW_ACTION_TYPE_END;
+
+        (attr, pattern, actions)
+    }
+}
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Converts an rte_flow error into a failure, keeping the reason reported by the driver.
+fn flow_error(name: &str, errno: libc::c_int, error: &rte_flow_error) -> Fail {
+    let message: String = if error.message.is_null() {
+        "unspecified".to_string()
+    } else {
+        unsafe { CStr::from_ptr(error.message) }.to_string_lossy().into_owned()
+    };
+    let cause: String = format!("{} failed (type={:?}): {}", name, error.type_, message);
+    Fail::new(errno, &cause)
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+/// Drop Trait Implementation for Flow Steering
+impl Drop for FlowSteering {
+    fn drop(&mut self) {
+        let ids: Vec<FlowId> = self.rules.keys().copied().collect();
+        for id in ids {
+            if let Err(e) = self.remove(id) {
+                warn!("failed to remove flow rule {:?}: {:?}", id, e);
+            }
+        }
+        if !self.rules.is_empty() {
+            if let Err(e) = self.flush() {
+                warn!("failed to flush flow rules on port {:?}: {:?}", self.port_id, e);
+            }
+        }
+    }
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        FlowId,
+        FlowItems,
+        FlowPattern,
+        FlowProtocol,
+        FlowRule,
+        FlowSteering,
+    };
+    use crate::runtime::libdpdk::{
+        rte_flow_action_type_RTE_FLOW_ACTION_TYPE_END,
+        rte_flow_action_type_RTE_FLOW_ACTION_TYPE_QUEUE,
+        rte_flow_item_type_RTE_FLOW_ITEM_TYPE_END,
+        rte_flow_item_type_RTE_FLOW_ITEM_TYPE_ETH,
+        rte_flow_item_type_RTE_FLOW_ITEM_TYPE_IPV4,
+        rte_flow_item_type_RTE_FLOW_ITEM_TYPE_TCP,
+        rte_flow_item_type_RTE_FLOW_ITEM_TYPE_UDP,
+    };
+    use ::anyhow::Result;
+    use ::std::{
+        ffi::c_void,
+        net::{
+            Ipv4Addr,
+            SocketAddrV4,
+        },
+    };
+
+    const LOCAL: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(192, 168, 1, 1), 80);
+    const REMOTE: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(192, 168, 1, 2), 49152);
+
+    /// Tests if patterns leave the fields they do not match as wildcards.
+    #[test]
+    fn test_flow_pattern() -> Result<()> {
+        let pattern: FlowPattern = FlowPattern::five_tuple(FlowProtocol::Tcp, REMOTE, LOCAL);
+        crate::ensure_eq!(pattern.src_addr, Some(*REMOTE.ip()));
+        crate::ensure_eq!(pattern.dst_addr, Some(*LOCAL.ip()));
+        crate::ensure_eq!(pattern.src_port, Some(REMOTE.port()));
+        crate::ensure_eq!(pattern.dst_port, Some(LOCAL.port()));
+
+        let pattern: FlowPattern = FlowPattern::dst_port(FlowProtocol::Udp, 53);
+        crate::ensure_eq!(pattern.protocol, FlowProtocol::Udp);
+        crate::ensure_eq!((pattern.src_addr, pattern.dst_addr), (None, None));
+        crate::ensure_eq!((pattern.src_port, pattern.dst_port), (None, Some(53)));
+
+        let pattern: FlowPattern = FlowPattern::src_port(FlowProtocol::Tcp, 443);
+        crate::ensure_eq!((pattern.src_addr, pattern.dst_addr), (None, None));
+        crate::ensure_eq!((pattern.src_port, pattern.dst_port), (Some(443), None));
+
+        Ok(())
+    }
+
+    /// Tests if rules that target a missing queue are refused before reaching the NIC.
+    #[test]
+    fn test_flow_bad_queue() -> Result<()> {
+        let mut steering: FlowSteering = FlowSteering::new(0, 4);
+        let rule: FlowRule = FlowRule {
+            pattern: FlowPattern::dst_port(FlowProtocol::Tcp, 80),
+            queue_id: 4,
+        };
+        match steering.validate(&rule) {
+            Err(e) => crate::ensure_eq!(e.errno, libc::EINVAL),
+            Ok(()) => anyhow::bail!("validate should fail on a missing queue"),
+        }
+        match steering.install(rule) {
+            Err(e) => crate::ensure_eq!(e.errno, libc::EINVAL),
+            Ok(_) => anyhow::bail!("install should fail on a missing queue"),
+        }
+        crate::ensure_eq!(steering.steer(rule), None);
+        crate::ensure_eq!(steering.list().is_empty(), true);
+
+        Ok(())
+    }
+
+    /// Tests if removing a rule that was never installed fails.
+    #[test]
+    fn test_flow_remove_unknown() -> Result<()> {
+        let mut steering: FlowSteering = FlowSteering::new(0, 4);
+        match steering.remove(FlowId(0)) {
+            Err(e) => crate::ensure_eq!(e.errno, libc::ENOENT),
+            Ok(_) => anyhow::bail!("remove should fail on an unknown rule"),
+        }
+
+        Ok(())
+    }
+
+    /// Tests if the spec and mask of a TCP rule are in network byte order, and only mask matched fields.
+    #[test]
+    fn test_flow_items_tcp() -> Result<()> {
+        let rule: FlowRule = FlowRule {
+            pattern: FlowPattern::five_tuple(FlowProtocol::Tcp, REMOTE, LOCAL),
+            queue_id: 1,
+        };
+        let items: FlowItems = FlowItems::new(&rule);
+
+        // Fields of packed headers are copied out before being compared.
+        let (src_addr, dst_addr): (u32, u32) = (items.ipv4[0].src_addr, items.ipv4[0].dst_addr);
+        crate::ensure_eq!(src_addr.to_ne_bytes(), [192, 168, 1, 2]);
+        crate::ensure_eq!(dst_addr.to_ne_bytes(), [192, 168, 1, 1]);
+        let (src_mask, dst_mask): (u32, u32) = (items.ipv4[1].src_addr, items.ipv4[1].dst_addr);
+        crate::ensure_eq!((src_mask, dst_mask), (u32::MAX, u32::MAX));
+
+        let (src_port, dst_port): (u16, u16) = (items.tcp[0].src_port, items.tcp[0].dst_port);
+        crate::ensure_eq!(src_port.to_ne_bytes(), 49152u16.to_be_bytes());
+        crate::ensure_eq!(dst_port.to_ne_bytes(), 80u16.to_be_bytes());
+        let (src_mask, dst_mask): (u16, u16) = (items.tcp[1].src_port, items.tcp[1].dst_port);
+        crate::ensure_eq!((src_mask, dst_mask), (u16::MAX, u16::MAX));
+
+        // UDP headers are left alone.
+        let (src_mask, dst_mask): (u16, u16) = (items.udp[1].src_port, items.udp[1].dst_port);
+        crate::ensure_eq!((src_mask, dst_mask), (0, 0));
+        crate::ensure_eq!(items.queue.index, 1);
+
+        Ok(())
+    }
+
+    /// Tests if a UDP rule on a local port leaves addresses and the remote port unmasked.
+    #[test]
+    fn test_flow_items_udp() -> Result<()> {
+        let rule: FlowRule = FlowRule {
+            pattern: FlowPattern::dst_port(FlowProtocol::Udp, 53),
+            queue_id: 2,
+        };
+        let items: FlowItems = FlowItems::new(&rule);
+
+        let (src_mask, dst_mask): (u32, u32) = (items.ipv4[1].src_addr, items.ipv4[1].dst_addr);
+        crate::ensure_eq!((src_mask, dst_mask), (0, 0));
+        let (src_port, dst_port): (u16, u16) = (items.udp[0].src_port, items.udp[0].dst_port);
+        crate::ensure_eq!(src_port, 0);
+        crate::ensure_eq!(dst_port.to_ne_bytes(), 53u16.to_be_bytes());
+        let (src_mask, dst_mask): (u16, u16) = (items.udp[1].src_port, items.udp[1].dst_port);
+        crate::ensure_eq!((src_mask, dst_mask), (0, u16::MAX));
+        let (src_mask, dst_mask): (u16, u16) = (items.tcp[1].src_port, items.tcp[1].dst_port);
+        crate::ensure_eq!((src_mask, dst_mask), (0, 0));
+
+        Ok(())
+    }
+
+    /// Tests if rules are built as an ingress ETH / IPV4 / TCP|UDP pattern that steers to a single queue.
+    #[test]
+    fn test_flow_items_build() -> Result<()> {
+        for (protocol, item_type) in [
+            (FlowProtocol::Tcp, rte_flow_item_type_RTE_FLOW_ITEM_TYPE_TCP),
+            (FlowProtocol::Udp, rte_flow_item_type_RTE_FLOW_ITEM_TYPE_UDP),
+        ] {
+            let rule: FlowRule = FlowRule {
+                pattern: FlowPattern::dst_port(protocol, 80),
+                queue_id: 3,
+            };
+            let mut items: FlowItems = FlowItems::new(&rule);
+            let (attr, pattern, actions) = items.build(&rule);
+            crate::ensure_eq!((attr.ingress(), attr.egress()), (1, 0));
+
+            crate::ensure_eq!(pattern[0].type_, rte_flow_item_type_RTE_FLOW_ITEM_TYPE_ETH);
+            crate::ensure_eq!(pattern[0].spec.is_null(), true);
+            crate::ensure_eq!(pattern[1].type_, rte_flow_item_type_RTE_FLOW_ITEM_TYPE_IPV4);
+            crate::ensure_eq!(pattern[1].spec, &items.ipv4[0] as *const _ as *const c_void);
+            crate::ensure_eq!(pattern[1].mask, &items.ipv4[1] as *const _ as *const c_void);
+            crate::ensure_eq!(pattern[2].type_, item_type);
+            crate::ensure_eq!(pattern[3].type_, rte_flow_item_type_RTE_FLOW_ITEM_TYPE_END);
+
+            crate::ensure_eq!(actions[0].type_, rte_flow_action_type_RTE_FLOW_ACTION_TYPE_QUEUE);
+            crate::ensure_eq!(actions[0].conf, &items.queue as *const _ as *const c_void);
+            crate::ensure_eq!(actions[1].type_, rte_flow_action_type_RTE_FLOW_ACTION_TYPE_END);
+        }
+
+        Ok(())
+    }
+}
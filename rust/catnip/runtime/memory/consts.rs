@@ -28,3 +28,6 @@ pub const DEFAULT_MAX_BODY_SIZE: usize = (RTE_MBUF_DEFAULT_BUF_SIZE + RTE_PKTMBU
 
 /// Default per-thread cache size.
 pub const DEFAULT_CACHE_SIZE: usize = 250;
+
+/// Size of the private area of body buffers, which applications may use to carry metadata along with a packet.
+pub const MBUF_PRIV_SIZE: usize = 64;
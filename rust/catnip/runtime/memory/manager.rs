@@ -5,7 +5,10 @@
 // Imports
 //==============================================================================
 
-use super::mempool::MemoryPool;
+use super::{
+    consts::MBUF_PRIV_SIZE,
+    mempool::MemoryPool,
+};
 use crate::{
     inetstack::protocols::{
         ethernet2::ETHERNET2_HEADER_SIZE,
@@ -249,6 +252,7 @@ impl Inner {
         let header_pool: MemoryPool = MemoryPool::new(
             CString::new("header_pool")?,
             header_mbuf_size,
+            0,
             config.get_header_pool_size(),
             config.get_cache_size(),
         )?;
@@ -257,6 +261,7 @@ impl Inner {
         let body_pool: MemoryPool = MemoryPool::new(
             CString::new("body_pool")?,
             config.get_max_body_size(),
+            MBUF_PRIV_SIZE,
             config.get_body_pool_size(),
             config.get_cache_size(),
         )?;
@@ -35,14 +35,20 @@ pub struct MemoryPool {
 
 /// Associated functions for memory pool.
 impl MemoryPool {
-    /// Creates a new memory pool.
-    pub fn new(name: CString, data_room_size: usize, pool_size: usize, cache_size: usize) -> Result<Self, Fail> {
+    /// Creates a new memory pool, whose buffers have a private area of `priv_size` bytes.
+    pub fn new(
+        name: CString,
+        data_room_size: usize,
+        priv_size: usize,
+        pool_size: usize,
+        cache_size: usize,
+    ) -> Result<Self, Fail> {
         let pool: *mut rte_mempool = unsafe {
             rte_pktmbuf_pool_create(
                 name.as_ptr(),
                 pool_size as u32,
                 cache_size as u32,
-                0,
+                priv_size as u16,
                 data_room_size as u16,
                 rte_socket_id() as i32,
             )
@@ -1,17 +1,24 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT license.
 
+pub mod flow;
 pub mod memory;
 mod network;
+mod tx;
 
 //==============================================================================
 // Imports
 //==============================================================================
 
-use self::memory::{
-    consts::DEFAULT_MAX_BODY_SIZE,
-    MemoryManager,
+use self::{
+    memory::{
+        consts::DEFAULT_MAX_BODY_SIZE,
+        MemoryManager,
+    },
+    tx::TxBatch,
 };
+
+pub use self::tx::TxStats;
 use crate::{
     runtime::{
         libdpdk::{
@@ -28,6 +35,7 @@ use crate::{
             rte_eth_find_next_owned_by,
             rte_eth_promiscuous_enable,
             rte_eth_rx_mq_mode_RTE_ETH_MQ_RX_NONE as RTE_ETH_MQ_RX_NONE,
+            rte_eth_rx_mq_mode_RTE_ETH_MQ_RX_RSS as RTE_ETH_MQ_RX_RSS,
             rte_eth_tx_mq_mode_RTE_ETH_MQ_TX_NONE as RTE_ETH_MQ_TX_NONE,
             rte_eth_rx_queue_setup,
             rte_eth_rxconf,
@@ -58,7 +66,7 @@ use crate::{
         },
         Runtime,
     }, 
-    collections::ring::RingBuffer, 
+    collections::worker_channel::WorkerEnd,
     inetstack::protocols::tcp::established::ControlBlock,
 };
 use ::anyhow::{
@@ -70,9 +78,11 @@ use dpdk_rs::rte_mbuf;
 use ::std::{
     sync::Arc,
     collections::HashMap,
+    cell::RefCell,
     ffi::CString,
     mem::MaybeUninit,
     net::Ipv4Addr,
+    rc::Rc,
     time::Duration,
 };
 
@@ -95,13 +105,19 @@ macro_rules! expect_zero {
 // Structures
 //==============================================================================
 
+/// A packet handed from a dispatcher to the worker that owns its connection.
+pub type WorkerRequest = (*mut rte_mbuf, *mut ControlBlock<RECEIVE_BATCH_SIZE>);
+
 /// DPDK Runtime
 #[derive(Clone)]
 pub struct DPDKRuntime {
     mm: Arc<MemoryManager>,
-    pub ring: *mut RingBuffer<(*mut rte_mbuf, *mut ControlBlock<RECEIVE_BATCH_SIZE>)>,
+    /// Channel through which the dispatcher hands packets over, if this runtime belongs to a worker.
+    pub channel: Option<Rc<WorkerEnd<WorkerRequest>>>,
     port_id: u16,
     queue_id: u16,
+    /// Packets waiting to be sent on the TX queue of this runtime.
+    tx: Rc<RefCell<TxBatch>>,
     pub link_addr: MacAddress,
     pub ipv4_addr: Ipv4Addr,
     pub arp_options: ArpConfig,
@@ -153,10 +169,9 @@ impl DPDKRuntime {
         mtu: u16,
         tcp_checksum_offload: bool,
         udp_checksum_offload: bool,
-        nr_queues: u16,
+        rx_queues: u16,
+        tx_queues: u16,
     ) -> Result<(), Error> {
-        let rx_queues = 1;
-        let tx_queues = nr_queues;
         let rx_ring_size: u16 = 4096;
         let tx_ring_size: u16 = 4096;
 
@@ -169,7 +184,12 @@ impl DPDKRuntime {
         // println!("dev_info: {:?}", dev_info);
         let mut port_conf: rte_eth_conf = unsafe { MaybeUninit::zeroed().assume_init() };
 
-        port_conf.rxmode.mq_mode = RTE_ETH_MQ_RX_NONE;
+        // Spread flows across receive queues with RSS.
+        port_conf.rxmode.mq_mode = if rx_queues > 1 {
+            RTE_ETH_MQ_RX_RSS
+        } else {
+            RTE_ETH_MQ_RX_NONE
+        };
         port_conf.txmode.mq_mode = RTE_ETH_MQ_TX_NONE;
 
         port_conf.rxmode.max_lro_pkt_size = if use_jumbo_frames {
@@ -253,7 +273,7 @@ impl DPDKRuntime {
         port_id: u16,
         queue_id: u16,
         mm: Arc<MemoryManager>,
-        ring: *mut RingBuffer<(*mut rte_mbuf, *mut ControlBlock<RECEIVE_BATCH_SIZE>)>,
+        channel: Option<WorkerEnd<WorkerRequest>>,
     ) -> DPDKRuntime {
         let arp_options = ArpConfig::new(
             Some(Duration::from_secs(15)),
@@ -261,6 +281,7 @@ impl DPDKRuntime {
             Some(5),
             Some(arp_table),
             Some(disable_arp),
+            None,
         );
 
         let tcp_options = TcpConfig::new(
@@ -285,9 +306,10 @@ impl DPDKRuntime {
 
         Self {
             mm,
-            ring,
+            channel: channel.map(Rc::new),
             port_id,
             queue_id,
+            tx: Rc::new(RefCell::new(TxBatch::new(port_id, queue_id))),
             link_addr,
             ipv4_addr,
             arp_options,
@@ -295,6 +317,11 @@ impl DPDKRuntime {
             udp_options,
         }
     }
+
+    /// Returns the counters of the TX queue of this runtime.
+    pub fn tx_stats(&self) -> TxStats {
+        self.tx.borrow().stats()
+    }
 }
 
 //==============================================================================
@@ -10,7 +10,6 @@ use crate::{
     inetstack::protocols::{ethernet2::MIN_PAYLOAD_SIZE, tcp::established::ControlBlock},
     runtime::{
         libdpdk::{
-            rte_eth_tx_burst,
             rte_mbuf,
             rte_pktmbuf_chain,
         },
@@ -86,14 +85,13 @@ impl<const N: usize> NetworkRuntime<N> for DPDKRuntime {
                     mbuf.into_mbuf().expect("mbuf should not be empty")
                 };
 
-                let mut header_mbuf_ptr: *mut rte_mbuf = header_mbuf.into_mbuf().expect("mbuf should not be empty");
+                let header_mbuf_ptr: *mut rte_mbuf = header_mbuf.into_mbuf().expect("mbuf should not be empty");
                 // Safety: rte_pktmbuf_chain is a FFI that is safe to call as both of its args are valid MBuf pointers.
                 unsafe {
                     // Attach the body MBuf onto the header MBuf's buffer chain.
                     assert_eq!(rte_pktmbuf_chain(header_mbuf_ptr, body_mbuf), 0);
                 }
-                let num_sent = unsafe { rte_eth_tx_burst(self.port_id, 0, &mut header_mbuf_ptr, 1) };
-                assert_eq!(num_sent, 1);
+                self.tx.borrow_mut().push(header_mbuf_ptr);
             }
             // Otherwise, write in the inline space.
             else {
@@ -111,9 +109,8 @@ impl<const N: usize> NetworkRuntime<N> for DPDKRuntime {
                 let frame_size = std::cmp::max(header_size + body.len(), MIN_PAYLOAD_SIZE);
                 header_mbuf.trim(header_mbuf.len() - frame_size).unwrap();
 
-                let mut header_mbuf_ptr: *mut rte_mbuf = header_mbuf.into_mbuf().expect("mbuf cannot be empty");
-                let num_sent = unsafe { rte_eth_tx_burst(self.port_id, 0, &mut header_mbuf_ptr, 1) };
-                assert_eq!(num_sent, 1);
+                let header_mbuf_ptr: *mut rte_mbuf = header_mbuf.into_mbuf().expect("mbuf cannot be empty");
+                self.tx.borrow_mut().push(header_mbuf_ptr);
             }
         }
         // No body on our packet, just send the headers.
@@ -127,22 +124,27 @@ impl<const N: usize> NetworkRuntime<N> for DPDKRuntime {
             }
             let frame_size = std::cmp::max(header_size, MIN_PAYLOAD_SIZE);
             header_mbuf.trim(header_mbuf.len() - frame_size).unwrap();
-            let mut header_mbuf_ptr: *mut rte_mbuf = header_mbuf.into_mbuf().expect("mbuf cannot be empty");
-            let num_sent = unsafe { rte_eth_tx_burst(self.port_id, 0, &mut header_mbuf_ptr, 1) };
-            assert_eq!(num_sent, 1);
+            let header_mbuf_ptr: *mut rte_mbuf = header_mbuf.into_mbuf().expect("mbuf cannot be empty");
+            self.tx.borrow_mut().push(header_mbuf_ptr);
         }
     }
 
     fn receive(&self)-> ArrayVec<(*mut rte_mbuf, *mut ControlBlock<RECEIVE_BATCH_SIZE>), RECEIVE_BATCH_SIZE> {
         let mut out: ArrayVec<(*mut rte_mbuf, *mut ControlBlock<RECEIVE_BATCH_SIZE>), RECEIVE_BATCH_SIZE> = ArrayVec::new();
 
-        unsafe {
-            match (*self.ring).try_dequeue() {
-                Some((pkt, cb)) => out.push((pkt, cb)),
-                _ => {}
+        // Packets queued outside of a poll should not wait for the next one for too long.
+        self.tx.borrow_mut().flush_expired();
+
+        if let Some(channel) = self.channel.as_ref() {
+            if let Some((pkt, cb)) = channel.try_recv_request() {
+                out.push((pkt, cb));
             }
         }
 
         out
     }
+
+    fn flush(&self) {
+        self.tx.borrow_mut().flush();
+    }
 }
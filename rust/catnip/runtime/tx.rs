@@ -0,0 +1,304 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use crate::runtime::libdpdk::{
+    rte_eth_tx_burst,
+    rte_mbuf,
+    rte_pktmbuf_free,
+};
+use ::arrayvec::ArrayVec;
+use ::std::time::{
+    Duration,
+    Instant,
+};
+
+//==============================================================================
+// Constants
+//==============================================================================
+
+/// Number of packets that triggers a flush.
+const TX_BATCH_SIZE: usize = 32;
+
+/// Maximum time a packet may wait in the batch before being flushed.
+const TX_FLUSH_TIMEOUT: Duration = Duration::from_micros(20);
+
+/// Number of bursts that may find the TX ring full before the remaining packets are dropped.
+const TX_MAX_RETRIES: usize = 64;
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Transmit counters.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TxStats {
+    /// Number of calls to rte_eth_tx_burst.
+    pub nr_bursts: u64,
+    /// Number of packets handed over to the NIC.
+    pub nr_sent: u64,
+    /// Number of bursts that found the TX ring full.
+    pub nr_ring_full: u64,
+    /// Number of packets dropped because the TX ring stayed full.
+    pub nr_dropped: u64,
+}
+
+/// Packets waiting to be sent on a TX queue.
+pub struct TxBatch {
+    port_id: u16,
+    queue_id: u16,
+    pkts: ArrayVec<*mut rte_mbuf, TX_BATCH_SIZE>,
+    /// When the oldest packet in the batch was queued.
+    oldest: Option<Instant>,
+    stats: TxStats,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for TX Batches
+impl TxBatch {
+    pub fn new(port_id: u16, queue_id: u16) -> Self {
+        Self {
+            port_id,
+            queue_id,
+            pkts: ArrayVec::new(),
+            oldest: None,
+            stats: TxStats::default(),
+        }
+    }
+
+    /// Queues a packet, and flushes the batch if it is full or if it has been waiting for too long.
+    pub fn push(&mut self, pkt: *mut rte_mbuf) {
+        if self.queue(pkt, Instant::now()) {
+            self.flush();
+        }
+    }
+
+    /// Flushes the batch if its oldest packet has been waiting for too long.
+    pub fn flush_expired(&mut self) {
+        if self.is_expired(Instant::now()) {
+            self.flush();
+        }
+    }
+
+    /// Hands all queued packets over to the NIC, retrying on partial sends.
+    pub fn flush(&mut self) {
+        let (port_id, queue_id): (u16, u16) = (self.port_id, self.queue_id);
+        self.flush_with(
+            |pkts| unsafe { rte_eth_tx_burst(port_id, queue_id, pkts.as_mut_ptr(), pkts.len() as u16) } as usize,
+            |pkt| unsafe { rte_pktmbuf_free(pkt) },
+        );
+    }
+
+    pub fn stats(&self) -> TxStats {
+        self.stats
+    }
+
+    /// Queues a packet at time `now`, and returns whether the batch is due for a flush.
+    fn queue(&mut self, pkt: *mut rte_mbuf, now: Instant) -> bool {
+        self.pkts.push(pkt);
+        let oldest: Instant = *self.oldest.get_or_insert(now);
+        self.pkts.is_full() || now.duration_since(oldest) >= TX_FLUSH_TIMEOUT
+    }
+
+    /// Returns whether the oldest packet in the batch has been waiting for too long at time `now`.
+    fn is_expired(&self, now: Instant) -> bool {
+        match self.oldest {
+            Some(oldest) => now.duration_since(oldest) >= TX_FLUSH_TIMEOUT,
+            None => false,
+        }
+    }
+
+    /// Empties the batch through `burst`, which returns how many packets it sent, and hands the packets that could not
+    /// be sent to `free`.
+    fn flush_with(
+        &mut self,
+        mut burst: impl FnMut(&mut [*mut rte_mbuf]) -> usize,
+        mut free: impl FnMut(*mut rte_mbuf),
+    ) {
+        let mut sent: usize = 0;
+        let mut retries: usize = 0;
+        while sent < self.pkts.len() {
+            let n: usize = burst(&mut self.pkts[sent..]);
+            self.stats.nr_bursts += 1;
+            sent += n;
+            if sent < self.pkts.len() {
+                self.stats.nr_ring_full += 1;
+                retries += 1;
+                if retries == TX_MAX_RETRIES {
+                    break;
+                }
+            }
+        }
+        self.stats.nr_sent += sent as u64;
+
+        // The TX ring stayed full, so give up on the remaining packets.
+        let nr_dropped: usize = self.pkts.len() - sent;
+        if nr_dropped > 0 {
+            warn!("tx ring full, dropping {:?} packets (queue_id={:?})", nr_dropped, self.queue_id);
+            for pkt in self.pkts.drain(sent..) {
+                free(pkt);
+            }
+            self.stats.nr_dropped += nr_dropped as u64;
+        }
+
+        self.pkts.clear();
+        self.oldest = None;
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+/// Drop Trait Implementation for TX Batches
+impl Drop for TxBatch {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        TxBatch,
+        TxStats,
+        TX_BATCH_SIZE,
+        TX_FLUSH_TIMEOUT,
+        TX_MAX_RETRIES,
+    };
+    use crate::runtime::libdpdk::rte_mbuf;
+    use ::anyhow::Result;
+    use ::std::time::{
+        Duration,
+        Instant,
+    };
+
+    /// Builds a packet that is only compared, never dereferenced.
+    fn fake_pkt(i: usize) -> *mut rte_mbuf {
+        (i + 1) as *mut rte_mbuf
+    }
+
+    /// Tests if a batch is due for a flush once it is full.
+    #[test]
+    fn test_tx_batch_size() -> Result<()> {
+        let now: Instant = Instant::now();
+        let mut batch: TxBatch = TxBatch::new(0, 0);
+        for i in 0..TX_BATCH_SIZE - 1 {
+            crate::ensure_eq!(batch.queue(fake_pkt(i), now), false);
+        }
+        crate::ensure_eq!(batch.queue(fake_pkt(TX_BATCH_SIZE - 1), now), true);
+
+        let mut sent: Vec<*mut rte_mbuf> = Vec::new();
+        batch.flush_with(
+            |pkts| {
+                sent.extend_from_slice(pkts);
+                pkts.len()
+            },
+            |_| (),
+        );
+        crate::ensure_eq!(sent, (0..TX_BATCH_SIZE).map(fake_pkt).collect::<Vec<*mut rte_mbuf>>());
+        crate::ensure_eq!(batch.stats().nr_bursts, 1);
+        crate::ensure_eq!(batch.stats().nr_sent, TX_BATCH_SIZE as u64);
+
+        // The next batch starts empty.
+        crate::ensure_eq!(batch.queue(fake_pkt(0), now), false);
+        batch.flush_with(|pkts| pkts.len(), |_| ());
+
+        Ok(())
+    }
+
+    /// Tests if a batch is due for a flush once its oldest packet has waited for the timeout.
+    #[test]
+    fn test_tx_batch_timeout() -> Result<()> {
+        let now: Instant = Instant::now();
+        let mut batch: TxBatch = TxBatch::new(0, 0);
+        crate::ensure_eq!(batch.is_expired(now + TX_FLUSH_TIMEOUT), false);
+
+        crate::ensure_eq!(batch.queue(fake_pkt(0), now), false);
+        crate::ensure_eq!(batch.queue(fake_pkt(1), now + Duration::from_micros(19)), false);
+        crate::ensure_eq!(batch.is_expired(now + Duration::from_micros(19)), false);
+        crate::ensure_eq!(batch.is_expired(now + TX_FLUSH_TIMEOUT), true);
+        crate::ensure_eq!(batch.queue(fake_pkt(2), now + TX_FLUSH_TIMEOUT), true);
+
+        // Flushing restarts the clock.
+        batch.flush_with(|pkts| pkts.len(), |_| ());
+        crate::ensure_eq!(batch.is_expired(now + 2 * TX_FLUSH_TIMEOUT), false);
+        crate::ensure_eq!(batch.stats().nr_sent, 3);
+
+        Ok(())
+    }
+
+    /// Tests if packets left over by a partial send are sent by the next bursts, in order.
+    #[test]
+    fn test_tx_batch_partial_send() -> Result<()> {
+        let now: Instant = Instant::now();
+        let mut batch: TxBatch = TxBatch::new(0, 0);
+        for i in 0..TX_BATCH_SIZE {
+            batch.queue(fake_pkt(i), now);
+        }
+
+        let mut sent: Vec<*mut rte_mbuf> = Vec::new();
+        let mut freed: usize = 0;
+        batch.flush_with(
+            |pkts| {
+                let n: usize = std::cmp::min(pkts.len(), 10);
+                sent.extend_from_slice(&pkts[..n]);
+                n
+            },
+            |_| freed += 1,
+        );
+        crate::ensure_eq!(sent, (0..TX_BATCH_SIZE).map(fake_pkt).collect::<Vec<*mut rte_mbuf>>());
+        crate::ensure_eq!(freed, 0);
+        let stats: TxStats = batch.stats();
+        crate::ensure_eq!(stats.nr_bursts, 4);
+        crate::ensure_eq!(stats.nr_ring_full, 3);
+        crate::ensure_eq!(stats.nr_sent, TX_BATCH_SIZE as u64);
+        crate::ensure_eq!(stats.nr_dropped, 0);
+
+        Ok(())
+    }
+
+    /// Tests if packets are dropped once the TX ring stays full for too many bursts.
+    #[test]
+    fn test_tx_batch_ring_full() -> Result<()> {
+        let now: Instant = Instant::now();
+        let mut batch: TxBatch = TxBatch::new(0, 0);
+        for i in 0..4 {
+            batch.queue(fake_pkt(i), now);
+        }
+
+        // The first burst sends a single packet, and the ring is full from then on.
+        let mut nr_bursts: usize = 0;
+        let mut freed: Vec<*mut rte_mbuf> = Vec::new();
+        batch.flush_with(
+            |_| {
+                nr_bursts += 1;
+                if nr_bursts == 1 {
+                    1
+                } else {
+                    0
+                }
+            },
+            |pkt| freed.push(pkt),
+        );
+        crate::ensure_eq!(freed, (1..4).map(fake_pkt).collect::<Vec<*mut rte_mbuf>>());
+        let stats: TxStats = batch.stats();
+        crate::ensure_eq!(stats.nr_bursts, TX_MAX_RETRIES as u64);
+        crate::ensure_eq!(stats.nr_ring_full, TX_MAX_RETRIES as u64);
+        crate::ensure_eq!(stats.nr_sent, 1);
+        crate::ensure_eq!(stats.nr_dropped, 3);
+        crate::ensure_eq!(batch.is_expired(now + TX_FLUSH_TIMEOUT), false);
+
+        Ok(())
+    }
+}
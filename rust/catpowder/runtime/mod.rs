@@ -64,6 +64,7 @@ impl LinuxRuntime {
             Some(2),
             Some(arp),
             Some(false),
+            None,
         );
 
         // TODO: Make this constructor return a Result and drop expect() calls bellow.
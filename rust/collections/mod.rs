@@ -6,3 +6,4 @@ pub mod intrusive;
 pub mod raw_array;
 pub mod ring;
 pub mod shared_ring;
+pub mod worker_channel;
@@ -160,6 +160,15 @@ where
         self.buffer.capacity() - 1
     }
 
+    /// Peeks the target ring buffer and returns the number of items in it.
+    #[allow(unused)]
+    pub fn len(&self) -> usize {
+        let front_cached: usize = self.get_front();
+        let back_cached: usize = self.get_back();
+
+        back_cached.wrapping_sub(front_cached) & self.mask
+    }
+
     /// Peeks the target ring buffer and checks if it is full.
     #[allow(unused)]
     pub fn is_full(&self) -> bool {
@@ -255,27 +264,27 @@ where
     /// Atomically gets the `front` index.
     fn get_front(&self) -> usize {
         let front: &mut AtomicUsize = AtomicUsize::from_mut(unsafe { &mut *self.front_ptr });
-        let front_cached: usize = front.load(atomic::Ordering::Relaxed);
+        let front_cached: usize = front.load(atomic::Ordering::Acquire);
         front_cached
     }
 
     /// Atomically sets the `front` index.
     fn set_front(&self, val: usize) {
         let front: &AtomicUsize = AtomicUsize::from_mut(unsafe { &mut *self.front_ptr });
-        front.store(val, atomic::Ordering::Relaxed);
+        front.store(val, atomic::Ordering::Release);
     }
 
     /// Atomically gets the `back` index.
     fn get_back(&self) -> usize {
         let back: &mut AtomicUsize = AtomicUsize::from_mut(unsafe { &mut *self.back_ptr });
-        let back_cached: usize = back.load(atomic::Ordering::Relaxed);
+        let back_cached: usize = back.load(atomic::Ordering::Acquire);
         back_cached
     }
 
     /// Atomically sets the `back` index.
     fn set_back(&self, val: usize) {
         let back: &AtomicUsize = AtomicUsize::from_mut(unsafe { &mut *self.back_ptr });
-        back.store(val, atomic::Ordering::Relaxed);
+        back.store(val, atomic::Ordering::Release);
     }
 }
 
@@ -363,6 +372,7 @@ mod test {
         // Check if buffer state is consistent.
         crate::ensure_eq!(ring.is_empty(), false);
         crate::ensure_eq!(ring.is_full(), true);
+        crate::ensure_eq!(ring.len(), ring.capacity());
 
         // Remove items from the ring buffer.
         for i in 0..ring.capacity() {
@@ -373,6 +383,7 @@ mod test {
         // Check if buffer state is consistent.
         crate::ensure_eq!(ring.is_empty(), true);
         crate::ensure_eq!(ring.is_full(), false);
+        crate::ensure_eq!(ring.len(), 0);
 
         Ok(())
     }
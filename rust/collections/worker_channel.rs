@@ -0,0 +1,335 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use crate::{
+    collections::ring::RingBuffer,
+    runtime::fail::Fail,
+};
+use ::core::{
+    cell::{
+        Cell,
+        RefCell,
+    },
+    sync::atomic::{
+        AtomicU8,
+        Ordering,
+    },
+};
+use ::std::{
+    collections::VecDeque,
+    sync::Arc,
+};
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// State of a worker, as seen by its dispatcher.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WorkerState {
+    /// The worker ran out of work, but the dispatcher did not notice it yet.
+    Idle = 0,
+    /// The dispatcher noticed that the worker is idle.
+    Acknowledged = 1,
+    /// The worker has work to do.
+    Busy = 2,
+}
+
+/// Out-of-band command sent from a dispatcher to a worker.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Control {
+    /// The worker should stop serving requests.
+    Terminate,
+}
+
+/// Message sent from a dispatcher to a worker.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Message<T: Copy> {
+    /// A request the worker should serve.
+    Request(T),
+    /// A control command.
+    Control(Control),
+}
+
+/// Notice sent from a worker to its dispatcher.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Completion {
+    /// The worker finished serving a request.
+    Done,
+    /// The worker was preempted while serving a request, and put it aside.
+    Preempted,
+}
+
+/// A single-producer single-consumer channel between a dispatcher and one of its workers.
+///
+/// The channel is only reachable through its two endpoints, which can be moved to another core but not shared nor
+/// cloned. Requests and control commands flow from the [DispatcherEnd] to the [WorkerEnd], completions flow back, and
+/// a state word tells the dispatcher whether the worker is idle.
+pub struct WorkerChannel<T: Copy> {
+    /// Messages from the dispatcher to the worker.
+    requests: RingBuffer<Message<T>>,
+    /// Notices from the worker to the dispatcher.
+    completions: RingBuffer<Completion>,
+    /// State of the worker, see [WorkerState].
+    state: AtomicU8,
+}
+
+/// Dispatcher endpoint of a [WorkerChannel].
+pub struct DispatcherEnd<T: Copy> {
+    channel: Arc<WorkerChannel<T>>,
+    /// Number of requests served by the worker.
+    nr_done: Cell<usize>,
+    /// Number of times the worker was preempted.
+    nr_preempted: Cell<usize>,
+}
+
+/// Worker endpoint of a [WorkerChannel].
+pub struct WorkerEnd<T: Copy> {
+    channel: Arc<WorkerChannel<T>>,
+    /// Control commands received while looking for requests.
+    controls: RefCell<VecDeque<Control>>,
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+/// Associated functions for worker channels.
+impl<T: Copy> WorkerChannel<T> {
+    /// Creates a channel that holds up to `capacity - 1` messages in each direction, and returns its endpoints. The
+    /// worker starts idle, and the dispatcher is assumed to know it.
+    pub fn new(capacity: usize) -> Result<(DispatcherEnd<T>, WorkerEnd<T>), Fail> {
+        let channel: Arc<WorkerChannel<T>> = Arc::new(Self {
+            requests: RingBuffer::<Message<T>>::new(capacity)?,
+            completions: RingBuffer::<Completion>::new(capacity)?,
+            state: AtomicU8::new(WorkerState::Acknowledged as u8),
+        });
+
+        let dispatcher: DispatcherEnd<T> = DispatcherEnd {
+            channel: channel.clone(),
+            nr_done: Cell::new(0),
+            nr_preempted: Cell::new(0),
+        };
+        let worker: WorkerEnd<T> = WorkerEnd {
+            channel,
+            controls: RefCell::new(VecDeque::new()),
+        };
+
+        Ok((dispatcher, worker))
+    }
+
+    /// Loads the state word.
+    fn state(&self) -> WorkerState {
+        match self.state.load(Ordering::Acquire) {
+            0 => WorkerState::Idle,
+            1 => WorkerState::Acknowledged,
+            _ => WorkerState::Busy,
+        }
+    }
+}
+
+/// Associated functions for dispatcher endpoints.
+impl<T: Copy> DispatcherEnd<T> {
+    /// Attempts to send a request to the worker, giving it back if the channel is full.
+    pub fn try_request(&self, request: T) -> Result<(), T> {
+        // Mark the worker busy before publishing the request, so that it cannot be seen idle with the request pending.
+        self.channel.state.store(WorkerState::Busy as u8, Ordering::Release);
+        match self.channel.requests.try_enqueue(Message::Request(request)) {
+            Ok(()) => Ok(()),
+            Err(Message::Request(request)) => Err(request),
+            Err(Message::Control(_)) => unreachable!("should have given back a request"),
+        }
+    }
+
+    /// Marks the worker busy, for work that is handed over to it outside of the channel.
+    pub fn set_busy(&self) {
+        self.channel.state.store(WorkerState::Busy as u8, Ordering::Release);
+    }
+
+    /// Sends a control command to the worker. This function may block (spin) while the channel is full.
+    pub fn control(&self, control: Control) {
+        self.channel.requests.enqueue(Message::Control(control));
+    }
+
+    /// Number of messages the worker did not receive yet.
+    pub fn len(&self) -> usize {
+        self.channel.requests.len()
+    }
+
+    /// Checks if the worker received all messages.
+    pub fn is_empty(&self) -> bool {
+        self.channel.requests.is_empty()
+    }
+
+    /// Checks if the channel cannot take more messages.
+    pub fn is_full(&self) -> bool {
+        self.channel.requests.is_full()
+    }
+
+    /// Current state of the worker.
+    pub fn state(&self) -> WorkerState {
+        self.channel.state()
+    }
+
+    /// Checks if the worker became idle since the last call, and takes note of it.
+    pub fn acknowledge_idle(&self) -> bool {
+        // The worker still has requests to receive.
+        if !self.channel.requests.is_empty() {
+            return false;
+        }
+        self.channel
+            .state
+            .compare_exchange(
+                WorkerState::Idle as u8,
+                WorkerState::Acknowledged as u8,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .is_ok()
+    }
+
+    /// Drains the completions sent by the worker, returning how many there were.
+    pub fn poll_completions(&self) -> usize {
+        let mut n: usize = 0;
+        while let Some(completion) = self.channel.completions.try_dequeue() {
+            match completion {
+                Completion::Done => self.nr_done.set(self.nr_done.get() + 1),
+                Completion::Preempted => self.nr_preempted.set(self.nr_preempted.get() + 1),
+            }
+            n += 1;
+        }
+        n
+    }
+
+    /// Number of requests the worker reported as served.
+    pub fn nr_done(&self) -> usize {
+        self.nr_done.get()
+    }
+
+    /// Number of times the worker reported being preempted.
+    pub fn nr_preempted(&self) -> usize {
+        self.nr_preempted.get()
+    }
+}
+
+/// Associated functions for worker endpoints.
+impl<T: Copy> WorkerEnd<T> {
+    /// Attempts to receive a message from the dispatcher.
+    pub fn try_recv(&self) -> Option<Message<T>> {
+        let message: Message<T> = self.channel.requests.try_dequeue()?;
+        if let Message::Request(_) = message {
+            self.channel.state.store(WorkerState::Busy as u8, Ordering::Release);
+        }
+        Some(message)
+    }
+
+    /// Attempts to receive a request from the dispatcher. Control commands found on the way are put aside, and can
+    /// be retrieved with [Self::take_control].
+    pub fn try_recv_request(&self) -> Option<T> {
+        loop {
+            match self.try_recv()? {
+                Message::Request(request) => return Some(request),
+                Message::Control(control) => self.controls.borrow_mut().push_back(control),
+            }
+        }
+    }
+
+    /// Takes the oldest control command put aside by [Self::try_recv_request].
+    pub fn take_control(&self) -> Option<Control> {
+        self.controls.borrow_mut().pop_front()
+    }
+
+    /// Tells the dispatcher that this worker ran out of work.
+    pub fn set_idle(&self) {
+        self.channel.state.store(WorkerState::Idle as u8, Ordering::Release);
+    }
+
+    /// Sends a completion to the dispatcher. This function may block (spin) while the channel is full.
+    pub fn complete(&self, completion: Completion) {
+        self.channel.completions.enqueue(completion);
+    }
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod test {
+    use super::{
+        Completion,
+        Control,
+        DispatcherEnd,
+        Message,
+        WorkerChannel,
+        WorkerEnd,
+        WorkerState,
+    };
+    use ::anyhow::Result;
+
+    /// Capacity for channels.
+    const CHANNEL_CAPACITY: usize = 4;
+
+    /// Tests if requests and control commands are received in order, and change the state of the worker.
+    #[test]
+    fn request_control() -> Result<()> {
+        let (dispatcher, worker): (DispatcherEnd<u32>, WorkerEnd<u32>) = WorkerChannel::new(CHANNEL_CAPACITY)?;
+        crate::ensure_eq!(dispatcher.state(), WorkerState::Acknowledged);
+
+        crate::ensure_eq!(dispatcher.try_request(1), Ok(()));
+        dispatcher.control(Control::Terminate);
+        crate::ensure_eq!(dispatcher.try_request(2), Ok(()));
+        crate::ensure_eq!(dispatcher.is_full(), true);
+        crate::ensure_eq!(dispatcher.try_request(3), Err(3));
+        crate::ensure_eq!(dispatcher.state(), WorkerState::Busy);
+
+        crate::ensure_eq!(worker.try_recv(), Some(Message::Request(1)));
+        crate::ensure_eq!(worker.try_recv_request(), Some(2));
+        crate::ensure_eq!(worker.try_recv_request(), None);
+        crate::ensure_eq!(worker.take_control(), Some(Control::Terminate));
+        crate::ensure_eq!(worker.take_control(), None);
+        crate::ensure_eq!(dispatcher.is_empty(), true);
+
+        Ok(())
+    }
+
+    /// Tests if the dispatcher notices an idle worker exactly once, and only after it received all requests.
+    #[test]
+    fn acknowledge_idle() -> Result<()> {
+        let (dispatcher, worker): (DispatcherEnd<u32>, WorkerEnd<u32>) = WorkerChannel::new(CHANNEL_CAPACITY)?;
+        crate::ensure_eq!(dispatcher.acknowledge_idle(), false);
+
+        crate::ensure_eq!(dispatcher.try_request(1), Ok(()));
+        worker.set_idle();
+        crate::ensure_eq!(dispatcher.acknowledge_idle(), false);
+
+        crate::ensure_eq!(worker.try_recv_request(), Some(1));
+        crate::ensure_eq!(dispatcher.acknowledge_idle(), false);
+        worker.set_idle();
+        crate::ensure_eq!(dispatcher.acknowledge_idle(), true);
+        crate::ensure_eq!(dispatcher.acknowledge_idle(), false);
+        crate::ensure_eq!(dispatcher.state(), WorkerState::Acknowledged);
+
+        Ok(())
+    }
+
+    /// Tests if completions are accounted by the dispatcher.
+    #[test]
+    fn completions() -> Result<()> {
+        let (dispatcher, worker): (DispatcherEnd<u32>, WorkerEnd<u32>) = WorkerChannel::new(CHANNEL_CAPACITY)?;
+
+        worker.complete(Completion::Preempted);
+        worker.complete(Completion::Done);
+        crate::ensure_eq!(dispatcher.poll_completions(), 2);
+        crate::ensure_eq!(dispatcher.poll_completions(), 0);
+        crate::ensure_eq!(dispatcher.nr_done(), 1);
+        crate::ensure_eq!(dispatcher.nr_preempted(), 1);
+
+        Ok(())
+    }
+}
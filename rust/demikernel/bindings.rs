@@ -12,16 +12,24 @@ use crate::{
         LibOS,
     },
     pal::{
-        constants::AF_INET,
+        constants::{
+            AF_INET,
+            AF_INET6,
+        },
         data_structures::{
             SockAddrIn,
             Socklen,
         },
         functions::get_addr_from_sock_addr_in,
+        linux,
     },
     runtime::{
         fail::Fail,
         logging,
+        network::socket::{
+            SocketOption,
+            SocketOptionName,
+        },
         types::{
             demi_qresult_t,
             demi_qtoken_t,
@@ -43,6 +51,7 @@ use ::std::{
     mem,
     net::{
         Ipv4Addr,
+        SocketAddr,
         SocketAddrV4,
     },
     ptr,
@@ -192,13 +201,8 @@ pub extern "C" fn demi_bind(qd: c_int, saddr: *const sockaddr, size: Socklen) ->
         return libc::EINVAL;
     }
 
-    // Check if socket address length is invalid.
-    if size as usize != mem::size_of::<SockAddrIn>() {
-        return libc::EINVAL;
-    }
-
     // Get socket address.
-    let endpoint: SocketAddrV4 = match sockaddr_to_socketaddrv4(saddr) {
+    let endpoint: SocketAddr = match sockaddr_to_socketaddr(saddr, size) {
         Ok(endpoint) => endpoint,
         Err(e) => {
             trace!("demi_bind() failed: {:?}", e);
@@ -295,13 +299,8 @@ pub extern "C" fn demi_connect(
         return libc::EINVAL;
     }
 
-    // Check if socket address length is invalid.
-    if size as usize != mem::size_of::<SockAddrIn>() {
-        return libc::EINVAL;
-    }
-
     // Get socket address.
-    let endpoint: SocketAddrV4 = match sockaddr_to_socketaddrv4(saddr) {
+    let endpoint: SocketAddr = match sockaddr_to_socketaddr(saddr, size) {
         Ok(endpoint) => endpoint,
         Err(e) => {
             trace!("demi_connect() failed: {:?}", e);
@@ -374,15 +373,10 @@ pub extern "C" fn demi_pushto(
         return libc::EINVAL;
     }
 
-    // Check if socket address length is invalid.
-    if size as usize != mem::size_of::<SockAddrIn>() {
-        return libc::EINVAL;
-    }
-
     let sga: &demi_sgarray_t = unsafe { &*sga };
 
     // Get socket address.
-    let endpoint: SocketAddrV4 = match sockaddr_to_socketaddrv4(saddr) {
+    let endpoint: SocketAddr = match sockaddr_to_socketaddr(saddr, size) {
         Ok(endpoint) => endpoint,
         Err(e) => {
             trace!("demi_pushto() failed: {:?}", e);
@@ -686,18 +680,68 @@ pub extern "C" fn demi_sgafree(sga: *mut demi_sgarray_t) -> c_int {
 // getsockname
 //======================================================================================================================
 
-#[allow(unused)]
 #[no_mangle]
 pub extern "C" fn demi_getsockname(qd: c_int, saddr: *mut sockaddr, size: *mut Socklen) -> c_int {
-    // TODO: Implement this system call.
-    libc::ENOSYS
+    trace!("demi_getsockname()");
+
+    // Check if socket address is invalid.
+    if saddr.is_null() || size.is_null() {
+        return libc::EINVAL;
+    }
+
+    // Issue getsockname operation.
+    let ret: Result<i32, Fail> = do_syscall(|libos| match libos.getsockname(qd.into()) {
+        Ok(local) => {
+            socketaddr_to_sockaddr(&local, saddr, size);
+            0
+        },
+        Err(e) => {
+            trace!("demi_getsockname() failed: {:?}", e);
+            e.errno
+        },
+    });
+
+    match ret {
+        Ok(ret) => ret,
+        Err(e) => e.errno,
+    }
+}
+
+//======================================================================================================================
+// getpeername
+//======================================================================================================================
+
+#[no_mangle]
+pub extern "C" fn demi_getpeername(qd: c_int, saddr: *mut sockaddr, size: *mut Socklen) -> c_int {
+    trace!("demi_getpeername()");
+
+    // Check if socket address is invalid.
+    if saddr.is_null() || size.is_null() {
+        return libc::EINVAL;
+    }
+
+    // Issue getpeername operation.
+    let ret: Result<i32, Fail> = do_syscall(|libos| match libos.getpeername(qd.into()) {
+        Ok(remote) => {
+            socketaddr_to_sockaddr(&remote, saddr, size);
+            0
+        },
+        Err(e) => {
+            trace!("demi_getpeername() failed: {:?}", e);
+            e.errno
+        },
+    });
+
+    match ret {
+        Ok(ret) => ret,
+        Err(e) => e.errno,
+    }
 }
 
 //======================================================================================================================
 // setsockopt
 //======================================================================================================================
 
-#[allow(unused)]
 #[no_mangle]
 pub extern "C" fn demi_setsockopt(
     qd: c_int,
@@ -706,15 +750,43 @@ pub extern "C" fn demi_setsockopt(
     optval: *const c_void,
     optlen: Socklen,
 ) -> c_int {
-    // TODO: Implement this system call.
-    libc::ENOSYS
+    trace!("demi_setsockopt() level={:?} optname={:?}", level, optname);
+
+    // Check if option value is invalid.
+    if optval.is_null() {
+        return libc::EINVAL;
+    }
+
+    // Parse option.
+    let optval: &[u8] = unsafe { slice::from_raw_parts(optval as *const u8, optlen as usize) };
+    let option: SocketOption =
+        match SocketOptionName::from_raw(level, optname).and_then(|name| SocketOption::from_raw(name, optval)) {
+            Ok(option) => option,
+            Err(e) => {
+                trace!("demi_setsockopt() failed: {:?}", e);
+                return e.errno;
+            },
+        };
+
+    // Issue setsockopt operation.
+    let ret: Result<i32, Fail> = do_syscall(|libos| match libos.setsockopt(qd.into(), option) {
+        Ok(()) => 0,
+        Err(e) => {
+            trace!("demi_setsockopt() failed: {:?}", e);
+            e.errno
+        },
+    });
+
+    match ret {
+        Ok(ret) => ret,
+        Err(e) => e.errno,
+    }
 }
 
 //======================================================================================================================
 // getsockopt
 //======================================================================================================================
 
-#[allow(unused)]
 #[no_mangle]
 pub extern "C" fn demi_getsockopt(
     qd: c_int,
@@ -723,8 +795,43 @@ pub extern "C" fn demi_getsockopt(
     optval: *mut c_void,
     optlen: *mut Socklen,
 ) -> c_int {
-    // TODO: Implement this system call.
-    libc::ENOSYS
+    trace!("demi_getsockopt() level={:?} optname={:?}", level, optname);
+
+    // Check if option value is invalid.
+    if optval.is_null() || optlen.is_null() {
+        return libc::EINVAL;
+    }
+
+    let name: SocketOptionName = match SocketOptionName::from_raw(level, optname) {
+        Ok(name) => name,
+        Err(e) => {
+            trace!("demi_getsockopt() failed: {:?}", e);
+            return e.errno;
+        },
+    };
+
+    // Issue getsockopt operation.
+    let ret: Result<i32, Fail> = do_syscall(|libos| match libos.getsockopt(qd.into(), name) {
+        Ok(option) => {
+            let optval: &mut [u8] = unsafe { slice::from_raw_parts_mut(optval as *mut u8, *optlen as usize) };
+            match option.to_raw(optval) {
+                Ok(len) => {
+                    unsafe { *optlen = len as Socklen };
+                    0
+                },
+                Err(e) => e.errno,
+            }
+        },
+        Err(e) => {
+            trace!("demi_getsockopt() failed: {:?}", e);
+            e.errno
+        },
+    });
+
+    match ret {
+        Ok(ret) => ret,
+        Err(e) => e.errno,
+    }
 }
 
 //======================================================================================================================
@@ -742,22 +849,64 @@ fn do_syscall<T>(f: impl FnOnce(&mut LibOS) -> T) -> Result<T, Fail> {
     }
 }
 
-/// Converts a [sockaddr] into a [SocketAddrV4].
-fn sockaddr_to_socketaddrv4(saddr: *const sockaddr) -> Result<SocketAddrV4, Fail> {
-    // TODO: Change the logic bellow and rename this function once we support V6 addresses as well.
-    let sin: SockAddrIn = unsafe { *mem::transmute::<*const sockaddr, *const SockAddrIn>(saddr) };
-    if sin.sin_family != AF_INET {
-        return Err(Fail::new(libc::ENOTSUP, "communication domain not supported"));
+/// Writes a [SocketAddr] into a [sockaddr], truncating it if `size` is too small, and stores its full size in `size`.
+fn socketaddr_to_sockaddr(addr: &SocketAddr, saddr: *mut sockaddr, size: *mut Socklen) {
+    let len: usize = match addr {
+        SocketAddr::V4(addr) => {
+            let sin: sockaddr = linux::socketaddrv4_to_sockaddr(addr);
+            let len: usize = mem::size_of::<SockAddrIn>();
+            unsafe {
+                ptr::copy_nonoverlapping(
+                    &sin as *const sockaddr as *const u8,
+                    saddr as *mut u8,
+                    len.min(*size as usize),
+                )
+            };
+            len
+        },
+        SocketAddr::V6(addr) => {
+            let sin6: libc::sockaddr_in6 = linux::socketaddrv6_to_sockaddr_in6(addr);
+            let len: usize = mem::size_of::<libc::sockaddr_in6>();
+            unsafe {
+                ptr::copy_nonoverlapping(
+                    &sin6 as *const libc::sockaddr_in6 as *const u8,
+                    saddr as *mut u8,
+                    len.min(*size as usize),
+                )
+            };
+            len
+        },
     };
-    let addr: Ipv4Addr = Ipv4Addr::from(u32::from_be(get_addr_from_sock_addr_in(&sin)));
-    let port: u16 = u16::from_be(sin.sin_port);
-    Ok(SocketAddrV4::new(addr, port))
+    unsafe { *size = len as Socklen };
 }
 
-#[test]
-fn test_sockaddr_to_socketaddrv4() {
-    // TODO: assign something meaningful to sa_family and check it once we support V6 addresses as well.
+/// Converts a [sockaddr] of `size` bytes into a [SocketAddr].
+fn sockaddr_to_socketaddr(saddr: *const sockaddr, size: Socklen) -> Result<SocketAddr, Fail> {
+    // The address family sits at the same offset in all socket addresses.
+    if (size as usize) < mem::size_of::<SockAddrIn>() {
+        return Err(Fail::new(libc::EINVAL, "socket address is too small"));
+    }
+    let sin: SockAddrIn = unsafe { ptr::read_unaligned(saddr as *const SockAddrIn) };
+    if sin.sin_family == AF_INET {
+        if size as usize != mem::size_of::<SockAddrIn>() {
+            return Err(Fail::new(libc::EINVAL, "bad size for an IPv4 socket address"));
+        }
+        let addr: Ipv4Addr = Ipv4Addr::from(u32::from_be(get_addr_from_sock_addr_in(&sin)));
+        let port: u16 = u16::from_be(sin.sin_port);
+        Ok(SocketAddr::V4(SocketAddrV4::new(addr, port)))
+    } else if sin.sin_family == AF_INET6 {
+        if size as usize != mem::size_of::<libc::sockaddr_in6>() {
+            return Err(Fail::new(libc::EINVAL, "bad size for an IPv6 socket address"));
+        }
+        let sin6: libc::sockaddr_in6 = unsafe { ptr::read_unaligned(saddr as *const libc::sockaddr_in6) };
+        Ok(SocketAddr::V6(linux::sockaddr_in6_to_socketaddrv6(&sin6)))
+    } else {
+        Err(Fail::new(libc::ENOTSUP, "communication domain not supported"))
+    }
+}
 
+#[test]
+fn test_sockaddr_to_socketaddr() {
     // SocketAddrV4: 127.0.0.1:80
     let saddr: libc::sockaddr = {
         sockaddr {
@@ -765,11 +914,29 @@ fn test_sockaddr_to_socketaddrv4() {
             sa_data: [0, 80, 127, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0],
         }
     };
-    match sockaddr_to_socketaddrv4(&saddr) {
-        Ok(addr) => {
+    match sockaddr_to_socketaddr(&saddr, mem::size_of::<SockAddrIn>() as Socklen) {
+        Ok(SocketAddr::V4(addr)) => {
             assert_eq!(addr.port(), 80);
             assert_eq!(addr.ip(), &Ipv4Addr::new(127, 0, 0, 1));
         },
         _ => panic!("failed to convert"),
     }
 }
+
+#[test]
+fn test_sockaddr_to_socketaddr_v6() {
+    // SocketAddrV6: [::1]:80
+    let addr: ::std::net::SocketAddrV6 = ::std::net::SocketAddrV6::new(::std::net::Ipv6Addr::LOCALHOST, 80, 0, 0);
+    let sin6: libc::sockaddr_in6 = linux::socketaddrv6_to_sockaddr_in6(&addr);
+    let saddr: *const sockaddr = &sin6 as *const libc::sockaddr_in6 as *const sockaddr;
+    match sockaddr_to_socketaddr(saddr, mem::size_of::<libc::sockaddr_in6>() as Socklen) {
+        Ok(SocketAddr::V6(converted)) => assert_eq!(converted, addr),
+        _ => panic!("failed to convert"),
+    }
+
+    // A truncated address is rejected.
+    match sockaddr_to_socketaddr(saddr, mem::size_of::<SockAddrIn>() as Socklen) {
+        Err(e) => assert_eq!(e.errno, libc::EINVAL),
+        _ => panic!("converted a truncated address"),
+    }
+}
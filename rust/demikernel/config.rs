@@ -59,4 +59,19 @@ impl Config {
         }
         local_ipv4_addr
     }
+
+    /// Reads the local IPv6 address parameter from the underlying configuration file.  IPv6 is disabled when the
+    /// parameter is absent.
+    #[cfg(any(feature = "catnip-libos", feature = "catpowder-libos"))]
+    pub fn local_ipv6_addr(&self) -> Option<::std::net::Ipv6Addr> {
+        // FIXME: this function should return a result.
+        use ::std::net::Ipv6Addr;
+
+        // FIXME: Change the follow key from "catnip" to "demikernel".
+        let local_ipv6_addr: Ipv6Addr = self.0["catnip"]["my_ipv6_addr"].as_str()?.parse().unwrap();
+        if local_ipv6_addr.is_unspecified() || local_ipv6_addr.is_multicast() {
+            panic!("Invalid IPv6 address");
+        }
+        Some(local_ipv6_addr)
+    }
 }
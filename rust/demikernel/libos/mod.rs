@@ -21,23 +21,32 @@ use crate::{
         limits,
         logging,
         types::{
+            demi_opcode_t,
             demi_qresult_t,
             demi_sgarray_t,
         },
         QDesc,
         QToken,
-        libdpdk::rte_mbuf, 
-        network::consts::RECEIVE_BATCH_SIZE, OperationTask, timer::TimerRc,
+        network::{
+            consts::RECEIVE_BATCH_SIZE,
+            socket::{
+                SocketOption,
+                SocketOptionName,
+            },
+        },
+        OperationTask, timer::TimerRc,
     },
     inetstack::protocols::tcp::established::ControlBlock,
     scheduler::{TaskHandle, Scheduler}, 
-    catnip::{runtime::{memory::MemoryManager, DPDKRuntime}, interop::pack_result}, 
-    collections::ring::RingBuffer, OperationResult,
+    catnip::{runtime::{memory::MemoryManager, DPDKRuntime, WorkerRequest}, interop::pack_result}, 
+    collections::worker_channel::WorkerEnd, OperationResult,
 };
 use ::std::{
     env,
+    mem,
+    rc::Rc,
     sync::Arc,
-    net::SocketAddrV4,
+    net::SocketAddr,
     time::{
         Duration,
         Instant,
@@ -79,7 +88,7 @@ pub enum LibOS {
 /// Associated functions for LibOS.
 impl LibOS {
     /// Instantiates a new LibOS.
-    pub fn new(libos_name: LibOSName, queue_id: u16, mm: Arc<MemoryManager>, ring: *mut RingBuffer<(*mut rte_mbuf, *mut ControlBlock<RECEIVE_BATCH_SIZE>)>) -> Result<Self, Fail> {
+    pub fn new(libos_name: LibOSName, queue_id: u16, mm: Arc<MemoryManager>, channel: WorkerEnd<WorkerRequest>) -> Result<Self, Fail> {
         logging::initialize();
 
         // Read in configuration file.
@@ -106,7 +115,7 @@ impl LibOS {
             #[cfg(feature = "catpowder-libos")]
             LibOSName::Catpowder => Self::NetworkLibOS(NetworkLibOS::Catpowder(CatpowderLibOS::new(&config))),
             #[cfg(feature = "catnip-libos")]
-            LibOSName::Catnip => Self::NetworkLibOS(NetworkLibOS::Catnip(CatnipLibOS::new(&config, queue_id, mm, ring))),
+            LibOSName::Catnip => Self::NetworkLibOS(NetworkLibOS::Catnip(CatnipLibOS::new(&config, queue_id, mm, channel))),
             #[cfg(feature = "catmem-libos")]
             LibOSName::Catmem => Self::MemoryLibOS(MemoryLibOS::Catmem(CatmemLibOS::new())),
             #[cfg(feature = "catloop-libos")]
@@ -161,7 +170,7 @@ impl LibOS {
     }
 
     /// Binds a socket to a local address.
-    pub fn bind(&mut self, sockqd: QDesc, local: SocketAddrV4) -> Result<(), Fail> {
+    pub fn bind(&mut self, sockqd: QDesc, local: SocketAddr) -> Result<(), Fail> {
         let result: Result<(), Fail> = match self {
             LibOS::NetworkLibOS(libos) => libos.bind(sockqd, local),
             LibOS::MemoryLibOS(_) => Err(Fail::new(libc::ENOTSUP, "bind() is not supported on memory liboses")),
@@ -197,7 +206,7 @@ impl LibOS {
     }
 
     /// Initiates a connection with a remote TCP socket.
-    pub fn connect(&mut self, sockqd: QDesc, remote: SocketAddrV4) -> Result<QToken, Fail> {
+    pub fn connect(&mut self, sockqd: QDesc, remote: SocketAddr) -> Result<QToken, Fail> {
         let result: Result<QToken, Fail> = match self {
             LibOS::NetworkLibOS(libos) => libos.connect(sockqd, remote),
             LibOS::MemoryLibOS(_) => Err(Fail::new(libc::ENOTSUP, "connect() is not supported on memory liboses")),
@@ -231,6 +240,38 @@ impl LibOS {
         result
     }
 
+    /// Sets an option on a socket.
+    pub fn setsockopt(&mut self, sockqd: QDesc, option: SocketOption) -> Result<(), Fail> {
+        match self {
+            LibOS::NetworkLibOS(libos) => libos.setsockopt(sockqd, option),
+            LibOS::MemoryLibOS(_) => Err(Fail::new(libc::ENOTSOCK, "setsockopt() is not supported on memory libOSes")),
+        }
+    }
+
+    /// Gets the value of an option on a socket.
+    pub fn getsockopt(&self, sockqd: QDesc, name: SocketOptionName) -> Result<SocketOption, Fail> {
+        match self {
+            LibOS::NetworkLibOS(libos) => libos.getsockopt(sockqd, name),
+            LibOS::MemoryLibOS(_) => Err(Fail::new(libc::ENOTSOCK, "getsockopt() is not supported on memory libOSes")),
+        }
+    }
+
+    /// Gets the local address of a socket.
+    pub fn getsockname(&self, sockqd: QDesc) -> Result<SocketAddr, Fail> {
+        match self {
+            LibOS::NetworkLibOS(libos) => libos.getsockname(sockqd),
+            LibOS::MemoryLibOS(_) => Err(Fail::new(libc::ENOTSOCK, "getsockname() is not supported on memory libOSes")),
+        }
+    }
+
+    /// Gets the remote address of a socket.
+    pub fn getpeername(&self, sockqd: QDesc) -> Result<SocketAddr, Fail> {
+        match self {
+            LibOS::NetworkLibOS(libos) => libos.getpeername(sockqd),
+            LibOS::MemoryLibOS(_) => Err(Fail::new(libc::ENOTSOCK, "getpeername() is not supported on memory libOSes")),
+        }
+    }
+
     /// Pushes a scatter-gather array to an I/O queue.
     pub fn push(&mut self, cb: *mut ControlBlock<RECEIVE_BATCH_SIZE>, sga: &demi_sgarray_t) -> Result<QToken, Fail> {
         let result: Result<QToken, Fail> = match self {
@@ -244,7 +285,7 @@ impl LibOS {
     }
 
     /// Pushes a scatter-gather array to a UDP socket.
-    pub fn pushto(&mut self, qd: QDesc, sga: &demi_sgarray_t, to: SocketAddrV4) -> Result<QToken, Fail> {
+    pub fn pushto(&mut self, qd: QDesc, sga: &demi_sgarray_t, to: SocketAddr) -> Result<QToken, Fail> {
         let result: Result<QToken, Fail> = match self {
             LibOS::NetworkLibOS(libos) => libos.pushto(qd, sga, to),
             LibOS::MemoryLibOS(_) => Err(Fail::new(libc::ENOTSUP, "pushto() is not supported on memory liboses")),
@@ -422,48 +463,71 @@ impl LibOS {
         }
     }
 
-    pub fn get_ring(&self) -> *mut RingBuffer<(*mut rte_mbuf, *mut ControlBlock<RECEIVE_BATCH_SIZE>)> {
+    pub fn get_channel(&self) -> Rc<WorkerEnd<WorkerRequest>> {
         match self {
-            LibOS::NetworkLibOS(libos) => libos.get_ring(),
+            LibOS::NetworkLibOS(libos) => libos.get_channel(),
             LibOS::MemoryLibOS(_libos) => todo!()
         }
     }
 
-    pub fn wait_push_pop(&mut self, pushed: Option<QToken>) -> Vec<(*mut ControlBlock<RECEIVE_BATCH_SIZE>, demi_qresult_t)> {
+    /// Waits until a pop completes, or one of the `pushed` operations does. Completed pushes are removed from
+    /// `pushed`, the others are left there to be polled by later calls.
+    pub fn wait_push_pop(&mut self, pushed: &mut Vec<QToken>) -> Vec<(*mut ControlBlock<RECEIVE_BATCH_SIZE>, demi_qresult_t)> {
         trace!("wait_push_pop(): pushed={:?}", pushed);
-        let mut output: Vec<(*mut ControlBlock<RECEIVE_BATCH_SIZE>, demi_qresult_t)> = Vec::new();
 
         loop {
-            let mut poped = self.poll();
-
-            // Retrieve associated push
-            match pushed {
-                Some(qt) => {
-                    let handle = self.schedule(qt).unwrap();
-                    if handle.has_completed() {
-                        let result = self.pack_result(handle, qt).unwrap();
-                        output.push((std::ptr::null_mut(), result));
-                    }
-                },
-                _ => {}
-            };
-            
-            for (_cb, qt) in poped.iter_mut() {
-                let handle = self.schedule(*qt).unwrap();
-    
-                if handle.has_completed() {
-                    let task: OperationTask = OperationTask::from(self.get_scheduler().remove(handle).as_any());
-    
-                    let (cb, r): (*mut ControlBlock<RECEIVE_BATCH_SIZE>, OperationResult) = task.get_result().expect("Coroutine not finished");
-    
-                    let res = unsafe { pack_result(self.get_rt().clone(), r, (*cb).qd, (*qt).into()) };
-                    
-                    output.push((cb, res));
-                }
-            }
+            let output: Vec<(*mut ControlBlock<RECEIVE_BATCH_SIZE>, demi_qresult_t)> = self.try_push_pop(pushed);
 
             if !output.is_empty() {
-                break;
+                return output;
+            }
+        }
+    }
+
+    /// Same as [wait_push_pop](Self::wait_push_pop), but returns right away if nothing has completed. Requests that
+    /// carried nothing to pop are reported with [demi_opcode_t::DEMI_OPC_INVALID].
+    pub fn try_push_pop(&mut self, pushed: &mut Vec<QToken>) -> Vec<(*mut ControlBlock<RECEIVE_BATCH_SIZE>, demi_qresult_t)> {
+        let mut output: Vec<(*mut ControlBlock<RECEIVE_BATCH_SIZE>, demi_qresult_t)> = Vec::new();
+        let mut poped = self.poll();
+
+        // Retrieve associated pushes. The ones that did not complete yet keep their task, so hold on to them.
+        let mut i: usize = 0;
+        while i < pushed.len() {
+            let qt: QToken = pushed[i];
+            let handle = self.schedule(qt).unwrap();
+            if handle.has_completed() {
+                let task: OperationTask = OperationTask::from(self.get_scheduler().remove(handle).as_any());
+                let (cb, r): (*mut ControlBlock<RECEIVE_BATCH_SIZE>, OperationResult) = task.get_result().expect("Coroutine not finished");
+                let result = unsafe { pack_result(self.get_rt().clone(), r, (*cb).qd, qt.into()) };
+                output.push((cb, result));
+                pushed.swap_remove(i);
+            } else {
+                i += 1;
+            }
+        }
+
+        for (pop_cb, qt) in poped.iter_mut() {
+            let handle = self.schedule(*qt).unwrap();
+
+            if handle.has_completed() {
+                let task: OperationTask = OperationTask::from(self.get_scheduler().remove(handle).as_any());
+
+                let (cb, r): (*mut ControlBlock<RECEIVE_BATCH_SIZE>, OperationResult) = task.get_result().expect("Coroutine not finished");
+
+                let res = unsafe { pack_result(self.get_rt().clone(), r, (*cb).qd, (*qt).into()) };
+
+                output.push((cb, res));
+            } else {
+                // Nothing was received for this request (e.g. it carried a pure ACK), and nobody waits for its pop.
+                // Report it all the same, so that the caller releases the request and accounts for it.
+                let qr: demi_qresult_t = demi_qresult_t {
+                    qr_opcode: demi_opcode_t::DEMI_OPC_INVALID,
+                    qr_qd: unsafe { (**pop_cb).qd.into() },
+                    qr_qt: (*qt).into(),
+                    qr_ret: 0,
+                    qr_value: unsafe { mem::zeroed() },
+                };
+                output.push((*pop_cb, qr));
             }
         }
 
@@ -14,8 +14,13 @@ use crate::{
         },
         QDesc,
         QToken, 
-        libdpdk::rte_mbuf,
-        network::consts::RECEIVE_BATCH_SIZE, 
+        network::{
+            consts::RECEIVE_BATCH_SIZE,
+            socket::{
+                SocketOption,
+                SocketOptionName,
+            },
+        },
         timer::TimerRc,
     },
     scheduler::{
@@ -23,11 +28,20 @@ use crate::{
         Scheduler
     }, 
     inetstack::protocols::tcp::established::ControlBlock, 
-    catnip::runtime::DPDKRuntime, 
-    collections::ring::RingBuffer,
+    catnip::runtime::{
+        DPDKRuntime,
+        WorkerRequest,
+    },
+    collections::worker_channel::WorkerEnd,
+};
+use ::std::net::{
+    SocketAddr,
+    SocketAddrV4,
+};
+use std::{
+    rc::Rc,
+    sync::Arc,
 };
-use ::std::net::SocketAddrV4;
-use std::sync::Arc;
 
 #[cfg(feature = "catcollar-libos")]
 use crate::catcollar::CatcollarLibOS;
@@ -92,20 +106,20 @@ impl NetworkLibOS {
     }
 
     /// Binds a socket to a local address.
-    pub fn bind(&mut self, sockqd: QDesc, local: SocketAddrV4) -> Result<(), Fail> {
+    pub fn bind(&mut self, sockqd: QDesc, local: SocketAddr) -> Result<(), Fail> {
         match self {
             #[cfg(feature = "catpowder-libos")]
             NetworkLibOS::Catpowder(libos) => libos.bind(sockqd, local),
             #[cfg(all(feature = "catnap-libos", target_os = "linux"))]
-            NetworkLibOS::Catnap(libos) => libos.bind(sockqd, local),
+            NetworkLibOS::Catnap(libos) => libos.bind(sockqd, socketaddr_to_socketaddrv4(local)?),
             #[cfg(all(feature = "catnapw-libos", target_os = "windows"))]
-            NetworkLibOS::CatnapW(libos) => libos.bind(sockqd, local),
+            NetworkLibOS::CatnapW(libos) => libos.bind(sockqd, socketaddr_to_socketaddrv4(local)?),
             #[cfg(feature = "catcollar-libos")]
-            NetworkLibOS::Catcollar(libos) => libos.bind(sockqd, local),
+            NetworkLibOS::Catcollar(libos) => libos.bind(sockqd, socketaddr_to_socketaddrv4(local)?),
             #[cfg(feature = "catnip-libos")]
             NetworkLibOS::Catnip(libos) => libos.bind(sockqd, local),
             #[cfg(feature = "catloop-libos")]
-            NetworkLibOS::Catloop(libos) => libos.bind(sockqd, local),
+            NetworkLibOS::Catloop(libos) => libos.bind(sockqd, socketaddr_to_socketaddrv4(local)?),
         }
     }
 
@@ -161,20 +175,20 @@ impl NetworkLibOS {
     }
 
     /// Initiates a connection with a remote TCP peer.
-    pub fn connect(&mut self, sockqd: QDesc, remote: SocketAddrV4) -> Result<QToken, Fail> {
+    pub fn connect(&mut self, sockqd: QDesc, remote: SocketAddr) -> Result<QToken, Fail> {
         match self {
             #[cfg(feature = "catpowder-libos")]
             NetworkLibOS::Catpowder(libos) => libos.connect(sockqd, remote),
             #[cfg(all(feature = "catnap-libos", target_os = "linux"))]
-            NetworkLibOS::Catnap(libos) => libos.connect(sockqd, remote),
+            NetworkLibOS::Catnap(libos) => libos.connect(sockqd, socketaddr_to_socketaddrv4(remote)?),
             #[cfg(all(feature = "catnapw-libos", target_os = "windows"))]
-            NetworkLibOS::CatnapW(libos) => libos.connect(sockqd, remote),
+            NetworkLibOS::CatnapW(libos) => libos.connect(sockqd, socketaddr_to_socketaddrv4(remote)?),
             #[cfg(feature = "catcollar-libos")]
-            NetworkLibOS::Catcollar(libos) => libos.connect(sockqd, remote),
+            NetworkLibOS::Catcollar(libos) => libos.connect(sockqd, socketaddr_to_socketaddrv4(remote)?),
             #[cfg(feature = "catnip-libos")]
             NetworkLibOS::Catnip(libos) => libos.connect(sockqd, remote),
             #[cfg(feature = "catloop-libos")]
-            NetworkLibOS::Catloop(libos) => libos.connect(sockqd, remote),
+            NetworkLibOS::Catloop(libos) => libos.connect(sockqd, socketaddr_to_socketaddrv4(remote)?),
         }
     }
 
@@ -213,6 +227,78 @@ impl NetworkLibOS {
         }
     }
 
+    /// Sets an option on a socket.
+    pub fn setsockopt(&mut self, sockqd: QDesc, option: SocketOption) -> Result<(), Fail> {
+        match self {
+            #[cfg(feature = "catpowder-libos")]
+            NetworkLibOS::Catpowder(_) => Err(Fail::new(libc::ENOTSUP, "setsockopt() is not supported")),
+            #[cfg(all(feature = "catnap-libos", target_os = "linux"))]
+            NetworkLibOS::Catnap(_) => Err(Fail::new(libc::ENOTSUP, "setsockopt() is not supported")),
+            #[cfg(all(feature = "catnapw-libos", target_os = "windows"))]
+            NetworkLibOS::CatnapW(_) => Err(Fail::new(libc::ENOTSUP, "setsockopt() is not supported")),
+            #[cfg(feature = "catcollar-libos")]
+            NetworkLibOS::Catcollar(_) => Err(Fail::new(libc::ENOTSUP, "setsockopt() is not supported")),
+            #[cfg(feature = "catnip-libos")]
+            NetworkLibOS::Catnip(libos) => libos.setsockopt(sockqd, option),
+            #[cfg(feature = "catloop-libos")]
+            NetworkLibOS::Catloop(_) => Err(Fail::new(libc::ENOTSUP, "setsockopt() is not supported")),
+        }
+    }
+
+    /// Gets the value of an option on a socket.
+    pub fn getsockopt(&self, sockqd: QDesc, name: SocketOptionName) -> Result<SocketOption, Fail> {
+        match self {
+            #[cfg(feature = "catpowder-libos")]
+            NetworkLibOS::Catpowder(_) => Err(Fail::new(libc::ENOTSUP, "getsockopt() is not supported")),
+            #[cfg(all(feature = "catnap-libos", target_os = "linux"))]
+            NetworkLibOS::Catnap(_) => Err(Fail::new(libc::ENOTSUP, "getsockopt() is not supported")),
+            #[cfg(all(feature = "catnapw-libos", target_os = "windows"))]
+            NetworkLibOS::CatnapW(_) => Err(Fail::new(libc::ENOTSUP, "getsockopt() is not supported")),
+            #[cfg(feature = "catcollar-libos")]
+            NetworkLibOS::Catcollar(_) => Err(Fail::new(libc::ENOTSUP, "getsockopt() is not supported")),
+            #[cfg(feature = "catnip-libos")]
+            NetworkLibOS::Catnip(libos) => libos.getsockopt(sockqd, name),
+            #[cfg(feature = "catloop-libos")]
+            NetworkLibOS::Catloop(_) => Err(Fail::new(libc::ENOTSUP, "getsockopt() is not supported")),
+        }
+    }
+
+    /// Gets the local address of a socket.
+    pub fn getsockname(&self, sockqd: QDesc) -> Result<SocketAddr, Fail> {
+        match self {
+            #[cfg(feature = "catpowder-libos")]
+            NetworkLibOS::Catpowder(_) => Err(Fail::new(libc::ENOTSUP, "getsockname() is not supported")),
+            #[cfg(all(feature = "catnap-libos", target_os = "linux"))]
+            NetworkLibOS::Catnap(_) => Err(Fail::new(libc::ENOTSUP, "getsockname() is not supported")),
+            #[cfg(all(feature = "catnapw-libos", target_os = "windows"))]
+            NetworkLibOS::CatnapW(_) => Err(Fail::new(libc::ENOTSUP, "getsockname() is not supported")),
+            #[cfg(feature = "catcollar-libos")]
+            NetworkLibOS::Catcollar(_) => Err(Fail::new(libc::ENOTSUP, "getsockname() is not supported")),
+            #[cfg(feature = "catnip-libos")]
+            NetworkLibOS::Catnip(libos) => libos.getsockname(sockqd),
+            #[cfg(feature = "catloop-libos")]
+            NetworkLibOS::Catloop(_) => Err(Fail::new(libc::ENOTSUP, "getsockname() is not supported")),
+        }
+    }
+
+    /// Gets the remote address of a socket.
+    pub fn getpeername(&self, sockqd: QDesc) -> Result<SocketAddr, Fail> {
+        match self {
+            #[cfg(feature = "catpowder-libos")]
+            NetworkLibOS::Catpowder(_) => Err(Fail::new(libc::ENOTSUP, "getpeername() is not supported")),
+            #[cfg(all(feature = "catnap-libos", target_os = "linux"))]
+            NetworkLibOS::Catnap(_) => Err(Fail::new(libc::ENOTSUP, "getpeername() is not supported")),
+            #[cfg(all(feature = "catnapw-libos", target_os = "windows"))]
+            NetworkLibOS::CatnapW(_) => Err(Fail::new(libc::ENOTSUP, "getpeername() is not supported")),
+            #[cfg(feature = "catcollar-libos")]
+            NetworkLibOS::Catcollar(_) => Err(Fail::new(libc::ENOTSUP, "getpeername() is not supported")),
+            #[cfg(feature = "catnip-libos")]
+            NetworkLibOS::Catnip(libos) => libos.getpeername(sockqd),
+            #[cfg(feature = "catloop-libos")]
+            NetworkLibOS::Catloop(_) => Err(Fail::new(libc::ENOTSUP, "getpeername() is not supported")),
+        }
+    }
+
     /// Pushes a scatter-gather array to a TCP socket.
     pub fn push(&mut self, cb: *mut ControlBlock<RECEIVE_BATCH_SIZE>, sga: &demi_sgarray_t) -> Result<QToken, Fail> {
         match self {
@@ -232,16 +318,16 @@ impl NetworkLibOS {
     }
 
     /// Pushes a scatter-gather array to a UDP socket.
-    pub fn pushto(&mut self, sockqd: QDesc, sga: &demi_sgarray_t, to: SocketAddrV4) -> Result<QToken, Fail> {
+    pub fn pushto(&mut self, sockqd: QDesc, sga: &demi_sgarray_t, to: SocketAddr) -> Result<QToken, Fail> {
         match self {
             #[cfg(feature = "catpowder-libos")]
             NetworkLibOS::Catpowder(libos) => libos.pushto(sockqd, sga, to),
             #[cfg(all(feature = "catnap-libos", target_os = "linux"))]
-            NetworkLibOS::Catnap(libos) => libos.pushto(sockqd, sga, to),
+            NetworkLibOS::Catnap(libos) => libos.pushto(sockqd, sga, socketaddr_to_socketaddrv4(to)?),
             #[cfg(all(feature = "catnapw-libos", target_os = "windows"))]
-            NetworkLibOS::CatnapW(libos) => libos.pushto(sockqd, sga, to),
+            NetworkLibOS::CatnapW(libos) => libos.pushto(sockqd, sga, socketaddr_to_socketaddrv4(to)?),
             #[cfg(feature = "catcollar-libos")]
-            NetworkLibOS::Catcollar(libos) => libos.pushto(sockqd, sga, to),
+            NetworkLibOS::Catcollar(libos) => libos.pushto(sockqd, sga, socketaddr_to_socketaddrv4(to)?),
             #[cfg(feature = "catnip-libos")]
             NetworkLibOS::Catnip(libos) => libos.pushto(sockqd, sga, to),
             #[cfg(feature = "catloop-libos")]
@@ -407,7 +493,7 @@ impl NetworkLibOS {
         }
     }
 
-    pub fn get_ring(&self) -> *mut RingBuffer<(*mut rte_mbuf, *mut ControlBlock<RECEIVE_BATCH_SIZE>)> {
+    pub fn get_channel(&self) -> Rc<WorkerEnd<WorkerRequest>> {
         match self {
             #[cfg(feature = "catpowder-libos")]
             NetworkLibOS::Catpowder(libos) => libos.poll_bg_work(),
@@ -418,9 +504,22 @@ impl NetworkLibOS {
             #[cfg(feature = "catcollar-libos")]
             NetworkLibOS::Catcollar(libos) => libos.poll(),
             #[cfg(feature = "catnip-libos")]
-            NetworkLibOS::Catnip(libos) => libos.get_ring(),
+            NetworkLibOS::Catnip(libos) => libos.get_channel(),
             #[cfg(feature = "catloop-libos")]
             NetworkLibOS::Catloop(libos) => libos.poll(),
         }
     }
 }
+
+//======================================================================================================================
+// Standalone Functions
+//======================================================================================================================
+
+/// Converts a [SocketAddr] into a [SocketAddrV4], for libOSes that only support IPv4.
+#[allow(dead_code)]
+fn socketaddr_to_socketaddrv4(addr: SocketAddr) -> Result<SocketAddrV4, Fail> {
+    match addr {
+        SocketAddr::V4(addr) => Ok(addr),
+        SocketAddr::V6(_) => Err(Fail::new(libc::EAFNOSUPPORT, "IPv6 is not supported by this libOS")),
+    }
+}
@@ -34,18 +34,25 @@ impl<V> Record<V> {
         }
         false
     }
+
+    /// Asserts if the target cache entry expires before `deadline`, without having expired at `now`.
+    fn expires_before(&self, now: Instant, deadline: Instant) -> bool {
+        match self.expiration {
+            Some(e) => now < e && e < deadline,
+            None => false,
+        }
+    }
 }
 
 /// # TTL Cache
 ///
 /// Entries in this structure fall in one of the following kinds: those that
 /// have an expiration time, and those that don't. The latter are assigned to
-/// `None` expiration.
+/// `None` expiration. Expired entries are never returned, and are dropped on
+/// the next cleanup.
 pub struct HashTtlCache<K, V> {
     /// Living values.
     map: HashMap<K, Record<V>>,
-    /// Dead values.
-    graveyard: HashMap<K, V>,
     /// Default expiration.
     default_ttl: Option<Duration>,
     /// Current time.
@@ -64,7 +71,6 @@ where
 
         HashTtlCache {
             map: HashMap::default(),
-            graveyard: HashMap::default(),
             default_ttl,
             clock: now,
         }
@@ -72,7 +78,6 @@ where
 
     // Cleanups the cache.
     pub fn clear(&mut self) {
-        self.graveyard.clear();
         self.map.clear();
     }
 
@@ -113,15 +118,10 @@ where
         self.insert_with_ttl(key, value, self.default_ttl)
     }
 
-    /// Removes an entry from the cache.
+    /// Removes an entry from the cache, and returns its value.
     pub fn remove(&mut self, key: &K) -> Option<V> {
         match self.get(key) {
-            Some(_) => {
-                let (k, v): (K, Record<V>) = self.map.remove_entry(key).unwrap();
-                // FIXME: should be returning the removed memory not the old one from
-                // the graveyard, but we can't without cloning the removed value
-                self.graveyard.insert(k, v.value)
-            },
+            Some(_) => self.map.remove(key).map(|r| r.value),
             None => {
                 warn!("Trying to remove key that does not exist");
                 None
@@ -129,9 +129,26 @@ where
         }
     }
 
-    // Gets an entry from the cache.
+    // Gets an entry from the cache, unless it has expired.
     pub fn get(&self, key: &K) -> Option<&V> {
-        return self.map.get(key).map(|r| &r.value);
+        match self.map.get(key) {
+            Some(r) if !r.has_expired(self.clock) => Some(&r.value),
+            _ => None,
+        }
+    }
+
+    /// Iterates over the entries that expire within `window`, so that they can be refreshed before they go away.
+    /// Entries without an expiration time never show up.
+    pub fn expiring(&self, window: Duration) -> impl Iterator<Item = (&K, &V)> {
+        let clock: Instant = self.clock;
+        let deadline: Instant = clock + window;
+        self.map.iter().flat_map(move |(key, record)| {
+            if record.expires_before(clock, deadline) {
+                Some((key, &record.value))
+            } else {
+                None
+            }
+        })
     }
 
     // Iterator.
@@ -146,21 +163,11 @@ where
         })
     }
 
-    /// Collect dead entries in the cache.
-    pub fn cleanup(&mut self) {
-        let mut dead_entries: Vec<K> = Vec::new();
-
-        // Collect dead entries.
-        for (k, v) in self.map.iter() {
-            if v.has_expired(self.clock) {
-                dead_entries.push(k.clone());
-            }
-        }
-
-        // Put dead_entries entries in the graveyard.
-        for k in dead_entries {
-            let (k, v) = self.map.remove_entry(&k).unwrap();
-            self.graveyard.insert(k, v.value);
-        }
+    /// Drops dead entries from the cache, and returns how many there were.
+    pub fn cleanup(&mut self) -> usize {
+        let clock: Instant = self.clock;
+        let len: usize = self.map.len();
+        self.map.retain(|_, r| !r.has_expired(clock));
+        len - self.map.len()
     }
 }
@@ -165,3 +165,48 @@ fn add_and_remove_object() -> Result<()> {
 
     Ok(())
 }
+
+/// Tests that expired objects are not returned, even before the cache gets cleaned up.
+#[test]
+fn hide_expired_before_cleanup() -> Result<()> {
+    let now: Instant = Instant::now();
+    let ttl: Duration = Duration::from_secs(1);
+    let mut cache: HashTtlCache<&str, char> = HashTtlCache::new(now, Some(ttl));
+    cache.insert("a", 'a');
+    cache.insert_with_ttl("b", 'b', None);
+
+    cache.advance_clock(now + ttl);
+    crate::ensure_eq!(cache.get(&"a"), None);
+    crate::ensure_eq!(cache.iter().count(), 1);
+    crate::ensure_eq!(cache.cleanup(), 1);
+    crate::ensure_eq!(cache.get(&"b"), Some(&'b'));
+
+    Ok(())
+}
+
+/// Tests that objects show up as expiring only within the window that precedes their expiration.
+#[test]
+fn expiring_window() -> Result<()> {
+    let now: Instant = Instant::now();
+    let ttl: Duration = Duration::from_secs(10);
+    let window: Duration = Duration::from_secs(3);
+    let mut cache: HashTtlCache<&str, char> = HashTtlCache::new(now, Some(ttl));
+    cache.insert("a", 'a');
+    cache.insert_with_ttl("b", 'b', None);
+    crate::ensure_eq!(cache.expiring(window).count(), 0);
+
+    cache.advance_clock(now + Duration::from_secs(8));
+    let expiring: Vec<(&&str, &char)> = cache.expiring(window).collect();
+    crate::ensure_eq!(expiring, vec![(&"a", &'a')]);
+
+    // Refreshing an object pushes its expiration back.
+    cache.insert("a", 'a');
+    crate::ensure_eq!(cache.expiring(window).count(), 0);
+
+    // Expired objects are no longer worth refreshing.
+    cache.advance_clock(now + Duration::from_secs(20));
+    crate::ensure_eq!(cache.expiring(window).count(), 0);
+    crate::ensure_eq!(cache.remove(&"b"), Some('b'));
+
+    Ok(())
+}
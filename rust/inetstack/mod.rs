@@ -8,14 +8,19 @@
 use crate::{
     inetstack::protocols::{
         ethernet2::{
+            EtherType2,
             Ethernet2Header,
         },
+        ipv4::RoutingTable,
+        queue::InetQueue,
         tcp::{
             operations::{
                 PushFuture, 
                 PopFuture,
             },
             established::ControlBlock,
+            peer::Socket,
+            queue::TcpQueue,
         },
         Peer,
     },
@@ -26,11 +31,16 @@ use crate::{
             config::{
                 TcpConfig,
             },
+            socket::{
+                SocketOption,
+                SocketOptionName,
+            },
             types::MacAddress,
             NetworkRuntime, 
             consts::RECEIVE_BATCH_SIZE,
         },
         queue::{
+            IoQueueTable,
             Operation,
             OperationResult,
             OperationTask,
@@ -49,7 +59,8 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use ::std::{
     net::{
         Ipv4Addr,
-        SocketAddrV4,
+        Ipv6Addr,
+        SocketAddr,
     },
     pin::Pin,
     sync::Arc,
@@ -68,6 +79,9 @@ pub mod futures;
 pub mod options;
 pub mod protocols;
 
+#[cfg(test)]
+pub mod test_helpers;
+
 //======================================================================================================================
 // Constants
 //======================================================================================================================
@@ -106,12 +120,15 @@ impl TASLock {
 }
 
 pub struct InetStack<const N: usize> {
-    ipv4: Peer<N>,
+    ip: Peer<N>,
     rt: Arc<dyn NetworkRuntime<RECEIVE_BATCH_SIZE>>,
     local_link_addr: MacAddress,
     scheduler: Arc<Scheduler>,
     clock: TimerRc,
     ts_iters: usize,
+    qtable: IoQueueTable<InetQueue<N>>,
+    /// Options that new sockets start with.
+    tcp_config: TcpConfig,
 }
 
 impl<const N: usize> InetStack<N> {
@@ -121,19 +138,25 @@ impl<const N: usize> InetStack<N> {
         clock: TimerRc,
         local_link_addr: MacAddress,
         local_ipv4_addr: Ipv4Addr,
+        routes: RoutingTable,
+        local_ipv6_addr: Option<Ipv6Addr>,
         tcp_config: TcpConfig,
     ) -> Result<Self, Fail> {
-        let ipv4: Peer<N> = Peer::new(
+        let ip: Peer<N> = Peer::new(
             local_ipv4_addr,
-            tcp_config,
+            routes,
+            local_ipv6_addr,
+            tcp_config.clone(),
         )?;
         Ok(Self {
-            ipv4,
+            ip,
             rt,
             local_link_addr,
             scheduler,
             clock,
             ts_iters: 0,
+            qtable: IoQueueTable::new(),
+            tcp_config,
         })
     }
 
@@ -163,14 +186,25 @@ impl<const N: usize> InetStack<N> {
     /// defined in the libc crate. Currently, the following families are supported:
     ///
     /// - AF_INET Internet Protocol Version 4 (IPv4)
+    /// - AF_INET6 Internet Protocol Version 6 (IPv6)
     ///
     /// **Return Vale**
     ///
     /// Upon successful completion, a file descriptor for the newly created
     /// socket is returned. Upon failure, `Fail` is returned instead.
     ///
-    pub fn socket(&mut self, _domain: c_int, _socket_type: c_int, _protocol: c_int) -> Result<QDesc, Fail> {
-        Err(Fail::new(libc::ENOTSUP, "Not supported"))
+    pub fn socket(&mut self, domain: c_int, socket_type: c_int, _protocol: c_int) -> Result<QDesc, Fail> {
+        trace!("socket(): domain={:?} type={:?}", domain, socket_type);
+
+        if domain != libc::AF_INET && domain != libc::AF_INET6 {
+            return Err(Fail::new(libc::ENOTSUP, "address family not supported"));
+        }
+        if socket_type != libc::SOCK_STREAM {
+            return Err(Fail::new(libc::ENOTSUP, "socket type not supported"));
+        }
+
+        let queue: TcpQueue<N> = TcpQueue::with_config(self.tcp_config.clone());
+        Ok(self.qtable.alloc(InetQueue::Tcp(queue)))
     }
 
     ///
@@ -184,8 +218,40 @@ impl<const N: usize> InetStack<N> {
     /// Upon successful completion, `Ok(())` is returned. Upon failure, `Fail` is
     /// returned instead.
     ///
-    pub fn bind(&mut self, _qd: QDesc, _local: SocketAddrV4) -> Result<(), Fail> {
-        Err(Fail::new(libc::ENOTSUP, "Not supported"))
+    pub fn bind(&mut self, qd: QDesc, local: SocketAddr) -> Result<(), Fail> {
+        trace!("bind(): qd={:?} local={:?}", qd, local);
+
+        let reuse_addr: bool = self.get_tcp_queue(&qd)?.get_tcp_config().get_reuse_addr();
+
+        // Check whether the address is in use. Sockets that both allow it may share an address, unless one listens.
+        for (_, queue) in self.qtable.get_values() {
+            let InetQueue::Tcp(queue) = queue;
+            let in_use: bool = match queue.get_socket() {
+                Socket::Inactive(Some(addr)) => {
+                    *addr == local && !(reuse_addr && queue.get_tcp_config().get_reuse_addr())
+                },
+                Socket::Listening(socket) => socket.endpoint() == local,
+                _ => false,
+            };
+            if in_use {
+                let cause: String = format!("address is already bound to a socket (qd={:?})", qd);
+                error!("bind(): {}", cause);
+                return Err(Fail::new(libc::EADDRINUSE, &cause));
+            }
+        }
+
+        let queue: &mut TcpQueue<N> = self.get_tcp_queue_mut(&qd)?;
+        match queue.get_socket() {
+            Socket::Inactive(None) => {
+                queue.set_socket(Socket::Inactive(Some(local)));
+                Ok(())
+            },
+            _ => {
+                let cause: String = format!("socket is already bound to an address (qd={:?})", qd);
+                error!("bind(): {}", cause);
+                Err(Fail::new(libc::EINVAL, &cause))
+            },
+        }
     }
 
     ///
@@ -236,7 +302,7 @@ impl<const N: usize> InetStack<N> {
     /// remote endpoints. Upon failure, `Fail` is
     /// returned instead.
     ///
-    pub fn connect(&mut self, _qd: QDesc, _remote: SocketAddrV4) -> Result<QToken, Fail> {
+    pub fn connect(&mut self, _qd: QDesc, _remote: SocketAddr) -> Result<QToken, Fail> {
         Err(Fail::new(libc::ENOTSUP, "Not supported"))
     }
 
@@ -250,8 +316,36 @@ impl<const N: usize> InetStack<N> {
     /// Upon successful completion, `Ok(())` is returned. Upon failure, `Fail` is
     /// returned instead.
     ///
-    pub fn close(&mut self, _qd: QDesc) -> Result<(), Fail> {
-        Err(Fail::new(libc::ENOTSUP, "Not supported"))
+    pub fn close(&mut self, qd: QDesc) -> Result<(), Fail> {
+        trace!("close(): qd={:?}", qd);
+
+        if let Socket::Established(socket) = self.get_tcp_queue(&qd)?.get_socket() {
+            socket.close()?;
+        }
+        self.qtable.free(&qd);
+        Ok(())
+    }
+
+    /// Sets an option on the socket referred to by `qd`.
+    pub fn setsockopt(&mut self, qd: QDesc, option: SocketOption) -> Result<(), Fail> {
+        trace!("setsockopt(): qd={:?} option={:?}", qd, option);
+        self.get_tcp_queue_mut(&qd)?.setsockopt(option)
+    }
+
+    /// Gets the value of an option on the socket referred to by `qd`.
+    pub fn getsockopt(&self, qd: QDesc, name: SocketOptionName) -> Result<SocketOption, Fail> {
+        trace!("getsockopt(): qd={:?} name={:?}", qd, name);
+        Ok(self.get_tcp_queue(&qd)?.getsockopt(name))
+    }
+
+    /// Gets the local address of the socket referred to by `qd`.
+    pub fn getsockname(&self, qd: QDesc) -> Result<SocketAddr, Fail> {
+        self.get_tcp_queue(&qd)?.getsockname()
+    }
+
+    /// Gets the remote address of the socket referred to by `qd`.
+    pub fn getpeername(&self, qd: QDesc) -> Result<SocketAddr, Fail> {
+        self.get_tcp_queue(&qd)?.getpeername()
     }
 
     ///
@@ -273,7 +367,9 @@ impl<const N: usize> InetStack<N> {
     pub fn do_push(&mut self, cb: *mut ControlBlock<RECEIVE_BATCH_SIZE>, buf: DemiBuffer) -> Result<OperationTask, Fail> {
         unsafe { debug!("do_push(): qd={:?}", (*cb).qd) };
 
-        let future: PushFuture<RECEIVE_BATCH_SIZE> = self.ipv4.tcp.push(cb, buf);
+        let future: PushFuture<RECEIVE_BATCH_SIZE> = self.ip.tcp.push(cb, buf);
+        // Pushing may have armed timers that the dispatcher has to know about.
+        unsafe { (*cb).publish_timers() };
         let coroutine: Pin<Box<Operation>> = Box::pin(async move {
             // Wait for push to complete.
             let result: Result<(), Fail> = future.await;
@@ -295,13 +391,13 @@ impl<const N: usize> InetStack<N> {
 
     /// Pushes a buffer to a UDP socket.
     /// TODO: Rename this function to pushto() once we have a common buffer representation across all libOSes.
-    pub fn do_pushto(&mut self, _qd: QDesc, _buf: DemiBuffer, _to: SocketAddrV4) -> Result<OperationTask, Fail> {
+    pub fn do_pushto(&mut self, _qd: QDesc, _buf: DemiBuffer, _to: SocketAddr) -> Result<OperationTask, Fail> {
         Err(Fail::new(libc::ENOTSUP, "Not supported"))
     }
 
     /// Pushes raw data to a UDP socket.
     /// TODO: Move this function to demikernel repo once we have a common buffer representation across all libOSes.
-    pub fn pushto2(&mut self, _qd: QDesc, _data: &[u8], _remote: SocketAddrV4) -> Result<QToken, Fail> {
+    pub fn pushto2(&mut self, _qd: QDesc, _data: &[u8], _remote: SocketAddr) -> Result<QToken, Fail> {
         Err(Fail::new(libc::ENOTSUP, "Not supported"))
     }
 
@@ -347,7 +443,11 @@ impl<const N: usize> InetStack<N> {
         {
             return Err(Fail::new(libc::EINVAL, "physical destination address mismatch"));
         }
-        self.ipv4.receive(cb, payload)
+        match header.ether_type() {
+            EtherType2::Ipv4 => self.ip.receive(cb, payload, self.clock.now()),
+            EtherType2::Ipv6 => self.ip.receive_ipv6(cb, payload),
+            _ => Err(Fail::new(libc::ENOTSUP, "unsupported ether type")),
+        }
     }
 
     /// Scheduler will poll all futures that are ready to make progress.
@@ -388,7 +488,7 @@ impl<const N: usize> InetStack<N> {
                         (*cb).set_scheduler(self.scheduler.clone());
 
                         let task_id: String = format!("Inetstack::TCP::pop for qd={:?}", (*cb).qd);
-                        let future: PopFuture<RECEIVE_BATCH_SIZE> = self.ipv4.tcp.pop(cb, None);
+                        let future: PopFuture<RECEIVE_BATCH_SIZE> = self.ip.tcp.pop(cb, None);
                         let coroutine: Pin<Box<Operation>> = Box::pin(async move {
                             // Wait for pop to complete.
                             let result: Result<DemiBuffer, Fail> = future.await;
@@ -410,17 +510,24 @@ impl<const N: usize> InetStack<N> {
 
                     self.scheduler.poll();
 
-                    let buf: DemiBuffer = unsafe { DemiBuffer::from_mbuf(pkt) };
+                    // Requests without a packet are sent by the dispatcher only so that we service the timers.
+                    if !pkt.is_null() {
+                        let buf: DemiBuffer = unsafe { DemiBuffer::from_mbuf(pkt) };
 
-                    if let Err(e) = self.do_receive(cb, buf) {
-                        warn!("Dropped packet: {:?}", e);
+                        if let Err(e) = self.do_receive(cb, buf) {
+                            warn!("Dropped packet: {:?}", e);
+                        }
                     }
+                    unsafe { (*cb).poll_timers() };
                     // // TODO: This is a workaround for https://github.com/demikernel/inetstack/issues/149.
                     self.scheduler.poll();
                 }
             }
         }
 
+        // Send whatever this poll produced.
+        self.rt.flush();
+
         if self.ts_iters == 0 {
             self.clock.advance_clock(Instant::now());
         }
@@ -432,4 +539,20 @@ impl<const N: usize> InetStack<N> {
     pub fn get_clock(&self) -> TimerRc {
         self.clock.clone()
     }
+
+    /// Looks up the TCP queue associated with a queue descriptor.
+    fn get_tcp_queue(&self, qd: &QDesc) -> Result<&TcpQueue<N>, Fail> {
+        match self.qtable.get(qd) {
+            Some(InetQueue::Tcp(queue)) => Ok(queue),
+            None => Err(Fail::new(libc::EBADF, "bad queue descriptor")),
+        }
+    }
+
+    /// Looks up the TCP queue associated with a queue descriptor, for modification.
+    fn get_tcp_queue_mut(&mut self, qd: &QDesc) -> Result<&mut TcpQueue<N>, Fail> {
+        match self.qtable.get_mut(qd) {
+            Some(InetQueue::Tcp(queue)) => Ok(queue),
+            None => Err(Fail::new(libc::EBADF, "bad queue descriptor")),
+        }
+    }
 }
@@ -9,6 +9,7 @@ use crate::{
     },
 };
 use ::std::{
+    cell::Cell,
     collections::HashMap,
     net::Ipv4Addr,
     time::{
@@ -30,13 +31,15 @@ const DUMMY_MAC_ADDRESS: MacAddress = MacAddress::new([0; 6]);
 #[derive(Debug)]
 struct Record {
     link_addr: MacAddress,
+    /// Whether the resolution was looked up since it was last refreshed.  Only those are worth refreshing, the others
+    /// are left to age out.
+    used: Cell<bool>,
 }
 
 ///
 /// # ARP Cache
 /// - TODO: Allow multiple waiters for the same address
 /// - TODO: Deregister waiters here when the receiver goes away.
-/// - TODO: Implement eviction.
 /// - TODO: Implement remove.
 pub struct ArpCache {
     /// Cache for IPv4 Addresses
@@ -63,10 +66,14 @@ impl ArpCache {
             disable,
         };
 
-        // Populate cache.
+        // Populate cache.  Configured resolutions never expire.
         if let Some(values) = values {
             for (&k, &v) in values {
-                peer.insert(k, v);
+                let record = Record {
+                    link_addr: v,
+                    used: Cell::new(false),
+                };
+                peer.cache.insert_with_ttl(k, record, None);
             }
         }
 
@@ -75,7 +82,10 @@ impl ArpCache {
 
     /// Caches an address resolution.
     pub fn insert(&mut self, ipv4_addr: Ipv4Addr, link_addr: MacAddress) -> Option<MacAddress> {
-        let record = Record { link_addr };
+        let record = Record {
+            link_addr,
+            used: Cell::new(false),
+        };
         self.cache.insert(ipv4_addr, record).map(|r| r.link_addr)
     }
 
@@ -84,15 +94,38 @@ impl ArpCache {
         if self.disable {
             Some(&DUMMY_MAC_ADDRESS)
         } else {
-            self.cache.get(&ipv4_addr).map(|r| &r.link_addr)
+            self.cache.get(&ipv4_addr).map(|r| {
+                r.used.set(true);
+                &r.link_addr
+            })
         }
     }
 
+    /// Checks whether the MAC address of given IPv4 address is known, without counting as a use of it.
+    pub fn contains(&self, ipv4_addr: Ipv4Addr) -> bool {
+        self.cache.get(&ipv4_addr).is_some()
+    }
+
+    /// Takes the resolutions that are in use and expire within `window`, so that they can be refreshed.  Each of them
+    /// is only returned again once it gets used again.
+    pub fn take_stale(&self, window: Duration) -> Vec<(Ipv4Addr, MacAddress)> {
+        self.cache
+            .expiring(window)
+            .filter(|(_, r)| r.used.replace(false))
+            .map(|(k, r)| (*k, r.link_addr))
+            .collect()
+    }
+
     /// Advances internal clock of the ARP Cache.
     pub fn advance_clock(&mut self, now: Instant) {
         self.cache.advance_clock(now)
     }
 
+    /// Evicts expired resolutions, and returns how many there were.
+    pub fn cleanup(&mut self) -> usize {
+        self.cache.cleanup()
+    }
+
     /// Clears the ARP cache.
     #[allow(unused)]
     pub fn clear(&mut self) {
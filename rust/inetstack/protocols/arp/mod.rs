@@ -4,5 +4,12 @@
 mod cache;
 mod packet;
 mod peer;
+mod pending;
 
-pub use peer::ArpPeer;
+pub use self::{
+    peer::{
+        ArpPeer,
+        ArpStats,
+    },
+    pending::PendingPacket,
+};
@@ -8,6 +8,10 @@ use super::{
         ArpMessage,
         ArpOperation,
     },
+    pending::{
+        PendingPacket,
+        PendingQueue,
+    },
 };
 use crate::{
     inetstack::{
@@ -42,28 +46,71 @@ use ::futures::{
     FutureExt,
 };
 use ::libc::{
+    EADDRINUSE,
     EBADMSG,
     ETIMEDOUT,
 };
 use ::std::{
     cell::{
+        Cell,
         RefCell,
         RefMut,
     },
+    cmp,
     collections::{
+        hash_map::Entry,
         HashMap,
         LinkedList,
     },
     future::Future,
     net::Ipv4Addr,
     rc::Rc,
-    time::Duration,
+    time::{
+        Duration,
+        Instant,
+    },
 };
 
+//==============================================================================
+// Constants
+//==============================================================================
+
+/// Number of announcements that we send for our address (RFC 5227 Section 1.1).
+const ANNOUNCE_NUM: usize = 2;
+
+/// Time between announcements (RFC 5227 Section 1.1).
+const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Minimum time between announcements that defend our address against a conflicting host (RFC 5227 Section 1.1).
+const DEFEND_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Longest time before expiry at which resolutions that are in use get refreshed.
+const MAX_REFRESH_WINDOW: Duration = Duration::from_secs(5);
+
 //==============================================================================
 // Structures
 //==============================================================================
 
+/// Counters of the ARP peer.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ArpStats {
+    /// Requests sent, including refreshes and retries.
+    pub requests_sent: u64,
+    pub replies_sent: u64,
+    pub requests_received: u64,
+    pub replies_received: u64,
+    /// Resolutions that expired.
+    pub evicted: u64,
+    /// Packets that had to wait for a resolution.
+    pub queued: u64,
+    /// Packets that were dropped while waiting for a resolution, because the queue was full or the resolution failed.
+    pub dropped: u64,
+    /// Messages from another host that claims our address.
+    pub conflicts: u64,
+    /// Announcements of our address, including the ones that defend it.
+    pub announcements: u64,
+}
+
 ///
 /// Arp Peer
 ///
@@ -75,9 +122,14 @@ pub struct ArpPeer<const N: usize> {
     local_ipv4_addr: Ipv4Addr,
     cache: Rc<RefCell<ArpCache>>,
     waiters: Rc<RefCell<HashMap<Ipv4Addr, LinkedList<Sender<MacAddress>>>>>,
+    /// Packets that wait for a resolution, by next hop.
+    pending: Rc<RefCell<HashMap<Ipv4Addr, PendingQueue>>>,
+    stats: Rc<RefCell<ArpStats>>,
+    /// When we last defended our address.
+    last_defense: Rc<Cell<Option<Instant>>>,
     arp_config: ArpConfig,
 
-    /// The background co-routine cleans up the ARP cache from time to time.
+    /// The background co-routine ages the ARP cache, retries pending resolutions and announces our address.
     /// We annotate it as unused because the compiler believes that it is never called which is not the case.
     #[allow(unused)]
     background: Rc<TaskHandle>,
@@ -103,10 +155,22 @@ impl<const N: usize> ArpPeer<N> {
             arp_config.get_disable_arp(),
         )));
 
+        let pending: Rc<RefCell<HashMap<Ipv4Addr, PendingQueue>>> = Rc::new(RefCell::new(HashMap::default()));
+        let stats: Rc<RefCell<ArpStats>> = Rc::new(RefCell::new(ArpStats::default()));
+
         // This is a future returned by the async function.
         let task: BackgroundTask = BackgroundTask::new(
             String::from("Inetstack::arp::background"),
-            Box::pin(Self::background(clock.clone(), cache.clone())),
+            Box::pin(Self::background(
+                rt.clone(),
+                clock.clone(),
+                local_link_addr,
+                local_ipv4_addr,
+                arp_config.clone(),
+                cache.clone(),
+                pending.clone(),
+                stats.clone(),
+            )),
         );
         let handle: TaskHandle = match scheduler.insert(task) {
             Some(handle) => handle,
@@ -124,10 +188,19 @@ impl<const N: usize> ArpPeer<N> {
             local_ipv4_addr,
             cache,
             waiters: Rc::new(RefCell::new(HashMap::default())),
+            pending,
+            stats,
+            last_defense: Rc::new(Cell::new(None)),
             arp_config,
             background: Rc::new(handle),
         };
 
+        // Let the network know about our address, so that stale resolutions of other hosts get updated.  The
+        // background co-routine sends the remaining announcements.
+        if !peer.arp_config.get_disable_arp() {
+            peer.announce();
+        }
+
         Ok(peer)
     }
 
@@ -142,6 +215,12 @@ impl<const N: usize> ArpPeer<N> {
                 let _ = sender.send(link_addr);
             }
         }
+        let queue: Option<PendingQueue> = self.pending.borrow_mut().remove(&ipv4_addr);
+        if let Some(queue) = queue {
+            for pkt in queue.drain(link_addr) {
+                self.rt.transmit(pkt);
+            }
+        }
         self.cache.borrow_mut().insert(ipv4_addr, link_addr)
     }
 
@@ -163,20 +242,124 @@ impl<const N: usize> ArpPeer<N> {
         rx.map(|r| r.expect("Dropped waiter?"))
     }
 
-    /// Background task that cleans up the ARP cache from time to time.
-    async fn background(clock: TimerRc, cache: Rc<RefCell<ArpCache>>) {
+    /// Builds a request for the link address of `ipv4_addr`, sent to `dst_link_addr`.
+    fn request(
+        local_link_addr: MacAddress,
+        local_ipv4_addr: Ipv4Addr,
+        dst_link_addr: MacAddress,
+        ipv4_addr: Ipv4Addr,
+    ) -> ArpMessage {
+        ArpMessage::new(
+            Ethernet2Header::new(dst_link_addr, local_link_addr, EtherType2::Arp),
+            ArpHeader::new(
+                ArpOperation::Request,
+                local_link_addr,
+                local_ipv4_addr,
+                MacAddress::broadcast(),
+                ipv4_addr,
+            ),
+        )
+    }
+
+    /// Builds an announcement of our address: a broadcast request in which we ask for our own address
+    /// (RFC 5227 Section 2.3).
+    fn announcement(local_link_addr: MacAddress, local_ipv4_addr: Ipv4Addr) -> ArpMessage {
+        ArpMessage::new(
+            Ethernet2Header::new(MacAddress::broadcast(), local_link_addr, EtherType2::Arp),
+            ArpHeader::new(
+                ArpOperation::Request,
+                local_link_addr,
+                local_ipv4_addr,
+                MacAddress::nil(),
+                local_ipv4_addr,
+            ),
+        )
+    }
+
+    /// Background task that ages the ARP cache, retries pending resolutions and announces our address.
+    async fn background(
+        rt: Rc<dyn NetworkRuntime<N>>,
+        clock: TimerRc,
+        local_link_addr: MacAddress,
+        local_ipv4_addr: Ipv4Addr,
+        arp_config: ArpConfig,
+        cache: Rc<RefCell<ArpCache>>,
+        pending: Rc<RefCell<HashMap<Ipv4Addr, PendingQueue>>>,
+        stats: Rc<RefCell<ArpStats>>,
+    ) {
+        // Resolutions that are in use get refreshed while there is still time to retry before they expire.
+        let refresh_window: Duration = cmp::min(MAX_REFRESH_WINDOW, arp_config.get_cache_ttl() / 3);
+        let mut announcements_left: usize = if arp_config.get_disable_arp() {
+            0
+        } else {
+            ANNOUNCE_NUM - 1
+        };
+        let mut next_announcement: Instant = clock.now() + ANNOUNCE_INTERVAL;
         loop {
-            let current_time = clock.now();
-            {
+            let current_time: Instant = clock.now();
+            let stale: Vec<(Ipv4Addr, MacAddress)> = {
                 let mut cache = cache.borrow_mut();
                 cache.advance_clock(current_time);
-                // TODO: re-enable eviction once TCP/IP stack is fully functional.
-                // cache.clear();
+                stats.borrow_mut().evicted += cache.cleanup() as u64;
+                cache.take_stale(refresh_window)
+            };
+
+            // Refresh with unicast requests, which do not bother the other hosts (RFC 1122 Section 2.3.2.1).
+            for (ipv4_addr, link_addr) in stale {
+                debug!("refreshing `{}/{}`", ipv4_addr, link_addr);
+                rt.transmit(Box::new(Self::request(
+                    local_link_addr,
+                    local_ipv4_addr,
+                    link_addr,
+                    ipv4_addr,
+                )));
+                stats.borrow_mut().requests_sent += 1;
+            }
+
+            // Retry the resolutions that packets are waiting for, and drop the packets of those that failed.
+            let mut failed: Vec<Ipv4Addr> = Vec::new();
+            for (&ipv4_addr, queue) in pending.borrow_mut().iter_mut() {
+                if !queue.is_expired(current_time) {
+                    continue;
+                }
+                if queue.retry(current_time + arp_config.get_request_timeout()) {
+                    rt.transmit(Box::new(Self::request(
+                        local_link_addr,
+                        local_ipv4_addr,
+                        MacAddress::broadcast(),
+                        ipv4_addr,
+                    )));
+                    stats.borrow_mut().requests_sent += 1;
+                } else {
+                    failed.push(ipv4_addr);
+                }
+            }
+            for ipv4_addr in failed {
+                if let Some(queue) = pending.borrow_mut().remove(&ipv4_addr) {
+                    warn!("ARP query timeout; dropping {} packets for {}", queue.len(), ipv4_addr);
+                    stats.borrow_mut().dropped += queue.len() as u64;
+                }
+            }
+
+            if announcements_left > 0 && current_time >= next_announcement {
+                rt.transmit(Box::new(Self::announcement(local_link_addr, local_ipv4_addr)));
+                stats.borrow_mut().announcements += 1;
+                announcements_left -= 1;
+                next_announcement += ANNOUNCE_INTERVAL;
             }
+
             clock.wait(clock.clone(), Duration::from_secs(1)).await;
         }
     }
 
+    /// Announces our address.
+    fn announce(&self) {
+        debug!("announcing `{}/{}`", self.local_ipv4_addr, self.local_link_addr);
+        self.rt
+            .transmit(Box::new(Self::announcement(self.local_link_addr, self.local_ipv4_addr)));
+        self.stats.borrow_mut().announcements += 1;
+    }
+
     pub fn receive(&mut self, buf: DemiBuffer) -> Result<(), Fail> {
         // from RFC 826:
         // > ?Do I have the hardware type in ar$hrd?
@@ -186,6 +369,45 @@ impl<const N: usize> ArpPeer<N> {
         let header = ArpHeader::parse(buf)?;
         debug!("Received {:?}", header);
 
+        // Our own announcements may come back to us, there is nothing to learn from them.
+        if header.get_sender_hardware_addr() == self.local_link_addr {
+            return Ok(());
+        }
+
+        // from RFC 5227:
+        // > At any time, if a host receives an ARP packet (request *or* reply) where the 'sender IP address' is (one
+        // > of) the host's own IP address(es) configured on that interface, but the 'sender hardware address' does not
+        // > match any of the host's own interface addresses, then this is a conflicting ARP packet.
+        if header.get_sender_protocol_addr() == self.local_ipv4_addr
+            && header.get_sender_hardware_addr() != self.local_link_addr
+        {
+            warn!(
+                "address conflict with `{}/{}`",
+                header.get_sender_protocol_addr(),
+                header.get_sender_hardware_addr()
+            );
+            self.stats.borrow_mut().conflicts += 1;
+            // Defend our address, but do not get into an endless fight over it.
+            let now: Instant = self.clock.now();
+            let defend: bool = match self.last_defense.get() {
+                Some(last_defense) => now.saturating_duration_since(last_defense) >= DEFEND_INTERVAL,
+                None => true,
+            };
+            if defend {
+                self.last_defense.set(Some(now));
+                self.announce();
+            }
+            return Err(Fail::new(EADDRINUSE, "IPv4 address conflict"));
+        }
+
+        match header.get_operation() {
+            ArpOperation::Request => self.stats.borrow_mut().requests_received += 1,
+            ArpOperation::Reply => self.stats.borrow_mut().replies_received += 1,
+        }
+
+        // Probes for an address come from 0.0.0.0 (RFC 5227 Section 2.1.1), which is nothing to cache.
+        let is_probe: bool = header.get_sender_protocol_addr().is_unspecified();
+
         // from RFC 826:
         // > Merge_flag := false
         // > If the pair <protocol type, sender protocol address> is
@@ -193,7 +415,7 @@ impl<const N: usize> ArpPeer<N> {
         // > hardware address field of the entry with the new
         // > information in the packet and set Merge_flag to true.
         let merge_flag = {
-            if self.cache.borrow().get(header.get_sender_protocol_addr()).is_some() {
+            if !is_probe && self.cache.borrow().contains(header.get_sender_protocol_addr()) {
                 self.do_insert(header.get_sender_protocol_addr(), header.get_sender_hardware_addr());
                 true
             } else {
@@ -214,7 +436,7 @@ impl<const N: usize> ArpPeer<N> {
         // > If Merge_flag is false, add the triplet <protocol type,
         // > sender protocol address, sender hardware address> to
         // > the translation table.
-        if !merge_flag {
+        if !merge_flag && !is_probe {
             self.do_insert(header.get_sender_protocol_addr(), header.get_sender_hardware_addr());
         }
 
@@ -235,6 +457,7 @@ impl<const N: usize> ArpPeer<N> {
                 );
                 debug!("Responding {:?}", reply);
                 self.rt.transmit(Box::new(reply));
+                self.stats.borrow_mut().replies_sent += 1;
                 Ok(())
             },
             ArpOperation::Reply => {
@@ -255,6 +478,49 @@ impl<const N: usize> ArpPeer<N> {
         self.cache.borrow().get(ipv4_addr).cloned()
     }
 
+    /// Sends the packet that `build` makes for the link address of `ipv4_addr`.  If that address is not known yet,
+    /// the packet waits for it, along with the latest others for the same address, instead of being dropped.
+    pub fn transmit_to(&self, ipv4_addr: Ipv4Addr, build: PendingPacket) {
+        let link_addr: Option<MacAddress> = self.cache.borrow().get(ipv4_addr).copied();
+        if let Some(link_addr) = link_addr {
+            self.rt.transmit(build(link_addr));
+            return;
+        }
+
+        let mut stats: RefMut<ArpStats> = self.stats.borrow_mut();
+        stats.queued += 1;
+        match self.pending.borrow_mut().entry(ipv4_addr) {
+            Entry::Occupied(mut entry) => {
+                if entry.get_mut().push(build) {
+                    stats.dropped += 1;
+                }
+            },
+            Entry::Vacant(entry) => {
+                let deadline: Instant = self.clock.now() + self.arp_config.get_request_timeout();
+                let queue: &mut PendingQueue = entry.insert(PendingQueue::new(
+                    self.arp_config.get_pending_queue_len(),
+                    self.arp_config.get_retry_count(),
+                    deadline,
+                ));
+                if queue.push(build) {
+                    stats.dropped += 1;
+                }
+                self.rt.transmit(Box::new(Self::request(
+                    self.local_link_addr,
+                    self.local_ipv4_addr,
+                    MacAddress::broadcast(),
+                    ipv4_addr,
+                )));
+                stats.requests_sent += 1;
+            },
+        }
+    }
+
+    /// Returns the counters of the target ARP peer.
+    pub fn get_stats(&self) -> ArpStats {
+        *self.stats.borrow()
+    }
+
     pub fn query(&self, ipv4_addr: Ipv4Addr) -> impl Future<Output = Result<MacAddress, Fail>> {
         let rt = self.rt.clone();
         let mut arp = self.clone();
@@ -267,16 +533,7 @@ impl<const N: usize> ArpPeer<N> {
             if let Some(&link_addr) = cache.borrow().get(ipv4_addr) {
                 return Ok(link_addr);
             }
-            let msg = ArpMessage::new(
-                Ethernet2Header::new(MacAddress::broadcast(), local_link_addr, EtherType2::Arp),
-                ArpHeader::new(
-                    ArpOperation::Request,
-                    local_link_addr,
-                    local_ipv4_addr,
-                    MacAddress::broadcast(),
-                    ipv4_addr,
-                ),
-            );
+            let msg: ArpMessage = Self::request(local_link_addr, local_ipv4_addr, MacAddress::broadcast(), ipv4_addr);
             let mut arp_response = arp.do_wait_link_addr(ipv4_addr).fuse();
 
             // from TCP/IP illustrated, chapter 4:
@@ -285,6 +542,7 @@ impl<const N: usize> ArpPeer<N> {
             let result = {
                 for i in 0..arp_options.get_retry_count() + 1 {
                     rt.transmit(Box::new(msg.clone()));
+                    arp.stats.borrow_mut().requests_sent += 1;
                     let timer = clock.wait(clock.clone(), arp_options.get_request_timeout());
 
                     match arp_response.with_timeout(timer).await {
@@ -0,0 +1,164 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use crate::runtime::network::{
+    types::MacAddress,
+    PacketBuf,
+};
+use ::std::{
+    collections::VecDeque,
+    time::Instant,
+};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Packet that waits for the link address of its next hop.  It gets built once the address is known.
+pub type PendingPacket = Box<dyn FnOnce(MacAddress) -> Box<dyn PacketBuf>>;
+
+/// Packets that wait for the same address resolution, oldest first (RFC 1122 Section 2.3.2.2).
+pub struct PendingQueue {
+    packets: VecDeque<PendingPacket>,
+    /// Maximum number of packets in the queue.
+    capacity: usize,
+    /// Number of requests that may still be sent before giving up on the resolution.
+    retries_left: usize,
+    /// When the last request times out.
+    deadline: Instant,
+}
+
+//==============================================================================
+// Associated Functions
+//==============================================================================
+
+impl PendingQueue {
+    /// Creates an empty queue for a resolution whose first request times out at `deadline`.
+    pub fn new(capacity: usize, retries: usize, deadline: Instant) -> Self {
+        Self {
+            packets: VecDeque::with_capacity(capacity),
+            capacity,
+            retries_left: retries,
+            deadline,
+        }
+    }
+
+    /// Queues `packet`, and returns whether an older packet had to be dropped to make room for it.  RFC 1122 asks to
+    /// keep the latest packet.
+    pub fn push(&mut self, packet: PendingPacket) -> bool {
+        if self.capacity == 0 {
+            return true;
+        }
+        let dropped: bool = self.packets.len() == self.capacity;
+        if dropped {
+            self.packets.pop_front();
+        }
+        self.packets.push_back(packet);
+        dropped
+    }
+
+    /// Returns whether the last request timed out at `now`.
+    pub fn is_expired(&self, now: Instant) -> bool {
+        now >= self.deadline
+    }
+
+    /// Accounts for a new request that times out at `deadline`.  Returns false if no retry is left.
+    pub fn retry(&mut self, deadline: Instant) -> bool {
+        if self.retries_left == 0 {
+            return false;
+        }
+        self.retries_left -= 1;
+        self.deadline = deadline;
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.packets.len()
+    }
+
+    /// Builds the queued packets for `link_addr`, oldest first.
+    pub fn drain(self, link_addr: MacAddress) -> impl Iterator<Item = Box<dyn PacketBuf>> {
+        self.packets.into_iter().map(move |build| build(link_addr))
+    }
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        PendingPacket,
+        PendingQueue,
+    };
+    use crate::runtime::{
+        memory::DemiBuffer,
+        network::{
+            types::MacAddress,
+            PacketBuf,
+        },
+    };
+    use ::anyhow::Result;
+    use ::std::time::{
+        Duration,
+        Instant,
+    };
+
+    /// Packet that only carries a tag in its header.
+    struct TaggedPacket(u8);
+
+    impl PacketBuf for TaggedPacket {
+        fn header_size(&self) -> usize {
+            1
+        }
+
+        fn write_header(&self, buf: &mut [u8]) {
+            buf[0] = self.0;
+        }
+
+        fn body_size(&self) -> usize {
+            0
+        }
+
+        fn take_body(&self) -> Option<DemiBuffer> {
+            None
+        }
+    }
+
+    fn tagged(tag: u8) -> PendingPacket {
+        Box::new(move |_: MacAddress| -> Box<dyn PacketBuf> { Box::new(TaggedPacket(tag)) })
+    }
+
+    /// Tests that a full queue drops its oldest packets, and that retries run out.
+    #[test]
+    fn drop_oldest_and_retry() -> Result<()> {
+        let now: Instant = Instant::now();
+        let mut queue: PendingQueue = PendingQueue::new(2, 1, now + Duration::from_secs(1));
+
+        crate::ensure_eq!(queue.push(tagged(1)), false);
+        crate::ensure_eq!(queue.push(tagged(2)), false);
+        crate::ensure_eq!(queue.push(tagged(3)), true);
+        crate::ensure_eq!(queue.len(), 2);
+
+        crate::ensure_eq!(queue.is_expired(now), false);
+        crate::ensure_eq!(queue.is_expired(now + Duration::from_secs(1)), true);
+        crate::ensure_eq!(queue.retry(now + Duration::from_secs(2)), true);
+        crate::ensure_eq!(queue.is_expired(now + Duration::from_secs(1)), false);
+        crate::ensure_eq!(queue.retry(now + Duration::from_secs(3)), false);
+
+        let mut tags: Vec<u8> = Vec::new();
+        for packet in queue.drain(MacAddress::broadcast()) {
+            let mut buf: [u8; 1] = [0];
+            packet.write_header(&mut buf);
+            tags.push(buf[0]);
+        }
+        crate::ensure_eq!(tags, vec![2, 3]);
+
+        Ok(())
+    }
+}
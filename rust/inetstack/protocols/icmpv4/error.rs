@@ -0,0 +1,451 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+// ICMPv4 error messages (RFC 792).
+// An error quotes the IPv4 header and the first 8 bytes of the payload of the datagram that caused it, which is enough
+// to tell which transport endpoint sent that datagram.  RFC 1122 Section 3.2.2 lists the datagrams that must never be
+// answered with an error, so that errors cannot multiply.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use super::datagram::{
+    Icmpv4Header,
+    Icmpv4Message,
+    Icmpv4Type2,
+};
+use crate::{
+    inetstack::protocols::{
+        ethernet2::{
+            EtherType2,
+            Ethernet2Header,
+        },
+        ip::IpProtocol,
+        ipv4::{
+            Ipv4Header,
+            RoutingTable,
+            IPV4_HEADER_MIN_SIZE,
+        },
+    },
+    runtime::{
+        fail::Fail,
+        memory::DemiBuffer,
+    },
+};
+use ::libc::{
+    c_int,
+    EBADMSG,
+    ECONNREFUSED,
+    EHOSTUNREACH,
+    EMSGSIZE,
+    ENETUNREACH,
+    ENOPROTOOPT,
+    ENOTSUP,
+};
+use ::std::{
+    cmp,
+    convert::{
+        TryFrom,
+        TryInto,
+    },
+    net::{
+        Ipv4Addr,
+        SocketAddr,
+        SocketAddrV4,
+    },
+};
+
+//==============================================================================
+// Constants
+//==============================================================================
+
+/// Number of payload bytes of the offending datagram that an error quotes.
+const QUOTED_PAYLOAD_SIZE: usize = 8;
+
+/// Types of ICMPv4 messages that report errors, as opposed to queries.
+const ICMPV4_ERROR_TYPES: [u8; 5] = [3, 4, 5, 11, 12];
+
+/// MTUs to fall back on when a router does not report the MTU of its next hop (RFC 1191 Section 7).
+const MTU_PLATEAUS: [u16; 10] = [32000, 17914, 8166, 4352, 2002, 1492, 1006, 508, 296, 68];
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// What an ICMPv4 error reports.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Icmpv4ErrorKind {
+    NetUnreachable,
+    HostUnreachable,
+    ProtocolUnreachable,
+    PortUnreachable,
+    /// The datagram was too big for the next hop and could not be fragmented.
+    FragmentationNeeded { next_hop_mtu: u16 },
+    TtlExceeded,
+    ReassemblyTimeExceeded,
+}
+
+/// ICMPv4 error, along with the endpoints of the datagram that caused it.
+#[derive(Copy, Clone, Debug)]
+pub struct Icmpv4Error {
+    kind: Icmpv4ErrorKind,
+    /// Protocol of the offending datagram.
+    protocol: IpProtocol,
+    /// Where the offending datagram came from, which is this host for errors that it receives.
+    local: SocketAddrV4,
+    /// Where the offending datagram was going.
+    remote: SocketAddrV4,
+    /// First bytes of the transport header of the offending datagram.
+    transport_hdr: [u8; QUOTED_PAYLOAD_SIZE],
+}
+
+//==============================================================================
+// Associated Functions
+//==============================================================================
+
+impl Icmpv4ErrorKind {
+    /// Returns the error code that sockets report for the target error.
+    pub fn get_errno(&self) -> c_int {
+        match self {
+            Icmpv4ErrorKind::NetUnreachable => ENETUNREACH,
+            Icmpv4ErrorKind::HostUnreachable => EHOSTUNREACH,
+            Icmpv4ErrorKind::ProtocolUnreachable => ENOPROTOOPT,
+            Icmpv4ErrorKind::PortUnreachable => ECONNREFUSED,
+            Icmpv4ErrorKind::FragmentationNeeded { .. } => EMSGSIZE,
+            Icmpv4ErrorKind::TtlExceeded | Icmpv4ErrorKind::ReassemblyTimeExceeded => EHOSTUNREACH,
+        }
+    }
+
+    /// Returns whether the target error means that the destination will never answer, in which case connections
+    /// should be aborted.  Other errors may be transient (RFC 1122 Section 4.2.3.9).
+    pub fn is_hard(&self) -> bool {
+        match self {
+            Icmpv4ErrorKind::ProtocolUnreachable | Icmpv4ErrorKind::PortUnreachable => true,
+            _ => false,
+        }
+    }
+
+    /// Returns the type and code that carry the target error.
+    fn serialize(&self) -> (Icmpv4Type2, u8) {
+        let unreachable = |next_hop_mtu: u16| Icmpv4Type2::DestinationUnreachable { next_hop_mtu };
+        match *self {
+            Icmpv4ErrorKind::NetUnreachable => (unreachable(0), 0),
+            Icmpv4ErrorKind::HostUnreachable => (unreachable(0), 1),
+            Icmpv4ErrorKind::ProtocolUnreachable => (unreachable(0), 2),
+            Icmpv4ErrorKind::PortUnreachable => (unreachable(0), 3),
+            Icmpv4ErrorKind::FragmentationNeeded { next_hop_mtu } => (unreachable(next_hop_mtu), 4),
+            Icmpv4ErrorKind::TtlExceeded => (Icmpv4Type2::TimeExceeded, 0),
+            Icmpv4ErrorKind::ReassemblyTimeExceeded => (Icmpv4Type2::TimeExceeded, 1),
+        }
+    }
+}
+
+impl Icmpv4Error {
+    /// Parses an ICMPv4 message, which has to be an error that we know of.  Datagrams other than TCP and UDP ones have
+    /// no ports, so their endpoints only make sense as addresses.
+    pub fn parse(buf: DemiBuffer) -> Result<Self, Fail> {
+        let (icmpv4_hdr, data) = Icmpv4Header::parse(buf)?;
+        let quoted: &[u8] = &data[..];
+        if quoted.len() < IPV4_HEADER_MIN_SIZE as usize || quoted[0] >> 4 != 4 {
+            return Err(Fail::new(EBADMSG, "ICMPv4 error does not quote an IPv4 header"));
+        }
+        let hdr_size: usize = ((quoted[0] & 0xF) as usize) << 2;
+        if hdr_size < IPV4_HEADER_MIN_SIZE as usize || quoted.len() < hdr_size + QUOTED_PAYLOAD_SIZE {
+            return Err(Fail::new(EBADMSG, "ICMPv4 error quotes too little of the datagram"));
+        }
+        let total_length: u16 = u16::from_be_bytes([quoted[2], quoted[3]]);
+        let protocol: IpProtocol = IpProtocol::try_from(quoted[9])?;
+        let src_addr: Ipv4Addr = Ipv4Addr::new(quoted[12], quoted[13], quoted[14], quoted[15]);
+        let dst_addr: Ipv4Addr = Ipv4Addr::new(quoted[16], quoted[17], quoted[18], quoted[19]);
+        let transport_hdr: [u8; QUOTED_PAYLOAD_SIZE] =
+            quoted[hdr_size..(hdr_size + QUOTED_PAYLOAD_SIZE)].try_into().unwrap();
+        let src_port: u16 = u16::from_be_bytes([transport_hdr[0], transport_hdr[1]]);
+        let dst_port: u16 = u16::from_be_bytes([transport_hdr[2], transport_hdr[3]]);
+
+        let code: u8 = icmpv4_hdr.get_code();
+        let kind: Icmpv4ErrorKind = match icmpv4_hdr.get_protocol() {
+            Icmpv4Type2::DestinationUnreachable { next_hop_mtu } => match code {
+                0 | 6 | 9 | 11 => Icmpv4ErrorKind::NetUnreachable,
+                2 => Icmpv4ErrorKind::ProtocolUnreachable,
+                3 => Icmpv4ErrorKind::PortUnreachable,
+                4 => Icmpv4ErrorKind::FragmentationNeeded {
+                    next_hop_mtu: match next_hop_mtu {
+                        // Routers that predate RFC 1191 leave this field zeroed.
+                        0 => guess_next_hop_mtu(total_length),
+                        _ => next_hop_mtu,
+                    },
+                },
+                // Source route failures and administrative filtering mean the host cannot be reached through here.
+                _ => Icmpv4ErrorKind::HostUnreachable,
+            },
+            Icmpv4Type2::TimeExceeded => match code {
+                1 => Icmpv4ErrorKind::ReassemblyTimeExceeded,
+                _ => Icmpv4ErrorKind::TtlExceeded,
+            },
+            _ => return Err(Fail::new(ENOTSUP, "unsupported ICMPv4 error")),
+        };
+
+        Ok(Self {
+            kind,
+            protocol,
+            local: SocketAddrV4::new(src_addr, src_port),
+            remote: SocketAddrV4::new(dst_addr, dst_port),
+            transport_hdr,
+        })
+    }
+
+    /// Builds the error that reports `kind` back to the source of `datagram`, which arrived in a frame with
+    /// `ethernet2_hdr`.  `datagram` starts at its IPv4 header.  Returns `None` for datagrams that must not be answered
+    /// with an error.
+    pub fn reply(
+        kind: Icmpv4ErrorKind,
+        ethernet2_hdr: &Ethernet2Header,
+        routes: &RoutingTable,
+        datagram: &[u8],
+    ) -> Option<Icmpv4Message> {
+        if !Self::may_reply(ethernet2_hdr, routes, datagram) {
+            return None;
+        }
+        let hdr_size: usize = ((datagram[0] & 0xF) as usize) << 2;
+        let total_length: usize = u16::from_be_bytes([datagram[2], datagram[3]]) as usize;
+        let quoted_size: usize = cmp::min(cmp::min(datagram.len(), total_length), hdr_size + QUOTED_PAYLOAD_SIZE);
+        let data: DemiBuffer = DemiBuffer::from_slice(&datagram[..quoted_size]).ok()?;
+
+        let src_addr: Ipv4Addr = Ipv4Addr::new(datagram[12], datagram[13], datagram[14], datagram[15]);
+        let dst_addr: Ipv4Addr = Ipv4Addr::new(datagram[16], datagram[17], datagram[18], datagram[19]);
+        let (icmpv4_type, code): (Icmpv4Type2, u8) = kind.serialize();
+        Some(Icmpv4Message::new(
+            Ethernet2Header::new(ethernet2_hdr.src_addr(), ethernet2_hdr.dst_addr(), EtherType2::Ipv4),
+            Ipv4Header::new(dst_addr, src_addr, IpProtocol::ICMPv4),
+            Icmpv4Header::new(icmpv4_type, code),
+            data,
+        ))
+    }
+
+    /// Returns whether RFC 1122 Section 3.2.2 lets us answer `datagram` with an error.  It rules out errors about
+    /// errors, datagrams that were not meant for this host alone, fragments other than the first one and datagrams
+    /// whose source does not name a single host.
+    pub fn may_reply(ethernet2_hdr: &Ethernet2Header, routes: &RoutingTable, datagram: &[u8]) -> bool {
+        if datagram.len() < IPV4_HEADER_MIN_SIZE as usize || datagram[0] >> 4 != 4 {
+            return false;
+        }
+        let hdr_size: usize = ((datagram[0] & 0xF) as usize) << 2;
+        if hdr_size < IPV4_HEADER_MIN_SIZE as usize || datagram.len() < hdr_size {
+            return false;
+        }
+        if ethernet2_hdr.dst_addr().is_broadcast() || ethernet2_hdr.dst_addr().is_multicast() {
+            return false;
+        }
+
+        let fragment_offset: u16 = u16::from_be_bytes([datagram[6], datagram[7]]) & 0x1fff;
+        if fragment_offset != 0 {
+            return false;
+        }
+
+        let src_addr: Ipv4Addr = Ipv4Addr::new(datagram[12], datagram[13], datagram[14], datagram[15]);
+        let dst_addr: Ipv4Addr = Ipv4Addr::new(datagram[16], datagram[17], datagram[18], datagram[19]);
+        if dst_addr.is_multicast() || routes.is_broadcast(dst_addr) {
+            return false;
+        }
+        // Class E addresses, including the limited broadcast address, are not host addresses either.
+        if src_addr.is_unspecified()
+            || src_addr.is_loopback()
+            || src_addr.is_multicast()
+            || src_addr.octets()[0] >= 240
+            || routes.is_broadcast(src_addr)
+        {
+            return false;
+        }
+
+        // Errors about datagrams that we cannot tell apart from errors are not sent either.
+        if datagram[9] == IpProtocol::ICMPv4 as u8 {
+            return match datagram.get(hdr_size) {
+                Some(icmpv4_type) => !ICMPV4_ERROR_TYPES.contains(icmpv4_type),
+                None => false,
+            };
+        }
+        true
+    }
+
+    pub fn get_kind(&self) -> Icmpv4ErrorKind {
+        self.kind
+    }
+
+    pub fn get_protocol(&self) -> IpProtocol {
+        self.protocol
+    }
+
+    pub fn get_local(&self) -> SocketAddr {
+        SocketAddr::V4(self.local)
+    }
+
+    pub fn get_remote(&self) -> SocketAddr {
+        SocketAddr::V4(self.remote)
+    }
+
+    /// Returns the first bytes of the transport header of the offending datagram.  For TCP, these hold the ports and
+    /// the sequence number.
+    pub fn get_transport_header(&self) -> &[u8; QUOTED_PAYLOAD_SIZE] {
+        &self.transport_hdr
+    }
+}
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Guesses the MTU of a next hop that rejected a datagram of `total_length` bytes, as the highest plateau below it.
+fn guess_next_hop_mtu(total_length: u16) -> u16 {
+    MTU_PLATEAUS
+        .iter()
+        .copied()
+        .find(|plateau| *plateau < total_length)
+        .unwrap_or(MTU_PLATEAUS[MTU_PLATEAUS.len() - 1])
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Icmpv4Error,
+        Icmpv4ErrorKind,
+    };
+    use crate::{
+        inetstack::protocols::{
+            ethernet2::{
+                EtherType2,
+                Ethernet2Header,
+            },
+            ip::IpProtocol,
+            ipv4::{
+                Ipv4Header,
+                RoutingTable,
+            },
+        },
+        runtime::{
+            memory::DemiBuffer,
+            network::{
+                types::MacAddress,
+                PacketBuf,
+            },
+        },
+    };
+    use ::anyhow::Result;
+    use ::std::net::{
+        Ipv4Addr,
+        SocketAddr,
+    };
+
+    const LOCAL_LINK_ADDR: MacAddress = MacAddress::new([0x2, 0, 0, 0, 0, 0x1]);
+    const REMOTE_LINK_ADDR: MacAddress = MacAddress::new([0x2, 0, 0, 0, 0, 0x2]);
+
+    fn routes() -> RoutingTable {
+        RoutingTable::new(Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(255, 255, 255, 0)).unwrap()
+    }
+
+    /// Makes a UDP datagram from 10.0.0.2:1234 to `dst_addr`:53, which arrived in a frame for `dst_link_addr`.
+    fn udp_datagram(dst_addr: Ipv4Addr, dst_link_addr: MacAddress) -> (Ethernet2Header, Vec<u8>) {
+        let ipv4_hdr: Ipv4Header = Ipv4Header::new(Ipv4Addr::new(10, 0, 0, 2), dst_addr, IpProtocol::UDP);
+        let payload: [u8; 12] = [0x04, 0xd2, 0x00, 0x35, 0x00, 0x0c, 0x00, 0x00, 1, 2, 3, 4];
+        let mut datagram: Vec<u8> = vec![0; ipv4_hdr.compute_size()];
+        ipv4_hdr.serialize(&mut datagram, payload.len());
+        datagram.extend_from_slice(&payload);
+        let ethernet2_hdr: Ethernet2Header = Ethernet2Header::new(dst_link_addr, REMOTE_LINK_ADDR, EtherType2::Ipv4);
+        (ethernet2_hdr, datagram)
+    }
+
+    /// Serializes a message, and parses it back as if it was just received.
+    fn receive(message: &dyn PacketBuf) -> Result<(Ipv4Header, Icmpv4Error)> {
+        let mut bytes: Vec<u8> = vec![0; message.header_size()];
+        message.write_header(&mut bytes);
+        if let Some(body) = message.take_body() {
+            bytes.extend_from_slice(&body[..]);
+        }
+        let (_, buf): (Ethernet2Header, DemiBuffer) = Ethernet2Header::parse(DemiBuffer::from_slice(&bytes)?)?;
+        let (ipv4_hdr, buf): (Ipv4Header, DemiBuffer) = Ipv4Header::parse(buf)?;
+        Ok((ipv4_hdr, Icmpv4Error::parse(buf)?))
+    }
+
+    /// Tests that an error names the endpoints of the datagram it reports, from the point of view of its sender.
+    #[test]
+    fn reply_quotes_datagram() -> Result<()> {
+        let (ethernet2_hdr, datagram): (Ethernet2Header, Vec<u8>) =
+            udp_datagram(Ipv4Addr::new(10, 0, 0, 1), LOCAL_LINK_ADDR);
+        let message = Icmpv4Error::reply(Icmpv4ErrorKind::PortUnreachable, &ethernet2_hdr, &routes(), &datagram)
+            .expect("datagram should be answered");
+
+        let (ipv4_hdr, error): (Ipv4Header, Icmpv4Error) = receive(&message)?;
+        crate::ensure_eq!(ipv4_hdr.get_src_addr(), Ipv4Addr::new(10, 0, 0, 1));
+        crate::ensure_eq!(ipv4_hdr.get_dest_addr(), Ipv4Addr::new(10, 0, 0, 2));
+        crate::ensure_eq!(error.get_kind(), Icmpv4ErrorKind::PortUnreachable);
+        crate::ensure_eq!(error.get_kind().get_errno(), libc::ECONNREFUSED);
+        crate::ensure_eq!(error.get_protocol(), IpProtocol::UDP);
+        crate::ensure_eq!(error.get_local(), SocketAddr::from((Ipv4Addr::new(10, 0, 0, 2), 1234)));
+        crate::ensure_eq!(error.get_remote(), SocketAddr::from((Ipv4Addr::new(10, 0, 0, 1), 53)));
+        crate::ensure_eq!(&error.get_transport_header()[..], &datagram[20..28]);
+
+        let message = Icmpv4Error::reply(
+            Icmpv4ErrorKind::FragmentationNeeded { next_hop_mtu: 1400 },
+            &ethernet2_hdr,
+            &routes(),
+            &datagram,
+        )
+        .expect("datagram should be answered");
+        let (_, error): (Ipv4Header, Icmpv4Error) = receive(&message)?;
+        crate::ensure_eq!(
+            error.get_kind(),
+            Icmpv4ErrorKind::FragmentationNeeded { next_hop_mtu: 1400 }
+        );
+
+        Ok(())
+    }
+
+    /// Tests that a "fragmentation needed" error without an MTU falls back on the plateau below the datagram size.
+    #[test]
+    fn guess_next_hop_mtu() -> Result<()> {
+        crate::ensure_eq!(super::guess_next_hop_mtu(1500), 1492);
+        crate::ensure_eq!(super::guess_next_hop_mtu(1492), 1006);
+        crate::ensure_eq!(super::guess_next_hop_mtu(40), 68);
+        Ok(())
+    }
+
+    /// Tests that datagrams that RFC 1122 rules out are never answered with an error.
+    #[test]
+    fn reply_suppressed() -> Result<()> {
+        let routes: RoutingTable = routes();
+
+        // Frames that went to everyone.
+        let (ethernet2_hdr, datagram): (Ethernet2Header, Vec<u8>) =
+            udp_datagram(Ipv4Addr::new(10, 0, 0, 1), MacAddress::broadcast());
+        crate::ensure_eq!(Icmpv4Error::may_reply(&ethernet2_hdr, &routes, &datagram), false);
+
+        // Datagrams to the directed broadcast address of the local network.
+        let (ethernet2_hdr, datagram): (Ethernet2Header, Vec<u8>) =
+            udp_datagram(Ipv4Addr::new(10, 0, 0, 255), LOCAL_LINK_ADDR);
+        crate::ensure_eq!(Icmpv4Error::may_reply(&ethernet2_hdr, &routes, &datagram), false);
+
+        // Fragments other than the first one.
+        let (ethernet2_hdr, mut datagram): (Ethernet2Header, Vec<u8>) =
+            udp_datagram(Ipv4Addr::new(10, 0, 0, 1), LOCAL_LINK_ADDR);
+        crate::ensure_eq!(Icmpv4Error::may_reply(&ethernet2_hdr, &routes, &datagram), true);
+        datagram[7] = 1;
+        crate::ensure_eq!(Icmpv4Error::may_reply(&ethernet2_hdr, &routes, &datagram), false);
+
+        // Errors themselves.
+        let (ethernet2_hdr, datagram): (Ethernet2Header, Vec<u8>) =
+            udp_datagram(Ipv4Addr::new(10, 0, 0, 1), LOCAL_LINK_ADDR);
+        let message = Icmpv4Error::reply(Icmpv4ErrorKind::PortUnreachable, &ethernet2_hdr, &routes, &datagram)
+            .expect("datagram should be answered");
+        let mut bytes: Vec<u8> = vec![0; message.header_size()];
+        message.write_header(&mut bytes);
+        bytes.extend_from_slice(&message.take_body().unwrap()[..]);
+        let ethernet2_hdr: Ethernet2Header = Ethernet2Header::new(REMOTE_LINK_ADDR, LOCAL_LINK_ADDR, EtherType2::Ipv4);
+        crate::ensure_eq!(Icmpv4Error::may_reply(&ethernet2_hdr, &routes, &bytes[14..]), false);
+
+        Ok(())
+    }
+}
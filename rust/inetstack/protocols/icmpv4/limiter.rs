@@ -0,0 +1,124 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use ::std::{
+    cmp,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+//==============================================================================
+// Constants
+//==============================================================================
+
+/// Default number of ICMP errors that a host sends per second.
+pub const DEFAULT_ICMPV4_ERROR_RATE: u32 = 1000;
+
+/// Default number of ICMP errors that a host sends back-to-back.
+pub const DEFAULT_ICMPV4_ERROR_BURST: u32 = 50;
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Token bucket that limits the rate of ICMP errors that we send (RFC 1812 Section 4.3.2.8), so that a flood of bad
+/// datagrams does not turn into a flood of errors.
+#[derive(Debug)]
+pub struct ErrorRateLimiter {
+    /// Time it takes to earn one token.
+    interval: Duration,
+    /// Maximum number of tokens.
+    burst: u32,
+    tokens: u32,
+    /// When tokens were last added, if they ever were.
+    last_refill: Option<Instant>,
+}
+
+//==============================================================================
+// Associated Functions
+//==============================================================================
+
+impl ErrorRateLimiter {
+    /// Creates a limiter that lets `rate` errors out per second, with bursts of up to `burst` errors.
+    pub fn new(rate: u32, burst: u32) -> Self {
+        Self {
+            interval: Duration::from_secs(1) / cmp::max(rate, 1),
+            burst,
+            tokens: burst,
+            last_refill: None,
+        }
+    }
+
+    /// Returns whether an error may be sent at `now`, taking a token if so.
+    pub fn try_acquire(&mut self, now: Instant) -> bool {
+        let last_refill: Instant = *self.last_refill.get_or_insert(now);
+        let elapsed: Duration = now.saturating_duration_since(last_refill);
+        let new_tokens: u32 = cmp::min(elapsed.as_nanos() / self.interval.as_nanos(), self.burst as u128) as u32;
+        if new_tokens != 0 {
+            self.tokens = cmp::min(self.tokens + new_tokens, self.burst);
+            // Carry over the time spent earning a fraction of a token, unless the bucket is full anyway.
+            self.last_refill = Some(if self.tokens == self.burst {
+                now
+            } else {
+                last_refill + self.interval * new_tokens
+            });
+        }
+
+        if self.tokens == 0 {
+            return false;
+        }
+        self.tokens -= 1;
+        true
+    }
+}
+
+impl Default for ErrorRateLimiter {
+    fn default() -> Self {
+        Self::new(DEFAULT_ICMPV4_ERROR_RATE, DEFAULT_ICMPV4_ERROR_BURST)
+    }
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::ErrorRateLimiter;
+    use ::anyhow::Result;
+    use ::std::time::{
+        Duration,
+        Instant,
+    };
+
+    /// Tests that bursts are let through, and that errors then go out no faster than the rate.
+    #[test]
+    fn burst_then_rate() -> Result<()> {
+        let now: Instant = Instant::now();
+        let mut limiter: ErrorRateLimiter = ErrorRateLimiter::new(100, 3);
+
+        for _ in 0..3 {
+            crate::ensure_eq!(limiter.try_acquire(now), true);
+        }
+        crate::ensure_eq!(limiter.try_acquire(now), false);
+        crate::ensure_eq!(limiter.try_acquire(now + Duration::from_millis(5)), false);
+        crate::ensure_eq!(limiter.try_acquire(now + Duration::from_millis(10)), true);
+        crate::ensure_eq!(limiter.try_acquire(now + Duration::from_millis(15)), false);
+        crate::ensure_eq!(limiter.try_acquire(now + Duration::from_millis(20)), true);
+
+        // Idling refills the bucket, but no further than the burst.
+        let later: Instant = now + Duration::from_secs(10);
+        for _ in 0..3 {
+            crate::ensure_eq!(limiter.try_acquire(later), true);
+        }
+        crate::ensure_eq!(limiter.try_acquire(later), false);
+
+        Ok(())
+    }
+}
@@ -2,6 +2,19 @@
 // Licensed under the MIT license.
 
 mod datagram;
+mod error;
+mod limiter;
 mod peer;
 
+pub use self::{
+    error::{
+        Icmpv4Error,
+        Icmpv4ErrorKind,
+    },
+    limiter::{
+        ErrorRateLimiter,
+        DEFAULT_ICMPV4_ERROR_BURST,
+        DEFAULT_ICMPV4_ERROR_RATE,
+    },
+};
 pub use peer::Icmpv4Peer;
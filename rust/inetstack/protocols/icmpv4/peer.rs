@@ -17,7 +17,10 @@ use crate::{
                 Icmpv4Type2,
             },
             ip::IpProtocol,
-            ipv4::Ipv4Header,
+            ipv4::{
+                Ipv4Header,
+                RoutingTable,
+            },
         },
     },
     runtime::{
@@ -26,6 +29,7 @@ use crate::{
         network::{
             types::MacAddress,
             NetworkRuntime,
+            PacketBuf,
         },
         queue::BackgroundTask,
         timer::{
@@ -120,6 +124,9 @@ pub struct Icmpv4Peer<const N: usize> {
     /// Underlying ARP Peer
     arp: ArpPeer<N>,
 
+    /// Routes to remote peers
+    routes: Rc<RoutingTable>,
+
     /// Transmitter
     tx: mpsc::UnboundedSender<(Ipv4Addr, u16, u16, DemiBuffer)>,
 
@@ -146,18 +153,20 @@ impl<const N: usize> Icmpv4Peer<N> {
         local_link_addr: MacAddress,
         local_ipv4_addr: Ipv4Addr,
         arp: ArpPeer<N>,
+        routes: RoutingTable,
         rng_seed: [u8; 32],
     ) -> Result<Self, Fail> {
         let (tx, rx) = mpsc::unbounded();
+        let routes: Rc<RoutingTable> = Rc::new(routes);
         let requests = ReqQueue::new();
         let rng: Rc<RefCell<SmallRng>> = Rc::new(RefCell::new(SmallRng::from_seed(rng_seed)));
         let task: BackgroundTask = BackgroundTask::new(
             String::from("Inetstack::ICMP::background"),
             Box::pin(Self::background(
-                rt.clone(),
                 local_link_addr,
                 local_ipv4_addr,
                 arp.clone(),
+                routes.clone(),
                 rx,
             )),
         );
@@ -175,6 +184,7 @@ impl<const N: usize> Icmpv4Peer<N> {
             local_link_addr,
             local_ipv4_addr,
             arp,
+            routes,
             tx,
             requests: Rc::new(RefCell::new(requests)),
             seq: Wrapping(0),
@@ -185,31 +195,34 @@ impl<const N: usize> Icmpv4Peer<N> {
 
     /// Background task for replying to ICMP messages.
     async fn background(
-        rt: Rc<dyn NetworkRuntime<N>>,
         local_link_addr: MacAddress,
         local_ipv4_addr: Ipv4Addr,
         arp: ArpPeer<N>,
+        routes: Rc<RoutingTable>,
         mut rx: mpsc::UnboundedReceiver<(Ipv4Addr, u16, u16, DemiBuffer)>,
     ) {
         // Reply requests.
         while let Some((dst_ipv4_addr, id, seq_num, data)) = rx.next().await {
-            debug!("initiating ARP query");
-            let dst_link_addr: MacAddress = match arp.query(dst_ipv4_addr).await {
-                Ok(dst_link_addr) => dst_link_addr,
+            let next_hop: Ipv4Addr = match routes.next_hop(dst_ipv4_addr) {
+                Ok(next_hop) => next_hop,
                 Err(e) => {
                     warn!("reply_to_ping({}, {}, {}) failed: {:?}", dst_ipv4_addr, id, seq_num, e);
                     continue;
                 },
             };
-            debug!("ARP query complete ({} -> {})", dst_ipv4_addr, dst_link_addr);
             debug!("reply ping ({}, {}, {})", dst_ipv4_addr, id, seq_num);
-            // Send reply message.
-            rt.transmit(Box::new(Icmpv4Message::new(
-                Ethernet2Header::new(dst_link_addr, local_link_addr, EtherType2::Ipv4),
-                Ipv4Header::new(local_ipv4_addr, dst_ipv4_addr, IpProtocol::ICMPv4),
-                Icmpv4Header::new(Icmpv4Type2::EchoReply { id, seq_num }, 0),
-                data,
-            )));
+            // Send reply message, once the next hop is resolved.
+            arp.transmit_to(
+                next_hop,
+                Box::new(move |dst_link_addr: MacAddress| -> Box<dyn PacketBuf> {
+                    Box::new(Icmpv4Message::new(
+                        Ethernet2Header::new(dst_link_addr, local_link_addr, EtherType2::Ipv4),
+                        Ipv4Header::new(local_ipv4_addr, dst_ipv4_addr, IpProtocol::ICMPv4),
+                        Icmpv4Header::new(Icmpv4Type2::EchoReply { id, seq_num }, 0),
+                        data,
+                    ))
+                }),
+            );
         }
     }
 
@@ -274,6 +287,7 @@ impl<const N: usize> Icmpv4Peer<N> {
         let seq_num: u16 = self.make_seq_num();
         let echo_request: Icmpv4Type2 = Icmpv4Type2::EchoRequest { id, seq_num };
         let arp: ArpPeer<N> = self.arp.clone();
+        let routes: Rc<RoutingTable> = self.routes.clone();
         let rt: Rc<dyn NetworkRuntime<N>> = self.rt.clone();
         let clock: TimerRc = self.clock.clone();
         let requests: Rc<RefCell<ReqQueue>> = self.requests.clone();
@@ -282,8 +296,9 @@ impl<const N: usize> Icmpv4Peer<N> {
         async move {
             let t0: Instant = clock.now();
             debug!("initiating ARP query");
-            let dst_link_addr: MacAddress = arp.query(dst_ipv4_addr).await?;
-            debug!("ARP query complete ({} -> {})", dst_ipv4_addr, dst_link_addr);
+            let next_hop: Ipv4Addr = routes.next_hop(dst_ipv4_addr)?;
+            let dst_link_addr: MacAddress = arp.query(next_hop).await?;
+            debug!("ARP query complete ({} -> {})", next_hop, dst_link_addr);
 
             let data: DemiBuffer = DemiBuffer::new(datagram::ICMPV4_ECHO_REQUEST_MESSAGE_SIZE);
 
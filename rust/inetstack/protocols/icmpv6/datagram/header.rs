@@ -0,0 +1,160 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+use super::protocol::Icmpv6Type2;
+use crate::{
+    inetstack::protocols::{
+        ip::{
+            IpHeader,
+            IpProtocol,
+        },
+        ipv6::Ipv6Header,
+    },
+    runtime::{
+        fail::Fail,
+        memory::DemiBuffer,
+    },
+};
+use ::libc::EBADMSG;
+use ::std::convert::TryInto;
+
+/// Size of ICMPv6 Headers (in bytes)
+pub const ICMPV6_HEADER_SIZE: usize = 8;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Icmpv6Header {
+    protocol: Icmpv6Type2,
+    code: u8,
+}
+
+/// Associate Functions for Icmpv6Header
+impl Icmpv6Header {
+    /// Creates a header for a ICMPv6 Message.
+    pub fn new(icmpv6_type: Icmpv6Type2, code: u8) -> Self {
+        Self {
+            protocol: icmpv6_type,
+            code,
+        }
+    }
+
+    /// Returns the size of the target ICMPv6 header.
+    pub fn size(&self) -> usize {
+        ICMPV6_HEADER_SIZE
+    }
+
+    /// Parses an ICMPv6 message.  Unlike ICMPv4, the checksum covers the IPv6 pseudo-header (RFC 4443 Section 2.3).
+    pub fn parse(ipv6_hdr: &Ipv6Header, mut buf: DemiBuffer) -> Result<(Self, DemiBuffer), Fail> {
+        if buf.len() < ICMPV6_HEADER_SIZE {
+            return Err(Fail::new(EBADMSG, "ICMPv6 datagram too small for header"));
+        }
+        let hdr_buf: &[u8; ICMPV6_HEADER_SIZE] = &buf[..ICMPV6_HEADER_SIZE].try_into().unwrap();
+
+        let type_byte: u8 = hdr_buf[0];
+        let code: u8 = hdr_buf[1];
+        let checksum: u16 = u16::from_be_bytes([hdr_buf[2], hdr_buf[3]]);
+        if checksum != Self::checksum(ipv6_hdr, hdr_buf, &buf[ICMPV6_HEADER_SIZE..]) {
+            return Err(Fail::new(EBADMSG, "ICMPv6 checksum mismatch"));
+        }
+        let rest_of_header: &[u8; 4] = hdr_buf[4..8].try_into().unwrap();
+        let icmpv6_type: Icmpv6Type2 = Icmpv6Type2::parse(type_byte, rest_of_header)?;
+
+        buf.adjust(ICMPV6_HEADER_SIZE)?;
+        Ok((
+            Self {
+                protocol: icmpv6_type,
+                code,
+            },
+            buf,
+        ))
+    }
+
+    pub fn serialize(&self, buf: &mut [u8], ipv6_hdr: &Ipv6Header, data: &[u8]) {
+        let buf: &mut [u8; ICMPV6_HEADER_SIZE] = (&mut buf[..ICMPV6_HEADER_SIZE]).try_into().unwrap();
+        let (type_byte, rest_of_header) = self.protocol.serialize();
+        buf[0] = type_byte;
+        buf[1] = self.code;
+        // Skip the checksum for now.
+        buf[4..8].copy_from_slice(&rest_of_header[..]);
+        let checksum: u16 = Self::checksum(ipv6_hdr, buf, data);
+        buf[2..4].copy_from_slice(&checksum.to_be_bytes());
+    }
+
+    fn checksum(ipv6_hdr: &Ipv6Header, buf: &[u8; ICMPV6_HEADER_SIZE], body: &[u8]) -> u16 {
+        let mut state: u32 = 0xffff;
+        state += IpHeader::V6(*ipv6_hdr).pseudo_header_sum(IpProtocol::ICMPv6, ICMPV6_HEADER_SIZE + body.len());
+        state += u16::from_be_bytes([buf[0], buf[1]]) as u32;
+        // Skip the checksum.
+        state += 0;
+        state += u16::from_be_bytes([buf[4], buf[5]]) as u32;
+        state += u16::from_be_bytes([buf[6], buf[7]]) as u32;
+
+        let mut chunks_iter = body.chunks_exact(2);
+        while let Some(chunk) = chunks_iter.next() {
+            state += u16::from_be_bytes([chunk[0], chunk[1]]) as u32;
+        }
+        if let Some(&b) = chunks_iter.remainder().get(0) {
+            state += u16::from_be_bytes([b, 0]) as u32;
+        }
+
+        while state > 0xFFFF {
+            state -= 0xFFFF;
+        }
+        !state as u16
+    }
+
+    pub fn get_protocol(&self) -> Icmpv6Type2 {
+        self.protocol
+    }
+
+    pub fn get_code(&self) -> u8 {
+        self.code
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Icmpv6Header,
+        ICMPV6_HEADER_SIZE,
+    };
+    use crate::{
+        inetstack::protocols::{
+            icmpv6::datagram::Icmpv6Type2,
+            ip::IpProtocol,
+            ipv6::Ipv6Header,
+        },
+        runtime::memory::DemiBuffer,
+    };
+    use ::anyhow::Result;
+    use ::std::net::Ipv6Addr;
+
+    /// Tests that the checksum covers the pseudo-header, so a message cannot be taken for one between other hosts.
+    #[test]
+    fn checksum_covers_pseudo_header() -> Result<()> {
+        let src_addr: Ipv6Addr = Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1);
+        let dst_addr: Ipv6Addr = Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 2);
+        let ipv6_hdr: Ipv6Header = Ipv6Header::new(src_addr, dst_addr, IpProtocol::ICMPv6);
+        let echo_request: Icmpv6Type2 = Icmpv6Type2::EchoRequest { id: 7, seq_num: 1 };
+        let data: [u8; 3] = [1, 2, 3];
+
+        let mut bytes: Vec<u8> = vec![0; ICMPV6_HEADER_SIZE];
+        Icmpv6Header::new(echo_request, 0).serialize(&mut bytes, &ipv6_hdr, &data);
+        bytes.extend_from_slice(&data);
+
+        let (header, payload): (Icmpv6Header, DemiBuffer) =
+            Icmpv6Header::parse(&ipv6_hdr, DemiBuffer::from_slice(&bytes)?)?;
+        crate::ensure_eq!(header.get_protocol(), echo_request);
+        crate::ensure_eq!(&payload[..], &data[..]);
+
+        let other_hdr: Ipv6Header =
+            Ipv6Header::new(Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 3), dst_addr, IpProtocol::ICMPv6);
+        crate::ensure_eq!(
+            Icmpv6Header::parse(&other_hdr, DemiBuffer::from_slice(&bytes)?)
+                .map_err(|e| e.errno)
+                .err(),
+            Some(libc::EBADMSG)
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,78 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+use super::Icmpv6Header;
+use crate::{
+    inetstack::protocols::{
+        ethernet2::Ethernet2Header,
+        ipv6::Ipv6Header,
+    },
+    runtime::{
+        memory::DemiBuffer,
+        network::PacketBuf,
+    },
+};
+
+/// Message for ICMPv6
+#[derive(Clone)]
+pub struct Icmpv6Message {
+    ethernet2_hdr: Ethernet2Header,
+    ipv6_hdr: Ipv6Header,
+    icmpv6_hdr: Icmpv6Header,
+    data: DemiBuffer,
+}
+
+/// Associated Functions for Icmpv6Message
+impl Icmpv6Message {
+    /// Creates an ICMPv6 message.
+    pub fn new(
+        ethernet2_hdr: Ethernet2Header,
+        ipv6_hdr: Ipv6Header,
+        icmpv6_hdr: Icmpv6Header,
+        data: DemiBuffer,
+    ) -> Self {
+        Self {
+            ethernet2_hdr,
+            ipv6_hdr,
+            icmpv6_hdr,
+            data,
+        }
+    }
+}
+
+/// PacketBuf Trait Implementation for Icmpv6Message
+impl PacketBuf for Icmpv6Message {
+    fn header_size(&self) -> usize {
+        self.ethernet2_hdr.compute_size() + self.ipv6_hdr.compute_size() + self.icmpv6_hdr.size()
+    }
+
+    fn body_size(&self) -> usize {
+        self.data.len()
+    }
+
+    fn write_header(&self, buf: &mut [u8]) {
+        let eth_hdr_size: usize = self.ethernet2_hdr.compute_size();
+        let ipv6_hdr_size: usize = self.ipv6_hdr.compute_size();
+        let icmpv6_hdr_size: usize = self.icmpv6_hdr.size();
+        let mut cur_pos: usize = 0;
+
+        self.ethernet2_hdr
+            .serialize(&mut buf[cur_pos..(cur_pos + eth_hdr_size)]);
+        cur_pos += eth_hdr_size;
+
+        let ipv6_payload_len: usize = icmpv6_hdr_size + self.body_size();
+        self.ipv6_hdr
+            .serialize(&mut buf[cur_pos..(cur_pos + ipv6_hdr_size)], ipv6_payload_len);
+        cur_pos += ipv6_hdr_size;
+
+        self.icmpv6_hdr.serialize(
+            &mut buf[cur_pos..(cur_pos + icmpv6_hdr_size)],
+            &self.ipv6_hdr,
+            &self.data,
+        );
+    }
+
+    fn take_body(&self) -> Option<DemiBuffer> {
+        Some(self.data.clone())
+    }
+}
@@ -0,0 +1,14 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+mod header;
+mod message;
+mod protocol;
+
+pub use self::{
+    header::ICMPV6_HEADER_SIZE,
+    protocol::ICMPV6_ECHO_REQUEST_MESSAGE_SIZE,
+};
+pub use header::Icmpv6Header;
+pub use message::Icmpv6Message;
+pub use protocol::Icmpv6Type2;
@@ -0,0 +1,7 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+pub(crate) mod datagram;
+mod peer;
+
+pub use peer::Icmpv6Peer;
@@ -0,0 +1,318 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+use crate::{
+    inetstack::{
+        futures::UtilityMethods,
+        protocols::{
+            ethernet2::{
+                EtherType2,
+                Ethernet2Header,
+            },
+            icmpv6::datagram::{
+                self,
+                Icmpv6Header,
+                Icmpv6Message,
+                Icmpv6Type2,
+            },
+            ip::IpProtocol,
+            ipv6::Ipv6Header,
+            ndp::NdpPeer,
+        },
+    },
+    runtime::{
+        fail::Fail,
+        memory::DemiBuffer,
+        network::{
+            types::MacAddress,
+            NetworkRuntime,
+        },
+        queue::BackgroundTask,
+        timer::{
+            TimerRc,
+            WaitFuture,
+        },
+    },
+    scheduler::{
+        Scheduler,
+        TaskHandle,
+    },
+};
+use ::futures::{
+    channel::{
+        mpsc,
+        oneshot::{
+            channel,
+            Receiver,
+            Sender,
+        },
+    },
+    FutureExt,
+    StreamExt,
+};
+use ::rand::{
+    prelude::SmallRng,
+    Rng,
+    SeedableRng,
+};
+use ::std::{
+    cell::RefCell,
+    collections::HashMap,
+    future::Future,
+    net::Ipv6Addr,
+    num::Wrapping,
+    process,
+    rc::Rc,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+//==============================================================================
+// ReqQueue
+//==============================================================================
+
+/// Queue of Requests
+struct ReqQueue(HashMap<(u16, u16), Sender<()>>);
+
+/// Associate Implementation for ReqQueue
+impl ReqQueue {
+    /// Creates an empty queue of requests.
+    pub fn new() -> Self {
+        Self { 0: HashMap::new() }
+    }
+
+    /// Inserts a new request in the target queue of  requests.
+    pub fn insert(&mut self, req: (u16, u16), tx: Sender<()>) -> Option<Sender<()>> {
+        self.0.insert(req, tx)
+    }
+
+    /// Removes a request from the target queue of requests.
+    pub fn remove(&mut self, req: &(u16, u16)) -> Option<Sender<()>> {
+        self.0.remove(req)
+    }
+}
+
+//==============================================================================
+// Icmpv6Peer
+//==============================================================================
+
+///
+/// Internet Control Message Protocol for IPv6 (ICMPv6)
+///
+/// This is the counterpart of ICMP for the Internet Protocol version 6 (IPv6).
+/// Besides echo and error messages, it carries Neighbor Discovery, which we
+/// hand over to the NDP peer.
+///
+/// ICMPv6 is defined in RFC 4443.
+///
+pub struct Icmpv6Peer<const N: usize> {
+    /// Underlying Runtime
+    rt: Rc<dyn NetworkRuntime<N>>,
+
+    clock: TimerRc,
+
+    local_link_addr: MacAddress,
+    local_ipv6_addr: Ipv6Addr,
+
+    /// Underlying NDP Peer
+    ndp: NdpPeer<N>,
+
+    /// Transmitter
+    tx: mpsc::UnboundedSender<(Ipv6Addr, u16, u16, DemiBuffer)>,
+
+    /// Queue of Requests
+    requests: Rc<RefCell<ReqQueue>>,
+
+    /// Sequence Number
+    seq: Wrapping<u16>,
+
+    rng: Rc<RefCell<SmallRng>>,
+
+    /// The background co-routine relies to incoming PING requests.
+    /// We annotate it as unused because the compiler believes that it is never called which is not the case.
+    #[allow(unused)]
+    background: TaskHandle,
+}
+
+impl<const N: usize> Icmpv6Peer<N> {
+    /// Creates a new peer for handling ICMPv6.
+    pub fn new(
+        rt: Rc<dyn NetworkRuntime<N>>,
+        scheduler: Scheduler,
+        clock: TimerRc,
+        local_link_addr: MacAddress,
+        local_ipv6_addr: Ipv6Addr,
+        ndp: NdpPeer<N>,
+        rng_seed: [u8; 32],
+    ) -> Result<Self, Fail> {
+        let (tx, rx) = mpsc::unbounded();
+        let requests = ReqQueue::new();
+        let rng: Rc<RefCell<SmallRng>> = Rc::new(RefCell::new(SmallRng::from_seed(rng_seed)));
+        let task: BackgroundTask = BackgroundTask::new(
+            String::from("Inetstack::ICMPv6::background"),
+            Box::pin(Self::background(
+                rt.clone(),
+                local_link_addr,
+                local_ipv6_addr,
+                ndp.clone(),
+                rx,
+            )),
+        );
+        let handle: TaskHandle = match scheduler.insert(task) {
+            Some(handle) => handle,
+            None => {
+                let message: String = format!("failed to schedule background co-routine for ICMPv6 module");
+                error!("{}", message);
+                return Err(Fail::new(libc::EAGAIN, &message));
+            },
+        };
+        Ok(Icmpv6Peer {
+            rt,
+            clock,
+            local_link_addr,
+            local_ipv6_addr,
+            ndp,
+            tx,
+            requests: Rc::new(RefCell::new(requests)),
+            seq: Wrapping(0),
+            rng,
+            background: handle,
+        })
+    }
+
+    /// Background task for replying to ICMPv6 messages.
+    async fn background(
+        rt: Rc<dyn NetworkRuntime<N>>,
+        local_link_addr: MacAddress,
+        local_ipv6_addr: Ipv6Addr,
+        ndp: NdpPeer<N>,
+        mut rx: mpsc::UnboundedReceiver<(Ipv6Addr, u16, u16, DemiBuffer)>,
+    ) {
+        // Reply requests.
+        while let Some((dst_ipv6_addr, id, seq_num, data)) = rx.next().await {
+            debug!("initiating NDP query");
+            let dst_link_addr: MacAddress = match ndp.query(dst_ipv6_addr).await {
+                Ok(dst_link_addr) => dst_link_addr,
+                Err(e) => {
+                    warn!("reply_to_ping({}, {}, {}) failed: {:?}", dst_ipv6_addr, id, seq_num, e);
+                    continue;
+                },
+            };
+            debug!("NDP query complete ({} -> {})", dst_ipv6_addr, dst_link_addr);
+            debug!("reply ping ({}, {}, {})", dst_ipv6_addr, id, seq_num);
+            // Send reply message.
+            rt.transmit(Box::new(Icmpv6Message::new(
+                Ethernet2Header::new(dst_link_addr, local_link_addr, EtherType2::Ipv6),
+                Ipv6Header::new(local_ipv6_addr, dst_ipv6_addr, IpProtocol::ICMPv6),
+                Icmpv6Header::new(Icmpv6Type2::EchoReply { id, seq_num }, 0),
+                data,
+            )));
+        }
+    }
+
+    /// Parses and handles a ICMPv6 message.
+    pub fn receive(&mut self, ipv6_header: &Ipv6Header, buf: DemiBuffer) -> Result<(), Fail> {
+        let (icmpv6_hdr, data) = Icmpv6Header::parse(ipv6_header, buf)?;
+        debug!("ICMPv6 received {:?}", icmpv6_hdr);
+        match icmpv6_hdr.get_protocol() {
+            Icmpv6Type2::EchoRequest { id, seq_num } => {
+                self.tx
+                    .unbounded_send((ipv6_header.get_src_addr(), id, seq_num, data))
+                    .unwrap();
+            },
+            Icmpv6Type2::EchoReply { id, seq_num } => {
+                if let Some(tx) = self.requests.borrow_mut().remove(&(id, seq_num)) {
+                    let _ = tx.send(());
+                }
+            },
+            Icmpv6Type2::NeighborSolicitation | Icmpv6Type2::NeighborAdvertisement { .. } => {
+                return self.ndp.receive(ipv6_header, &icmpv6_hdr, data);
+            },
+            _ => {
+                warn!("Unsupported ICMPv6 message: {:?}", icmpv6_hdr);
+            },
+        }
+        Ok(())
+    }
+
+    /// Computes the identifier for an ICMPv6 message.
+    fn make_id(&self) -> u16 {
+        let mut state: u32 = 0xFFFF;
+        for segment in self.local_ipv6_addr.segments() {
+            state += segment as u32;
+        }
+
+        let mut pid_buf: [u8; 4] = [0u8; 4];
+        pid_buf[0..4].copy_from_slice(&process::id().to_be_bytes());
+        state += u16::from_be_bytes([pid_buf[0], pid_buf[1]]) as u32;
+        state += u16::from_be_bytes([pid_buf[2], pid_buf[3]]) as u32;
+
+        let nonce: [u8; 2] = self.rng.borrow_mut().gen();
+        state += u16::from_be_bytes([nonce[0], nonce[1]]) as u32;
+
+        while state > 0xFFFF {
+            state -= 0xFFFF;
+        }
+        !state as u16
+    }
+
+    /// Computes sequence number for an ICMPv6 message.
+    fn make_seq_num(&mut self) -> u16 {
+        let Wrapping(seq_num) = self.seq;
+        self.seq += Wrapping(1);
+        seq_num
+    }
+
+    /// Sends a ping to a remote peer.Wrapping
+    pub fn ping(
+        &mut self,
+        dst_ipv6_addr: Ipv6Addr,
+        timeout: Option<Duration>,
+    ) -> impl Future<Output = Result<Duration, Fail>> {
+        let timeout: Duration = timeout.unwrap_or_else(|| Duration::from_millis(5000));
+        let id: u16 = self.make_id();
+        let seq_num: u16 = self.make_seq_num();
+        let echo_request: Icmpv6Type2 = Icmpv6Type2::EchoRequest { id, seq_num };
+        let ndp: NdpPeer<N> = self.ndp.clone();
+        let rt: Rc<dyn NetworkRuntime<N>> = self.rt.clone();
+        let clock: TimerRc = self.clock.clone();
+        let requests: Rc<RefCell<ReqQueue>> = self.requests.clone();
+        let local_link_addr: MacAddress = self.local_link_addr.clone();
+        let local_ipv6_addr: Ipv6Addr = self.local_ipv6_addr.clone();
+        async move {
+            let t0: Instant = clock.now();
+            debug!("initiating NDP query");
+            let dst_link_addr: MacAddress = ndp.query(dst_ipv6_addr).await?;
+            debug!("NDP query complete ({} -> {})", dst_ipv6_addr, dst_link_addr);
+
+            let data: DemiBuffer = DemiBuffer::new(datagram::ICMPV6_ECHO_REQUEST_MESSAGE_SIZE);
+
+            let msg: Icmpv6Message = Icmpv6Message::new(
+                Ethernet2Header::new(dst_link_addr, local_link_addr, EtherType2::Ipv6),
+                Ipv6Header::new(local_ipv6_addr, dst_ipv6_addr, IpProtocol::ICMPv6),
+                Icmpv6Header::new(echo_request, 0),
+                data,
+            );
+            rt.transmit(Box::new(msg));
+            let rx: Receiver<()> = {
+                let (tx, rx) = channel();
+                assert!(requests.borrow_mut().insert((id, seq_num), tx).is_none());
+                rx
+            };
+            let timer: WaitFuture<TimerRc> = clock.wait(clock.clone(), timeout);
+            match rx.fuse().with_timeout(timer).await? {
+                // Request completed successfully.
+                Ok(_) => Ok(clock.now() - t0),
+                // Request expired.
+                Err(_) => {
+                    let message: String = format!("timer expired");
+                    requests.borrow_mut().remove(&(id, seq_num));
+                    error!("ping(): {}", message);
+                    Err(Fail::new(libc::ETIMEDOUT, &message))
+                },
+            }
+        }
+    }
+}
@@ -0,0 +1,37 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// Explicit Congestion Notification (ECN) codepoints, carried in the two low-order bits of the IPv4 TOS byte.
+/// See RFC 3168 Section 5.
+#[repr(u8)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Ecn {
+    /// Not ECN-Capable Transport
+    NotEct = 0b00,
+    /// ECN-Capable Transport (1)
+    Ect1 = 0b01,
+    /// ECN-Capable Transport (0)
+    Ect0 = 0b10,
+    /// Congestion Experienced
+    Ce = 0b11,
+}
+
+//======================================================================================================================
+// Trait Implementations
+//======================================================================================================================
+
+/// From trait implementation.  Only the two low-order bits of `value` are looked at.
+impl From<u8> for Ecn {
+    fn from(value: u8) -> Self {
+        match value & 0b11 {
+            0b00 => Ecn::NotEct,
+            0b01 => Ecn::Ect1,
+            0b10 => Ecn::Ect0,
+            _ => Ecn::Ce,
+        }
+    }
+}
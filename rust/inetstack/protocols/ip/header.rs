@@ -0,0 +1,152 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use crate::inetstack::protocols::{
+    ethernet2::EtherType2,
+    ip::{
+        Ecn,
+        IpProtocol,
+    },
+    ipv4::Ipv4Header,
+    ipv6::Ipv6Header,
+};
+use ::std::net::IpAddr;
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// Header of the IP datagram that carries a transport segment, for protocols that run on both IP versions.
+#[derive(Debug, Copy, Clone)]
+pub enum IpHeader {
+    V4(Ipv4Header),
+    V6(Ipv6Header),
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl IpHeader {
+    /// Instantiates an empty header of the IP version that `src_addr` and `dst_addr` belong to.  Both addresses must
+    /// belong to the same version.
+    pub fn new(src_addr: IpAddr, dst_addr: IpAddr, protocol: IpProtocol) -> Self {
+        match (src_addr, dst_addr) {
+            (IpAddr::V4(src_addr), IpAddr::V4(dst_addr)) => IpHeader::V4(Ipv4Header::new(src_addr, dst_addr, protocol)),
+            (IpAddr::V6(src_addr), IpAddr::V6(dst_addr)) => IpHeader::V6(Ipv6Header::new(src_addr, dst_addr, protocol)),
+            _ => panic!("mismatched IP versions ({:?} -> {:?})", src_addr, dst_addr),
+        }
+    }
+
+    /// Computes the size of the target IP header.
+    pub fn compute_size(&self) -> usize {
+        match self {
+            IpHeader::V4(header) => header.compute_size(),
+            IpHeader::V6(header) => header.compute_size(),
+        }
+    }
+
+    /// Serializes the target IP header.
+    pub fn serialize(&self, buf: &mut [u8], payload_len: usize) {
+        match self {
+            IpHeader::V4(header) => header.serialize(buf, payload_len),
+            IpHeader::V6(header) => header.serialize(buf, payload_len),
+        }
+    }
+
+    /// Returns the EtherType of frames that carry the target IP header.
+    pub fn get_ether_type(&self) -> EtherType2 {
+        match self {
+            IpHeader::V4(_) => EtherType2::Ipv4,
+            IpHeader::V6(_) => EtherType2::Ipv6,
+        }
+    }
+
+    /// Returns the source address stored in the target IP header.
+    pub fn get_src_addr(&self) -> IpAddr {
+        match self {
+            IpHeader::V4(header) => IpAddr::V4(header.get_src_addr()),
+            IpHeader::V6(header) => IpAddr::V6(header.get_src_addr()),
+        }
+    }
+
+    /// Returns the destination address stored in the target IP header.
+    pub fn get_dest_addr(&self) -> IpAddr {
+        match self {
+            IpHeader::V4(header) => IpAddr::V4(header.get_dest_addr()),
+            IpHeader::V6(header) => IpAddr::V6(header.get_dest_addr()),
+        }
+    }
+
+    /// Returns the upper-layer protocol of the target IP header.
+    pub fn get_protocol(&self) -> IpProtocol {
+        match self {
+            IpHeader::V4(header) => header.get_protocol(),
+            IpHeader::V6(header) => header.get_protocol(),
+        }
+    }
+
+    /// Returns the ECN field stored in the target IP header.
+    pub fn get_ecn(&self) -> Ecn {
+        match self {
+            IpHeader::V4(header) => header.get_ecn(),
+            IpHeader::V6(header) => header.get_ecn(),
+        }
+    }
+
+    /// Sets the ECN field of the target IP header.
+    pub fn set_ecn(&mut self, ecn: Ecn) {
+        match self {
+            IpHeader::V4(header) => header.set_ecn(ecn),
+            IpHeader::V6(header) => header.set_ecn(ecn),
+        }
+    }
+
+    /// Folds the pseudo-header that upper-layer checksums cover into a one's complement sum (RFC 793 Section 3.1 for
+    /// IPv4, RFC 8200 Section 8.1 for IPv6).  Both come down to the addresses, the protocol and the upper-layer length.
+    pub fn pseudo_header_sum(&self, protocol: IpProtocol, length: usize) -> u32 {
+        let mut state: u32 = 0;
+        match self {
+            IpHeader::V4(header) => {
+                for chunk in header.get_src_addr().octets().chunks_exact(2) {
+                    state += u16::from_be_bytes([chunk[0], chunk[1]]) as u32;
+                }
+                for chunk in header.get_dest_addr().octets().chunks_exact(2) {
+                    state += u16::from_be_bytes([chunk[0], chunk[1]]) as u32;
+                }
+            },
+            IpHeader::V6(header) => {
+                for chunk in header.get_src_addr().octets().chunks_exact(2) {
+                    state += u16::from_be_bytes([chunk[0], chunk[1]]) as u32;
+                }
+                for chunk in header.get_dest_addr().octets().chunks_exact(2) {
+                    state += u16::from_be_bytes([chunk[0], chunk[1]]) as u32;
+                }
+            },
+        }
+        state += u16::from_be_bytes([0, protocol as u8]) as u32;
+        state += (length >> 16) as u32;
+        state += (length & 0xffff) as u32;
+        state
+    }
+}
+
+//======================================================================================================================
+// Trait Implementations
+//======================================================================================================================
+
+impl From<Ipv4Header> for IpHeader {
+    fn from(header: Ipv4Header) -> Self {
+        IpHeader::V4(header)
+    }
+}
+
+impl From<Ipv6Header> for IpHeader {
+    fn from(header: Ipv6Header) -> Self {
+        IpHeader::V6(header)
+    }
+}
@@ -1,10 +1,14 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT license.
 
+mod ecn;
 mod ephemeral;
+mod header;
 mod protocol;
 
 pub use self::{
+    ecn::Ecn,
     ephemeral::EphemeralPorts,
+    header::IpHeader,
     protocol::IpProtocol,
 };
@@ -12,7 +12,7 @@ use ::std::convert::TryFrom;
 // Structures
 //======================================================================================================================
 
-/// Ipv4 Protocol
+/// IP Protocol, as found in the IPv4 protocol field and the IPv6 next header field
 #[repr(u8)]
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum IpProtocol {
@@ -22,6 +22,8 @@ pub enum IpProtocol {
     TCP = 0x06,
     /// User Datagram Protocol
     UDP = 0x11,
+    /// Internet Control Message Protocol for IPv6
+    ICMPv6 = 0x3a,
 }
 
 //======================================================================================================================
@@ -37,7 +39,8 @@ impl TryFrom<u8> for IpProtocol {
             0x01 => Ok(IpProtocol::ICMPv4),
             0x06 => Ok(IpProtocol::TCP),
             0x11 => Ok(IpProtocol::UDP),
-            _ => Err(Fail::new(libc::ENOTSUP, "unsupported IPv4 protocol")),
+            0x3a => Ok(IpProtocol::ICMPv6),
+            _ => Err(Fail::new(libc::ENOTSUP, "unsupported IP protocol")),
         }
     }
 }
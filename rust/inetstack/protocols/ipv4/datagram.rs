@@ -6,7 +6,10 @@
 //==============================================================================
 
 use crate::{
-    inetstack::protocols::ip::IpProtocol,
+    inetstack::protocols::ip::{
+        Ecn,
+        IpProtocol,
+    },
     runtime::{
         fail::Fail,
         memory::DemiBuffer,
@@ -69,7 +72,7 @@ pub struct Ipv4Header {
     /// Differentiated Services Code Point (6 bits).
     dscp: u8,
     /// Explicit Congestion Notification (2 bits).
-    ecn: u8,
+    ecn: Ecn,
     /// Total length of the packet including header and data (16 bits).
     #[allow(unused)]
     total_length: u16,
@@ -104,7 +107,7 @@ impl Ipv4Header {
             version: IPV4_VERSION,
             ihl: IPV4_IHL_NO_OPTIONS,
             dscp: 0,
-            ecn: 0,
+            ecn: Ecn::NotEct,
             total_length: IPV4_HEADER_MIN_SIZE as u16,
             identification: 0,
             flags: IPV4_CTRL_FLAG_DF,
@@ -153,10 +156,7 @@ impl Ipv4Header {
         }
 
         // Explicit congestion notification.
-        let ecn: u8 = hdr_buf[1] & 3;
-        if ecn != 0 {
-            warn!("ignoring ecn field (ecn={:?})", ecn);
-        }
+        let ecn: Ecn = Ecn::from(hdr_buf[1]);
 
         // Total length.
         let total_length: u16 = u16::from_be_bytes([hdr_buf[2], hdr_buf[3]]);
@@ -188,19 +188,8 @@ impl Ipv4Header {
             return Err(Fail::new(EBADMSG, "ipv4 datagram is marked as evil"));
         }
 
-        // TODO: drop this check once we support fragmentation.
-        if flags & IPV4_CTRL_FLAG_MF != 0 {
-            warn!("fragmentation is not supported flags={:?}", flags);
-            return Err(Fail::new(ENOTSUP, "ipv4 fragmentation is not supported"));
-        }
-
-        // Fragment offset.
+        // Fragment offset.  Fragments are put back together by the reassembly table, not here.
         let fragment_offset: u16 = u16::from_be_bytes([hdr_buf[6], hdr_buf[7]]) & 0x1fff;
-        // TODO: drop this check once we support fragmentation.
-        if fragment_offset != 0 {
-            warn!("fragmentation is not supported offset={:?}", fragment_offset);
-            return Err(Fail::new(ENOTSUP, "ipv4 fragmentation is not supported"));
-        }
 
         // Time to live.
         let time_to_live: u8 = hdr_buf[8];
@@ -260,7 +249,7 @@ impl Ipv4Header {
         buf[0] = (self.version << 4) | self.ihl;
 
         // DSCP + ECN.
-        buf[1] = (self.dscp << 2) | (self.ecn & 3);
+        buf[1] = (self.dscp << 2) | (self.ecn as u8);
 
         // Total Length.
         buf[2..4].copy_from_slice(&(IPV4_HEADER_MIN_SIZE + (payload_len as u16)).to_be_bytes());
@@ -305,6 +294,52 @@ impl Ipv4Header {
         self.protocol
     }
 
+    /// Returns the identification field stored in the target IPv4 header.
+    pub fn get_identification(&self) -> u16 {
+        self.identification
+    }
+
+    /// Sets the identification field of the target IPv4 header.
+    pub fn set_identification(&mut self, identification: u16) {
+        self.identification = identification;
+    }
+
+    /// Checks if the MF (more fragments) flag is set in the target IPv4 header.
+    pub fn get_more_fragments(&self) -> bool {
+        self.flags & IPV4_CTRL_FLAG_MF != 0
+    }
+
+    /// Returns where the payload of the target IPv4 header belongs in the original datagram (in bytes).
+    pub fn get_fragment_offset(&self) -> usize {
+        (self.fragment_offset as usize) << 3
+    }
+
+    /// Checks if the target IPv4 header belongs to a fragment, rather than to a whole datagram.
+    pub fn is_fragment(&self) -> bool {
+        self.get_more_fragments() || self.fragment_offset != 0
+    }
+
+    /// Makes the target IPv4 header describe a fragment that starts at `offset` (in bytes, a multiple of 8) in the
+    /// original datagram.  Fragments may not be fragmented any further if they are too big, so this clears DF.
+    pub fn set_fragment(&mut self, offset: usize, more_fragments: bool) {
+        debug_assert_eq!(offset & 0x7, 0);
+        self.fragment_offset = (offset >> 3) as u16 & 0x1fff;
+        self.flags &= !(IPV4_CTRL_FLAG_DF | IPV4_CTRL_FLAG_MF);
+        if more_fragments {
+            self.flags |= IPV4_CTRL_FLAG_MF;
+        }
+    }
+
+    /// Returns the ECN field stored in the target IPv4 header.
+    pub fn get_ecn(&self) -> Ecn {
+        self.ecn
+    }
+
+    /// Sets the ECN field of the target IPv4 header.
+    pub fn set_ecn(&mut self, ecn: Ecn) {
+        self.ecn = ecn;
+    }
+
     /// Computes the checksum of the target IPv4 header.
     pub fn compute_checksum(buf: &[u8]) -> u16 {
         let mut state: u32 = 0xffff;
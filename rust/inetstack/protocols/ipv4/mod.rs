@@ -2,13 +2,29 @@
 // Licensed under the MIT license.
 
 mod datagram;
+mod reassembly;
+mod routing;
+
+#[cfg(test)]
+mod tests;
 
 //==============================================================================
 // Exports
 //==============================================================================
 
-pub use self::datagram::{
-    Ipv4Header,
-    IPV4_HEADER_MIN_SIZE,
-    IPV4_HEADER_MAX_SIZE,
+pub use self::{
+    datagram::{
+        Ipv4Header,
+        IPV4_HEADER_MIN_SIZE,
+        IPV4_HEADER_MAX_SIZE,
+    },
+    reassembly::{
+        ReassemblyTable,
+        DEFAULT_REASSEMBLY_MEMORY,
+        DEFAULT_REASSEMBLY_TIMEOUT,
+    },
+    routing::{
+        Route,
+        RoutingTable,
+    },
 };
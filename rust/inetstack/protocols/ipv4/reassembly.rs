@@ -0,0 +1,283 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+// Reassembly of fragmented IPv4 datagrams.
+// See RFC 791 Section 3.2 for the algorithm, and RFC 5722 for why overlapping fragments are dropped rather than merged.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use super::{
+    datagram::IPV4_HEADER_MIN_SIZE,
+    Ipv4Header,
+};
+use crate::{
+    inetstack::protocols::ip::Ecn,
+    runtime::{
+        fail::Fail,
+        memory::DemiBuffer,
+    },
+};
+use ::libc::{
+    EBADMSG,
+    ENOBUFS,
+};
+use ::std::{
+    collections::HashMap,
+    net::Ipv4Addr,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+//==============================================================================
+// Constants
+//==============================================================================
+
+/// How long we wait for the missing fragments of a datagram.  RFC 791 suggests 15 seconds, Linux waits for 30.
+pub const DEFAULT_REASSEMBLY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How many bytes of fragments we hold on to, across all datagrams.
+pub const DEFAULT_REASSEMBLY_MEMORY: usize = 4 << 20;
+
+/// Bytes that a fragment holds on to besides its buffer, i.e. the metadata of the buffer (as big as an mbuf header).
+pub(super) const FRAGMENT_OVERHEAD: usize = 128;
+
+/// How many fragments a datagram may be cut into.  That is enough for the largest datagram over a 1280-byte MTU, and
+/// keeps a flood of tiny fragments from pinning a buffer each.
+pub(super) const MAX_FRAGMENTS: usize = 64;
+
+/// Largest payload an IPv4 datagram can carry.
+const MAX_PAYLOAD_SIZE: usize = u16::MAX as usize - IPV4_HEADER_MIN_SIZE as usize;
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Fragments that belong to the same datagram share these (RFC 791 Section 3.2).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct FragmentKey {
+    src_addr: Ipv4Addr,
+    dst_addr: Ipv4Addr,
+    protocol: u8,
+    identification: u16,
+}
+
+/// A datagram whose fragments have not all arrived yet.
+struct PartialDatagram {
+    // Header of the first fragment, once we have it.
+    header: Option<Ipv4Header>,
+    // Received fragments, as (offset, payload).  They never overlap.
+    fragments: Vec<(usize, DemiBuffer)>,
+    // Size of the original payload, once we have the last fragment.
+    total_size: Option<usize>,
+    // Bytes of payload received so far.
+    received: usize,
+    // Bytes charged against the memory limit for the fragments.
+    charged: usize,
+    // Did any fragment experience congestion?
+    congestion_experienced: bool,
+    // When we give up on this datagram.
+    expiry: Instant,
+}
+
+/// Datagrams being reassembled.
+pub struct ReassemblyTable {
+    datagrams: HashMap<FragmentKey, PartialDatagram>,
+    // How long a datagram may take to arrive completely.
+    timeout: Duration,
+    // Upper bound on `memory`.
+    max_memory: usize,
+    // Bytes charged for the fragments held across all datagrams.
+    memory: usize,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+impl PartialDatagram {
+    fn new(expiry: Instant) -> Self {
+        Self {
+            header: None,
+            fragments: Vec::new(),
+            total_size: None,
+            received: 0,
+            charged: 0,
+            congestion_experienced: false,
+            expiry,
+        }
+    }
+
+    fn is_complete(&self) -> bool {
+        self.header.is_some() && self.total_size == Some(self.received)
+    }
+
+    /// Puts the fragments back together, once all of them arrived.
+    fn assemble(self) -> (Ipv4Header, DemiBuffer) {
+        let mut header: Ipv4Header = self.header.expect("complete datagram should have its first fragment");
+        let total_size: usize = self.total_size.expect("complete datagram should have its last fragment");
+
+        let mut buf: DemiBuffer = DemiBuffer::new(total_size as u16);
+        for (offset, fragment) in self.fragments.iter() {
+            buf[*offset..(*offset + fragment.len())].copy_from_slice(&fragment[..]);
+        }
+
+        header.set_fragment(0, false);
+        // A congestion mark on any fragment applies to the whole datagram (RFC 3168 Section 5.3).
+        if self.congestion_experienced {
+            header.set_ecn(Ecn::Ce);
+        }
+        (header, buf)
+    }
+}
+
+impl ReassemblyTable {
+    pub fn new(timeout: Duration, max_memory: usize) -> Self {
+        Self {
+            datagrams: HashMap::new(),
+            timeout,
+            max_memory,
+            memory: 0,
+        }
+    }
+
+    /// Number of datagrams waiting for fragments.
+    pub fn len(&self) -> usize {
+        self.datagrams.len()
+    }
+
+    /// Bytes charged for the fragments currently held.
+    pub fn memory(&self) -> usize {
+        self.memory
+    }
+
+    /// Drops all datagrams whose reassembly timed out, and returns how many were dropped.
+    pub fn expire(&mut self, now: Instant) -> usize {
+        if self.datagrams.is_empty() {
+            return 0;
+        }
+        let before: usize = self.datagrams.len();
+        let mut freed: usize = 0;
+        self.datagrams.retain(|_, datagram| {
+            if datagram.expiry > now {
+                return true;
+            }
+            freed += datagram.charged;
+            false
+        });
+        self.memory -= freed;
+        before - self.datagrams.len()
+    }
+
+    /// Adds a fragment to the datagram it belongs to.  Returns the whole datagram once its last missing fragment
+    /// arrived, and `None` while fragments are still missing.
+    pub fn insert(
+        &mut self,
+        header: Ipv4Header,
+        payload: DemiBuffer,
+        now: Instant,
+    ) -> Result<Option<(Ipv4Header, DemiBuffer)>, Fail> {
+        let offset: usize = header.get_fragment_offset();
+        let size: usize = payload.len();
+        // A fragment pins its whole buffer, however little of it the payload takes.
+        let charge: usize = payload.capacity().max(size) + FRAGMENT_OVERHEAD;
+        let end: usize = offset + size;
+        let more_fragments: bool = header.get_more_fragments();
+
+        // All fragments but the last one carry a multiple of 8 bytes, and no fragment reaches past the largest
+        // datagram (which would otherwise overflow our buffer, as in the "ping of death").
+        if more_fragments && (size == 0 || size % 8 != 0) {
+            return Err(Fail::new(EBADMSG, "ipv4 fragment size is not a multiple of 8"));
+        }
+        if end > MAX_PAYLOAD_SIZE {
+            return Err(Fail::new(EBADMSG, "ipv4 fragment reaches past the largest datagram"));
+        }
+
+        let key: FragmentKey = FragmentKey {
+            src_addr: header.get_src_addr(),
+            dst_addr: header.get_dest_addr(),
+            protocol: header.get_protocol() as u8,
+            identification: header.get_identification(),
+        };
+        let expiry: Instant = now + self.timeout;
+        let datagram: &mut PartialDatagram = self
+            .datagrams
+            .entry(key)
+            .or_insert_with(|| PartialDatagram::new(expiry));
+
+        // The last fragment tells us how big the datagram is.  Nothing may contradict it.
+        let total_size: Option<usize> = if more_fragments { datagram.total_size } else { Some(end) };
+        let inconsistent: bool = match total_size {
+            Some(total_size) => {
+                datagram.total_size.map_or(false, |old| old != total_size)
+                    || end > total_size
+                    || datagram.fragments.iter().any(|(o, f)| o + f.len() > total_size)
+            },
+            None => false,
+        };
+        if inconsistent {
+            self.remove(&key);
+            return Err(Fail::new(EBADMSG, "ipv4 fragments disagree on the datagram size"));
+        }
+
+        // Retransmitted fragments are harmless, but any other overlap is either broken or an attack.
+        if datagram.fragments.iter().any(|(o, f)| *o == offset && o + f.len() == end) {
+            return Ok(None);
+        }
+        if datagram.fragments.iter().any(|(o, f)| offset < o + f.len() && *o < end) {
+            self.remove(&key);
+            return Err(Fail::new(EBADMSG, "overlapping ipv4 fragments"));
+        }
+        if datagram.fragments.len() == MAX_FRAGMENTS {
+            self.remove(&key);
+            return Err(Fail::new(EBADMSG, "too many ipv4 fragments"));
+        }
+
+        // Make room by giving up on the oldest datagrams first.
+        while self.memory + charge > self.max_memory {
+            let oldest: Option<FragmentKey> = self
+                .datagrams
+                .iter()
+                .filter(|(k, _)| **k != key)
+                .min_by_key(|(_, datagram)| datagram.expiry)
+                .map(|(k, _)| *k);
+            match oldest {
+                Some(oldest) => {
+                    warn!("ipv4 reassembly memory exhausted, dropping datagram {:?}", oldest);
+                    self.remove(&oldest);
+                },
+                None => {
+                    self.remove(&key);
+                    return Err(Fail::new(ENOBUFS, "ipv4 reassembly memory exhausted"));
+                },
+            }
+        }
+
+        let datagram: &mut PartialDatagram = self.datagrams.get_mut(&key).expect("datagram should still be there");
+        if offset == 0 {
+            datagram.header = Some(header);
+        }
+        datagram.total_size = total_size;
+        datagram.congestion_experienced |= header.get_ecn() == Ecn::Ce;
+        datagram.received += size;
+        datagram.charged += charge;
+        datagram.fragments.push((offset, payload));
+        self.memory += charge;
+
+        if !datagram.is_complete() {
+            return Ok(None);
+        }
+        let datagram: PartialDatagram = self.remove(&key).expect("datagram should still be there");
+        Ok(Some(datagram.assemble()))
+    }
+
+    // Stops reassembling a datagram, and releases its fragments.
+    fn remove(&mut self, key: &FragmentKey) -> Option<PartialDatagram> {
+        let datagram: PartialDatagram = self.datagrams.remove(key)?;
+        self.memory -= datagram.charged;
+        Some(datagram)
+    }
+}
@@ -0,0 +1,189 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+// Static routing for IPv4.
+// Destinations resolve to the next hop that ARP should be asked about: either the destination itself, if it sits on
+// the local network, or the gateway of the route with the longest matching prefix (RFC 1812 Section 5.2.4.3).
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use crate::runtime::fail::Fail;
+use ::libc::{
+    EINVAL,
+    ENETUNREACH,
+};
+use ::std::{
+    collections::HashMap,
+    net::Ipv4Addr,
+};
+
+//==============================================================================
+// Constants
+//==============================================================================
+
+/// Number of distinct prefix lengths, from /0 to /32.
+const NR_PREFIX_LENS: usize = 33;
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Route to a network that is reached through a gateway.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Route {
+    destination: Ipv4Addr,
+    prefix_len: u8,
+    gateway: Ipv4Addr,
+}
+
+/// Routing table of the IPv4 layer.  The local network is always on-link, other destinations go through the gateway
+/// of the most specific route that covers them.
+#[derive(Clone, Debug)]
+pub struct RoutingTable {
+    local_ipv4_addr: Ipv4Addr,
+    /// Prefix length of the local network.
+    local_prefix_len: u8,
+    /// Gateways, indexed by prefix length and then by destination network.
+    routes: Vec<HashMap<u32, Ipv4Addr>>,
+    /// Bit `i` is set if there is a route with a prefix length of `i`, so lookups only visit lengths that are in use.
+    prefix_lens: u64,
+}
+
+//==============================================================================
+// Associated Functions
+//==============================================================================
+
+impl Route {
+    /// Creates a route to `destination`/`prefix_len` through `gateway`.
+    pub fn new(destination: Ipv4Addr, prefix_len: u8, gateway: Ipv4Addr) -> Result<Self, Fail> {
+        if prefix_len as usize >= NR_PREFIX_LENS {
+            return Err(Fail::new(EINVAL, "prefix length is too long"));
+        }
+        if u32::from(destination) & !prefix_mask(prefix_len) != 0 {
+            return Err(Fail::new(EINVAL, "destination has host bits set"));
+        }
+        Ok(Self {
+            destination,
+            prefix_len,
+            gateway,
+        })
+    }
+
+    /// Creates the default route through `gateway`.
+    pub fn default_route(gateway: Ipv4Addr) -> Self {
+        Self {
+            destination: Ipv4Addr::UNSPECIFIED,
+            prefix_len: 0,
+            gateway,
+        }
+    }
+
+    pub fn get_destination(&self) -> Ipv4Addr {
+        self.destination
+    }
+
+    pub fn get_prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    pub fn get_gateway(&self) -> Ipv4Addr {
+        self.gateway
+    }
+}
+
+impl RoutingTable {
+    /// Creates a routing table for a host with address `local_ipv4_addr` on a network with mask `netmask`.  A netmask
+    /// of 0.0.0.0 puts every destination on-link.
+    pub fn new(local_ipv4_addr: Ipv4Addr, netmask: Ipv4Addr) -> Result<Self, Fail> {
+        let mask: u32 = u32::from(netmask);
+        if mask.leading_ones() + mask.trailing_zeros() != 32 {
+            return Err(Fail::new(EINVAL, "netmask is not contiguous"));
+        }
+        Ok(Self {
+            local_ipv4_addr,
+            local_prefix_len: mask.leading_ones() as u8,
+            routes: vec![HashMap::new(); NR_PREFIX_LENS],
+            prefix_lens: 0,
+        })
+    }
+
+    /// Adds `route` to the target table, replacing any route to the same network.  Gateways must be on-link.
+    pub fn add_route(&mut self, route: Route) -> Result<(), Fail> {
+        if !self.is_on_link(route.gateway) {
+            return Err(Fail::new(EINVAL, "gateway is not on the local network"));
+        }
+        let prefix_len: usize = route.prefix_len as usize;
+        self.routes[prefix_len].insert(u32::from(route.destination), route.gateway);
+        self.prefix_lens |= 1 << prefix_len;
+        Ok(())
+    }
+
+    /// Sets the gateway for destinations that no other route covers.
+    pub fn set_default_gateway(&mut self, gateway: Ipv4Addr) -> Result<(), Fail> {
+        self.add_route(Route::default_route(gateway))
+    }
+
+    /// Returns the gateway of the default route, if any.
+    pub fn get_default_gateway(&self) -> Option<Ipv4Addr> {
+        self.routes[0].get(&0).copied()
+    }
+
+    /// Returns the mask of the local network.
+    pub fn get_netmask(&self) -> Ipv4Addr {
+        Ipv4Addr::from(prefix_mask(self.local_prefix_len))
+    }
+
+    /// Returns whether `addr` is on the local network.
+    pub fn is_on_link(&self, addr: Ipv4Addr) -> bool {
+        let mask: u32 = prefix_mask(self.local_prefix_len);
+        u32::from(addr) & mask == u32::from(self.local_ipv4_addr) & mask
+    }
+
+    /// Returns whether `addr` is the limited broadcast address or the directed broadcast address of the local
+    /// network.  /31 and /32 networks have no directed broadcast address (RFC 3021).
+    pub fn is_broadcast(&self, addr: Ipv4Addr) -> bool {
+        if addr.is_broadcast() {
+            return true;
+        }
+        if self.local_prefix_len == 0 || self.local_prefix_len >= 31 {
+            return false;
+        }
+        u32::from(addr) == u32::from(self.local_ipv4_addr) | !prefix_mask(self.local_prefix_len)
+    }
+
+    /// Returns the address that link-layer resolution should be asked about to reach `dst_addr`.  TCP does not ask, as
+    /// it only opens connections passively, and those answer to the link-layer address that their handshake came from.
+    pub fn next_hop(&self, dst_addr: Ipv4Addr) -> Result<Ipv4Addr, Fail> {
+        // The local network wins over any route with a prefix that is as short or shorter.
+        let dst: u32 = u32::from(dst_addr);
+        let mut prefix_lens: u64 = self.prefix_lens;
+        while prefix_lens != 0 {
+            let prefix_len: u8 = (63 - prefix_lens.leading_zeros()) as u8;
+            if prefix_len <= self.local_prefix_len && self.is_on_link(dst_addr) {
+                break;
+            }
+            if let Some(gateway) = self.routes[prefix_len as usize].get(&(dst & prefix_mask(prefix_len))) {
+                return Ok(*gateway);
+            }
+            prefix_lens &= !(1 << prefix_len);
+        }
+        if self.is_on_link(dst_addr) {
+            return Ok(dst_addr);
+        }
+        Err(Fail::new(ENETUNREACH, "no route to destination"))
+    }
+}
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Returns the mask for a prefix of `prefix_len` bits.
+fn prefix_mask(prefix_len: u8) -> u32 {
+    match prefix_len {
+        0 => 0,
+        _ => u32::MAX << (32 - prefix_len as u32),
+    }
+}
@@ -0,0 +1,335 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+use super::{
+    reassembly::{
+        FRAGMENT_OVERHEAD,
+        MAX_FRAGMENTS,
+    },
+    Ipv4Header,
+    ReassemblyTable,
+    Route,
+    RoutingTable,
+};
+use crate::{
+    inetstack::protocols::{
+        ethernet2::{
+            EtherType2,
+            Ethernet2Header,
+        },
+        ip::{
+            Ecn,
+            IpProtocol,
+        },
+    },
+    runtime::{
+        memory::DemiBuffer,
+        network::types::MacAddress,
+    },
+};
+use ::anyhow::Result;
+use ::std::{
+    net::Ipv4Addr,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+const MEMORY: usize = 1 << 20;
+
+fn ipv4_header(identification: u16) -> Ipv4Header {
+    let mut header: Ipv4Header = Ipv4Header::new(
+        Ipv4Addr::new(10, 0, 0, 1),
+        Ipv4Addr::new(10, 0, 0, 2),
+        IpProtocol::UDP,
+    );
+    header.set_identification(identification);
+    header
+}
+
+fn ethernet2_header() -> Ethernet2Header {
+    Ethernet2Header::new(
+        MacAddress::new([0x2, 0, 0, 0, 0, 0x2]),
+        MacAddress::new([0x2, 0, 0, 0, 0, 0x1]),
+        EtherType2::Ipv4,
+    )
+}
+
+fn payload(size: usize) -> DemiBuffer {
+    let bytes: Vec<u8> = (0..size).map(|i| i as u8).collect();
+    DemiBuffer::from_slice(&bytes).expect("payload should fit in a buffer")
+}
+
+/// Makes a fragment of `header`'s datagram, at `offset` in `payload`.
+fn piece(header: Ipv4Header, payload: &DemiBuffer, offset: usize, size: usize, more: bool) -> (Ipv4Header, DemiBuffer) {
+    let mut header: Ipv4Header = header;
+    header.set_fragment(offset, more);
+    let data: DemiBuffer = DemiBuffer::from_slice(&payload[offset..(offset + size)]).unwrap();
+    (header, data)
+}
+
+/// Serializes a fragment, and parses it back as if it was just received.
+fn receive(header: Ipv4Header, data: &DemiBuffer) -> Result<(Ipv4Header, DemiBuffer)> {
+    let ethernet2_hdr: Ethernet2Header = ethernet2_header();
+    let eth_hdr_size: usize = ethernet2_hdr.compute_size();
+    let ipv4_hdr_size: usize = header.compute_size();
+    let mut bytes: Vec<u8> = vec![0; eth_hdr_size + ipv4_hdr_size];
+    ethernet2_hdr.serialize(&mut bytes[..eth_hdr_size]);
+    header.serialize(&mut bytes[eth_hdr_size..], data.len());
+    bytes.extend_from_slice(&data[..]);
+    let (_, buf): (Ethernet2Header, DemiBuffer) = Ethernet2Header::parse(DemiBuffer::from_slice(&bytes)?)?;
+    Ok(Ipv4Header::parse(buf)?)
+}
+
+/// Tests that a datagram cut in MTU-sized fragments goes through reassembly unscathed, in any order.
+#[test]
+fn reassemble_in_any_order() -> Result<()> {
+    let now: Instant = Instant::now();
+    let data: DemiBuffer = payload(3000);
+
+    let mut table: ReassemblyTable = ReassemblyTable::new(TIMEOUT, MEMORY);
+    let mut datagram: Option<(Ipv4Header, DemiBuffer)> = None;
+    for (offset, size, more) in [(2960, 40, false), (0, 1480, true), (1480, 1480, true)] {
+        let (header, buf): (Ipv4Header, DemiBuffer) = piece(ipv4_header(7), &data, offset, size, more);
+        let (header, buf): (Ipv4Header, DemiBuffer) = receive(header, &buf)?;
+        crate::ensure_eq!(header.is_fragment(), true);
+        crate::ensure_eq!(header.get_fragment_offset(), offset);
+        crate::ensure_eq!(header.get_more_fragments(), more);
+        crate::ensure_eq!(datagram.is_none(), true);
+        datagram = table.insert(header, buf, now)?;
+    }
+
+    let (header, buf): (Ipv4Header, DemiBuffer) = datagram.expect("datagram should be complete");
+    crate::ensure_eq!(header.is_fragment(), false);
+    crate::ensure_eq!(header.get_identification(), 7);
+    crate::ensure_eq!(&buf[..], &data[..]);
+    crate::ensure_eq!(table.len(), 0);
+    crate::ensure_eq!(table.memory(), 0);
+
+    Ok(())
+}
+
+/// Tests that retransmitted fragments are ignored, but that overlapping ones drop the datagram.
+#[test]
+fn reassemble_overlap() -> Result<()> {
+    let now: Instant = Instant::now();
+    let data: DemiBuffer = payload(64);
+    let mut table: ReassemblyTable = ReassemblyTable::new(TIMEOUT, MEMORY);
+
+    let (header, buf) = piece(ipv4_header(1), &data, 0, 32, true);
+    crate::ensure_eq!(table.insert(header, buf, now)?.is_none(), true);
+    let (header, buf) = piece(ipv4_header(1), &data, 0, 32, true);
+    crate::ensure_eq!(table.insert(header, buf, now)?.is_none(), true);
+    crate::ensure_eq!(table.memory(), 32 + FRAGMENT_OVERHEAD);
+
+    let (header, buf) = piece(ipv4_header(1), &data, 24, 40, false);
+    crate::ensure_eq!(table.insert(header, buf, now).map_err(|e| e.errno).err(), Some(libc::EBADMSG));
+    crate::ensure_eq!(table.len(), 0);
+    crate::ensure_eq!(table.memory(), 0);
+
+    Ok(())
+}
+
+/// Tests that fragments which would make the datagram too big, or that are not aligned, are rejected.
+#[test]
+fn reassemble_bad_fragments() -> Result<()> {
+    let now: Instant = Instant::now();
+    let data: DemiBuffer = payload(64);
+    let mut table: ReassemblyTable = ReassemblyTable::new(TIMEOUT, MEMORY);
+
+    let (header, buf) = piece(ipv4_header(1), &data, 0, 30, true);
+    crate::ensure_eq!(table.insert(header, buf, now).map_err(|e| e.errno).err(), Some(libc::EBADMSG));
+
+    let mut header: Ipv4Header = ipv4_header(2);
+    header.set_fragment(65528, false);
+    crate::ensure_eq!(
+        table.insert(header, payload(8), now).map_err(|e| e.errno).err(),
+        Some(libc::EBADMSG)
+    );
+
+    // Data past the end announced by the last fragment.
+    let (header, buf) = piece(ipv4_header(4), &data, 32, 8, false);
+    crate::ensure_eq!(table.insert(header, buf, now)?.is_none(), true);
+    let (header, buf) = piece(ipv4_header(4), &data, 40, 8, true);
+    crate::ensure_eq!(table.insert(header, buf, now).map_err(|e| e.errno).err(), Some(libc::EBADMSG));
+    crate::ensure_eq!(table.len(), 0);
+
+    Ok(())
+}
+
+/// Tests that incomplete datagrams are dropped once they time out.
+#[test]
+fn reassemble_timeout() -> Result<()> {
+    let now: Instant = Instant::now();
+    let data: DemiBuffer = payload(64);
+    let mut table: ReassemblyTable = ReassemblyTable::new(TIMEOUT, MEMORY);
+
+    let (header, buf) = piece(ipv4_header(1), &data, 0, 32, true);
+    table.insert(header, buf, now)?;
+    crate::ensure_eq!(table.expire(now + TIMEOUT - Duration::from_secs(1)), 0);
+    crate::ensure_eq!(table.expire(now + TIMEOUT), 1);
+    crate::ensure_eq!(table.memory(), 0);
+
+    // The rest of the datagram is of no use by itself.
+    let (header, buf) = piece(ipv4_header(1), &data, 32, 32, false);
+    crate::ensure_eq!(table.insert(header, buf, now + TIMEOUT)?.is_none(), true);
+
+    Ok(())
+}
+
+/// Tests that the oldest datagrams make room for new ones when memory runs out.
+#[test]
+fn reassemble_memory_limit() -> Result<()> {
+    let now: Instant = Instant::now();
+    let data: DemiBuffer = payload(64);
+    let mut table: ReassemblyTable = ReassemblyTable::new(TIMEOUT, 2 * (32 + FRAGMENT_OVERHEAD));
+
+    let (header, buf) = piece(ipv4_header(1), &data, 0, 32, true);
+    table.insert(header, buf, now)?;
+    let (header, buf) = piece(ipv4_header(2), &data, 0, 32, true);
+    table.insert(header, buf, now + Duration::from_secs(1))?;
+    let (header, buf) = piece(ipv4_header(3), &data, 0, 32, true);
+    table.insert(header, buf, now + Duration::from_secs(2))?;
+    crate::ensure_eq!(table.len(), 2);
+    crate::ensure_eq!(table.memory(), 2 * (32 + FRAGMENT_OVERHEAD));
+
+    // Datagram 1 was dropped.  Completing 2 drops 3, the oldest one left.
+    let (header, buf) = piece(ipv4_header(2), &data, 32, 32, false);
+    crate::ensure_eq!(table.insert(header, buf, now)?.is_some(), true);
+    crate::ensure_eq!(table.len(), 0);
+    crate::ensure_eq!(table.memory(), 0);
+
+    // A single datagram larger than the whole budget never fits.
+    let (header, buf) = piece(ipv4_header(4), &data, 0, 64, true);
+    crate::ensure_eq!(
+        ReassemblyTable::new(TIMEOUT, 32).insert(header, buf, now).map_err(|e| e.errno).err(),
+        Some(libc::ENOBUFS)
+    );
+
+    Ok(())
+}
+
+/// Tests that fragments are charged for the buffer they pin rather than their payload, and that a datagram cannot be
+/// cut into too many fragments.
+#[test]
+fn reassemble_fragment_cost() -> Result<()> {
+    let now: Instant = Instant::now();
+    let mut table: ReassemblyTable = ReassemblyTable::new(TIMEOUT, MEMORY);
+
+    // A tiny fragment that still holds on to a large buffer.
+    let mut buf: DemiBuffer = payload(1024);
+    buf.trim(1016)?;
+    let mut header: Ipv4Header = ipv4_header(1);
+    header.set_fragment(0, true);
+    table.insert(header, buf, now)?;
+    crate::ensure_eq!(table.memory(), 1024 + FRAGMENT_OVERHEAD);
+
+    let data: DemiBuffer = payload(8 * (MAX_FRAGMENTS + 1));
+    for i in 0..MAX_FRAGMENTS {
+        let (header, buf) = piece(ipv4_header(2), &data, 8 * i, 8, true);
+        crate::ensure_eq!(table.insert(header, buf, now)?.is_none(), true);
+    }
+    let (header, buf) = piece(ipv4_header(2), &data, 8 * MAX_FRAGMENTS, 8, false);
+    crate::ensure_eq!(table.insert(header, buf, now).map_err(|e| e.errno).err(), Some(libc::EBADMSG));
+    crate::ensure_eq!(table.len(), 1);
+    crate::ensure_eq!(table.memory(), 1024 + FRAGMENT_OVERHEAD);
+
+    Ok(())
+}
+
+/// Tests that a congestion mark on any fragment carries over to the whole datagram.
+#[test]
+fn reassemble_ecn() -> Result<()> {
+    let now: Instant = Instant::now();
+    let data: DemiBuffer = payload(64);
+    let mut table: ReassemblyTable = ReassemblyTable::new(TIMEOUT, MEMORY);
+
+    let (mut header, buf) = piece(ipv4_header(1), &data, 32, 32, false);
+    header.set_ecn(Ecn::Ce);
+    table.insert(header, buf, now)?;
+    let (mut header, buf) = piece(ipv4_header(1), &data, 0, 32, true);
+    header.set_ecn(Ecn::Ect0);
+    let (header, _): (Ipv4Header, DemiBuffer) = table.insert(header, buf, now)?.expect("datagram should be complete");
+    crate::ensure_eq!(header.get_ecn(), Ecn::Ce);
+
+    Ok(())
+}
+
+fn routing_table() -> Result<RoutingTable> {
+    let mut table: RoutingTable = RoutingTable::new(Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(255, 255, 255, 0))?;
+    table.set_default_gateway(Ipv4Addr::new(10, 0, 0, 254))?;
+    table.add_route(Route::new(Ipv4Addr::new(10, 1, 0, 0), 16, Ipv4Addr::new(10, 0, 0, 253))?)?;
+    table.add_route(Route::new(Ipv4Addr::new(10, 1, 2, 0), 24, Ipv4Addr::new(10, 0, 0, 252))?)?;
+    Ok(table)
+}
+
+/// Tests that destinations resolve to the gateway of the most specific route, unless they are on-link.
+#[test]
+fn route_longest_prefix_match() -> Result<()> {
+    let table: RoutingTable = routing_table()?;
+
+    crate::ensure_eq!(table.next_hop(Ipv4Addr::new(10, 0, 0, 7))?, Ipv4Addr::new(10, 0, 0, 7));
+    crate::ensure_eq!(table.next_hop(Ipv4Addr::new(10, 1, 2, 3))?, Ipv4Addr::new(10, 0, 0, 252));
+    crate::ensure_eq!(table.next_hop(Ipv4Addr::new(10, 1, 3, 3))?, Ipv4Addr::new(10, 0, 0, 253));
+    crate::ensure_eq!(table.next_hop(Ipv4Addr::new(8, 8, 8, 8))?, Ipv4Addr::new(10, 0, 0, 254));
+    crate::ensure_eq!(table.get_default_gateway(), Some(Ipv4Addr::new(10, 0, 0, 254)));
+
+    // A host route inside the local network still takes precedence.
+    let mut table: RoutingTable = table;
+    table.add_route(Route::new(Ipv4Addr::new(10, 0, 0, 9), 32, Ipv4Addr::new(10, 0, 0, 253))?)?;
+    crate::ensure_eq!(table.next_hop(Ipv4Addr::new(10, 0, 0, 9))?, Ipv4Addr::new(10, 0, 0, 253));
+
+    Ok(())
+}
+
+/// Tests that destinations that no route covers are unreachable, and that an unset netmask puts everything on-link.
+#[test]
+fn route_unreachable() -> Result<()> {
+    let table: RoutingTable = RoutingTable::new(Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(255, 255, 255, 0))?;
+    crate::ensure_eq!(
+        table.next_hop(Ipv4Addr::new(8, 8, 8, 8)).map_err(|e| e.errno).err(),
+        Some(libc::ENETUNREACH)
+    );
+
+    let table: RoutingTable = RoutingTable::new(Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::UNSPECIFIED)?;
+    crate::ensure_eq!(table.next_hop(Ipv4Addr::new(8, 8, 8, 8))?, Ipv4Addr::new(8, 8, 8, 8));
+
+    Ok(())
+}
+
+/// Tests that malformed configurations are rejected.
+#[test]
+fn route_bad_config() -> Result<()> {
+    crate::ensure_eq!(
+        RoutingTable::new(Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(255, 0, 255, 0))
+            .map_err(|e| e.errno)
+            .err(),
+        Some(libc::EINVAL)
+    );
+    crate::ensure_eq!(
+        Route::new(Ipv4Addr::new(10, 1, 0, 1), 16, Ipv4Addr::new(10, 0, 0, 253))
+            .map_err(|e| e.errno)
+            .err(),
+        Some(libc::EINVAL)
+    );
+    let mut table: RoutingTable = routing_table()?;
+    crate::ensure_eq!(
+        table.set_default_gateway(Ipv4Addr::new(10, 2, 0, 1)).map_err(|e| e.errno).err(),
+        Some(libc::EINVAL)
+    );
+
+    Ok(())
+}
+
+/// Tests that both the limited and the directed broadcast addresses are recognized.
+#[test]
+fn route_broadcast() -> Result<()> {
+    let table: RoutingTable = routing_table()?;
+    crate::ensure_eq!(table.is_broadcast(Ipv4Addr::BROADCAST), true);
+    crate::ensure_eq!(table.is_broadcast(Ipv4Addr::new(10, 0, 0, 255)), true);
+    crate::ensure_eq!(table.is_broadcast(Ipv4Addr::new(10, 0, 1, 255)), false);
+
+    Ok(())
+}
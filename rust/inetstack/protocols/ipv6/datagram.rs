@@ -0,0 +1,294 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use crate::{
+    inetstack::protocols::ip::{
+        Ecn,
+        IpProtocol,
+    },
+    runtime::{
+        fail::Fail,
+        memory::DemiBuffer,
+    },
+};
+use ::libc::{
+    EBADMSG,
+    ENOTSUP,
+};
+use ::std::{
+    convert::{
+        TryFrom,
+        TryInto,
+    },
+    net::Ipv6Addr,
+};
+
+//==============================================================================
+// Constants
+//==============================================================================
+
+/// Size of IPv6 header (in bytes).  Unlike IPv4, options live in extension headers, so the header has a fixed size.
+pub const IPV6_HEADER_SIZE: u16 = 40;
+
+/// Default hop limit value.
+const DEFAULT_IPV6_HOP_LIMIT: u8 = 64;
+
+/// Version number for IPv6.
+const IPV6_VERSION: u8 = 6;
+
+/// Extension Header: Hop-by-Hop Options.
+const IPV6_EXT_HOP_BY_HOP: u8 = 0;
+
+/// Extension Header: Routing.
+const IPV6_EXT_ROUTING: u8 = 43;
+
+/// Extension Header: Fragment.
+const IPV6_EXT_FRAGMENT: u8 = 44;
+
+/// Extension Header: Destination Options.
+const IPV6_EXT_DESTINATION_OPTIONS: u8 = 60;
+
+/// Size of the Fragment extension header (in bytes).
+const IPV6_EXT_FRAGMENT_SIZE: usize = 8;
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// IPv6 Datagram Header
+#[derive(Debug, Copy, Clone)]
+pub struct Ipv6Header {
+    /// Differentiated Services Code Point, from the traffic class (6 bits).
+    dscp: u8,
+    /// Explicit Congestion Notification, from the traffic class (2 bits).
+    ecn: Ecn,
+    /// Flow label (20 bits).
+    flow_label: u32,
+    /// Protocol of the upper-layer header, once extension headers are skipped (8 bits).
+    next_header: IpProtocol,
+    /// Hop limit, the IPv6 counterpart of the time to live (8 bits).
+    hop_limit: u8,
+    /// Source IP address (128 bits).
+    src_addr: Ipv6Addr,
+    /// Destination IP address (128 bits).
+    dst_addr: Ipv6Addr,
+}
+
+//==============================================================================
+// Associated Functions
+//==============================================================================
+
+/// Associated Functions for IPv6 Headers
+impl Ipv6Header {
+    /// Instantiates an empty IPv6 header.
+    pub fn new(src_addr: Ipv6Addr, dst_addr: Ipv6Addr, next_header: IpProtocol) -> Self {
+        Self {
+            dscp: 0,
+            ecn: Ecn::NotEct,
+            flow_label: 0,
+            next_header,
+            hop_limit: DEFAULT_IPV6_HOP_LIMIT,
+            src_addr,
+            dst_addr,
+        }
+    }
+
+    /// Computes the size of the target IPv6 header.  We never send extension headers.
+    pub fn compute_size(&self) -> usize {
+        IPV6_HEADER_SIZE as usize
+    }
+
+    /// Parses a buffer into an IPv6 header and payload.  Extension headers are skipped over, so the payload starts
+    /// at the upper-layer header.
+    pub fn parse(mut buf: DemiBuffer) -> Result<(Self, DemiBuffer), Fail> {
+        // The datagram should be as big as the header.
+        if buf.len() < (IPV6_HEADER_SIZE as usize) {
+            return Err(Fail::new(EBADMSG, "ipv6 datagram too small"));
+        }
+        let hdr_buf: &[u8; IPV6_HEADER_SIZE as usize] = &buf[..(IPV6_HEADER_SIZE as usize)].try_into().unwrap();
+
+        // IP version number.
+        let version: u8 = hdr_buf[0] >> 4;
+        if version != IPV6_VERSION {
+            return Err(Fail::new(ENOTSUP, "unsupported IP version"));
+        }
+
+        // Traffic class, split the same way as the IPv4 TOS byte.
+        let traffic_class: u8 = (hdr_buf[0] << 4) | (hdr_buf[1] >> 4);
+        let dscp: u8 = traffic_class >> 2;
+        if dscp != 0 {
+            warn!("ignoring dscp field (dscp={:?})", dscp);
+        }
+        let ecn: Ecn = Ecn::from(traffic_class);
+
+        // Flow label.
+        let flow_label: u32 = u32::from_be_bytes([0, hdr_buf[1] & 0xf, hdr_buf[2], hdr_buf[3]]);
+
+        // Payload length.  Zero means a jumbogram (RFC 2675), which no link we run on can carry.
+        let payload_length: u16 = u16::from_be_bytes([hdr_buf[4], hdr_buf[5]]);
+        if payload_length == 0 && hdr_buf[6] == IPV6_EXT_HOP_BY_HOP {
+            return Err(Fail::new(ENOTSUP, "ipv6 jumbograms are not supported"));
+        }
+        // NOTE: there may be padding bytes in the buffer.
+        if (IPV6_HEADER_SIZE as usize) + (payload_length as usize) > buf.len() {
+            return Err(Fail::new(EBADMSG, "ipv6 datagram size mismatch"));
+        }
+
+        // Hop limit.
+        let hop_limit: u8 = hdr_buf[7];
+        if hop_limit == 0 {
+            return Err(Fail::new(EBADMSG, "ipv6 datagram too old"));
+        }
+
+        // Source address.
+        let src_addr: Ipv6Addr = Ipv6Addr::from(<[u8; 16]>::try_from(&hdr_buf[8..24]).unwrap());
+        if src_addr.is_multicast() {
+            return Err(Fail::new(EBADMSG, "ipv6 source address is multicast"));
+        }
+
+        // Destination address.
+        let dst_addr: Ipv6Addr = Ipv6Addr::from(<[u8; 16]>::try_from(&hdr_buf[24..40]).unwrap());
+
+        // Truncate datagram.
+        let next_header: u8 = hdr_buf[6];
+        let padding_bytes: usize = buf.len() - (IPV6_HEADER_SIZE as usize) - (payload_length as usize);
+        buf.adjust(IPV6_HEADER_SIZE as usize)?;
+        buf.trim(padding_bytes)?;
+
+        // Skip extension headers.
+        let (next_header, ext_size): (u8, usize) = skip_extension_headers(next_header, &buf)?;
+        buf.adjust(ext_size)?;
+
+        // Upper-layer protocol.
+        let next_header: IpProtocol = IpProtocol::try_from(next_header)?;
+
+        let header: Ipv6Header = Self {
+            dscp,
+            ecn,
+            flow_label,
+            next_header,
+            hop_limit,
+            src_addr,
+            dst_addr,
+        };
+
+        Ok((header, buf))
+    }
+
+    /// Serializes the target IPv6 header.
+    pub fn serialize(&self, buf: &mut [u8], payload_len: usize) {
+        let buf: &mut [u8; IPV6_HEADER_SIZE as usize] = buf
+            .try_into()
+            .expect("buffer should be large enough to hold an IPv6 header");
+
+        // Version + Traffic Class + Flow Label.
+        let traffic_class: u8 = (self.dscp << 2) | (self.ecn as u8);
+        let first_word: u32 =
+            ((IPV6_VERSION as u32) << 28) | ((traffic_class as u32) << 20) | (self.flow_label & 0xfffff);
+        buf[0..4].copy_from_slice(&first_word.to_be_bytes());
+
+        // Payload Length.
+        buf[4..6].copy_from_slice(&(payload_len as u16).to_be_bytes());
+
+        // Next Header.
+        buf[6] = self.next_header as u8;
+
+        // Hop Limit.
+        buf[7] = self.hop_limit;
+
+        // Source Address.
+        buf[8..24].copy_from_slice(&self.src_addr.octets());
+
+        // Destination Address.
+        buf[24..40].copy_from_slice(&self.dst_addr.octets());
+    }
+
+    /// Returns the source address field stored in the target IPv6 header.
+    pub fn get_src_addr(&self) -> Ipv6Addr {
+        self.src_addr
+    }
+
+    /// Returns the destination address field stored in the target IPv6 header.
+    pub fn get_dest_addr(&self) -> Ipv6Addr {
+        self.dst_addr
+    }
+
+    /// Returns the upper-layer protocol of the target IPv6 header.
+    pub fn get_protocol(&self) -> IpProtocol {
+        self.next_header
+    }
+
+    /// Returns the hop limit field stored in the target IPv6 header.
+    pub fn get_hop_limit(&self) -> u8 {
+        self.hop_limit
+    }
+
+    /// Sets the hop limit field of the target IPv6 header.
+    pub fn set_hop_limit(&mut self, hop_limit: u8) {
+        self.hop_limit = hop_limit;
+    }
+
+    /// Returns the flow label field stored in the target IPv6 header.
+    pub fn get_flow_label(&self) -> u32 {
+        self.flow_label
+    }
+
+    /// Returns the ECN field stored in the target IPv6 header.
+    pub fn get_ecn(&self) -> Ecn {
+        self.ecn
+    }
+
+    /// Sets the ECN field of the target IPv6 header.
+    pub fn set_ecn(&mut self, ecn: Ecn) {
+        self.ecn = ecn;
+    }
+}
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Walks the extension headers at the start of `buf`, which follow a header whose next header field is `next_header`
+/// (RFC 8200 Section 4).  Returns the protocol of the upper-layer header and the offset at which it starts.
+pub fn skip_extension_headers(mut next_header: u8, buf: &[u8]) -> Result<(u8, usize), Fail> {
+    let mut offset: usize = 0;
+    loop {
+        let ext_buf: &[u8] = &buf[offset..];
+        match next_header {
+            IPV6_EXT_HOP_BY_HOP | IPV6_EXT_ROUTING | IPV6_EXT_DESTINATION_OPTIONS => {
+                if ext_buf.len() < 2 {
+                    return Err(Fail::new(EBADMSG, "ipv6 extension header too small"));
+                }
+                // The length does not count the first 8 bytes.
+                let ext_size: usize = (ext_buf[1] as usize + 1) << 3;
+                if ext_buf.len() < ext_size {
+                    return Err(Fail::new(EBADMSG, "ipv6 datagram too small to fit in extension header"));
+                }
+                // We are an end host, so we don't forward datagrams that still have segments to visit.
+                if next_header == IPV6_EXT_ROUTING && ext_buf[3] != 0 {
+                    return Err(Fail::new(ENOTSUP, "ipv6 routing header has segments left"));
+                }
+                next_header = ext_buf[0];
+                offset += ext_size;
+            },
+            IPV6_EXT_FRAGMENT => {
+                if ext_buf.len() < IPV6_EXT_FRAGMENT_SIZE {
+                    return Err(Fail::new(EBADMSG, "ipv6 datagram too small to fit in fragment header"));
+                }
+                // Atomic fragments (RFC 6946) hold a whole datagram, so they can be processed right away.
+                let fragment_offset: u16 = u16::from_be_bytes([ext_buf[2], ext_buf[3]]) >> 3;
+                let more_fragments: bool = ext_buf[3] & 0x1 != 0;
+                if fragment_offset != 0 || more_fragments {
+                    return Err(Fail::new(ENOTSUP, "ipv6 fragmentation is not supported"));
+                }
+                next_header = ext_buf[0];
+                offset += IPV6_EXT_FRAGMENT_SIZE;
+            },
+            _ => return Ok((next_header, offset)),
+        }
+    }
+}
@@ -0,0 +1,17 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+mod datagram;
+
+#[cfg(test)]
+mod tests;
+
+//==============================================================================
+// Exports
+//==============================================================================
+
+pub use self::datagram::{
+    skip_extension_headers,
+    Ipv6Header,
+    IPV6_HEADER_SIZE,
+};
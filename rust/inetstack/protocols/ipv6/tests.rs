@@ -0,0 +1,112 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+use super::{
+    Ipv6Header,
+    IPV6_HEADER_SIZE,
+};
+use crate::{
+    inetstack::protocols::ip::{
+        Ecn,
+        IpProtocol,
+    },
+    runtime::memory::DemiBuffer,
+};
+use ::anyhow::Result;
+use ::std::net::Ipv6Addr;
+
+const SRC_ADDR: Ipv6Addr = Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1);
+const DST_ADDR: Ipv6Addr = Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 2);
+
+/// Builds a datagram out of a header, raw extension headers and a payload.
+fn datagram(header: &Ipv6Header, next_header: u8, extensions: &[u8], payload: &[u8]) -> Result<DemiBuffer> {
+    let mut bytes: Vec<u8> = vec![0; IPV6_HEADER_SIZE as usize];
+    header.serialize(&mut bytes, extensions.len() + payload.len());
+    bytes[6] = next_header;
+    bytes.extend_from_slice(extensions);
+    bytes.extend_from_slice(payload);
+    Ok(DemiBuffer::from_slice(&bytes)?)
+}
+
+/// Tests that a header survives serialization and parsing, and that link-layer padding is dropped.
+#[test]
+fn serialize_and_parse() -> Result<()> {
+    let mut header: Ipv6Header = Ipv6Header::new(SRC_ADDR, DST_ADDR, IpProtocol::TCP);
+    header.set_ecn(Ecn::Ect0);
+    header.set_hop_limit(7);
+
+    let mut bytes: Vec<u8> = vec![0; IPV6_HEADER_SIZE as usize];
+    header.serialize(&mut bytes, 4);
+    crate::ensure_eq!(bytes[0] >> 4, 6);
+    crate::ensure_eq!(&bytes[4..8], &[0, 4, IpProtocol::TCP as u8, 7]);
+    bytes.extend_from_slice(&[1, 2, 3, 4, 0, 0]);
+
+    let (header, payload): (Ipv6Header, DemiBuffer) = Ipv6Header::parse(DemiBuffer::from_slice(&bytes)?)?;
+    crate::ensure_eq!(header.get_src_addr(), SRC_ADDR);
+    crate::ensure_eq!(header.get_dest_addr(), DST_ADDR);
+    crate::ensure_eq!(header.get_protocol(), IpProtocol::TCP);
+    crate::ensure_eq!(header.get_hop_limit(), 7);
+    crate::ensure_eq!(header.get_ecn(), Ecn::Ect0);
+    crate::ensure_eq!(&payload[..], &[1, 2, 3, 4]);
+
+    Ok(())
+}
+
+/// Tests that hop-by-hop options, destination options and atomic fragment headers are skipped.
+#[test]
+fn skip_extension_headers() -> Result<()> {
+    let header: Ipv6Header = Ipv6Header::new(SRC_ADDR, DST_ADDR, IpProtocol::TCP);
+    let extensions: Vec<u8> = [
+        // Hop-by-hop options, 8 bytes, followed by destination options.
+        &[60, 0, 1, 4, 0, 0, 0, 0][..],
+        // Destination options, 16 bytes, followed by a fragment header.
+        &[44, 1, 1, 12, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0][..],
+        // Atomic fragment, followed by TCP.
+        &[IpProtocol::TCP as u8, 0, 0, 0, 0, 0, 0, 42][..],
+    ]
+    .concat();
+    crate::ensure_eq!(
+        super::skip_extension_headers(0, &extensions)?,
+        (IpProtocol::TCP as u8, extensions.len())
+    );
+
+    let buf: DemiBuffer = datagram(&header, 0, &extensions, &[9, 9])?;
+    let (header, payload): (Ipv6Header, DemiBuffer) = Ipv6Header::parse(buf)?;
+    crate::ensure_eq!(header.get_protocol(), IpProtocol::TCP);
+    crate::ensure_eq!(&payload[..], &[9, 9]);
+
+    Ok(())
+}
+
+/// Tests that datagrams we cannot process as an end host are rejected.
+#[test]
+fn parse_unsupported() -> Result<()> {
+    let header: Ipv6Header = Ipv6Header::new(SRC_ADDR, DST_ADDR, IpProtocol::TCP);
+
+    // A real fragment.
+    let fragment: [u8; 8] = [IpProtocol::TCP as u8, 0, 0, 1, 0, 0, 0, 42];
+    let buf: DemiBuffer = datagram(&header, 44, &fragment, &[0; 8])?;
+    crate::ensure_eq!(Ipv6Header::parse(buf).map_err(|e| e.errno).err(), Some(libc::ENOTSUP));
+
+    // A routing header with segments left.
+    let routing: [u8; 8] = [IpProtocol::TCP as u8, 0, 4, 1, 0, 0, 0, 0];
+    let buf: DemiBuffer = datagram(&header, 43, &routing, &[])?;
+    crate::ensure_eq!(Ipv6Header::parse(buf).map_err(|e| e.errno).err(), Some(libc::ENOTSUP));
+
+    // An extension header that runs past the end of the datagram.
+    let truncated: [u8; 8] = [IpProtocol::TCP as u8, 1, 0, 0, 0, 0, 0, 0];
+    let buf: DemiBuffer = datagram(&header, 60, &truncated, &[])?;
+    crate::ensure_eq!(Ipv6Header::parse(buf).map_err(|e| e.errno).err(), Some(libc::EBADMSG));
+
+    // A payload length larger than what we received.
+    let mut bytes: Vec<u8> = vec![0; IPV6_HEADER_SIZE as usize];
+    header.serialize(&mut bytes, 100);
+    crate::ensure_eq!(
+        Ipv6Header::parse(DemiBuffer::from_slice(&bytes)?)
+            .map_err(|e| e.errno)
+            .err(),
+        Some(libc::EBADMSG)
+    );
+
+    Ok(())
+}
@@ -4,8 +4,11 @@
 pub mod arp;
 pub mod ethernet2;
 pub mod icmpv4;
+pub mod icmpv6;
 pub mod ip;
 pub mod ipv4;
+pub mod ipv6;
+pub mod ndp;
 mod peer;
 pub mod queue;
 pub mod tcp;
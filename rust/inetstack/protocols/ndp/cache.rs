@@ -0,0 +1,110 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+use crate::{
+    inetstack::collections::HashTtlCache,
+    runtime::{
+        network::types::MacAddress,
+        timer::TimerRc,
+    },
+};
+use ::std::{
+    collections::HashMap,
+    net::Ipv6Addr,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+//==============================================================================
+// Constants
+//==============================================================================
+
+const DUMMY_MAC_ADDRESS: MacAddress = MacAddress::new([0; 6]);
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+#[derive(Debug)]
+struct Record {
+    link_addr: MacAddress,
+}
+
+///
+/// # Neighbor Cache
+///
+/// The IPv6 counterpart of the Neighbor Cache (RFC 4861 Section 5.1).  Entries simply expire, instead of going through the
+/// reachability states of the RFC.
+pub struct NdpCache {
+    /// Cache for IPv6 Addresses
+    cache: HashTtlCache<Ipv6Addr, Record>,
+
+    /// Disable NDP?
+    disable: bool,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+impl NdpCache {
+    /// Creates a Neighbor Cache.
+    pub fn new(
+        clock: TimerRc,
+        default_ttl: Option<Duration>,
+        values: Option<&HashMap<Ipv6Addr, MacAddress>>,
+        disable: bool,
+    ) -> NdpCache {
+        let mut peer = NdpCache {
+            cache: HashTtlCache::new(clock.now(), default_ttl),
+            disable,
+        };
+
+        // Populate cache.
+        if let Some(values) = values {
+            for (&k, &v) in values {
+                peer.insert(k, v);
+            }
+        }
+
+        peer
+    }
+
+    /// Caches an address resolution.
+    pub fn insert(&mut self, ipv6_addr: Ipv6Addr, link_addr: MacAddress) -> Option<MacAddress> {
+        let record = Record { link_addr };
+        self.cache.insert(ipv6_addr, record).map(|r| r.link_addr)
+    }
+
+    /// Gets the MAC address of given IPv6 address.
+    pub fn get(&self, ipv6_addr: Ipv6Addr) -> Option<&MacAddress> {
+        if self.disable {
+            Some(&DUMMY_MAC_ADDRESS)
+        } else {
+            self.cache.get(&ipv6_addr).map(|r| &r.link_addr)
+        }
+    }
+
+    /// Advances internal clock of the Neighbor Cache.
+    pub fn advance_clock(&mut self, now: Instant) {
+        self.cache.advance_clock(now)
+    }
+
+    /// Clears the Neighbor Cache.
+    #[allow(unused)]
+    pub fn clear(&mut self) {
+        self.cache.clear();
+    }
+
+    // Exports address resolutions that are stored in the Neighbor Cache.
+    #[cfg(test)]
+    pub fn export(&self) -> HashMap<Ipv6Addr, MacAddress> {
+        let mut map: HashMap<Ipv6Addr, MacAddress> = HashMap::default();
+        for (k, v) in self.cache.iter() {
+            map.insert(*k, v.link_addr);
+        }
+        map
+    }
+}
@@ -0,0 +1,9 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+mod cache;
+mod packet;
+mod peer;
+
+pub use packet::solicited_node_addr;
+pub use peer::NdpPeer;
@@ -0,0 +1,215 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use crate::runtime::{
+    fail::Fail,
+    memory::DemiBuffer,
+    network::types::MacAddress,
+};
+use ::libc::EBADMSG;
+use ::std::{
+    convert::{
+        TryFrom,
+        TryInto,
+    },
+    net::Ipv6Addr,
+};
+
+//======================================================================================================================
+// Constants
+//======================================================================================================================
+
+/// Neighbor Discovery messages are sent with the largest hop limit, so receivers can tell they come from the link.
+pub const NDP_HOP_LIMIT: u8 = 255;
+
+/// Size of the reserved field and target address that start Neighbor Solicitations and Advertisements.
+const NDP_MESSAGE_SIZE: usize = 20;
+
+/// Size of a link-layer address option for Ethernet.
+const NDP_LINK_ADDR_OPTION_SIZE: usize = 8;
+
+//======================================================================================================================
+// Enumerations
+//======================================================================================================================
+
+/// Options that carry a link-layer address (RFC 4861 Section 4.6.1).
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NdpOption {
+    /// Link-layer address of the sender, in solicitations.
+    SourceLinkAddr = 1,
+    /// Link-layer address of the target, in advertisements.
+    TargetLinkAddr = 2,
+}
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// Body of Neighbor Solicitation and Neighbor Advertisement messages, after the ICMPv6 header (RFC 4861 Sections 4.3
+/// and 4.4).  Options other than the link-layer address are skipped.
+#[derive(Clone, Debug)]
+pub struct NeighborMessage {
+    target_addr: Ipv6Addr,
+    link_addr: Option<MacAddress>,
+}
+
+//======================================================================================================================
+// Associate Functions
+//======================================================================================================================
+
+impl NeighborMessage {
+    /// Creates the body of a Neighbor Solicitation or Advertisement.
+    pub fn new(target_addr: Ipv6Addr, link_addr: Option<MacAddress>) -> Self {
+        Self { target_addr, link_addr }
+    }
+
+    /// Parses the body of a Neighbor Solicitation or Advertisement, looking for a link-layer address in `option`.
+    pub fn parse(buf: DemiBuffer, option: NdpOption) -> Result<Self, Fail> {
+        if buf.len() < NDP_MESSAGE_SIZE {
+            return Err(Fail::new(EBADMSG, "NDP message too short"));
+        }
+        let target_addr: Ipv6Addr = Ipv6Addr::from(<[u8; 16]>::try_from(&buf[4..20]).unwrap());
+
+        let mut link_addr: Option<MacAddress> = None;
+        let mut options: &[u8] = &buf[NDP_MESSAGE_SIZE..];
+        while !options.is_empty() {
+            if options.len() < 2 {
+                return Err(Fail::new(EBADMSG, "NDP option too short"));
+            }
+            // The length is in units of 8 bytes, and may not be zero.
+            let option_size: usize = (options[1] as usize) << 3;
+            if option_size == 0 || options.len() < option_size {
+                return Err(Fail::new(EBADMSG, "invalid NDP option length"));
+            }
+            if options[0] == option as u8 && option_size == NDP_LINK_ADDR_OPTION_SIZE {
+                link_addr = Some(MacAddress::from_bytes(&options[2..8]));
+            }
+            options = &options[option_size..];
+        }
+
+        Ok(Self { target_addr, link_addr })
+    }
+
+    /// Serializes the target message, carrying its link-layer address in `option`.
+    pub fn serialize(&self, option: NdpOption) -> DemiBuffer {
+        let size: usize = NDP_MESSAGE_SIZE + self.link_addr.map_or(0, |_| NDP_LINK_ADDR_OPTION_SIZE);
+        let mut buf: DemiBuffer = DemiBuffer::new(size as u16);
+        // Reserved.
+        buf[0..4].copy_from_slice(&[0; 4]);
+        buf[4..20].copy_from_slice(&self.target_addr.octets());
+        if let Some(link_addr) = self.link_addr {
+            let option_buf: &mut [u8; NDP_LINK_ADDR_OPTION_SIZE] = (&mut buf[NDP_MESSAGE_SIZE..]).try_into().unwrap();
+            option_buf[0] = option as u8;
+            option_buf[1] = (NDP_LINK_ADDR_OPTION_SIZE >> 3) as u8;
+            option_buf[2..8].copy_from_slice(&link_addr.octets());
+        }
+        buf
+    }
+
+    pub fn get_target_addr(&self) -> Ipv6Addr {
+        self.target_addr
+    }
+
+    pub fn get_link_addr(&self) -> Option<MacAddress> {
+        self.link_addr
+    }
+}
+
+//======================================================================================================================
+// Standalone Functions
+//======================================================================================================================
+
+/// Returns the solicited-node multicast address that Neighbor Solicitations for `addr` are sent to (RFC 4291 Section
+/// 2.7.1).
+pub fn solicited_node_addr(addr: Ipv6Addr) -> Ipv6Addr {
+    let octets: [u8; 16] = addr.octets();
+    Ipv6Addr::new(
+        0xff02,
+        0,
+        0,
+        0,
+        0,
+        1,
+        0xff00 | octets[13] as u16,
+        u16::from_be_bytes([octets[14], octets[15]]),
+    )
+}
+
+/// Returns the Ethernet address that IPv6 multicast `addr` maps to (RFC 2464 Section 7).
+pub fn multicast_link_addr(addr: Ipv6Addr) -> MacAddress {
+    let octets: [u8; 16] = addr.octets();
+    MacAddress::new([0x33, 0x33, octets[12], octets[13], octets[14], octets[15]])
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        multicast_link_addr,
+        solicited_node_addr,
+        NdpOption,
+        NeighborMessage,
+    };
+    use crate::runtime::{
+        memory::DemiBuffer,
+        network::types::MacAddress,
+    };
+    use ::anyhow::Result;
+    use ::std::net::Ipv6Addr;
+
+    /// Tests that a solicitation carries its target and link-layer address across.
+    #[test]
+    fn serialize_and_parse() -> Result<()> {
+        let target_addr: Ipv6Addr = Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 2);
+        let link_addr: MacAddress = MacAddress::new([0x2, 0, 0, 0, 0, 0x1]);
+        let buf: DemiBuffer = NeighborMessage::new(target_addr, Some(link_addr)).serialize(NdpOption::SourceLinkAddr);
+        crate::ensure_eq!(buf.len(), 28);
+
+        let message: NeighborMessage = NeighborMessage::parse(buf.clone(), NdpOption::SourceLinkAddr)?;
+        crate::ensure_eq!(message.get_target_addr(), target_addr);
+        crate::ensure_eq!(message.get_link_addr(), Some(link_addr));
+
+        // The address is in the other kind of option.
+        let message: NeighborMessage = NeighborMessage::parse(buf, NdpOption::TargetLinkAddr)?;
+        crate::ensure_eq!(message.get_link_addr(), None);
+
+        Ok(())
+    }
+
+    /// Tests that options with a zero length are rejected, as they would make us loop forever.
+    #[test]
+    fn parse_zero_length_option() -> Result<()> {
+        let mut bytes: Vec<u8> = vec![0; 20];
+        bytes.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0]);
+        crate::ensure_eq!(
+            NeighborMessage::parse(DemiBuffer::from_slice(&bytes)?, NdpOption::SourceLinkAddr)
+                .map_err(|e| e.errno)
+                .err(),
+            Some(libc::EBADMSG)
+        );
+
+        Ok(())
+    }
+
+    /// Tests the mapping of addresses to solicited-node multicast groups, and of those to Ethernet addresses.
+    #[test]
+    fn solicited_node() -> Result<()> {
+        let addr: Ipv6Addr = "fe80::2aa:ff:fe28:9c5a".parse()?;
+        let group: Ipv6Addr = solicited_node_addr(addr);
+        crate::ensure_eq!(group, "ff02::1:ff28:9c5a".parse::<Ipv6Addr>()?);
+        crate::ensure_eq!(
+            multicast_link_addr(group),
+            MacAddress::new([0x33, 0x33, 0xff, 0x28, 0x9c, 0x5a])
+        );
+
+        Ok(())
+    }
+}
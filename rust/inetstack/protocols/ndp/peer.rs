@@ -0,0 +1,345 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+use super::{
+    cache::NdpCache,
+    packet::{
+        multicast_link_addr,
+        solicited_node_addr,
+        NdpOption,
+        NeighborMessage,
+        NDP_HOP_LIMIT,
+    },
+};
+use crate::{
+    inetstack::{
+        futures::UtilityMethods,
+        protocols::{
+            ethernet2::{
+                EtherType2,
+                Ethernet2Header,
+            },
+            icmpv6::datagram::{
+                Icmpv6Header,
+                Icmpv6Message,
+                Icmpv6Type2,
+            },
+            ip::IpProtocol,
+            ipv6::Ipv6Header,
+        },
+    },
+    runtime::{
+        fail::Fail,
+        memory::DemiBuffer,
+        network::{
+            config::NdpConfig,
+            types::MacAddress,
+            NetworkRuntime,
+        },
+        queue::BackgroundTask,
+        timer::TimerRc,
+    },
+    scheduler::{
+        Scheduler,
+        TaskHandle,
+    },
+};
+use ::futures::{
+    channel::oneshot::{
+        channel,
+        Receiver,
+        Sender,
+    },
+    FutureExt,
+};
+use ::libc::{
+    EBADMSG,
+    ENOTSUP,
+    ETIMEDOUT,
+};
+use ::std::{
+    cell::{
+        RefCell,
+        RefMut,
+    },
+    collections::{
+        HashMap,
+        LinkedList,
+    },
+    future::Future,
+    net::Ipv6Addr,
+    rc::Rc,
+    time::Duration,
+};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+///
+/// Neighbor Discovery Peer
+///
+/// The IPv6 counterpart of the ARP peer: it resolves link-layer addresses with Neighbor Solicitations and answers the
+/// ones for our address with Neighbor Advertisements (RFC 4861 Section 7).  Router discovery and redirects are not
+/// handled.
+///
+#[derive(Clone)]
+pub struct NdpPeer<const N: usize> {
+    rt: Rc<dyn NetworkRuntime<N>>,
+    clock: TimerRc,
+    local_link_addr: MacAddress,
+    local_ipv6_addr: Ipv6Addr,
+    cache: Rc<RefCell<NdpCache>>,
+    waiters: Rc<RefCell<HashMap<Ipv6Addr, LinkedList<Sender<MacAddress>>>>>,
+    ndp_config: NdpConfig,
+
+    /// The background co-routine cleans up the Neighbor Cache from time to time.
+    /// We annotate it as unused because the compiler believes that it is never called which is not the case.
+    #[allow(unused)]
+    background: Rc<TaskHandle>,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+impl<const N: usize> NdpPeer<N> {
+    pub fn new(
+        rt: Rc<dyn NetworkRuntime<N>>,
+        scheduler: Scheduler,
+        clock: TimerRc,
+        local_link_addr: MacAddress,
+        local_ipv6_addr: Ipv6Addr,
+        ndp_config: NdpConfig,
+    ) -> Result<NdpPeer<N>, Fail> {
+        let cache: Rc<RefCell<NdpCache>> = Rc::new(RefCell::new(NdpCache::new(
+            clock.clone(),
+            Some(ndp_config.get_cache_ttl()),
+            Some(ndp_config.get_initial_values()),
+            ndp_config.get_disable_ndp(),
+        )));
+
+        let task: BackgroundTask = BackgroundTask::new(
+            String::from("Inetstack::ndp::background"),
+            Box::pin(Self::background(clock.clone(), cache.clone())),
+        );
+        let handle: TaskHandle = match scheduler.insert(task) {
+            Some(handle) => handle,
+            None => {
+                return Err(Fail::new(
+                    libc::EAGAIN,
+                    "failed to schedule background co-routine for NDP module",
+                ))
+            },
+        };
+        let peer: NdpPeer<N> = NdpPeer {
+            rt,
+            clock,
+            local_link_addr,
+            local_ipv6_addr,
+            cache,
+            waiters: Rc::new(RefCell::new(HashMap::default())),
+            ndp_config,
+            background: Rc::new(handle),
+        };
+
+        Ok(peer)
+    }
+
+    /// Drops a waiter for a target IP address.
+    fn do_drop(&mut self, ipv6_addr: Ipv6Addr) {
+        self.waiters.borrow_mut().remove(&ipv6_addr);
+    }
+
+    fn do_insert(&mut self, ipv6_addr: Ipv6Addr, link_addr: MacAddress) -> Option<MacAddress> {
+        if let Some(wait_queue) = self.waiters.borrow_mut().remove(&ipv6_addr) {
+            for sender in wait_queue {
+                let _ = sender.send(link_addr);
+            }
+        }
+        self.cache.borrow_mut().insert(ipv6_addr, link_addr)
+    }
+
+    fn do_wait_link_addr(&mut self, ipv6_addr: Ipv6Addr) -> impl Future<Output = MacAddress> {
+        let (tx, rx): (Sender<MacAddress>, Receiver<MacAddress>) = channel();
+        if let Some(&link_addr) = self.cache.borrow().get(ipv6_addr) {
+            let _ = tx.send(link_addr);
+        } else {
+            let mut waiters: RefMut<HashMap<Ipv6Addr, LinkedList<Sender<MacAddress>>>> = self.waiters.borrow_mut();
+            if let Some(wait_queue) = waiters.get_mut(&ipv6_addr) {
+                warn!("Duplicate waiter for IP address: {}", ipv6_addr);
+                wait_queue.push_back(tx);
+            } else {
+                let mut wait_queue: LinkedList<Sender<MacAddress>> = LinkedList::new();
+                wait_queue.push_back(tx);
+                waiters.insert(ipv6_addr, wait_queue);
+            }
+        }
+        rx.map(|r| r.expect("Dropped waiter?"))
+    }
+
+    /// Background task that cleans up the Neighbor Cache from time to time.
+    async fn background(clock: TimerRc, cache: Rc<RefCell<NdpCache>>) {
+        loop {
+            let current_time = clock.now();
+            cache.borrow_mut().advance_clock(current_time);
+            clock.wait(clock.clone(), Duration::from_secs(1)).await;
+        }
+    }
+
+    /// Builds a Neighbor Discovery message from us.
+    fn make_message(
+        &self,
+        dst_link_addr: MacAddress,
+        dst_ipv6_addr: Ipv6Addr,
+        icmpv6_type: Icmpv6Type2,
+        body: DemiBuffer,
+    ) -> Icmpv6Message {
+        let mut ipv6_hdr: Ipv6Header = Ipv6Header::new(self.local_ipv6_addr, dst_ipv6_addr, IpProtocol::ICMPv6);
+        ipv6_hdr.set_hop_limit(NDP_HOP_LIMIT);
+        Icmpv6Message::new(
+            Ethernet2Header::new(dst_link_addr, self.local_link_addr, EtherType2::Ipv6),
+            ipv6_hdr,
+            Icmpv6Header::new(icmpv6_type, 0),
+            body,
+        )
+    }
+
+    /// Sends a Neighbor Advertisement for our address.
+    fn advertise(&self, dst_link_addr: MacAddress, dst_ipv6_addr: Ipv6Addr, solicited: bool) {
+        let body: DemiBuffer =
+            NeighborMessage::new(self.local_ipv6_addr, Some(self.local_link_addr)).serialize(NdpOption::TargetLinkAddr);
+        let icmpv6_type: Icmpv6Type2 = Icmpv6Type2::NeighborAdvertisement {
+            router: false,
+            solicited,
+            override_: true,
+        };
+        let reply: Icmpv6Message = self.make_message(dst_link_addr, dst_ipv6_addr, icmpv6_type, body);
+        debug!("Advertising {} to {}", self.local_ipv6_addr, dst_ipv6_addr);
+        self.rt.transmit(Box::new(reply));
+    }
+
+    /// Handles a Neighbor Discovery message, handed over by ICMPv6.
+    pub fn receive(&mut self, ipv6_hdr: &Ipv6Header, icmpv6_hdr: &Icmpv6Header, buf: DemiBuffer) -> Result<(), Fail> {
+        // from RFC 4861 Section 7.1:
+        // > The IP Hop Limit field has a value of 255, i.e., the packet
+        // > could not possibly have been forwarded by a router.
+        if ipv6_hdr.get_hop_limit() != NDP_HOP_LIMIT {
+            return Err(Fail::new(EBADMSG, "NDP message was forwarded"));
+        }
+        if icmpv6_hdr.get_code() != 0 {
+            return Err(Fail::new(EBADMSG, "invalid NDP code"));
+        }
+
+        match icmpv6_hdr.get_protocol() {
+            Icmpv6Type2::NeighborSolicitation => {
+                let message: NeighborMessage = NeighborMessage::parse(buf, NdpOption::SourceLinkAddr)?;
+                debug!("Received solicitation {:?}", message);
+                if message.get_target_addr() != self.local_ipv6_addr {
+                    return Err(Fail::new(EBADMSG, "unrecognized IP address"));
+                }
+
+                let src_ipv6_addr: Ipv6Addr = ipv6_hdr.get_src_addr();
+                if src_ipv6_addr.is_unspecified() {
+                    // from RFC 4861 Section 7.2.4:
+                    // > If the source of the solicitation is the unspecified address, the
+                    // > node MUST set the Solicited flag to zero and multicast the
+                    // > advertisement to the all-nodes address.
+                    let all_nodes: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1);
+                    self.advertise(multicast_link_addr(all_nodes), all_nodes, false);
+                    return Ok(());
+                }
+
+                // The solicitation tells us the sender's link-layer address, which we need to answer anyway.
+                let dst_link_addr: MacAddress = match message.get_link_addr() {
+                    Some(link_addr) => {
+                        self.do_insert(src_ipv6_addr, link_addr);
+                        link_addr
+                    },
+                    None => match self.cache.borrow().get(src_ipv6_addr) {
+                        Some(&link_addr) => link_addr,
+                        None => return Err(Fail::new(EBADMSG, "unknown link-layer address for solicitation")),
+                    },
+                };
+                self.advertise(dst_link_addr, src_ipv6_addr, true);
+                Ok(())
+            },
+            Icmpv6Type2::NeighborAdvertisement { solicited, .. } => {
+                if solicited && ipv6_hdr.get_dest_addr().is_multicast() {
+                    return Err(Fail::new(EBADMSG, "solicited advertisement sent to multicast address"));
+                }
+                let message: NeighborMessage = NeighborMessage::parse(buf, NdpOption::TargetLinkAddr)?;
+                debug!("Received advertisement {:?}", message);
+
+                // from RFC 4861 Section 7.2.5:
+                // > If no entry exists, the advertisement SHOULD be silently discarded.
+                let target_addr: Ipv6Addr = message.get_target_addr();
+                let known: bool =
+                    self.cache.borrow().get(target_addr).is_some() || self.waiters.borrow().contains_key(&target_addr);
+                match message.get_link_addr() {
+                    Some(link_addr) if known => {
+                        self.do_insert(target_addr, link_addr);
+                    },
+                    _ => debug!("Ignoring advertisement for {}", target_addr),
+                }
+                Ok(())
+            },
+            _ => Err(Fail::new(ENOTSUP, "unsupported NDP message")),
+        }
+    }
+
+    pub fn try_query(&self, ipv6_addr: Ipv6Addr) -> Option<MacAddress> {
+        self.cache.borrow().get(ipv6_addr).cloned()
+    }
+
+    pub fn query(&self, ipv6_addr: Ipv6Addr) -> impl Future<Output = Result<MacAddress, Fail>> {
+        let rt = self.rt.clone();
+        let mut ndp = self.clone();
+        let cache = self.cache.clone();
+        let ndp_options = self.ndp_config.clone();
+        let clock: TimerRc = self.clock.clone();
+        async move {
+            if let Some(&link_addr) = cache.borrow().get(ipv6_addr) {
+                return Ok(link_addr);
+            }
+            // Solicitations go to the multicast group of the target, rather than to everyone as ARP requests do.
+            let group: Ipv6Addr = solicited_node_addr(ipv6_addr);
+            let body: DemiBuffer =
+                NeighborMessage::new(ipv6_addr, Some(ndp.local_link_addr)).serialize(NdpOption::SourceLinkAddr);
+            let msg: Icmpv6Message = ndp.make_message(
+                multicast_link_addr(group),
+                group,
+                Icmpv6Type2::NeighborSolicitation,
+                body,
+            );
+            let mut ndp_response = ndp.do_wait_link_addr(ipv6_addr).fuse();
+
+            let result = {
+                for i in 0..ndp_options.get_retry_count() + 1 {
+                    rt.transmit(Box::new(msg.clone()));
+                    let timer = clock.wait(clock.clone(), ndp_options.get_request_timeout());
+
+                    match ndp_response.with_timeout(timer).await {
+                        Ok(link_addr) => {
+                            debug!("NDP result available ({})", link_addr);
+                            return Ok(link_addr);
+                        },
+                        Err(_) => {
+                            warn!("Neighbor Solicitation timeout; attempt {}.", i + 1);
+                        },
+                    }
+                }
+                Err(Fail::new(ETIMEDOUT, "NDP query timeout"))
+            };
+
+            ndp.do_drop(ipv6_addr);
+
+            result
+        }
+    }
+
+    #[cfg(test)]
+    pub fn export_cache(&self) -> HashMap<Ipv6Addr, MacAddress> {
+        self.cache.borrow().export()
+    }
+}
@@ -3,7 +3,19 @@
 
 use crate::{
     inetstack::protocols::{
-        ipv4::Ipv4Header,
+        icmpv4::Icmpv4Error,
+        ip::{
+            IpHeader,
+            IpProtocol,
+        },
+        ipv4::{
+            Ipv4Header,
+            ReassemblyTable,
+            RoutingTable,
+            DEFAULT_REASSEMBLY_MEMORY,
+            DEFAULT_REASSEMBLY_TIMEOUT,
+        },
+        ipv6::Ipv6Header,
         tcp::TcpPeer,
         tcp::established::ControlBlock,
     },
@@ -18,19 +30,34 @@ use crate::{
         },
     },
 };
-use ::libc::ENOTCONN;
+use ::libc::{
+    ENOTCONN,
+    ENOTSUP,
+};
 use ::std::{
-    net::Ipv4Addr,
+    net::{
+        Ipv4Addr,
+        Ipv6Addr,
+    },
+    time::Instant,
 };
 
 pub struct Peer<const N: usize> {
     local_ipv4_addr: Ipv4Addr,
+    /// Where datagrams to other hosts go next.
+    routes: RoutingTable,
+    /// IPv6 address of this host, if IPv6 is enabled.
+    local_ipv6_addr: Option<Ipv6Addr>,
     pub tcp: TcpPeer<N>,
+    /// Fragmented datagrams being put back together.
+    reassembly: ReassemblyTable,
 }
 
 impl<const N: usize> Peer<N> {
     pub fn new(
         local_ipv4_addr: Ipv4Addr,
+        routes: RoutingTable,
+        local_ipv6_addr: Option<Ipv6Addr>,
         tcp_config: TcpConfig,
     ) -> Result<Self, Fail> {
         let tcp: TcpPeer<N> = TcpPeer::new(
@@ -39,16 +66,62 @@ impl<const N: usize> Peer<N> {
 
         Ok(Peer {
             local_ipv4_addr,
+            routes,
+            local_ipv6_addr,
             tcp,
+            reassembly: ReassemblyTable::new(DEFAULT_REASSEMBLY_TIMEOUT, DEFAULT_REASSEMBLY_MEMORY),
         })
     }
 
-    pub fn receive(&mut self, cb: *mut ControlBlock<RECEIVE_BATCH_SIZE>, buf: DemiBuffer) -> Result<(), Fail> {
+    pub fn receive(
+        &mut self,
+        cb: *mut ControlBlock<RECEIVE_BATCH_SIZE>,
+        buf: DemiBuffer,
+        now: Instant,
+    ) -> Result<(), Fail> {
         let (header, payload) = Ipv4Header::parse(buf)?;
         debug!("Ipv4 received {:?}", header);
-        if header.get_dest_addr() != self.local_ipv4_addr && !header.get_dest_addr().is_broadcast() {
+        if header.get_dest_addr() != self.local_ipv4_addr && !self.routes.is_broadcast(header.get_dest_addr()) {
+            return Err(Fail::new(ENOTCONN, "invalid destination address"));
+        }
+
+        self.reassembly.expire(now);
+        let (header, payload) = if header.is_fragment() {
+            match self.reassembly.insert(header, payload, now)? {
+                Some(datagram) => datagram,
+                // Wait for the rest of the datagram.
+                None => return Ok(()),
+            }
+        } else {
+            (header, payload)
+        };
+        match header.get_protocol() {
+            IpProtocol::TCP => self.tcp.receive(cb, &IpHeader::V4(header), payload),
+            IpProtocol::ICMPv4 => {
+                let error: Icmpv4Error = Icmpv4Error::parse(payload)?;
+                debug!("ICMPv4 error received {:?}", error);
+                self.tcp.receive_icmp_error(cb, &error)
+            },
+            _ => Err(Fail::new(ENOTSUP, "unsupported IPv4 protocol")),
+        }
+    }
+
+    /// Returns the address that ARP should resolve to send a datagram to `dst_addr`.
+    pub fn next_hop(&self, dst_addr: Ipv4Addr) -> Result<Ipv4Addr, Fail> {
+        self.routes.next_hop(dst_addr)
+    }
+
+    /// Handles an incoming IPv6 datagram.  Only TCP runs on top of IPv6 for now: the dispatcher answers ICMPv6 echo
+    /// requests and Neighbor Discovery itself, so workers never see them.
+    pub fn receive_ipv6(&mut self, cb: *mut ControlBlock<RECEIVE_BATCH_SIZE>, buf: DemiBuffer) -> Result<(), Fail> {
+        let (header, payload) = Ipv6Header::parse(buf)?;
+        debug!("Ipv6 received {:?}", header);
+        if Some(header.get_dest_addr()) != self.local_ipv6_addr {
             return Err(Fail::new(ENOTCONN, "invalid destination address"));
         }
-        self.tcp.receive(cb, &header, payload)
+        if header.get_protocol() != IpProtocol::TCP {
+            return Err(Fail::new(ENOTSUP, "unsupported IPv6 next header"));
+        }
+        self.tcp.receive(cb, &IpHeader::V6(header), payload)
     }
 }
\ No newline at end of file
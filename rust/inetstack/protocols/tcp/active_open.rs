@@ -200,6 +200,7 @@
 
 //         let mut remote_window_scale = None;
 //         let mut mss = FALLBACK_MSS;
+//         let mut sack_permitted = false;
 //         for option in header.iter_options() {
 //             match option {
 //                 TcpOptions2::WindowScale(w) => {
@@ -210,6 +211,10 @@
 //                     info!("Received advertised MSS: {}", m);
 //                     mss = *m as usize;
 //                 },
+//                 TcpOptions2::SelectiveAcknowlegementPermitted => {
+//                     info!("Received SACK permitted");
+//                     sack_permitted = self.tcp_config.get_sack();
+//                 },
 //                 _ => continue,
 //             }
 //         }
@@ -257,6 +262,7 @@
 //             tx_window_size,
 //             remote_window_scale,
 //             mss,
+//             sack_permitted,
 //             congestion_control::None::new,
 //             None,
 //         );
@@ -299,6 +305,11 @@
 //                 tcp_hdr.push_option(TcpOptions2::WindowScale(tcp_config.get_window_scale()));
 //                 info!("Advertising window scale: {}", tcp_config.get_window_scale());
 
+//                 if tcp_config.get_sack() {
+//                     tcp_hdr.push_option(TcpOptions2::SelectiveAcknowlegementPermitted);
+//                     info!("Advertising SACK permitted");
+//                 }
+
 //                 debug!("Sending SYN {:?}", tcp_hdr);
 //                 let segment = TcpSegment {
 //                     ethernet2_hdr: Ethernet2Header::new(remote_link_addr, local_link_addr, EtherType2::Ipv4),
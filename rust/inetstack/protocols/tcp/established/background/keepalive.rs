@@ -0,0 +1,18 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+use super::ControlBlock;
+use crate::runtime::fail::Fail;
+use ::std::time::Instant;
+
+/// Probes idle connections and drops them once the peer stops answering (RFC 1122 Section 4.2.3.6).
+pub fn keepalive<const N: usize>(cb: &ControlBlock<N>, now: Instant) -> Result<(), Fail> {
+    // The deadline is pushed back every time our peer shows signs of life.
+    match cb.get_keepalive_deadline() {
+        Some(deadline) if deadline <= now => {
+            trace!("Keepalive Timer Expired");
+            cb.on_keepalive_timeout(now)
+        },
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,17 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+use super::ControlBlock;
+use crate::runtime::fail::Fail;
+use ::std::time::Instant;
+
+/// Resets connections that could not deliver their data within the linger timeout after being closed (SO_LINGER).
+pub fn linger<const N: usize>(cb: &ControlBlock<N>, now: Instant) -> Result<(), Fail> {
+    match cb.get_linger_deadline() {
+        Some(deadline) if deadline <= now => {
+            trace!("Linger Timer Expired");
+            cb.on_linger_timeout()
+        },
+        _ => Ok(()),
+    }
+}
@@ -2,46 +2,44 @@
 // Licensed under the MIT license.
 
 mod acknowledger;
+mod keepalive;
+mod linger;
+mod pacing;
+mod persist;
 mod retransmitter;
 mod sender;
+mod time_wait;
 
 use self::{
-    acknowledger::acknowledger,
-    retransmitter::retransmitter,
-    sender::sender,
+    keepalive::keepalive,
+    linger::linger,
+    pacing::pacing,
+    persist::persist,
 };
 use super::ControlBlock;
-use crate::runtime::QDesc;
-use ::futures::{
-    channel::mpsc,
-    FutureExt,
-};
-use ::std::rc::Rc;
-
-#[allow(dead_code)]
-pub async fn background<const N: usize>(
-    cb: Rc<ControlBlock<N>>,
-    fd: QDesc,
-    _dead_socket_tx: mpsc::UnboundedSender<QDesc>,
-) {
-    let acknowledger = acknowledger(cb.clone()).fuse();
-    futures::pin_mut!(acknowledger);
-
-    let retransmitter = retransmitter(cb.clone()).fuse();
-    futures::pin_mut!(retransmitter);
+use crate::runtime::fail::Fail;
+use ::std::time::Instant;
 
-    let sender = sender(cb.clone()).fuse();
-    futures::pin_mut!(sender);
-
-    let r = futures::select_biased! {
-        r = acknowledger => r,
-        r = retransmitter => r,
-        r = sender => r,
-    };
-    error!("Connection (fd {:?}) terminated: {:?}", fd, r);
+/// Runs the handlers of the connection timers that expired by `now`.  Connections live on whichever worker serves
+/// them, so rather than running a co-routine per connection, the worker polls the timers every time that it is handed
+/// the connection.  Fails if a timer shut the connection down.
+pub fn poll<const N: usize>(cb: &ControlBlock<N>, now: Instant) -> Result<(), Fail> {
+    keepalive(cb, now)?;
+    linger(cb, now)?;
+    persist(cb, now);
+    pacing(cb, now);
+    Ok(())
+}
 
-    // TODO Properly clean up Peer state for this connection.
-    // dead_socket_tx
-    //     .unbounded_send(fd)
-    //     .expect("Failed to terminate connection");
+/// Returns when the next connection timer expires, if any is armed.
+pub fn next_deadline<const N: usize>(cb: &ControlBlock<N>) -> Option<Instant> {
+    [
+        cb.get_keepalive_deadline(),
+        cb.get_linger_deadline(),
+        cb.get_persist_deadline(),
+        cb.get_pacing_deadline(),
+    ]
+    .into_iter()
+    .flatten()
+    .min()
 }
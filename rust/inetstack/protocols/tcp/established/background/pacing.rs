@@ -0,0 +1,17 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+use super::ControlBlock;
+use ::std::time::Instant;
+
+/// Sends the segments that the pacer held back, once they are due.
+pub fn pacing<const N: usize>(cb: &ControlBlock<N>, now: Instant) {
+    // The sender arms the deadline whenever the pacer holds back new data.
+    match cb.get_pacing_deadline() {
+        Some(deadline) if deadline <= now => {
+            trace!("Pacing Timer Expired");
+            cb.on_pacing_timeout(now);
+        },
+        _ => {},
+    }
+}
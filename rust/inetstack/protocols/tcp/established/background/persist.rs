@@ -0,0 +1,17 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+use super::ControlBlock;
+use ::std::time::Instant;
+
+/// Probes our peer's window while it is closed, so that a lost window update cannot stall the connection.
+pub fn persist<const N: usize>(cb: &ControlBlock<N>, now: Instant) {
+    // The sender arms the deadline when it runs out of window, and clears it when the window opens again.
+    match cb.get_persist_deadline() {
+        Some(deadline) if deadline <= now => {
+            trace!("Persist Timer Expired");
+            cb.on_persist_timeout(now);
+        },
+        _ => {},
+    }
+}
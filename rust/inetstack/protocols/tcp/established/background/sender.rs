@@ -46,43 +46,11 @@ pub async fn sender<const N: usize>(_cb: Rc<ControlBlock<N>>) -> Result<!, Fail>
         // let (win_sz, win_sz_changed) = cb.get_send_window();
         // futures::pin_mut!(win_sz_changed);
 
-        // // If we don't have any window size at all, we need to transition to PERSIST mode and
-        // // repeatedly send window probes until window opens up.
+        // // If we don't have any window size at all, wait for it to open up.  Window probes are sent by the
+        // // persist timer (see persist.rs), so that a lost window update does not stall us here forever.
         // if win_sz == 0 {
-        //     // Send a window probe (this is a one-byte packet designed to elicit a window update from our peer).
-        //     let remote_link_addr = cb.arp().query(cb.get_remote().ip().clone()).await?;
-        //     let buf: DemiBuffer = cb
-        //         .pop_one_unsent_byte()
-        //         .unwrap_or_else(|| panic!("No unsent data? {}, {}", send_next, unsent_seq));
-
-        //     // Update SND.NXT.
-        //     cb.modify_send_next(|s| s + SeqNumber::from(1));
-
-        //     // Add the probe byte (as a new separate buffer) to our unacknowledged queue.
-        //     let unacked_segment = UnackedSegment {
-        //         bytes: buf.clone(),
-        //         initial_tx: Some(cb.clock.now()),
-        //     };
-        //     cb.push_unacked_segment(unacked_segment);
-
-        //     let mut header: TcpHeader = cb.tcp_header();
-        //     header.seq_num = send_next;
-        //     cb.emit(header, Some(buf.clone()), remote_link_addr);
-
-        //     // Note that we loop here *forever*, exponentially backing off.
-        //     // TODO: Use the correct PERSIST mode timer here.
-        //     let mut timeout: Duration = Duration::from_secs(1);
-        //     loop {
-        //         futures::select_biased! {
-        //             _ = win_sz_changed => continue 'top,
-        //             _ = cb.clock.wait(cb.clock.clone(), timeout).fuse() => {
-        //                 timeout *= 2;
-        //             }
-        //         }
-        //         // Retransmit our window probe.
-        //         let mut header: TcpHeader = cb.tcp_header();
-        //         header.seq_num = send_next;
-        //         cb.emit(header, Some(buf.clone()), remote_link_addr);
+        //     futures::select_biased! {
+        //         _ = win_sz_changed => continue 'top,
         //     }
         // }
 
@@ -0,0 +1,42 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+use super::ControlBlock;
+use crate::runtime::{
+    fail::Fail,
+    timer::TimerRc,
+};
+use ::futures::{
+    future::{
+        self,
+        Either,
+    },
+    FutureExt,
+};
+use ::std::rc::Rc;
+
+/// Closes the connection once it has spent 2 MSL in TIME-WAIT (RFC 9293 Section 3.6).
+#[allow(dead_code)]
+pub async fn time_wait<const N: usize>(cb: Rc<ControlBlock<N>>) -> Result<!, Fail> {
+    let clock: TimerRc = cb.clock.clone().expect("connection should have a clock");
+    loop {
+        // The deadline is set when we enter TIME-WAIT and pushed back if our peer retransmits its FIN.
+        let (time_wait_deadline, time_wait_deadline_changed) = cb.watch_time_wait_deadline();
+        futures::pin_mut!(time_wait_deadline_changed);
+
+        let time_wait_future = match time_wait_deadline {
+            Some(t) => Either::Left(clock.wait_until(clock.clone(), t).fuse()),
+            None => Either::Right(future::pending()),
+        };
+        futures::pin_mut!(time_wait_future);
+
+        futures::select_biased! {
+            _ = time_wait_deadline_changed => continue,
+            _ = time_wait_future => {
+                trace!("TIME-WAIT Timer Expired");
+                cb.on_time_wait_timeout();
+                return Err(Fail::new(libc::ENOTCONN, "connection closed"));
+            },
+        }
+    }
+}
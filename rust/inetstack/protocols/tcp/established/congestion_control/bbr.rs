@@ -0,0 +1,486 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+// A rate-based congestion controller modeled after BBR (draft-cardwell-iccrg-bbr-congestion-control).  Rather than
+// reacting to losses, it keeps a model of the path (bottleneck bandwidth and minimum RTT), and sizes both cwnd and the
+// pacing rate from the bandwidth-delay product.  Delivery rates are sampled once per round trip, which is coarser than
+// the per-packet rate samples of the reference implementation, but needs no per-segment state in the sender.
+
+use super::{
+    CongestionControl,
+    FastRetransmitRecovery,
+    LimitedTransmit,
+    Options,
+    SlowStartCongestionAvoidance,
+};
+use crate::{
+    inetstack::protocols::tcp::SeqNumber,
+    runtime::watched::{
+        WatchFuture,
+        WatchedValue,
+    },
+};
+use ::std::{
+    cell::{
+        Cell,
+        RefCell,
+    },
+    cmp::{
+        max,
+        min,
+    },
+    collections::VecDeque,
+    convert::TryInto,
+    fmt::Debug,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BbrMode {
+    /// Probe for bandwidth exponentially, until the delivery rate stops growing.
+    Startup,
+    /// Drain the queue that Startup built.
+    Drain,
+    /// Cruise at the estimated bandwidth, probing for more once in a while.
+    ProbeBw,
+    /// Shrink the window to refresh the minimum RTT estimate.
+    ProbeRtt,
+}
+
+#[derive(Debug)]
+pub struct Bbr {
+    pub mss: u32,
+    pub cwnd: WatchedValue<u32>, // Congestion window: Max number of bytes that may be in flight.
+    pub mode: Cell<BbrMode>,
+    pub pacing_gain: Cell<f32>, // Multiplier of the bottleneck bandwidth for the pacing rate.
+    pub cwnd_gain: Cell<f32>,   // Multiplier of the bandwidth-delay product for cwnd.
+
+    // Path Model.
+    pub btl_bw_samples: RefCell<VecDeque<(u64, u64)>>, // Delivery rate samples (round, bytes per second) of the last rounds.
+    pub min_rtt: Cell<Option<Duration>>, // Smallest RTT seen recently.
+    pub min_rtt_stamp: Cell<Instant>, // When min_rtt was last refreshed.
+
+    // Round Trip Counting.
+    pub round_count: Cell<u64>,      // Number of round trips so far.
+    pub round_end: Cell<SeqNumber>,  // The current round trip ends when this is acknowledged.
+    pub round_start: Cell<Instant>,  // When the current round trip started.
+    pub round_delivered: Cell<u32>,  // Bytes delivered in the current round trip.
+
+    // Startup State.
+    pub full_bw: Cell<u64>,          // Largest bandwidth seen when probing for more in Startup.
+    pub full_bw_count: Cell<u32>,    // Number of rounds without significant bandwidth growth.
+    pub filled_pipe: Cell<bool>,     // Did Startup find the bottleneck bandwidth?
+
+    // ProbeBW State.
+    pub cycle_index: Cell<usize>,    // Current phase of the pacing gain cycle.
+    pub cycle_stamp: Cell<Instant>,  // When the current phase started.
+
+    // ProbeRTT State.
+    pub probe_rtt_done: Cell<Option<Instant>>, // When ProbeRTT ends, once the window has been drained.
+    pub prior_cwnd: Cell<u32>,       // cwnd before ProbeRTT or the last RTO, restored afterwards.
+
+    // Fast Retransmit State.
+    pub duplicate_ack_count: Cell<u32>, // The number of consecutive duplicate ACKs we've received.
+    pub fast_retransmit_now: WatchedValue<bool>, // Flag to cause the retransmitter to retransmit a segment now.
+    pub limited_transmit_cwnd_increase: WatchedValue<u32>, // The amount by which cwnd should be increased due to the limited transit algorithm.
+}
+
+impl CongestionControl for Bbr {
+    fn new(mss: usize, seq_no: SeqNumber, _options: Option<Options>) -> Box<dyn CongestionControl> {
+        Box::new(Self::create(mss, seq_no, Instant::now()))
+    }
+}
+
+impl Bbr {
+    const DUP_ACK_THRESHOLD: u32 = 3;
+    // Smallest gain that lets Startup double the delivery rate every round (2/ln(2)).
+    const HIGH_GAIN: f32 = 2.885;
+    // Gains of the ProbeBW cycle: probe for more bandwidth, drain what that queued, then cruise.
+    const PACING_GAIN_CYCLE: [f32; 8] = [1.25, 0.75, 1., 1., 1., 1., 1., 1.];
+    const CWND_GAIN: f32 = 2.;
+    // Length of the bottleneck bandwidth filter, in round trips.
+    const BTL_BW_FILTER_LEN: u64 = 10;
+    // Bandwidth growth below which Startup considers the pipe full, and for how many rounds.
+    const FULL_BW_THRESHOLD: f32 = 1.25;
+    const FULL_BW_COUNT: u32 = 3;
+    const MIN_RTT_FILTER_LEN: Duration = Duration::from_secs(10);
+    const PROBE_RTT_DURATION: Duration = Duration::from_millis(200);
+    // Smallest cwnd, in segments, which keeps delayed ACKs from stalling the connection.
+    const MIN_CWND_SEGMENTS: u32 = 4;
+
+    pub fn create(mss: usize, seq_no: SeqNumber, now: Instant) -> Self {
+        let mss: u32 = mss.try_into().unwrap();
+        Self {
+            mss,
+            cwnd: WatchedValue::new(Self::MIN_CWND_SEGMENTS * mss),
+            mode: Cell::new(BbrMode::Startup),
+            pacing_gain: Cell::new(Self::HIGH_GAIN),
+            cwnd_gain: Cell::new(Self::HIGH_GAIN),
+
+            btl_bw_samples: RefCell::new(VecDeque::new()),
+            min_rtt: Cell::new(None),
+            min_rtt_stamp: Cell::new(now),
+
+            round_count: Cell::new(0),
+            round_end: Cell::new(seq_no),
+            round_start: Cell::new(now),
+            round_delivered: Cell::new(0),
+
+            full_bw: Cell::new(0),
+            full_bw_count: Cell::new(0),
+            filled_pipe: Cell::new(false),
+
+            cycle_index: Cell::new(0),
+            cycle_stamp: Cell::new(now),
+
+            probe_rtt_done: Cell::new(None),
+            prior_cwnd: Cell::new(0),
+
+            duplicate_ack_count: Cell::new(0),
+            fast_retransmit_now: WatchedValue::new(false),
+            limited_transmit_cwnd_increase: WatchedValue::new(0),
+        }
+    }
+
+    /// Returns the estimated bottleneck bandwidth, in bytes per second.
+    pub fn btl_bw(&self) -> u64 {
+        self.btl_bw_samples
+            .borrow()
+            .iter()
+            .map(|(_, rate)| *rate)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Returns the estimated bandwidth-delay product, in bytes, if we have a model of the path already.
+    pub fn bdp(&self) -> Option<u32> {
+        let btl_bw: u64 = self.btl_bw();
+        match self.min_rtt.get() {
+            Some(min_rtt) if btl_bw > 0 => Some((btl_bw as f64 * min_rtt.as_secs_f64()) as u32),
+            _ => None,
+        }
+    }
+
+    pub fn on_ack(&self, now: Instant, send_unacked: SeqNumber, send_next: SeqNumber, ack_seq_no: SeqNumber) {
+        let bytes_acknowledged: u32 = (ack_seq_no - send_unacked).into();
+        if bytes_acknowledged == 0 {
+            self.on_dup_ack_received();
+            return;
+        }
+        self.duplicate_ack_count.set(0);
+        let bytes_in_flight: u32 = (send_next - ack_seq_no).into();
+
+        self.round_delivered.set(self.round_delivered.get() + bytes_acknowledged);
+        if ack_seq_no > self.round_end.get() {
+            self.on_round_end(now, send_next);
+        }
+
+        self.update_mode(now, bytes_in_flight);
+        self.update_cwnd(bytes_acknowledged);
+    }
+
+    pub fn on_rtt(&self, rtt: Duration, now: Instant) {
+        let expired: bool = now > self.min_rtt_stamp.get() + Self::MIN_RTT_FILTER_LEN;
+        if expired && self.mode.get() != BbrMode::ProbeRtt {
+            self.enter_probe_rtt();
+        }
+        if expired || self.min_rtt.get().map_or(true, |min_rtt| rtt <= min_rtt) {
+            self.min_rtt.set(Some(rtt));
+            self.min_rtt_stamp.set(now);
+        }
+    }
+
+    fn on_dup_ack_received(&self) {
+        let duplicate_ack_count: u32 = self.duplicate_ack_count.get() + 1;
+        self.duplicate_ack_count.set(duplicate_ack_count);
+        if duplicate_ack_count < Self::DUP_ACK_THRESHOLD {
+            self.limited_transmit_cwnd_increase.modify(|ltci| ltci + self.mss);
+        } else if duplicate_ack_count == Self::DUP_ACK_THRESHOLD {
+            // Losses do not change our model of the path, we only repair them.
+            self.fast_retransmit_now.set(true);
+        }
+    }
+
+    fn on_round_end(&self, now: Instant, send_next: SeqNumber) {
+        let elapsed: Duration = now.saturating_duration_since(self.round_start.get());
+        if !elapsed.is_zero() {
+            let rate: u64 = (self.round_delivered.get() as f64 / elapsed.as_secs_f64()) as u64;
+            let round: u64 = self.round_count.get();
+            let mut samples = self.btl_bw_samples.borrow_mut();
+            samples.push_back((round, rate));
+            while let Some((oldest, _)) = samples.front() {
+                if *oldest + Self::BTL_BW_FILTER_LEN > round {
+                    break;
+                }
+                samples.pop_front();
+            }
+        }
+
+        self.round_count.set(self.round_count.get() + 1);
+        self.round_end.set(send_next);
+        self.round_start.set(now);
+        self.round_delivered.set(0);
+
+        if !self.filled_pipe.get() {
+            self.check_full_pipe();
+        }
+    }
+
+    fn check_full_pipe(&self) {
+        let btl_bw: u64 = self.btl_bw();
+        if btl_bw as f32 >= self.full_bw.get() as f32 * Self::FULL_BW_THRESHOLD {
+            // Still growing.
+            self.full_bw.set(btl_bw);
+            self.full_bw_count.set(0);
+            return;
+        }
+        self.full_bw_count.set(self.full_bw_count.get() + 1);
+        if self.full_bw_count.get() >= Self::FULL_BW_COUNT {
+            self.filled_pipe.set(true);
+        }
+    }
+
+    fn update_mode(&self, now: Instant, bytes_in_flight: u32) {
+        match self.mode.get() {
+            BbrMode::Startup if self.filled_pipe.get() => {
+                self.mode.set(BbrMode::Drain);
+                self.pacing_gain.set(1. / Self::HIGH_GAIN);
+                self.cwnd_gain.set(Self::HIGH_GAIN);
+            },
+            BbrMode::Drain if self.bdp().map_or(false, |bdp| bytes_in_flight <= bdp) => self.enter_probe_bw(now),
+            BbrMode::ProbeBw => {
+                let phase_over: bool = match self.min_rtt.get() {
+                    Some(min_rtt) => now > self.cycle_stamp.get() + min_rtt,
+                    None => false,
+                };
+                if phase_over {
+                    let cycle_index: usize = (self.cycle_index.get() + 1) % Self::PACING_GAIN_CYCLE.len();
+                    self.cycle_index.set(cycle_index);
+                    self.cycle_stamp.set(now);
+                    self.pacing_gain.set(Self::PACING_GAIN_CYCLE[cycle_index]);
+                }
+            },
+            BbrMode::ProbeRtt => match self.probe_rtt_done.get() {
+                // Wait for the window to drain before timing ProbeRTT.
+                None if bytes_in_flight <= Self::MIN_CWND_SEGMENTS * self.mss => {
+                    self.probe_rtt_done.set(Some(now + Self::PROBE_RTT_DURATION));
+                },
+                Some(done) if now > done => self.exit_probe_rtt(now),
+                _ => (),
+            },
+            _ => (),
+        }
+    }
+
+    fn enter_probe_bw(&self, now: Instant) {
+        self.mode.set(BbrMode::ProbeBw);
+        self.cwnd_gain.set(Self::CWND_GAIN);
+        self.cycle_index.set(0);
+        self.cycle_stamp.set(now);
+        self.pacing_gain.set(Self::PACING_GAIN_CYCLE[0]);
+    }
+
+    fn enter_probe_rtt(&self) {
+        self.mode.set(BbrMode::ProbeRtt);
+        self.pacing_gain.set(1.);
+        self.cwnd_gain.set(1.);
+        self.prior_cwnd.set(max(self.prior_cwnd.get(), self.cwnd.get()));
+        self.probe_rtt_done.set(None);
+        self.cwnd
+            .set(min(self.cwnd.get(), Self::MIN_CWND_SEGMENTS * self.mss));
+    }
+
+    fn exit_probe_rtt(&self, now: Instant) {
+        self.min_rtt_stamp.set(now);
+        self.probe_rtt_done.set(None);
+        self.cwnd.set(max(self.cwnd.get(), self.prior_cwnd.get()));
+        self.prior_cwnd.set(0);
+        if self.filled_pipe.get() {
+            self.enter_probe_bw(now);
+        } else {
+            self.mode.set(BbrMode::Startup);
+            self.pacing_gain.set(Self::HIGH_GAIN);
+            self.cwnd_gain.set(Self::HIGH_GAIN);
+        }
+    }
+
+    fn update_cwnd(&self, bytes_acknowledged: u32) {
+        let min_cwnd: u32 = Self::MIN_CWND_SEGMENTS * self.mss;
+        if self.mode.get() == BbrMode::ProbeRtt {
+            self.cwnd.set(min_cwnd);
+            return;
+        }
+
+        let cwnd: u32 = self.cwnd.get() + bytes_acknowledged;
+        let cwnd: u32 = match self.bdp() {
+            // Grow towards the target, but only cap growth once Startup is over.
+            Some(bdp) if self.filled_pipe.get() => min(cwnd, (bdp as f32 * self.cwnd_gain.get()) as u32),
+            _ => cwnd,
+        };
+        self.cwnd.set(max(cwnd, min_cwnd));
+    }
+}
+
+impl SlowStartCongestionAvoidance for Bbr {
+    fn get_cwnd(&self) -> u32 {
+        self.cwnd.get()
+    }
+
+    fn watch_cwnd(&self) -> (u32, WatchFuture<'_, u32>) {
+        self.cwnd.watch()
+    }
+
+    fn on_send(&self, _rto: Duration, num_bytes_sent: u32) {
+        self.limited_transmit_cwnd_increase
+            .set_without_notify(self.limited_transmit_cwnd_increase.get().saturating_sub(num_bytes_sent));
+    }
+
+    fn on_ack_received(&self, _rto: Duration, send_unacked: SeqNumber, send_next: SeqNumber, ack_seq_no: SeqNumber) {
+        self.on_ack(Instant::now(), send_unacked, send_next, ack_seq_no)
+    }
+
+    fn on_rto(&self, _send_unacked: SeqNumber) {
+        // Everything in flight is presumed lost.  Start over from one segment, and grow back to the model from there.
+        self.prior_cwnd.set(max(self.prior_cwnd.get(), self.cwnd.get()));
+        self.cwnd.set(self.mss);
+    }
+
+    fn on_rtt_sample(&self, rtt: Duration) {
+        self.on_rtt(rtt, Instant::now())
+    }
+
+    fn get_pacing_rate(&self) -> Option<u64> {
+        match self.btl_bw() {
+            0 => None,
+            btl_bw => Some((btl_bw as f64 * self.pacing_gain.get() as f64) as u64),
+        }
+    }
+}
+
+impl FastRetransmitRecovery for Bbr {
+    fn get_duplicate_ack_count(&self) -> u32 {
+        self.duplicate_ack_count.get()
+    }
+
+    fn get_retransmit_now_flag(&self) -> bool {
+        self.fast_retransmit_now.get()
+    }
+
+    fn watch_retransmit_now_flag(&self) -> (bool, WatchFuture<'_, bool>) {
+        self.fast_retransmit_now.watch()
+    }
+
+    fn on_fast_retransmit(&self) {
+        self.fast_retransmit_now.set_without_notify(false);
+    }
+}
+
+impl LimitedTransmit for Bbr {
+    fn get_limited_transmit_cwnd_increase(&self) -> u32 {
+        self.limited_transmit_cwnd_increase.get()
+    }
+
+    fn watch_limited_transmit_cwnd_increase(&self) -> (u32, WatchFuture<'_, u32>) {
+        self.limited_transmit_cwnd_increase.watch()
+    }
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Bbr,
+        BbrMode,
+    };
+    use crate::inetstack::protocols::tcp::{
+        congestion_control::SlowStartCongestionAvoidance,
+        SeqNumber,
+    };
+    use ::anyhow::Result;
+    use ::std::{
+        cmp::max,
+        time::{
+            Duration,
+            Instant,
+        },
+    };
+
+    const MSS: u32 = 1000;
+    const RTT: Duration = Duration::from_millis(10);
+    // Bottleneck bandwidth of the simulated path, in bytes per second.
+    const CAPACITY: u64 = 10_000_000;
+
+    /// Sends a full window over a path with a single bottleneck, and ACKs it segment by segment.
+    fn round_trip(cc: &Bbr, una: &mut u32, now: &mut Instant) {
+        let cwnd: u32 = cc.get_cwnd();
+        let duration: Duration = max(RTT, Duration::from_secs_f64(cwnd as f64 / CAPACITY as f64));
+        let nxt: SeqNumber = SeqNumber::from(*una + cwnd);
+        let nr_segments: u32 = cwnd / MSS;
+        for _ in 0..nr_segments {
+            *now += duration / nr_segments;
+            cc.on_ack(*now, SeqNumber::from(*una), nxt, SeqNumber::from(*una + MSS));
+            *una += MSS;
+        }
+        cc.on_rtt(duration, *now);
+    }
+
+    // Test that Startup finds the bottleneck bandwidth, and that cwnd then settles around the bandwidth-delay product.
+    #[test]
+    fn startup() -> Result<()> {
+        let mut now: Instant = Instant::now();
+        let cc: Bbr = Bbr::create(MSS as usize, SeqNumber::from(0), now);
+        let mut una: u32 = 0;
+        crate::ensure_eq!(cc.get_pacing_rate(), None);
+
+        for _ in 0..32 {
+            round_trip(&cc, &mut una, &mut now);
+        }
+        crate::ensure_eq!(cc.filled_pipe.get(), true);
+        crate::ensure_eq!(cc.mode.get(), BbrMode::ProbeBw);
+
+        let btl_bw: u64 = cc.btl_bw();
+        crate::ensure_eq!(btl_bw > CAPACITY * 95 / 100 && btl_bw < CAPACITY * 105 / 100, true);
+        crate::ensure_eq!(cc.min_rtt.get(), Some(RTT));
+
+        // cwnd is twice the bandwidth-delay product in ProbeBW.
+        let bdp: u32 = (CAPACITY as f64 * RTT.as_secs_f64()) as u32;
+        let cwnd: u32 = cc.get_cwnd();
+        crate::ensure_eq!(cwnd > bdp * 19 / 10 && cwnd < bdp * 21 / 10, true);
+        crate::ensure_eq!(cc.get_pacing_rate().is_some(), true);
+
+        Ok(())
+    }
+
+    // Test that a stale minimum RTT sends us to ProbeRTT, and that we come back with our window afterwards.
+    #[test]
+    fn probe_rtt() -> Result<()> {
+        let start: Instant = Instant::now();
+        let cc: Bbr = Bbr::create(MSS as usize, SeqNumber::from(0), start);
+        cc.cwnd.set(50 * MSS);
+        cc.on_rtt(RTT, start);
+
+        let now: Instant = start + Duration::from_secs(11);
+        cc.on_rtt(2 * RTT, now);
+        crate::ensure_eq!(cc.mode.get(), BbrMode::ProbeRtt);
+        crate::ensure_eq!(cc.get_cwnd(), 4 * MSS);
+        crate::ensure_eq!(cc.min_rtt.get(), Some(2 * RTT));
+
+        // Once the window drained, ProbeRTT lasts for 200ms.
+        let una: SeqNumber = SeqNumber::from(0);
+        let nxt: SeqNumber = SeqNumber::from(4 * MSS);
+        cc.on_ack(now, una, nxt, una + SeqNumber::from(MSS));
+        crate::ensure_eq!(cc.mode.get(), BbrMode::ProbeRtt);
+        let later: Instant = now + Duration::from_millis(300);
+        cc.on_ack(later, una + SeqNumber::from(MSS), nxt, nxt);
+        crate::ensure_eq!(cc.mode.get(), BbrMode::Startup);
+        crate::ensure_eq!(cc.get_cwnd() >= 50 * MSS, true);
+
+        Ok(())
+    }
+}
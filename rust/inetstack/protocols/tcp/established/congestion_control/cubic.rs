@@ -59,6 +59,9 @@ pub struct Cubic {
     pub prev_ack_seq_no: Cell<SeqNumber>, // The previous highest ACK sequence number.
     pub recover: Cell<SeqNumber>, // If we receive dup ACKs with sequence numbers greater than this we'll attempt fast recovery.
 
+    // Explicit Congestion Notification State
+    pub ecn_recover: Cell<SeqNumber>, // We only react to congestion marks for data sent after this.
+
     pub limited_transmit_cwnd_increase: WatchedValue<u32>, // The amount by which cwnd should be increased due to the limited transit algorithm.
 }
 
@@ -95,6 +98,8 @@ impl CongestionControl for Cubic {
             prev_ack_seq_no: Cell::new(seq_no), // RFC6582 doesn't specify the initial value, but this seems sensible.
             duplicate_ack_count: Cell::new(0),
 
+            ecn_recover: Cell::new(seq_no),
+
             limited_transmit_cwnd_increase: WatchedValue::new(0),
         })
     }
@@ -323,6 +328,26 @@ impl SlowStartCongestionAvoidance for Cubic {
         }
     }
 
+    fn on_ecn_feedback(&self, send_next: SeqNumber, ack_seq_no: SeqNumber, _bytes_acknowledged: u32, ece: bool) {
+        // React to a congestion mark as to a loss, but without retransmitting anything, and only once per window of
+        // data (RFC 3168 Section 6.1.2).
+        if ece && !self.in_fast_recovery.get() && ack_seq_no > self.ecn_recover.get() {
+            let cwnd: u32 = self.cwnd.get();
+            if self.fast_convergence {
+                self.fast_convergence();
+            } else {
+                self.w_max.set(cwnd);
+            }
+            let reduced_cwnd: u32 = max((cwnd as f32 * Self::BETA_CUBIC) as u32, 2 * self.mss);
+            self.ssthresh.set(reduced_cwnd);
+            self.cwnd.set(reduced_cwnd);
+            // Unlike after fast recovery, we go straight back into congestion avoidance.
+            self.ca_start.set(Instant::now());
+            self.last_congestion_was_rto.set(false);
+            self.ecn_recover.set(send_next);
+        }
+    }
+
     fn on_rto(&self, send_unacked: SeqNumber) {
         // Handle timeout for any of the algorithms we could currently be using.
         self.on_rto_ss_ca();
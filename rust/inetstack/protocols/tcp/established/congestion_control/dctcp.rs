@@ -0,0 +1,224 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+// Data Center TCP, as described in RFC 8257.  DCTCP estimates the fraction of bytes that hit a congested queue from
+// ECN echoes, and shrinks cwnd in proportion to it instead of halving it.  Everything else is standard NewReno.
+
+use super::{
+    new_reno::NewReno,
+    CongestionControl,
+    FastRetransmitRecovery,
+    LimitedTransmit,
+    Options,
+    SlowStartCongestionAvoidance,
+};
+use crate::{
+    inetstack::protocols::tcp::SeqNumber,
+    runtime::watched::WatchFuture,
+};
+use ::std::{
+    cell::Cell,
+    cmp::max,
+    fmt::Debug,
+    time::Duration,
+};
+
+#[derive(Debug)]
+pub struct Dctcp {
+    pub reno: NewReno, // Loss recovery and window growth are left to NewReno.
+    pub g: f32,        // Weight given to new samples of the marked fraction.
+    pub alpha: Cell<f32>, // Estimate of the fraction of bytes that get marked.
+    pub bytes_acked: Cell<u32>, // Bytes acknowledged in the current observation window.
+    pub bytes_marked: Cell<u32>, // Bytes acknowledged with ECE in the current observation window.
+    pub window_end: Cell<SeqNumber>, // End of the current observation window, roughly one RTT worth of data.
+    pub reduced_in_window: Cell<bool>, // Did we already react to ECE in the current observation window?
+}
+
+impl CongestionControl for Dctcp {
+    fn new(mss: usize, seq_no: SeqNumber, options: Option<Options>) -> Box<dyn CongestionControl> {
+        let options: Options = options.unwrap_or_default();
+        // Defaults from RFC 8257 Section 4.2.  Starting with alpha at 1 is conservative on the first congestion event.
+        let g: f32 = options.get_float("dctcp_g").unwrap_or(1. / 16.) as f32;
+        let alpha: f32 = options.get_float("dctcp_alpha").unwrap_or(1.) as f32;
+        Box::new(Self::create(mss, seq_no, g, alpha))
+    }
+}
+
+impl Dctcp {
+    pub fn create(mss: usize, seq_no: SeqNumber, g: f32, alpha: f32) -> Self {
+        Self {
+            reno: NewReno::create(mss, seq_no),
+            g,
+            alpha: Cell::new(alpha),
+            bytes_acked: Cell::new(0),
+            bytes_marked: Cell::new(0),
+            window_end: Cell::new(seq_no),
+            reduced_in_window: Cell::new(false),
+        }
+    }
+
+    fn update_alpha(&self) {
+        let bytes_acked: u32 = self.bytes_acked.get();
+        let fraction: f32 = if bytes_acked == 0 {
+            0.
+        } else {
+            self.bytes_marked.get() as f32 / bytes_acked as f32
+        };
+        self.alpha
+            .set((1. - self.g) * self.alpha.get() + self.g * fraction);
+        self.bytes_acked.set(0);
+        self.bytes_marked.set(0);
+    }
+}
+
+impl SlowStartCongestionAvoidance for Dctcp {
+    fn get_cwnd(&self) -> u32 {
+        self.reno.get_cwnd()
+    }
+
+    fn watch_cwnd(&self) -> (u32, WatchFuture<'_, u32>) {
+        self.reno.watch_cwnd()
+    }
+
+    fn on_cwnd_check_before_send(&self) {
+        self.reno.on_cwnd_check_before_send()
+    }
+
+    fn on_ack_received(&self, rto: Duration, send_unacked: SeqNumber, send_next: SeqNumber, ack_seq_no: SeqNumber) {
+        self.reno.on_ack_received(rto, send_unacked, send_next, ack_seq_no)
+    }
+
+    fn on_ecn_feedback(&self, send_next: SeqNumber, ack_seq_no: SeqNumber, bytes_acknowledged: u32, ece: bool) {
+        self.bytes_acked.set(self.bytes_acked.get() + bytes_acknowledged);
+        if ece {
+            self.bytes_marked.set(self.bytes_marked.get() + bytes_acknowledged);
+        }
+
+        // Once a window's worth of data is acknowledged, fold what we saw into alpha (RFC 8257 Section 3.3).
+        if ack_seq_no > self.window_end.get() {
+            self.update_alpha();
+            self.window_end.set(send_next);
+            self.reduced_in_window.set(false);
+        }
+
+        // React to congestion at most once per window.
+        if ece && !self.reduced_in_window.get() {
+            let mss: u32 = self.reno.mss;
+            let cwnd: u32 = self.reno.cwnd.get();
+            let reduced_cwnd: u32 = max((cwnd as f32 * (1. - self.alpha.get() / 2.)) as u32, mss);
+            self.reno.ssthresh.set(max(reduced_cwnd, 2 * mss));
+            self.reno.cwnd.set(reduced_cwnd);
+            self.reduced_in_window.set(true);
+        }
+    }
+
+    fn on_rto(&self, send_unacked: SeqNumber) {
+        self.reno.on_rto(send_unacked)
+    }
+
+    fn on_send(&self, rto: Duration, num_sent_bytes: u32) {
+        self.reno.on_send(rto, num_sent_bytes)
+    }
+}
+
+impl FastRetransmitRecovery for Dctcp {
+    fn get_duplicate_ack_count(&self) -> u32 {
+        self.reno.get_duplicate_ack_count()
+    }
+
+    fn get_retransmit_now_flag(&self) -> bool {
+        self.reno.get_retransmit_now_flag()
+    }
+
+    fn watch_retransmit_now_flag(&self) -> (bool, WatchFuture<'_, bool>) {
+        self.reno.watch_retransmit_now_flag()
+    }
+
+    fn on_fast_retransmit(&self) {
+        self.reno.on_fast_retransmit()
+    }
+}
+
+impl LimitedTransmit for Dctcp {
+    fn get_limited_transmit_cwnd_increase(&self) -> u32 {
+        self.reno.get_limited_transmit_cwnd_increase()
+    }
+
+    fn watch_limited_transmit_cwnd_increase(&self) -> (u32, WatchFuture<'_, u32>) {
+        self.reno.watch_limited_transmit_cwnd_increase()
+    }
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::Dctcp;
+    use crate::inetstack::protocols::tcp::{
+        congestion_control::SlowStartCongestionAvoidance,
+        SeqNumber,
+    };
+    use ::anyhow::Result;
+    use ::std::time::Duration;
+
+    const MSS: u32 = 1000;
+    const RTO: Duration = Duration::from_secs(1);
+
+    /// Feeds one window of `n` segments, of which the first `marked` come back with ECE.
+    fn ack_window(cc: &Dctcp, una: &mut u32, n: u32, marked: u32) {
+        let nxt: SeqNumber = SeqNumber::from(*una + n * MSS);
+        for i in 0..n {
+            let ack: SeqNumber = SeqNumber::from(*una + MSS);
+            cc.on_ack_received(RTO, SeqNumber::from(*una), nxt, ack);
+            cc.on_ecn_feedback(nxt, ack, MSS, i < marked);
+            *una += MSS;
+        }
+    }
+
+    // Test that alpha tracks the fraction of marked bytes.
+    #[test]
+    fn alpha() -> Result<()> {
+        let cc: Dctcp = Dctcp::create(MSS as usize, SeqNumber::from(0), 1. / 16., 1.);
+        let mut una: u32 = 0;
+
+        // The first ACK closes the initial (empty) observation window.
+        ack_window(&cc, &mut una, 1, 0);
+        crate::ensure_eq!(cc.alpha.get(), 15. / 16.);
+
+        // Windows without marks make alpha decay.
+        for _ in 0..64 {
+            ack_window(&cc, &mut una, 4, 0);
+        }
+        crate::ensure_eq!(cc.alpha.get() < 0.02, true);
+
+        // Windows with every other segment marked make it converge to 1/2.
+        for _ in 0..256 {
+            ack_window(&cc, &mut una, 4, 2);
+        }
+        crate::ensure_eq!((cc.alpha.get() - 0.5).abs() < 0.02, true);
+
+        Ok(())
+    }
+
+    // Test that cwnd shrinks in proportion to alpha, once per window.
+    #[test]
+    fn reduction() -> Result<()> {
+        let cc: Dctcp = Dctcp::create(MSS as usize, SeqNumber::from(0), 1. / 16., 0.5);
+        cc.reno.cwnd.set(20 * MSS);
+        let una: SeqNumber = SeqNumber::from(0);
+        let nxt: SeqNumber = SeqNumber::from(20 * MSS);
+
+        // Start an observation window that ends at SND.NXT.
+        cc.window_end.set(nxt);
+        cc.on_ecn_feedback(nxt, una, 0, true);
+        crate::ensure_eq!(cc.get_cwnd(), 15 * MSS);
+
+        // More marks in the same window are ignored.
+        cc.on_ecn_feedback(nxt, una, 0, true);
+        crate::ensure_eq!(cc.get_cwnd(), 15 * MSS);
+
+        Ok(())
+    }
+}
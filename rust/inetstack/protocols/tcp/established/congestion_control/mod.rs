@@ -1,13 +1,19 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT license.
 
+mod bbr;
 mod cubic;
+mod dctcp;
+mod new_reno;
 mod none;
 mod options;
 
 use crate::{
     inetstack::protocols::tcp::SeqNumber,
-    runtime::watched::WatchFuture,
+    runtime::{
+        network::socket::CongestionAlgorithm,
+        watched::WatchFuture,
+    },
 };
 use ::std::{
     fmt::Debug,
@@ -15,7 +21,10 @@ use ::std::{
 };
 
 pub use self::{
+    bbr::Bbr,
     cubic::Cubic,
+    dctcp::Dctcp,
+    new_reno::NewReno,
     none::None,
     options::{
         OptionValue,
@@ -42,6 +51,18 @@ pub trait SlowStartCongestionAvoidance {
 
     // Called immediately before a segment is sent for the 1st time.
     fn on_send(&self, _rto: Duration, _num_sent_bytes: u32) {}
+
+    // Called after `on_ack_received`, with whether the ACK echoed a congestion experienced mark (ECE).  This is always
+    // false on connections that did not negotiate ECN.
+    fn on_ecn_feedback(&self, _send_next: SeqNumber, _ack_seq_no: SeqNumber, _bytes_acknowledged: u32, _ece: bool) {}
+
+    // Called for every new round-trip time measurement.
+    fn on_rtt_sample(&self, _rtt: Duration) {}
+
+    // Rate at which data should be sent, in bytes per second, for rate-based algorithms.
+    fn get_pacing_rate(&self) -> Option<u64> {
+        Option::None
+    }
 }
 
 pub trait FastRetransmitRecovery
@@ -81,3 +102,14 @@ pub trait CongestionControl: SlowStartCongestionAvoidance + FastRetransmitRecove
 }
 
 pub type CongestionControlConstructor = fn(usize, SeqNumber, Option<options::Options>) -> Box<dyn CongestionControl>;
+
+/// Returns the constructor of a congestion control algorithm.
+pub fn constructor(algorithm: CongestionAlgorithm) -> CongestionControlConstructor {
+    match algorithm {
+        CongestionAlgorithm::None => None::new,
+        CongestionAlgorithm::NewReno => NewReno::new,
+        CongestionAlgorithm::Cubic => Cubic::new,
+        CongestionAlgorithm::Dctcp => Dctcp::new,
+        CongestionAlgorithm::Bbr => Bbr::new,
+    }
+}
@@ -0,0 +1,372 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+// Standard TCP congestion control: slow start and congestion avoidance as described in RFC 5681, with appropriate
+// byte counting (RFC 3465), plus fast retransmit and the NewReno modification to fast recovery (RFC 6582).
+
+use super::{
+    CongestionControl,
+    FastRetransmitRecovery,
+    LimitedTransmit,
+    Options,
+    SlowStartCongestionAvoidance,
+};
+use crate::{
+    inetstack::protocols::tcp::SeqNumber,
+    runtime::watched::{
+        WatchFuture,
+        WatchedValue,
+    },
+};
+use ::std::{
+    cell::Cell,
+    cmp::{
+        max,
+        min,
+    },
+    convert::TryInto,
+    fmt::Debug,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+#[derive(Debug)]
+pub struct NewReno {
+    pub mss: u32,
+    // Slow Start / Congestion Avoidance State.
+    pub cwnd: WatchedValue<u32>, // Congestion window: Max number of bytes that may be in flight to prevent congestion.
+    pub ssthresh: Cell<u32>, // The size of cwnd at which we will change from using slow start to congestion avoidance.
+    pub bytes_acked: Cell<u32>, // Bytes acknowledged since cwnd was last increased in congestion avoidance.
+    pub initial_cwnd: u32,   // The initial value of cwnd, which gets used if the connection ever resets.
+    pub last_send_time: Cell<Instant>, // The moment at which we last sent data.
+    pub rto_at_last_send: Cell<Duration>, // The RTO at the moment we last sent data.
+
+    // Fast Recovery / Fast Retransmit State
+    pub duplicate_ack_count: Cell<u32>, // The number of consecutive duplicate ACKs we've received.
+    pub fast_retransmit_now: WatchedValue<bool>, // Flag to cause the retransmitter to retransmit a segment now.
+    pub in_fast_recovery: Cell<bool>,   // Are we currently in the `fast recovery` algorithm.
+    pub recover: Cell<SeqNumber>, // Highest sequence number sent when we entered fast recovery (RFC 6582).
+
+    // Explicit Congestion Notification State
+    pub ecn_recover: Cell<SeqNumber>, // Highest sequence number sent when we last reacted to a congestion mark.
+
+    pub limited_transmit_cwnd_increase: WatchedValue<u32>, // The amount by which cwnd should be increased due to the limited transit algorithm.
+}
+
+impl CongestionControl for NewReno {
+    fn new(mss: usize, seq_no: SeqNumber, _options: Option<Options>) -> Box<dyn CongestionControl> {
+        Box::new(Self::create(mss, seq_no))
+    }
+}
+
+impl NewReno {
+    const DUP_ACK_THRESHOLD: u32 = 3;
+
+    /// Creates the state of a connection, for algorithms that build on this one.
+    pub fn create(mss: usize, seq_no: SeqNumber) -> Self {
+        let mss: u32 = mss.try_into().unwrap();
+        // The initial value of cwnd is set according to RFC5681, section 3.1, page 7.
+        let initial_cwnd: u32 = match mss {
+            0..=1095 => 4 * mss,
+            1096..=2190 => 3 * mss,
+            _ => 2 * mss,
+        };
+
+        Self {
+            mss,
+            cwnd: WatchedValue::new(initial_cwnd),
+            ssthresh: Cell::new(u32::MAX), // According to RFC5681 ssthresh should be initialised 'arbitrarily high'.
+            bytes_acked: Cell::new(0),
+            initial_cwnd,
+            last_send_time: Cell::new(Instant::now()),
+            rto_at_last_send: Cell::new(Duration::new(1, 0)),
+
+            duplicate_ack_count: Cell::new(0),
+            fast_retransmit_now: WatchedValue::new(false),
+            in_fast_recovery: Cell::new(false),
+            recover: Cell::new(seq_no), // Recover set to initial send sequence number according to RFC6582.
+
+            ecn_recover: Cell::new(seq_no),
+
+            limited_transmit_cwnd_increase: WatchedValue::new(0),
+        }
+    }
+
+    /// Sets ssthresh after a congestion event, as in equation (4) of RFC 5681.
+    pub fn reduce_ssthresh(&self, flight_size: u32) {
+        self.ssthresh.set(max(flight_size / 2, 2 * self.mss));
+    }
+
+    fn on_dup_ack_received(&self, send_unacked: SeqNumber, send_next: SeqNumber, ack_seq_no: SeqNumber) {
+        let duplicate_ack_count: u32 = self.duplicate_ack_count.get() + 1;
+        self.duplicate_ack_count.set(duplicate_ack_count);
+
+        if duplicate_ack_count < Self::DUP_ACK_THRESHOLD {
+            // Limited transmit (RFC 3042): let one new segment out per duplicate ACK.
+            self.limited_transmit_cwnd_increase.modify(|ltci| ltci + self.mss);
+        } else if duplicate_ack_count == Self::DUP_ACK_THRESHOLD {
+            // Only enter fast recovery if this is not a leftover from the previous one (RFC 6582 Section 3.2).
+            if !self.in_fast_recovery.get() && ack_seq_no - SeqNumber::from(1) >= self.recover.get() {
+                let flight_size: u32 = (send_next - send_unacked).into();
+                self.reduce_ssthresh(flight_size);
+                self.recover.set(send_next);
+                self.in_fast_recovery.set(true);
+                self.cwnd.set(self.ssthresh.get() + Self::DUP_ACK_THRESHOLD * self.mss);
+                self.fast_retransmit_now.set(true);
+            }
+        } else if self.in_fast_recovery.get() {
+            // Inflate the window for every segment that left the network.
+            self.cwnd.modify(|c| c + self.mss);
+        }
+    }
+
+    fn on_ack_received_fast_recovery(&self, send_unacked: SeqNumber, send_next: SeqNumber, ack_seq_no: SeqNumber) {
+        let bytes_outstanding: u32 = (send_next - send_unacked).into();
+        let bytes_acknowledged: u32 = (ack_seq_no - send_unacked).into();
+        let mss: u32 = self.mss;
+
+        if ack_seq_no > self.recover.get() {
+            // Full acknowledgement: deflate the window.
+            self.cwnd
+                .set(min(self.ssthresh.get(), max(bytes_outstanding, mss) + mss));
+            self.in_fast_recovery.set(false);
+            self.bytes_acked.set(0);
+        } else {
+            // Partial acknowledgement: the next segment was lost too, retransmit it right away.
+            self.fast_retransmit_now.set(true);
+            let deflated: u32 = self.cwnd.get().saturating_sub(bytes_acknowledged);
+            if bytes_acknowledged >= mss {
+                self.cwnd.set(deflated + mss);
+            } else {
+                self.cwnd.set(max(deflated, mss));
+            }
+        }
+    }
+
+    fn on_ack_received_ss_ca(&self, bytes_acknowledged: u32) {
+        let mss: u32 = self.mss;
+        let cwnd: u32 = self.cwnd.get();
+
+        if cwnd < self.ssthresh.get() {
+            // Slow start.
+            self.cwnd.set(cwnd + min(bytes_acknowledged, mss));
+        } else {
+            // Congestion avoidance: one MSS per window's worth of acknowledged bytes.
+            let bytes_acked: u32 = self.bytes_acked.get() + bytes_acknowledged;
+            if bytes_acked >= cwnd {
+                self.bytes_acked.set(bytes_acked - cwnd);
+                self.cwnd.set(cwnd + mss);
+            } else {
+                self.bytes_acked.set(bytes_acked);
+            }
+        }
+    }
+}
+
+impl SlowStartCongestionAvoidance for NewReno {
+    fn get_cwnd(&self) -> u32 {
+        self.cwnd.get()
+    }
+
+    fn watch_cwnd(&self) -> (u32, WatchFuture<'_, u32>) {
+        self.cwnd.watch()
+    }
+
+    fn on_cwnd_check_before_send(&self) {
+        // Restart from the initial window after an idle period (RFC 5681 Section 4.1).
+        let idle: bool = Instant::now().duration_since(self.last_send_time.get()) > self.rto_at_last_send.get();
+        if idle {
+            self.cwnd.set(min(self.initial_cwnd, self.cwnd.get()));
+            self.limited_transmit_cwnd_increase.set_without_notify(0);
+        }
+    }
+
+    fn on_send(&self, rto: Duration, num_bytes_sent: u32) {
+        self.last_send_time.set(Instant::now());
+        self.rto_at_last_send.set(rto);
+        self.limited_transmit_cwnd_increase
+            .set_without_notify(self.limited_transmit_cwnd_increase.get().saturating_sub(num_bytes_sent));
+    }
+
+    fn on_ack_received(&self, _rto: Duration, send_unacked: SeqNumber, send_next: SeqNumber, ack_seq_no: SeqNumber) {
+        let bytes_acknowledged: u32 = (ack_seq_no - send_unacked).into();
+        if bytes_acknowledged == 0 {
+            self.on_dup_ack_received(send_unacked, send_next, ack_seq_no);
+        } else {
+            self.duplicate_ack_count.set(0);
+            if self.in_fast_recovery.get() {
+                self.on_ack_received_fast_recovery(send_unacked, send_next, ack_seq_no);
+            } else {
+                self.on_ack_received_ss_ca(bytes_acknowledged);
+            }
+        }
+    }
+
+    fn on_ecn_feedback(&self, send_next: SeqNumber, ack_seq_no: SeqNumber, _bytes_acknowledged: u32, ece: bool) {
+        // A congestion mark calls for the same reduction as a loss, minus the retransmission, and only once per window
+        // of data (RFC 3168 Section 6.1.2).  Fast recovery already reduced the window for this one.
+        if ece && !self.in_fast_recovery.get() && ack_seq_no > self.ecn_recover.get() {
+            self.reduce_ssthresh(self.cwnd.get());
+            self.cwnd.set(self.ssthresh.get());
+            self.bytes_acked.set(0);
+            self.ecn_recover.set(send_next);
+        }
+    }
+
+    fn on_rto(&self, send_unacked: SeqNumber) {
+        // We only know cwnd here, which bounds the flight size.
+        self.reduce_ssthresh(self.cwnd.get());
+        self.cwnd.set(self.mss);
+        self.bytes_acked.set(0);
+        self.recover.set(send_unacked);
+        self.in_fast_recovery.set(false);
+    }
+}
+
+impl FastRetransmitRecovery for NewReno {
+    fn get_duplicate_ack_count(&self) -> u32 {
+        self.duplicate_ack_count.get()
+    }
+
+    fn get_retransmit_now_flag(&self) -> bool {
+        self.fast_retransmit_now.get()
+    }
+
+    fn watch_retransmit_now_flag(&self) -> (bool, WatchFuture<'_, bool>) {
+        self.fast_retransmit_now.watch()
+    }
+
+    fn on_fast_retransmit(&self) {
+        self.fast_retransmit_now.set_without_notify(false);
+    }
+}
+
+impl LimitedTransmit for NewReno {
+    fn get_limited_transmit_cwnd_increase(&self) -> u32 {
+        self.limited_transmit_cwnd_increase.get()
+    }
+
+    fn watch_limited_transmit_cwnd_increase(&self) -> (u32, WatchFuture<'_, u32>) {
+        self.limited_transmit_cwnd_increase.watch()
+    }
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::NewReno;
+    use crate::inetstack::protocols::tcp::{
+        congestion_control::{
+            FastRetransmitRecovery,
+            SlowStartCongestionAvoidance,
+        },
+        SeqNumber,
+    };
+    use ::anyhow::Result;
+    use ::std::time::Duration;
+
+    const MSS: u32 = 1000;
+    const RTO: Duration = Duration::from_secs(1);
+
+    // Test that cwnd grows by one MSS per ACK'd segment in slow start, and by one MSS per window afterwards.
+    #[test]
+    fn slow_start_and_congestion_avoidance() -> Result<()> {
+        let cc: NewReno = NewReno::create(MSS as usize, SeqNumber::from(0));
+        crate::ensure_eq!(cc.get_cwnd(), 4 * MSS);
+
+        let mut una: u32 = 0;
+        for _ in 0..4 {
+            cc.on_ack_received(RTO, SeqNumber::from(una), SeqNumber::from(una + 4 * MSS), SeqNumber::from(una + MSS));
+            una += MSS;
+        }
+        crate::ensure_eq!(cc.get_cwnd(), 8 * MSS);
+
+        cc.ssthresh.set(8 * MSS);
+        for _ in 0..7 {
+            cc.on_ack_received(RTO, SeqNumber::from(una), SeqNumber::from(una + 8 * MSS), SeqNumber::from(una + MSS));
+            una += MSS;
+        }
+        crate::ensure_eq!(cc.get_cwnd(), 8 * MSS);
+        cc.on_ack_received(RTO, SeqNumber::from(una), SeqNumber::from(una + 8 * MSS), SeqNumber::from(una + MSS));
+        crate::ensure_eq!(cc.get_cwnd(), 9 * MSS);
+
+        Ok(())
+    }
+
+    // Test fast retransmit on the third duplicate ACK, and recovery through partial and full ACKs.
+    #[test]
+    fn fast_recovery() -> Result<()> {
+        let cc: NewReno = NewReno::create(MSS as usize, SeqNumber::from(0));
+        cc.cwnd.set(10 * MSS);
+        let una: SeqNumber = SeqNumber::from(10 * MSS);
+        let nxt: SeqNumber = SeqNumber::from(20 * MSS);
+
+        for _ in 0..2 {
+            cc.on_ack_received(RTO, una, nxt, una);
+        }
+        crate::ensure_eq!(cc.get_retransmit_now_flag(), false);
+        cc.on_ack_received(RTO, una, nxt, una);
+        crate::ensure_eq!(cc.get_retransmit_now_flag(), true);
+        crate::ensure_eq!(cc.ssthresh.get(), 5 * MSS);
+        crate::ensure_eq!(cc.get_cwnd(), 8 * MSS);
+        cc.on_fast_retransmit();
+
+        // Further duplicates inflate the window.
+        cc.on_ack_received(RTO, una, nxt, una);
+        crate::ensure_eq!(cc.get_cwnd(), 9 * MSS);
+
+        // A partial ACK retransmits the next hole.
+        let partial: SeqNumber = una + SeqNumber::from(2 * MSS);
+        cc.on_ack_received(RTO, una, nxt, partial);
+        crate::ensure_eq!(cc.get_retransmit_now_flag(), true);
+        crate::ensure_eq!(cc.get_cwnd(), 8 * MSS);
+
+        // A full ACK deflates the window to ssthresh.
+        cc.on_ack_received(RTO, partial, nxt, nxt);
+        crate::ensure_eq!(cc.in_fast_recovery.get(), false);
+        crate::ensure_eq!(cc.get_cwnd(), 5 * MSS);
+
+        Ok(())
+    }
+
+    // Test that congestion marks halve the window, once per window of data.
+    #[test]
+    fn ecn() -> Result<()> {
+        let cc: NewReno = NewReno::create(MSS as usize, SeqNumber::from(0));
+        cc.cwnd.set(10 * MSS);
+        let nxt: SeqNumber = SeqNumber::from(10 * MSS);
+
+        cc.on_ecn_feedback(nxt, SeqNumber::from(MSS), MSS, false);
+        crate::ensure_eq!(cc.get_cwnd(), 10 * MSS);
+        cc.on_ecn_feedback(nxt, SeqNumber::from(2 * MSS), MSS, true);
+        crate::ensure_eq!(cc.get_cwnd(), 5 * MSS);
+        crate::ensure_eq!(cc.ssthresh.get(), 5 * MSS);
+
+        // Marks for data sent before the reduction are ignored.
+        cc.on_ecn_feedback(nxt, SeqNumber::from(3 * MSS), MSS, true);
+        crate::ensure_eq!(cc.get_cwnd(), 5 * MSS);
+
+        // Marks for data sent after it are not.
+        cc.on_ecn_feedback(SeqNumber::from(20 * MSS), SeqNumber::from(11 * MSS), MSS, true);
+        crate::ensure_eq!(cc.get_cwnd(), 2 * MSS + MSS / 2);
+
+        Ok(())
+    }
+
+    // Test that a retransmission timeout collapses the window.
+    #[test]
+    fn rto() -> Result<()> {
+        let cc: NewReno = NewReno::create(MSS as usize, SeqNumber::from(0));
+        cc.cwnd.set(10 * MSS);
+        cc.on_rto(SeqNumber::from(0));
+        crate::ensure_eq!(cc.get_cwnd(), MSS);
+        crate::ensure_eq!(cc.ssthresh.get(), 5 * MSS);
+
+        Ok(())
+    }
+}
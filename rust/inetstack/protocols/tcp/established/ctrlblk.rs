@@ -2,28 +2,43 @@
 // Licensed under the MIT license.
 
 use super::{
+    background,
     congestion_control::{
         self,
         CongestionControlConstructor,
     },
     rto::RtoCalculator,
+    sack::MAX_SACK_BLOCKS,
     sender::{
         Sender,
         UnackedSegment,
     },
+    timestamps::{
+        find_timestamp_option,
+        Timestamps,
+    },
 };
 use crate::{
     inetstack::{protocols::{
-        ethernet2::{
-            EtherType2,
-            Ethernet2Header,
+        ethernet2::Ethernet2Header,
+        icmpv4::{
+            Icmpv4Error,
+            Icmpv4ErrorKind,
+        },
+        ip::{
+            Ecn,
+            IpHeader,
+            IpProtocol,
         },
-        ip::IpProtocol,
-        ipv4::Ipv4Header,
+        ipv4::IPV4_HEADER_MIN_SIZE,
         tcp::{
+            constants::MIN_MSS,
             segment::{
+                SelectiveAcknowlegement,
                 TcpHeader,
+                TcpOptions2,
                 TcpSegment,
+                MIN_TCP_HEADER_SIZE,
             },
             SeqNumber,
         },
@@ -33,6 +48,11 @@ use crate::{
         memory::DemiBuffer,
         network::{
             config::TcpConfig,
+            socket::{
+                CongestionAlgorithm,
+                SocketOption,
+                SocketOptionName,
+            },
             types::MacAddress,
             NetworkRuntime, consts::RECEIVE_BATCH_SIZE,
         },
@@ -51,10 +71,19 @@ use ::std::{
         RefCell,
         RefMut,
     },
+    cmp,
     collections::VecDeque,
     convert::TryInto,
-    net::SocketAddrV4,
-    sync::Arc,
+    net::SocketAddr,
+    sync::{
+        atomic::{
+            AtomicBool,
+            AtomicU64,
+            AtomicUsize,
+            Ordering,
+        },
+        Arc,
+    },
     task::{
         Context,
         Poll,
@@ -166,13 +195,16 @@ impl Receiver {
 pub struct ControlBlock<const N: usize> {
     pub spinlock: Box<Arc<TASLock>>,
     pub qd: QDesc,
-    local: SocketAddrV4,
-    remote: SocketAddrV4,
+    // Number of requests on this connection that workers are not done with yet.  The control block must outlive them.
+    nr_requests: AtomicUsize,
+    local: SocketAddr,
+    remote: SocketAddr,
 
     pub rt: Option<Arc<dyn NetworkRuntime<RECEIVE_BATCH_SIZE>>>,
     pub scheduler: Option<Arc<Scheduler>>,
     pub clock: Option<TimerRc>,
     local_link_addr: MacAddress,
+    // Where the handshake came from, which is the router for off-link peers.  It does not go through the routing table.
     pub remote_link_addr: MacAddress,
     tcp_config: TcpConfig,
 
@@ -205,6 +237,27 @@ pub struct ControlBlock<const N: usize> {
     //
     out_of_order: RefCell<VecDeque<(SeqNumber, DemiBuffer)>>,
 
+    // Start of the most recently received out-of-order segment.  Its SACK block is reported first (RFC 2018 Section 4).
+    last_out_of_order: Cell<Option<SeqNumber>>,
+
+    // Whether both ends agreed to use Selective Acknowledgments (RFC 2018).
+    sack_permitted: bool,
+
+    // Timestamps option state, if both ends agreed to use it (RFC 7323).
+    timestamps: Cell<Option<Timestamps>>,
+
+    // Whether both ends agreed to use Explicit Congestion Notification (RFC 3168).
+    ecn: bool,
+
+    // Whether our ACKs should carry ECE, to tell our peer that its segments ran into congestion.
+    ecn_echo: Cell<bool>,
+
+    // Whether our next segment with new data should carry CWR, to tell our peer that we reacted to its ECE.
+    ecn_cwr: Cell<bool>,
+
+    // SND.NXT when we last reacted to ECE.  Further ECEs for data sent before that are about the same congestion.
+    ecn_recover: Cell<SeqNumber>,
+
     // The sequence number of the FIN, if we received it out-of-order.
     // Note: This could just be a boolean to remember if we got a FIN; the sequence number is for checking correctness.
     pub out_of_order_fin: Cell<Option<SeqNumber>>,
@@ -225,6 +278,35 @@ pub struct ControlBlock<const N: usize> {
 
     // Retransmission Timeout (RTO) calculator.
     rto_calculator: RefCell<RtoCalculator>,
+
+    // When to send the next keepalive probe, if keepalives are enabled.
+    keepalive_deadline: WatchedValue<Option<Instant>>,
+
+    // Number of keepalive probes sent since we last heard from our peer.
+    keepalive_probes_sent: Cell<usize>,
+
+    // When TIME-WAIT ends and this connection can be reclaimed.
+    time_wait_deadline: WatchedValue<Option<Instant>>,
+
+    // When to give up on delivering what is left to send after the user closed the connection, if SO_LINGER is set.
+    linger_deadline: WatchedValue<Option<Instant>>,
+
+    // Why the connection was aborted, if it was.  Reported to the user on every subsequent operation.
+    error: RefCell<Option<Fail>>,
+
+    // Last transient error that an ICMP message reported.  Reported instead of a plain timeout if the connection times
+    // out (RFC 1122 Section 4.2.3.9).
+    soft_error: RefCell<Option<Fail>>,
+
+    // When this control block was created.  Never changes, so that any core can read it.
+    created: Instant,
+
+    // Earliest deadline of the timers, in nanoseconds since `created`, or u64::MAX if none is armed.  Published for the
+    // dispatcher, which must not look at the timers themselves.
+    next_timeout: AtomicU64,
+
+    // Set by other cores to have the connection aborted the next time that a worker services its timers.
+    abort_requested: AtomicBool,
 }
 
 //==============================================================================
@@ -232,8 +314,8 @@ pub struct ControlBlock<const N: usize> {
 impl<const N: usize> ControlBlock<N> {
     pub fn new(
         qd: QDesc,
-        local: SocketAddrV4,
-        remote: SocketAddrV4,
+        local: SocketAddr,
+        remote: SocketAddr,
         local_link_addr: MacAddress,
         remote_link_addr: MacAddress,
         tcp_config: TcpConfig,
@@ -245,14 +327,30 @@ impl<const N: usize> ControlBlock<N> {
         sender_window_size: u32,
         sender_window_scale: u8,
         sender_mss: usize,
+        sack_permitted: bool,
+        timestamps: Option<Timestamps>,
+        ecn: bool,
         cc_constructor: CongestionControlConstructor,
         congestion_control_options: Option<congestion_control::Options>,
     ) -> Self {
         let sender: Sender<N> = Sender::new(sender_seq_no, sender_window_size, sender_window_scale, sender_mss);
         let spinlock: Box<Arc<TASLock>> = Box::new(Arc::new(TASLock::new()));
+        // The connection is idle from the moment it is established, so start counting right away (RFC 1122 Section
+        // 4.2.3.6).
+        let created: Instant = Instant::now();
+        let keepalive_deadline: Option<Instant> = if tcp_config.get_keepalive() {
+            Some(created + tcp_config.get_keepalive_idle())
+        } else {
+            None
+        };
+        let next_timeout: u64 = match keepalive_deadline {
+            Some(deadline) => Self::nanos_since(created, deadline),
+            None => u64::MAX,
+        };
         Self {
             spinlock,
             qd,
+            nr_requests: AtomicUsize::new(0),
             local,
             remote,
             rt: None,
@@ -270,11 +368,27 @@ impl<const N: usize> ControlBlock<N> {
             waker: RefCell::new(None),
             out_of_order: RefCell::new(VecDeque::new()),
             out_of_order_fin: Cell::new(Option::None),
+            last_out_of_order: Cell::new(None),
+            sack_permitted,
+            timestamps: Cell::new(timestamps),
+            ecn,
+            ecn_echo: Cell::new(false),
+            ecn_cwr: Cell::new(false),
+            ecn_recover: Cell::new(sender_seq_no),
             receiver: Receiver::new(receiver_seq_no, receiver_seq_no),
             user_is_done_sending: Cell::new(false),
             cc: cc_constructor(sender_mss, sender_seq_no, congestion_control_options),
             retransmit_deadline: WatchedValue::new(None),
             rto_calculator: RefCell::new(RtoCalculator::new()),
+            keepalive_deadline: WatchedValue::new(keepalive_deadline),
+            keepalive_probes_sent: Cell::new(0),
+            time_wait_deadline: WatchedValue::new(None),
+            linger_deadline: WatchedValue::new(None),
+            error: RefCell::new(None),
+            soft_error: RefCell::new(None),
+            created,
+            next_timeout: AtomicU64::new(next_timeout),
+            abort_requested: AtomicBool::new(false),
         }
     }
 
@@ -290,15 +404,92 @@ impl<const N: usize> ControlBlock<N> {
         self.scheduler = Some(scheduler);
     }
 
-    pub fn get_local(&self) -> SocketAddrV4 {
+    /// Accounts for a request on this connection that is handed over to a worker.
+    pub fn hold(&self) {
+        self.nr_requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Accounts for a request on this connection that its worker is done with.  The worker must not touch the control
+    /// block for this request anymore.
+    pub fn release(&self) {
+        let nr_requests: usize = self.nr_requests.fetch_sub(1, Ordering::Release);
+        debug_assert!(nr_requests > 0, "released more requests than were held");
+    }
+
+    /// Checks if some worker may still use this control block.
+    pub fn is_held(&self) -> bool {
+        self.nr_requests.load(Ordering::Acquire) != 0
+    }
+
+    /// Runs the handlers of the timers that expired, as told by our clock, and publishes when the next one expires.
+    /// Workers call this for every request they serve, including the ones that the dispatcher only sends for this
+    /// purpose.
+    pub fn poll_timers(&self) {
+        if self.abort_requested.swap(false, Ordering::AcqRel) {
+            self.abort(Fail::new(libc::ECONNRESET, "connection reset"));
+        }
+        if let Some(clock) = &self.clock {
+            if let Err(e) = background::poll(self, clock.now()) {
+                debug!("Connection timer: {:?}", e);
+            }
+        }
+        self.publish_timers();
+    }
+
+    /// Publishes the earliest deadline of the timers, see [Self::has_expired_timers].
+    pub fn publish_timers(&self) {
+        let next_timeout: u64 = match background::next_deadline(self) {
+            Some(deadline) => Self::nanos_since(self.created, deadline),
+            None => u64::MAX,
+        };
+        self.next_timeout.store(next_timeout, Ordering::Release);
+    }
+
+    /// Checks if a timer expired by `now`, according to what was last published.  Unlike the other functions, this
+    /// one may be called from any core.
+    pub fn has_expired_timers(&self, now: Instant) -> bool {
+        if self.abort_requested.load(Ordering::Acquire) {
+            return true;
+        }
+        let next_timeout: u64 = self.next_timeout.load(Ordering::Acquire);
+        next_timeout != u64::MAX && Self::nanos_since(self.created, now) >= next_timeout
+    }
+
+    /// Asks the worker that next services the timers to abort the connection.  Unlike [Self::abort], this one may be
+    /// called from any core.
+    pub fn request_abort(&self) {
+        self.abort_requested.store(true, Ordering::Release);
+    }
+
+    /// Returns the current time, as told by the clock of the worker that serves the connection.  Connections that no
+    /// worker has served yet have no clock, so they go by the system clock, which the clocks of workers follow.
+    pub fn now(&self) -> Instant {
+        match &self.clock {
+            Some(clock) => clock.now(),
+            None => Instant::now(),
+        }
+    }
+
+    fn nanos_since(start: Instant, when: Instant) -> u64 {
+        cmp::min(when.saturating_duration_since(start).as_nanos(), (u64::MAX - 1) as u128) as u64
+    }
+
+    pub fn get_local(&self) -> SocketAddr {
         self.local
     }
 
-    pub fn get_remote(&self) -> SocketAddrV4 {
+    pub fn get_remote(&self) -> SocketAddr {
         self.remote
     }
 
+    pub fn get_state(&self) -> State {
+        self.state.get()
+    }
+
     pub fn send(&self, buf: DemiBuffer) -> Result<(), Fail> {
+        if let Some(e) = self.error.borrow().as_ref() {
+            return Err(e.clone());
+        }
         self.sender.send(buf, self)
     }
 
@@ -326,6 +517,10 @@ impl<const N: usize> ControlBlock<N> {
         self.cc.on_cwnd_check_before_send()
     }
 
+    pub fn congestion_control_get_duplicate_ack_count(&self) -> u32 {
+        self.cc.get_duplicate_ack_count()
+    }
+
     pub fn congestion_control_get_cwnd(&self) -> u32 {
         self.cc.get_cwnd()
     }
@@ -346,6 +541,35 @@ impl<const N: usize> ControlBlock<N> {
         self.sender.get_mss()
     }
 
+    pub fn get_nodelay(&self) -> bool {
+        self.tcp_config.get_nodelay()
+    }
+
+    pub fn get_send_buffer_size(&self) -> usize {
+        self.tcp_config.get_send_buffer_size()
+    }
+
+    pub fn get_cork(&self) -> bool {
+        self.tcp_config.get_cork()
+    }
+
+    pub fn get_pacing(&self) -> bool {
+        self.tcp_config.get_pacing()
+    }
+
+    pub fn get_max_pacing_rate(&self) -> Option<u64> {
+        self.tcp_config.get_max_pacing_rate()
+    }
+
+    pub fn congestion_control_get_pacing_rate(&self) -> Option<u64> {
+        self.cc.get_pacing_rate()
+    }
+
+    /// Checks if RTT is measured through the timestamps option, instead of by timing segments.
+    pub fn has_timestamps(&self) -> bool {
+        self.timestamps.get().is_some()
+    }
+
     pub fn get_send_window(&self) -> (u32, WatchFuture<u32>) {
         self.sender.get_send_window()
     }
@@ -378,18 +602,195 @@ impl<const N: usize> ControlBlock<N> {
         self.retransmit_deadline.watch()
     }
 
+    pub fn get_keepalive_deadline(&self) -> Option<Instant> {
+        self.keepalive_deadline.get()
+    }
+
+    /// Restarts the keepalive timer, as our peer is known to be alive at `now`.
+    pub fn restart_keepalive_timer(&self, now: Instant) {
+        self.keepalive_probes_sent.set(0);
+        if self.tcp_config.get_keepalive() && self.state.get() != State::TimeWait {
+            self.keepalive_deadline
+                .set(Some(now + self.tcp_config.get_keepalive_idle()));
+        } else {
+            self.keepalive_deadline.set(None);
+        }
+    }
+
+    /// Sends the next keepalive probe, or aborts the connection if our peer did not answer the previous ones.
+    pub fn on_keepalive_timeout(&self, now: Instant) -> Result<(), Fail> {
+        if self.keepalive_probes_sent.get() >= self.tcp_config.get_keepalive_probes() {
+            warn!("No answer to {} keepalive probes, dropping connection", self.keepalive_probes_sent.get());
+            let cause: Fail = self
+                .soft_error
+                .borrow_mut()
+                .take()
+                .unwrap_or_else(|| Fail::new(libc::ETIMEDOUT, "connection timed out"));
+            self.abort(cause.clone());
+            return Err(cause);
+        }
+
+        // RFC 1122 Section 4.2.3.6: A probe is an ACK carrying an old sequence number, which our peer must answer.
+        let mut header: TcpHeader = self.tcp_header();
+        let (send_unacked, _): (SeqNumber, _) = self.get_send_unacked();
+        header.seq_num = send_unacked - SeqNumber::from(1);
+        self.emit(header, None, self.remote_link_addr);
+
+        self.keepalive_probes_sent.set(self.keepalive_probes_sent.get() + 1);
+        self.keepalive_deadline
+            .set(Some(now + self.tcp_config.get_keepalive_interval()));
+        Ok(())
+    }
+
+    /// Shuts the connection down hard.  Pending and future operations fail with `cause`.
+    pub fn abort(&self, cause: Fail) {
+        self.state.set(State::Closed);
+        self.retransmit_deadline.set(None);
+        self.ack_deadline.set(None);
+        self.keepalive_deadline.set(None);
+        self.linger_deadline.set(None);
+        *self.error.borrow_mut() = Some(cause);
+        if let Some(w) = self.waker.borrow_mut().take() {
+            w.wake()
+        }
+    }
+
+    /// Handles an ICMP error about one of our segments.  Errors that do not quote a segment that is in flight are
+    /// ignored, as anyone could forge them (RFC 5927 Section 4.1).
+    pub fn receive_icmp_error(&self, error: &Icmpv4Error) {
+        if error.get_local() != self.local || error.get_remote() != self.remote {
+            debug!("Ignoring ICMP error for another connection: {:?}", error);
+            return;
+        }
+        let transport_hdr: &[u8; 8] = error.get_transport_header();
+        let seq_num: SeqNumber =
+            SeqNumber::from(u32::from_be_bytes([transport_hdr[4], transport_hdr[5], transport_hdr[6], transport_hdr[7]]));
+        let (send_unacked, _): (SeqNumber, _) = self.get_send_unacked();
+        let (send_next, _): (SeqNumber, _) = self.get_send_next();
+        if u32::from(seq_num - send_unacked) >= u32::from(send_next - send_unacked) {
+            debug!("Ignoring ICMP error for a segment that is not in flight: {:?}", error);
+            return;
+        }
+
+        let kind: Icmpv4ErrorKind = error.get_kind();
+        match kind {
+            // Path MTU discovery (RFC 1191 Section 6.1).
+            Icmpv4ErrorKind::FragmentationNeeded { next_hop_mtu } => {
+                // Like the one our peer announced, this MSS excludes TCP options (RFC 6691 Section 3).
+                let overhead: usize = IPV4_HEADER_MIN_SIZE as usize + MIN_TCP_HEADER_SIZE;
+                let mss: usize = cmp::max((next_hop_mtu as usize).saturating_sub(overhead), MIN_MSS);
+                if mss < self.get_mss() {
+                    info!("Path MTU is now {}, lowering MSS from {} to {}", next_hop_mtu, self.get_mss(), mss);
+                    let options_size: usize = self.tcp_header().compute_size() - MIN_TCP_HEADER_SIZE;
+                    self.sender.reduce_mss(mss, options_size);
+                    // The segment that did not fit was dropped, so there is no point in waiting for a timeout.
+                    self.retransmit();
+                }
+            },
+            _ if kind.is_hard() => {
+                warn!("Connection refused by {:?}: {:?}", self.remote, kind);
+                self.abort(Fail::new(kind.get_errno(), "destination unreachable"));
+            },
+            _ => {
+                debug!("Soft ICMP error: {:?}", kind);
+                *self.soft_error.borrow_mut() = Some(Fail::new(kind.get_errno(), "destination unreachable"));
+            },
+        }
+    }
+
+    /// Enters TIME-WAIT, turning off all other timers.  Also used to restart the 2 MSL timeout while in TIME-WAIT.
+    pub fn enter_time_wait(&self, now: Instant) {
+        self.state.set(State::TimeWait);
+        self.retransmit_deadline.set(None);
+        self.keepalive_deadline.set(None);
+        self.linger_deadline.set(None);
+        self.time_wait_deadline
+            .set(Some(now + 2 * self.tcp_config.get_msl()));
+    }
+
+    pub fn watch_time_wait_deadline(&self) -> (Option<Instant>, WatchFuture<Option<Instant>>) {
+        self.time_wait_deadline.watch()
+    }
+
+    pub fn get_time_wait_deadline(&self) -> Option<Instant> {
+        self.time_wait_deadline.get()
+    }
+
+    /// Ends TIME-WAIT.  Nothing can arrive from the old incarnation of this connection anymore.
+    pub fn on_time_wait_timeout(&self) {
+        self.time_wait_deadline.set(None);
+        self.state.set(State::Closed);
+    }
+
+    pub fn get_linger_deadline(&self) -> Option<Instant> {
+        self.linger_deadline.get()
+    }
+
+    /// Resets the connection if the data that was left to send when the user closed it is still not acknowledged once
+    /// the linger timeout expires.
+    pub fn on_linger_timeout(&self) -> Result<(), Fail> {
+        self.linger_deadline.set(None);
+        if self.state.get() == State::Closed || self.state.get() == State::TimeWait {
+            return Ok(());
+        }
+        let (send_unacked, _): (SeqNumber, _) = self.get_send_unacked();
+        let (unsent_seq_no, _): (SeqNumber, _) = self.get_unsent_seq_no();
+        if send_unacked == unsent_seq_no {
+            return Ok(());
+        }
+        warn!("Linger timeout expired with {} bytes left to send", u32::from(unsent_seq_no - send_unacked));
+        let cause: Fail = Fail::new(libc::ETIMEDOUT, "linger timeout expired");
+        self.reset(cause.clone());
+        Err(cause)
+    }
+
+    /// Sends a RST to our peer and aborts the connection with `cause`.
+    fn reset(&self, cause: Fail) {
+        let mut header: TcpHeader = self.tcp_header();
+        let (send_next, _): (SeqNumber, _) = self.get_send_next();
+        header.seq_num = send_next;
+        header.rst = true;
+        self.emit(header, None, self.remote_link_addr);
+        self.abort(cause);
+    }
+
+    pub fn get_receive_next(&self) -> SeqNumber {
+        self.receiver.receive_next.get()
+    }
+
+    pub fn get_persist_deadline(&self) -> Option<Instant> {
+        self.sender.get_persist_deadline()
+    }
+
+    pub fn on_persist_timeout(&self, now: Instant) {
+        self.sender.on_persist_timeout(self, now)
+    }
+
+    pub fn get_pacing_deadline(&self) -> Option<Instant> {
+        self.sender.get_pacing_deadline()
+    }
+
+    pub fn on_pacing_timeout(&self, now: Instant) {
+        self.sender.on_pacing_timeout(self, now)
+    }
+
     pub fn push_unacked_segment(&self, segment: UnackedSegment) {
         self.sender.push_unacked_segment(segment)
     }
 
     pub fn rto_add_sample(&self, rtt: Duration) {
-        self.rto_calculator.borrow_mut().add_sample(rtt)
+        self.rto_calculator.borrow_mut().add_sample(rtt);
+        self.cc.on_rtt_sample(rtt)
     }
 
     pub fn rto(&self) -> Duration {
         self.rto_calculator.borrow().rto()
     }
 
+    pub fn srtt(&self) -> Option<Duration> {
+        self.rto_calculator.borrow().srtt()
+    }
+
     pub fn rto_back_off(&self) {
         self.rto_calculator.borrow_mut().back_off()
     }
@@ -408,7 +809,7 @@ impl<const N: usize> ControlBlock<N> {
 
     // This is the main TCP receive routine.
     //
-    pub fn receive(&self, mut header: &mut TcpHeader, mut data: DemiBuffer) {
+    pub fn receive(&self, mut header: &mut TcpHeader, mut data: DemiBuffer, ecn: Ecn) {
         debug!(
             "{:?} Connection Receiving {} bytes + {:?}",
             self.state.get(),
@@ -420,10 +821,38 @@ impl<const N: usize> ControlBlock<N> {
 
         // TODO: We're probably getting "now" here in order to get a timestamp as close as possible to when we received
         // the packet.  However, this is wasteful if we don't take a path below that actually uses it.  Review this.
-        let now: Instant = match &self.clock {
-            Some(clock) => clock.now(),
-            None => todo!(),
-        };
+        let now: Instant = self.now();
+
+        // Any segment from our peer proves that it is still alive.
+        self.restart_keepalive_timer(now);
+
+        // Check the timestamps option first, as it may reveal a segment from a previous wrap of the sequence space.
+        // See RFC 7323 Section 5.3 (PAWS).
+        let mut echo_timestamp: Option<u32> = None;
+        if let Some(mut timestamps) = self.timestamps.get() {
+            match find_timestamp_option(header) {
+                Some((tsval, tsecr)) => {
+                    if !header.rst && timestamps.is_old(tsval, now) {
+                        // Old duplicate.  ACK and drop.
+                        debug!("PAWS: dropping segment with old timestamp {}", tsval);
+                        self.send_ack();
+                        return;
+                    }
+                    // Only remember timestamps of segments that start at or before what we acknowledge (Last.ACK.sent).
+                    if header.seq_num <= self.receiver.receive_next.get() {
+                        timestamps.update_recent(tsval, now);
+                        self.timestamps.set(Some(timestamps));
+                    }
+                    echo_timestamp = Some(tsecr);
+                },
+                None if !header.rst => {
+                    // RFC 7323 Section 3.2: Silently drop non-RST segments without timestamps once negotiated.
+                    debug!("Dropping segment without timestamps");
+                    return;
+                },
+                None => (),
+            }
+        }
 
         // Check to see if the segment is acceptable sequence-wise (i.e. contains some data that fits within the receive
         // window, or is a non-data segment with a sequence number that falls within the window).  Unacceptable segments
@@ -560,7 +989,7 @@ impl<const N: usize> ControlBlock<N> {
                 },
 
                 // Closing states.
-                State::Closing | State::LastAck | State::TimeWait => {
+                State::Closing | State::LastAck => {
                     // Enter Closed state.
                     self.state.set(State::Closed);
 
@@ -568,6 +997,9 @@ impl<const N: usize> ControlBlock<N> {
                     return;
                 },
 
+                // RFC 1337: Ignore RSTs in TIME-WAIT, as they would otherwise cut the 2 MSL wait short.
+                State::TimeWait => return,
+
                 // Should never happen.
                 state => panic!("Bad TCP state {:?}", state),
             }
@@ -601,6 +1033,11 @@ impl<const N: usize> ControlBlock<N> {
             return;
         }
 
+        // Remember whether our peer's segments run into congestion, so our ACKs can tell it.
+        if self.ecn {
+            self.receive_ecn(header, ecn);
+        }
+
         // TODO: RFC 5961 "Blind Data Injection Attack" prevention would have us perform additional ACK validation
         // checks here.
 
@@ -628,6 +1065,20 @@ impl<const N: usize> ControlBlock<N> {
             if header.ack_num <= send_next {
                 // This segment acknowledges new data (possibly and/or FIN).
                 let bytes_acknowledged: u32 = (header.ack_num - send_unacknowledged).into();
+                let ece: bool = self.ecn && header.ece;
+                self.cc
+                    .on_ecn_feedback(send_next, header.ack_num, bytes_acknowledged, ece);
+
+                // Tell our peer we reacted to the congestion it saw, once per window of data (RFC 3168 Section 6.1.2).
+                if ece && header.ack_num > self.ecn_recover.get() {
+                    self.ecn_cwr.set(true);
+                    self.ecn_recover.set(send_next);
+                }
+
+                // Take an RTT sample from the echoed timestamp (RFC 7323 Section 4).
+                if let (Some(timestamps), Some(tsecr)) = (self.timestamps.get(), echo_timestamp) {
+                    self.rto_add_sample(timestamps.rtt(tsecr, now));
+                }
 
                 // Remove the now acknowledged data from the unacknowledged queue.
                 self.sender.remove_acknowledged_data(self, bytes_acknowledged, now);
@@ -653,7 +1104,7 @@ impl<const N: usize> ControlBlock<N> {
                         },
                         State::Closing => {
                             // Our FIN is now ACK'd, so enter TIME-WAIT.
-                            self.state.set(State::TimeWait);
+                            self.enter_time_wait(now);
                         },
                         State::LastAck => {
                             // Our FIN is now ACK'd, so this connection can be safely closed.  In LAST-ACK state we
@@ -662,7 +1113,6 @@ impl<const N: usize> ControlBlock<N> {
                             // but we had already sent everything we're ever going to send (incl. FIN) at least once).
                             self.state.set(State::Closed);
                         },
-                        // TODO: Handle TimeWait to Closed transition.
                         _ => (),
                     }
                 } else {
@@ -682,8 +1132,21 @@ impl<const N: usize> ControlBlock<N> {
         } else {
             // Duplicate ACK (doesn't acknowledge anything new).  We can mostly ignore this, except for fast-retransmit.
             // TODO: Implement fast-retransmit.  In which case, we'd increment our dup-ack counter here.
+
+            // This may still be a window update, such as the answer to a window probe.
+            if header.ack_num == send_unacknowledged {
+                self.sender.update_send_window(header);
+            }
+        }
+
+        // Use the SACK blocks carried by this segment (if any) to drive loss recovery.
+        if self.sack_permitted {
+            self.sender.on_sack(self, header);
         }
 
+        // Send the data this ACK made room for, or keep probing if our peer's window is still closed.
+        self.sender.send_unsent(self, now);
+
         // TODO: Check the URG bit.  If we decide to support this, how should we do it?
         if header.urg {
             warn!("Got packet with URG bit set!");
@@ -751,12 +1214,12 @@ impl<const N: usize> ControlBlock<N> {
                 },
                 State::FinWait2 => {
                     // Enter TIME-WAIT.
-                    self.state.set(State::TimeWait);
-                    // TODO: Start the time-wait timer and turn off the other timers.
+                    self.enter_time_wait(now);
                 },
                 State::CloseWait | State::Closing | State::LastAck => (), // Remain in current state.
                 State::TimeWait => {
-                    // TODO: Remain in TIME-WAIT.  Restart the 2 MSL time-wait timeout.
+                    // Our peer retransmitted its FIN, so our ACK got lost.  Restart the 2 MSL time-wait timeout.
+                    self.enter_time_wait(now);
                 },
                 state => panic!("Bad TCP state {:?}", state), // Should never happen.
             }
@@ -790,6 +1253,31 @@ impl<const N: usize> ControlBlock<N> {
         }
     }
 
+    // Updates what our ACKs echo, given the ECN codepoint of a segment from our peer.
+    fn receive_ecn(&self, header: &TcpHeader, ecn: Ecn) {
+        let congestion_experienced: bool = ecn == Ecn::Ce;
+        if self.tcp_config.get_congestion_control() == CongestionAlgorithm::Dctcp {
+            // DCTCP senders estimate the share of marked segments, so we echo the mark of every segment as is.  This
+            // assumes our peer runs DCTCP too, which is the only sane setup for it.  ACKs we are holding back are for
+            // segments with the previous mark, so they go out before it changes (RFC 8257 Section 3.2).
+            if congestion_experienced != self.ecn_echo.get() {
+                if self.ack_deadline.get().is_some() {
+                    self.send_ack();
+                }
+                self.ecn_echo.set(congestion_experienced);
+            }
+        } else {
+            // Keep echoing until our peer tells us it reduced its window, unless this segment ran into congestion again
+            // (RFC 3168 Section 6.1.3).
+            if header.cwr {
+                self.ecn_echo.set(false);
+            }
+            if congestion_experienced {
+                self.ecn_echo.set(true);
+            }
+        }
+    }
+
     /// Handle the user's close request.
     ///
     /// In TCP parlance, a user's close request means "I have no more data to send".  The user may continue to receive
@@ -809,6 +1297,14 @@ impl<const N: usize> ControlBlock<N> {
         // only change state to FIN-WAIT-1 or LAST_ACK after we've actually been able to send the FIN.
         debug_assert!((self.state.get() == State::Established) || (self.state.get() == State::CloseWait));
 
+        // A zero linger timeout asks for an abortive close: drop whatever is left to send and reset the connection.
+        let linger: Option<Duration> = self.tcp_config.get_linger();
+        if linger == Some(Duration::ZERO) {
+            self.user_is_done_sending.set(true);
+            self.reset(Fail::new(libc::ECONNRESET, "connection reset on close"));
+            return Ok(());
+        }
+
         // Send a FIN.
         let fin_buf: DemiBuffer = DemiBuffer::new(0);
         self.send(fin_buf).expect("send failed");
@@ -818,6 +1314,12 @@ impl<const N: usize> ControlBlock<N> {
         // Remember that the user has called close.
         self.user_is_done_sending.set(true);
 
+        // Bound how long we keep trying to deliver the rest of our data.
+        if let (Some(timeout), Some(clock)) = (linger, &self.clock) {
+            self.linger_deadline.set(Some(clock.now() + timeout));
+        }
+        self.publish_timers();
+
         Ok(())
     }
 
@@ -843,10 +1345,67 @@ impl<const N: usize> ControlBlock<N> {
         header.ack = true;
         header.ack_num = self.receiver.receive_next.get();
 
+        // Every segment carries our clock and echoes our peer's (RFC 7323 Section 3.2).
+        if let Some(timestamps) = self.timestamps.get() {
+            let now: Instant = self.now();
+            header.push_option(TcpOptions2::Timestamp {
+                sender_timestamp: timestamps.now(now),
+                echo_timestamp: timestamps.get_recent(),
+            });
+        }
+
+        // Echo congestion marks until our peer reacts to them.
+        header.ece = self.ecn_echo.get();
+
+        // Report what we hold out-of-order.
+        if self.sack_permitted {
+            if let Some(option) = self.sack_option() {
+                header.push_option(option);
+            }
+        }
+
         // Return this header.
         header
     }
 
+    // Builds the SACK option describing the out-of-order store (RFC 2018 Section 4).  Contiguous segments are reported
+    // as a single block, and the block holding the most recently received segment comes first.
+    fn sack_option(&self) -> Option<TcpOptions2> {
+        let out_of_order = self.out_of_order.borrow();
+        if out_of_order.is_empty() {
+            return None;
+        }
+
+        let mut blocks: Vec<SelectiveAcknowlegement> = Vec::with_capacity(out_of_order.len());
+        let mut first: usize = 0;
+        for (start, buf) in out_of_order.iter() {
+            let end: SeqNumber = *start + SeqNumber::from(buf.len() as u32);
+            match blocks.last_mut() {
+                Some(block) if block.end == *start => block.end = end,
+                _ => blocks.push(SelectiveAcknowlegement { begin: *start, end }),
+            }
+            if self.last_out_of_order.get() == Some(*start) {
+                first = blocks.len() - 1;
+            }
+        }
+        let block: SelectiveAcknowlegement = blocks.remove(first);
+        blocks.insert(0, block);
+
+        // There is only room for three blocks alongside the timestamps option (RFC 2018 Section 3).
+        let max_sacks: usize = if self.has_timestamps() {
+            MAX_SACK_BLOCKS - 1
+        } else {
+            MAX_SACK_BLOCKS
+        };
+        let num_sacks: usize = blocks.len().min(max_sacks);
+        let mut sacks: [SelectiveAcknowlegement; MAX_SACK_BLOCKS] = [SelectiveAcknowlegement {
+            begin: SeqNumber::from(0),
+            end: SeqNumber::from(0),
+        }; MAX_SACK_BLOCKS];
+        sacks[..num_sacks].copy_from_slice(&blocks[..num_sacks]);
+        Some(TcpOptions2::SelectiveAcknowlegement { num_sacks, sacks })
+    }
+
     /// Send an ACK to our peer, reflecting our current state.
     pub fn send_ack(&self) {
         let mut header: TcpHeader = self.tcp_header();
@@ -860,7 +1419,7 @@ impl<const N: usize> ControlBlock<N> {
 
     /// Transmit this message to our connected peer.
     ///
-    pub fn emit(&self, header: TcpHeader, body: Option<DemiBuffer>, remote_link_addr: MacAddress) {
+    pub fn emit(&self, mut header: TcpHeader, body: Option<DemiBuffer>, remote_link_addr: MacAddress) {
         // Only perform this debug print in debug builds.  debug_assertions is compiler set in non-optimized builds.
         #[cfg(debug_assertions)]
         if body.is_some() {
@@ -874,11 +1433,21 @@ impl<const N: usize> ControlBlock<N> {
 
         let sent_fin: bool = header.fin;
 
+        // Only segments carrying new data are ECN-capable.  Pure ACKs and retransmissions are not, as we could not
+        // react to a mark on them (RFC 3168 Sections 6.1.4 and 6.1.5).
+        let mut ip_hdr: IpHeader = IpHeader::new(self.local.ip(), self.remote.ip(), IpProtocol::TCP);
+        if self.ecn && body.as_ref().map_or(false, |buf| buf.len() > 0) && header.seq_num == self.get_send_next().0 {
+            ip_hdr.set_ecn(Ecn::Ect0);
+            if self.ecn_cwr.replace(false) {
+                header.cwr = true;
+            }
+        }
+
         // Prepare description of TCP segment to send.
         // TODO: Change this to call lower levels to fill in their header information, handle routing, ARPing, etc.
         let segment = TcpSegment {
-            ethernet2_hdr: Ethernet2Header::new(remote_link_addr, self.local_link_addr, EtherType2::Ipv4),
-            ipv4_hdr: Ipv4Header::new(self.local.ip().clone(), self.remote.ip().clone(), IpProtocol::TCP),
+            ethernet2_hdr: Ethernet2Header::new(remote_link_addr, self.local_link_addr, ip_hdr.get_ether_type()),
+            ip_hdr,
             tcp_hdr: header,
             data: body,
             tx_checksum_offload: self.tcp_config.get_tx_checksum_offload(),
@@ -924,6 +1493,56 @@ impl<const N: usize> ControlBlock<N> {
         self.ack_deadline.set(when);
     }
 
+    /// Gets the value of a socket option on this connection.
+    pub fn get_option(&self, name: SocketOptionName) -> SocketOption {
+        match name {
+            SocketOptionName::ReceiveBuffer => SocketOption::ReceiveBuffer(self.receive_buffer_size as usize),
+            SocketOptionName::MaxSegment => SocketOption::MaxSegment(self.remote_mss()),
+            name => self.tcp_config.get_option(name),
+        }
+    }
+
+    /// Sets a socket option on this connection.
+    pub fn set_option(&mut self, option: SocketOption) -> Result<(), Fail> {
+        match option {
+            // The window scale was agreed on during the handshake, and we never shrink the window (RFC 793).
+            SocketOption::ReceiveBuffer(size) => {
+                let max_size: u32 = 0xffff << self.window_scale;
+                let size: u32 = size.min(max_size as usize) as u32;
+                self.receive_buffer_size = self.receive_buffer_size.max(size);
+                Ok(())
+            },
+            SocketOption::MaxSegment(_) => Err(Fail::new(
+                libc::EISCONN,
+                "cannot change the maximum segment size of a connected socket",
+            )),
+            SocketOption::Congestion(algorithm) => {
+                self.tcp_config = self.tcp_config.clone().set_option(option)?;
+                // The new algorithm starts from scratch, as the state of the old one means nothing to it.
+                let (send_unacked, _): (SeqNumber, _) = self.sender.get_send_unacked();
+                self.cc = congestion_control::constructor(algorithm)(self.get_mss(), send_unacked, None);
+                Ok(())
+            },
+            option => {
+                let name: SocketOptionName = option.name();
+                self.tcp_config = self.tcp_config.clone().set_option(option)?;
+                if let Some(clock) = &self.clock {
+                    let now: Instant = clock.now();
+                    match name {
+                        // Apply new keepalive settings right away, rather than after the next segment.
+                        SocketOptionName::KeepAlive | SocketOptionName::KeepIdle => self.restart_keepalive_timer(now),
+                        // Send what Nagle's algorithm or corking may have held back.
+                        SocketOptionName::NoDelay | SocketOptionName::Cork => self.sender.send_unsent(self, now),
+                        // A new rate applies from the next segment on.  Send what the old one held back, if it is due.
+                        SocketOptionName::MaxPacingRate => self.sender.send_unsent(self, now),
+                        _ => (),
+                    }
+                }
+                Ok(())
+            },
+        }
+    }
+
     pub fn get_receive_window_size(&self) -> u32 {
         let bytes_unread: u32 = (self.receiver.receive_next.get() - self.receiver.reader_next.get()).into();
         self.receive_buffer_size - bytes_unread
@@ -952,6 +1571,10 @@ impl<const N: usize> ControlBlock<N> {
         // But that will think data is available to be read once we've received a FIN, because FINs consume sequence
         // number space.  Now we call is_empty() on the receive queue instead.
         if self.receiver.recv_queue.borrow().is_empty() {
+            // Report why the connection went away, if it did.
+            if let Some(e) = self.error.borrow().as_ref() {
+                return Poll::Ready(Err(e.clone()));
+            }
             *self.waker.borrow_mut() = Some(ctx.waker().clone());
             return Poll::Pending;
         }
@@ -1063,6 +1686,7 @@ impl<const N: usize> ControlBlock<N> {
 
         // Insert the new segment into the correct position.
         out_of_order.insert(action_index, (new_start, buf));
+        self.last_out_of_order.set(Some(new_start));
 
         // If the out-of-order store now contains too many entries, delete the later entries.
         // TODO: The out-of-order store is already limited (in size) by our receive window, while the below check
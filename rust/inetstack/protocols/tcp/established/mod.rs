@@ -4,29 +4,49 @@
 mod background;
 pub mod congestion_control;
 mod ctrlblk;
+mod pacer;
+mod persist;
 mod rto;
+mod sack;
 mod sender;
+mod timestamps;
 
-pub use self::ctrlblk::{
-    ControlBlock,
-    State,
+pub use self::{
+    ctrlblk::{
+        ControlBlock,
+        State,
+    },
+    timestamps::Timestamps,
 };
 
 use crate::{
-    inetstack::protocols::tcp::segment::TcpHeader,
+    inetstack::protocols::{
+        ip::Ecn,
+        tcp::{
+            segment::TcpHeader,
+            SeqNumber,
+        },
+    },
     runtime::{
         fail::Fail,
         memory::DemiBuffer,
+        network::socket::{
+            SocketOption,
+            SocketOptionName,
+        },
         QDesc,
     },
 };
 use ::std::{
-    net::SocketAddrV4,
+    net::SocketAddr,
     task::{
         Context,
         Poll,
     },
-    time::Duration,
+    time::{
+        Duration,
+        Instant,
+    },
 };
 
 #[derive(Clone)]
@@ -41,8 +61,8 @@ impl<const N: usize> EstablishedSocket<N> {
         Self { cb, }
     }
 
-    pub fn receive(&self, header: &mut TcpHeader, data: DemiBuffer) {
-        unsafe { (*self.cb).receive(header, data) }
+    pub fn receive(&self, header: &mut TcpHeader, data: DemiBuffer, ecn: Ecn) {
+        unsafe { (*self.cb).receive(header, data, ecn) }
     }
 
     pub fn send(&self, buf: DemiBuffer) -> Result<(), Fail> {
@@ -69,7 +89,28 @@ impl<const N: usize> EstablishedSocket<N> {
         unsafe { (*self.cb).rto() }
     }
 
-    pub fn endpoints(&self) -> (SocketAddrV4, SocketAddrV4) {
+    pub fn endpoints(&self) -> (SocketAddr, SocketAddr) {
         unsafe { ((*self.cb).get_local(), (*self.cb).get_remote()) }
     }
+
+    pub fn state(&self) -> State {
+        unsafe { (*self.cb).get_state() }
+    }
+
+    /// Returns (SND.NXT, RCV.NXT), which is all we need to remember about a connection in TIME-WAIT.
+    pub fn sequence_numbers(&self) -> (SeqNumber, SeqNumber) {
+        unsafe { ((*self.cb).get_send_next().0, (*self.cb).get_receive_next()) }
+    }
+
+    pub fn time_wait_deadline(&self) -> Option<Instant> {
+        unsafe { (*self.cb).get_time_wait_deadline() }
+    }
+
+    pub fn get_option(&self, name: SocketOptionName) -> SocketOption {
+        unsafe { (*self.cb).get_option(name) }
+    }
+
+    pub fn set_option(&self, option: SocketOption) -> Result<(), Fail> {
+        unsafe { (*self.cb).set_option(option) }
+    }
 }
@@ -0,0 +1,99 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+// TCP pacing.
+// Spreads the segments of a window over the round trip, instead of sending them back-to-back as ACKs make room.
+
+use ::std::{
+    cmp,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+/// Pacing rate relative to cwnd / SRTT, in percent.  Pacing slightly faster than one window per round trip keeps the
+/// pacer from being what limits the window from growing.  Linux uses the same ratio in congestion avoidance.
+const PACING_GAIN_PERCENT: u64 = 120;
+
+#[derive(Debug)]
+pub struct Pacer {
+    // Earliest time at which the next segment may leave.
+    next_send_time: Option<Instant>,
+}
+
+impl Pacer {
+    pub fn new() -> Self {
+        Self { next_send_time: None }
+    }
+
+    /// Returns the rate at which a window of `cwnd` bytes should be paced, in bytes per second.
+    pub fn window_rate(cwnd: u32, srtt: Duration) -> u64 {
+        let srtt_us: u64 = cmp::max(srtt.as_micros() as u64, 1);
+        cmp::max(cwnd as u64 * PACING_GAIN_PERCENT * 10_000 / srtt_us, 1)
+    }
+
+    /// Returns when the next segment may leave, or `None` if it may leave right away.
+    pub fn release_time(&self, now: Instant) -> Option<Instant> {
+        self.next_send_time.filter(|t| *t > now)
+    }
+
+    /// Accounts for a segment of `size` bytes sent at `now`, at `rate` bytes per second.  Time spent idle does not
+    /// build up credit, so the segments that follow never go out in a burst.
+    pub fn on_send(&mut self, now: Instant, size: usize, rate: u64) {
+        let gap: Duration = Duration::from_nanos((size as u128 * 1_000_000_000 / cmp::max(rate, 1) as u128) as u64);
+        let start: Instant = self.next_send_time.map_or(now, |t| cmp::max(t, now));
+        self.next_send_time = Some(start + gap);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Pacer;
+    use ::anyhow::Result;
+    use ::std::time::{
+        Duration,
+        Instant,
+    };
+
+    // Test that segments are spaced by their size over the rate.
+    #[test]
+    fn spacing() -> Result<()> {
+        let now: Instant = Instant::now();
+        let mut pacer: Pacer = Pacer::new();
+        crate::ensure_eq!(pacer.release_time(now), None);
+
+        // 1000 bytes at 1 MB/s take a millisecond.
+        pacer.on_send(now, 1000, 1_000_000);
+        crate::ensure_eq!(pacer.release_time(now), Some(now + Duration::from_millis(1)));
+        pacer.on_send(now, 1000, 1_000_000);
+        crate::ensure_eq!(pacer.release_time(now), Some(now + Duration::from_millis(2)));
+        crate::ensure_eq!(pacer.release_time(now + Duration::from_millis(2)), None);
+
+        Ok(())
+    }
+
+    // Test that idle time does not let a burst through.
+    #[test]
+    fn no_credit() -> Result<()> {
+        let now: Instant = Instant::now();
+        let mut pacer: Pacer = Pacer::new();
+
+        pacer.on_send(now, 1000, 1_000_000);
+        let later: Instant = now + Duration::from_secs(1);
+        crate::ensure_eq!(pacer.release_time(later), None);
+        pacer.on_send(later, 1000, 1_000_000);
+        crate::ensure_eq!(pacer.release_time(later), Some(later + Duration::from_millis(1)));
+
+        Ok(())
+    }
+
+    // Test that a window is paced over a bit less than a round trip.
+    #[test]
+    fn window_rate() -> Result<()> {
+        crate::ensure_eq!(Pacer::window_rate(100_000, Duration::from_millis(10)), 12_000_000);
+        crate::ensure_eq!(Pacer::window_rate(0, Duration::from_millis(10)), 1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,80 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+// TCP persist timer.
+// See RFC 9293 Section 3.8.6.1 for details on probing zero windows.
+
+use ::std::time::Duration;
+
+/// Upper bound on the time between two window probes.  Probing never stops while the window stays closed.
+const MAX_PERSIST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Largest backoff exponent we keep track of.  Anything above hits `MAX_PERSIST_TIMEOUT` for any sane RTO.
+const MAX_BACKOFF: u32 = 16;
+
+#[derive(Debug)]
+pub struct PersistTimer {
+    // Number of window probes sent since the window closed.
+    backoff: u32,
+}
+
+impl PersistTimer {
+    pub fn new() -> Self {
+        Self { backoff: 0 }
+    }
+
+    /// Returns how long to wait before the next window probe, and backs off exponentially for the one after.
+    pub fn next_timeout(&mut self, rto: Duration) -> Duration {
+        let timeout: Duration = rto
+            .checked_mul(1 << self.backoff)
+            .map_or(MAX_PERSIST_TIMEOUT, |timeout| timeout.min(MAX_PERSIST_TIMEOUT));
+        self.backoff = (self.backoff + 1).min(MAX_BACKOFF);
+        timeout
+    }
+
+    /// Starts over from the RTO, once our peer opens its window.
+    pub fn reset(&mut self) {
+        self.backoff = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        PersistTimer,
+        MAX_PERSIST_TIMEOUT,
+    };
+    use ::anyhow::Result;
+    use ::std::time::Duration;
+
+    // Test that probes back off exponentially, up to a bound.
+    #[test]
+    fn backoff() -> Result<()> {
+        let rto: Duration = Duration::from_millis(200);
+        let mut timer: PersistTimer = PersistTimer::new();
+
+        crate::ensure_eq!(timer.next_timeout(rto), Duration::from_millis(200));
+        crate::ensure_eq!(timer.next_timeout(rto), Duration::from_millis(400));
+        crate::ensure_eq!(timer.next_timeout(rto), Duration::from_millis(800));
+        for _ in 0..64 {
+            timer.next_timeout(rto);
+        }
+        crate::ensure_eq!(timer.next_timeout(rto), MAX_PERSIST_TIMEOUT);
+
+        Ok(())
+    }
+
+    // Test that an open window restarts the backoff.
+    #[test]
+    fn reset() -> Result<()> {
+        let rto: Duration = Duration::from_secs(1);
+        let mut timer: PersistTimer = PersistTimer::new();
+
+        timer.next_timeout(rto);
+        timer.next_timeout(rto);
+        timer.reset();
+        crate::ensure_eq!(timer.next_timeout(rto), rto);
+
+        Ok(())
+    }
+}
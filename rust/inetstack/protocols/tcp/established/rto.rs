@@ -87,4 +87,13 @@ impl RtoCalculator {
     pub fn rto(&self) -> Duration {
         Duration::from_secs_f64(self.rto)
     }
+
+    /// Gets the smoothed RTT, if we have measured the RTT at all.
+    pub fn srtt(&self) -> Option<Duration> {
+        if self.received_sample {
+            Some(Duration::from_secs_f64(self.srtt))
+        } else {
+            None
+        }
+    }
 }
@@ -0,0 +1,224 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+// TCP Selective Acknowledgment (SACK) scoreboard.
+// See RFC 2018 for the option itself, and RFC 6675 for SACK-based loss recovery.
+
+use crate::inetstack::protocols::tcp::{
+    segment::SelectiveAcknowlegement,
+    SeqNumber,
+};
+
+/// Maximum number of SACK blocks that fit in a TCP header (RFC 2018 Section 3).
+pub const MAX_SACK_BLOCKS: usize = 4;
+
+/// Number of SACKed segments above a hole that mark it as lost (RFC 6675 Section 2).
+const DUP_THRESH: usize = 3;
+
+#[derive(Debug)]
+pub struct Scoreboard {
+    // SACKed ranges of sequence numbers, as [begin, end).  Sorted and not overlapping nor adjacent.
+    blocks: Vec<(SeqNumber, SeqNumber)>,
+
+    // Highest sequence number retransmitted during the current recovery.  In RFC 6675 terms, this is HighRxt.
+    high_rxt: SeqNumber,
+
+    // Value of SND.NXT when the current recovery started, if any.  In RFC 6675 terms, this is RecoveryPoint.
+    recovery_point: Option<SeqNumber>,
+}
+
+impl Scoreboard {
+    pub fn new(seq_no: SeqNumber) -> Self {
+        Self {
+            blocks: Vec::new(),
+            high_rxt: seq_no,
+            recovery_point: None,
+        }
+    }
+
+    /// Records the SACK blocks carried by an ACK.  Blocks that do not fall between SND.UNA and SND.NXT are ignored.
+    /// Returns true if any data was SACKed for the first time.
+    pub fn update(&mut self, send_unacked: SeqNumber, send_next: SeqNumber, sacks: &[SelectiveAcknowlegement]) -> bool {
+        let mut updated: bool = false;
+        for sack in sacks {
+            if sack.begin >= sack.end || sack.begin < send_unacked || sack.end > send_next {
+                warn!("Ignoring bogus SACK block [{}, {})", sack.begin, sack.end);
+                continue;
+            }
+            if !self.is_sacked(sack.begin, sack.end) {
+                self.insert(sack.begin, sack.end);
+                updated = true;
+            }
+        }
+        updated
+    }
+
+    /// Forgets about data below the cumulative acknowledgment, and ends the recovery if it is complete.
+    pub fn advance(&mut self, send_unacked: SeqNumber) {
+        self.blocks.retain(|&(_, end)| end > send_unacked);
+        if let Some(first) = self.blocks.first_mut() {
+            if first.0 < send_unacked {
+                first.0 = send_unacked;
+            }
+        }
+        if self.high_rxt < send_unacked {
+            self.high_rxt = send_unacked;
+        }
+        if let Some(recovery_point) = self.recovery_point {
+            if send_unacked >= recovery_point {
+                self.recovery_point = None;
+            }
+        }
+    }
+
+    /// Forgets everything.  Called on retransmission timeouts, since the receiver may have reneged (RFC 2018 Section 8).
+    pub fn clear(&mut self, send_unacked: SeqNumber) {
+        self.blocks.clear();
+        self.high_rxt = send_unacked;
+        self.recovery_point = None;
+    }
+
+    /// Checks if the range [begin, end) was entirely SACKed.
+    pub fn is_sacked(&self, begin: SeqNumber, end: SeqNumber) -> bool {
+        self.blocks.iter().any(|&(b, e)| b <= begin && end <= e)
+    }
+
+    /// Checks if the byte at `seq_no` is deemed lost (IsLost() in RFC 6675 Section 4).
+    pub fn is_lost(&self, seq_no: SeqNumber, mss: usize) -> bool {
+        let mut nr_blocks: usize = 0;
+        let mut sacked_bytes: usize = 0;
+        for &(begin, end) in self.blocks.iter().filter(|&&(_, end)| end > seq_no) {
+            let begin: SeqNumber = if begin > seq_no { begin } else { seq_no + SeqNumber::from(1) };
+            nr_blocks += 1;
+            sacked_bytes += u32::from(end - begin) as usize;
+        }
+        nr_blocks >= DUP_THRESH || sacked_bytes > (DUP_THRESH - 1) * mss
+    }
+
+    /// Checks if anything was SACKed.
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    pub fn in_recovery(&self) -> bool {
+        self.recovery_point.is_some()
+    }
+
+    /// Starts a loss recovery that ends once everything below `send_next` is acknowledged.
+    pub fn enter_recovery(&mut self, send_unacked: SeqNumber, send_next: SeqNumber) {
+        self.recovery_point = Some(send_next);
+        self.high_rxt = send_unacked;
+    }
+
+    pub fn get_high_rxt(&self) -> SeqNumber {
+        self.high_rxt
+    }
+
+    pub fn set_high_rxt(&mut self, seq_no: SeqNumber) {
+        if seq_no > self.high_rxt {
+            self.high_rxt = seq_no;
+        }
+    }
+
+    // Inserts [begin, end), merging it with the blocks it overlaps or touches.
+    fn insert(&mut self, mut begin: SeqNumber, mut end: SeqNumber) {
+        let mut index: usize = 0;
+        while index < self.blocks.len() {
+            let (b, e) = self.blocks[index];
+            if e < begin {
+                index += 1;
+            } else if end < b {
+                break;
+            } else {
+                if b < begin {
+                    begin = b;
+                }
+                if e > end {
+                    end = e;
+                }
+                self.blocks.remove(index);
+            }
+        }
+        self.blocks.insert(index, (begin, end));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Scoreboard;
+    use crate::inetstack::protocols::tcp::{
+        segment::SelectiveAcknowlegement,
+        SeqNumber,
+    };
+    use ::anyhow::Result;
+
+    const MSS: usize = 100;
+
+    fn sack(begin: u32, end: u32) -> SelectiveAcknowlegement {
+        SelectiveAcknowlegement {
+            begin: SeqNumber::from(begin),
+            end: SeqNumber::from(end),
+        }
+    }
+
+    // Test that SACK blocks are merged, and that bogus ones are ignored.
+    #[test]
+    fn update() -> Result<()> {
+        let mut scoreboard: Scoreboard = Scoreboard::new(SeqNumber::from(0));
+        let (una, nxt): (SeqNumber, SeqNumber) = (SeqNumber::from(0), SeqNumber::from(1000));
+
+        crate::ensure_eq!(scoreboard.update(una, nxt, &[sack(200, 300), sack(500, 600)]), true);
+        crate::ensure_eq!(scoreboard.update(una, nxt, &[sack(200, 300)]), false);
+        crate::ensure_eq!(scoreboard.update(una, nxt, &[sack(900, 1100), sack(300, 200)]), false);
+        crate::ensure_eq!(scoreboard.update(una, nxt, &[sack(300, 500)]), true);
+        crate::ensure_eq!(scoreboard.is_sacked(SeqNumber::from(200), SeqNumber::from(600)), true);
+        crate::ensure_eq!(scoreboard.is_sacked(SeqNumber::from(100), SeqNumber::from(300)), false);
+
+        // A cumulative ACK in the middle of a block trims it.
+        scoreboard.advance(SeqNumber::from(250));
+        crate::ensure_eq!(scoreboard.is_sacked(SeqNumber::from(250), SeqNumber::from(600)), true);
+        scoreboard.advance(SeqNumber::from(600));
+        crate::ensure_eq!(scoreboard.is_empty(), true);
+
+        Ok(())
+    }
+
+    // Test that a hole is deemed lost once enough data above it is SACKed.
+    #[test]
+    fn is_lost() -> Result<()> {
+        let mut scoreboard: Scoreboard = Scoreboard::new(SeqNumber::from(0));
+        let (una, nxt): (SeqNumber, SeqNumber) = (SeqNumber::from(0), SeqNumber::from(1000));
+
+        scoreboard.update(una, nxt, &[sack(100, 200), sack(300, 400)]);
+        crate::ensure_eq!(scoreboard.is_lost(SeqNumber::from(0), MSS), false);
+
+        // Three discontiguous blocks.
+        scoreboard.update(una, nxt, &[sack(500, 600)]);
+        crate::ensure_eq!(scoreboard.is_lost(SeqNumber::from(0), MSS), true);
+        crate::ensure_eq!(scoreboard.is_lost(SeqNumber::from(200), MSS), false);
+
+        // More than (DupThresh - 1) * MSS bytes.
+        scoreboard.update(una, nxt, &[sack(600, 701)]);
+        crate::ensure_eq!(scoreboard.is_lost(SeqNumber::from(200), MSS), true);
+
+        Ok(())
+    }
+
+    // Test that recovery ends once the recovery point is acknowledged.
+    #[test]
+    fn recovery() -> Result<()> {
+        let mut scoreboard: Scoreboard = Scoreboard::new(SeqNumber::from(0));
+        scoreboard.enter_recovery(SeqNumber::from(0), SeqNumber::from(1000));
+        crate::ensure_eq!(scoreboard.in_recovery(), true);
+
+        scoreboard.set_high_rxt(SeqNumber::from(100));
+        scoreboard.advance(SeqNumber::from(500));
+        crate::ensure_eq!(scoreboard.get_high_rxt(), SeqNumber::from(500));
+        crate::ensure_eq!(scoreboard.in_recovery(), true);
+
+        scoreboard.advance(SeqNumber::from(1000));
+        crate::ensure_eq!(scoreboard.in_recovery(), false);
+
+        Ok(())
+    }
+}
@@ -1,10 +1,19 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT license.
 
-use super::ControlBlock;
+use super::{
+    pacer::Pacer,
+    persist::PersistTimer,
+    sack::Scoreboard,
+    ControlBlock,
+};
 use crate::{
     inetstack::protocols::tcp::{
-        segment::TcpHeader,
+        segment::{
+            TcpHeader,
+            TcpOptions2,
+            MIN_TCP_HEADER_SIZE,
+        },
         SeqNumber,
     },
     runtime::{
@@ -17,6 +26,7 @@ use crate::{
     },
 };
 use ::libc::{
+    EAGAIN,
     EBUSY,
     EINVAL,
 };
@@ -24,7 +34,9 @@ use ::std::{
     cell::{
         Cell,
         RefCell,
+        RefMut,
     },
+    cmp,
     collections::VecDeque,
     convert::TryInto,
     fmt,
@@ -44,6 +56,11 @@ pub struct UnackedSegment {
     pub initial_tx: Option<Instant>,
 }
 
+// Number of sequence numbers taken by a segment on the unacknowledged queue.  The end-of-send marker takes one, for FIN.
+fn seq_len(segment: &UnackedSegment) -> u32 {
+    cmp::max(segment.bytes.len(), 1) as u32
+}
+
 /// Hard limit for unsent queue.
 /// TODO: Remove this.  We should limit the unsent queue by either having a (configurable) send buffer size (in bytes,
 /// not segments) and rejecting send requests that exceed that, or by limiting the user's send buffer allocations.
@@ -88,9 +105,26 @@ pub struct Sender<const N: usize> {
     // RFC 1323: Number of bits to shift advertised window, defaults to zero.
     window_scale: u8,
 
-    // Maximum Segment Size currently in use for this connection.
-    // TODO: Revisit this once we support path MTU discovery.
-    mss: usize,
+    // Maximum Segment Size currently in use for this connection.  Path MTU discovery may lower it.
+    mss: Cell<usize>,
+
+    // What our peer told us it received out-of-order, if SACK was negotiated.
+    scoreboard: RefCell<Scoreboard>,
+
+    // When to send the next window probe, if our peer's window is closed and we have data for it.
+    persist_deadline: WatchedValue<Option<Instant>>,
+
+    // Backoff of window probes.
+    persist_timer: RefCell<PersistTimer>,
+
+    // End of the last segment we sent that was smaller than the MSS.  Nagle's algorithm waits for it to be ACK'd.
+    small_segment_end: Cell<SeqNumber>,
+
+    // When to send the next segment held back by the pacer, if any.
+    pacing_deadline: WatchedValue<Option<Instant>>,
+
+    // Spacing of new data, if pacing is enabled.
+    pacer: RefCell<Pacer>,
 }
 
 impl<const N: usize> fmt::Debug for Sender<N> {
@@ -101,7 +135,7 @@ impl<const N: usize> fmt::Debug for Sender<N> {
             .field("unsent_seq_no", &self.unsent_seq_no)
             .field("send_window", &self.send_window)
             .field("window_scale", &self.window_scale)
-            .field("mss", &self.mss)
+            .field("mss", &self.mss.get())
             .finish()
     }
 }
@@ -120,12 +154,60 @@ impl<const N: usize> Sender<N> {
             send_window_last_update_ack: Cell::new(seq_no),
 
             window_scale,
-            mss,
+            mss: Cell::new(mss),
+            scoreboard: RefCell::new(Scoreboard::new(seq_no)),
+            persist_deadline: WatchedValue::new(None),
+            persist_timer: RefCell::new(PersistTimer::new()),
+            small_segment_end: Cell::new(seq_no),
+            pacing_deadline: WatchedValue::new(None),
+            pacer: RefCell::new(Pacer::new()),
         }
     }
 
     pub fn get_mss(&self) -> usize {
+        self.mss.get()
+    }
+
+    /// Largest payload that fits in a segment that carries `header`.  The MSS leaves no room for TCP options, so they
+    /// have to come out of the payload (RFC 6691 Section 3).
+    fn segment_size(&self, header: &TcpHeader) -> usize {
         self.mss
+            .get()
+            .saturating_sub(header.compute_size() - MIN_TCP_HEADER_SIZE)
+    }
+
+    /// Lowers the MSS to `mss`, splitting the segments that our peer has yet to acknowledge so that each of them can be
+    /// retransmitted as is, with `options_size` bytes of TCP options.
+    pub fn reduce_mss(&self, mss: usize, options_size: usize) {
+        if mss >= self.mss.get() {
+            return;
+        }
+        self.mss.set(mss);
+        let mss: usize = mss.saturating_sub(options_size);
+
+        let mut unacked_queue: RefMut<VecDeque<UnackedSegment>> = self.unacked_queue.borrow_mut();
+        let mut i: usize = 0;
+        while i < unacked_queue.len() {
+            if unacked_queue[i].bytes.len() > mss {
+                let mut tail: DemiBuffer = unacked_queue[i].bytes.clone();
+                tail.adjust(mss).expect("segment should be longer than the MSS");
+                let len: usize = unacked_queue[i].bytes.len();
+                unacked_queue[i]
+                    .bytes
+                    .trim(len - mss)
+                    .expect("segment should be longer than the MSS");
+                // Neither piece is an RTT sample anymore, as the original segment went out whole.
+                unacked_queue[i].initial_tx = None;
+                unacked_queue.insert(
+                    i + 1,
+                    UnackedSegment {
+                        bytes: tail,
+                        initial_tx: None,
+                    },
+                );
+            }
+            i += 1;
+        }
     }
 
     pub fn get_send_window(&self) -> (u32, WatchFuture<u32>) {
@@ -173,6 +255,14 @@ impl<const N: usize> Sender<N> {
             .try_into()
             .map_err(|_| Fail::new(EINVAL, "buffer too large"))?;
 
+        // Bound the data that we hold on to, sent or not, by the send buffer size (SO_SNDBUF).  A buffer that is larger
+        // than the whole send buffer is still accepted once everything before it is acknowledged, so that it can make
+        // progress at all.
+        let buffered: u32 = (self.unsent_seq_no.get() - self.send_unacked.get()).into();
+        if buf_len > 0 && buffered > 0 && (buffered as usize) + (buf_len as usize) > cb.get_send_buffer_size() {
+            return Err(Fail::new(EAGAIN, "send buffer is full"));
+        }
+
         // TODO: What we should do here:
         //
         // Conceptually, we should take the provided buffer and add it to the unsent queue.  Then calculate the amount
@@ -198,7 +288,6 @@ impl<const N: usize> Sender<N> {
             let send_next: SeqNumber = self.send_next.get();
             let sent_data: u32 = (send_next - send_unacknowledged).into();
 
-            // TODO: What limits buffer len to MSS?
             let in_flight_after_send: u32 = sent_data + buf_len;
 
             // Before we get cwnd for the check, we prompt it to shrink it if the connection has been idle.
@@ -209,15 +298,25 @@ impl<const N: usize> Sender<N> {
             let effective_cwnd: u32 = cwnd + cb.congestion_control_get_limited_transmit_cwnd_increase();
 
             let win_sz: u32 = self.send_window.get();
+            let now: Instant = cb.now();
 
-            if win_sz > 0 && win_sz >= in_flight_after_send && effective_cwnd >= in_flight_after_send {
+            // Buffers that do not fit in a single segment go through the unsent queue, which cuts them.
+            let mut header: TcpHeader = cb.tcp_header();
+            let segment_size: usize = self.segment_size(&header);
+
+            if win_sz > 0
+                && win_sz >= in_flight_after_send
+                && effective_cwnd >= in_flight_after_send
+                && buf_len as usize <= segment_size
+                && (buf_len == 0 || !self.should_delay(cb, buf_len as usize, segment_size))
+                && !self.should_pace(cb, now)
+            {
                 // This hook is primarily intended to record the last time we sent data, so we can later tell if
                 // the connection has been idle.
                 let rto: Duration = cb.rto();
                 cb.congestion_control_on_send(rto, sent_data);
 
                 // Prepare the segment and send it.
-                let mut header: TcpHeader = cb.tcp_header();
                 header.seq_num = send_next;
                 if buf_len == 0 {
                     // This buffer is the end-of-send marker.
@@ -227,6 +326,8 @@ impl<const N: usize> Sender<N> {
                 }
                 trace!("Send immediate");
                 cb.emit(header, Some(buf.clone()), cb.remote_link_addr);
+                self.on_segment_sent(send_next, buf.len(), segment_size);
+                self.on_paced_send(cb, now, buf.len());
 
                 // Update SND.NXT.
                 self.send_next.modify(|s| s + SeqNumber::from(buf_len));
@@ -237,14 +338,14 @@ impl<const N: usize> Sender<N> {
                 // Put the segment we just sent on the retransmission queue.
                 let unacked_segment = UnackedSegment {
                     bytes: buf,
-                    initial_tx: Some(cb.clock.as_ref().unwrap().now()),
+                    initial_tx: Some(now),
                 };
                 self.unacked_queue.borrow_mut().push_back(unacked_segment);
 
                 // Start the retransmission timer if it isn't already running.
                 if cb.get_retransmit_deadline().is_none() {
                     let rto: Duration = cb.rto();
-                    cb.set_retransmit_deadline(Some(cb.clock.as_ref().unwrap().now() + rto));
+                    cb.set_retransmit_deadline(Some(now + rto));
                 }
 
                 return Ok(());
@@ -262,11 +363,229 @@ impl<const N: usize> Sender<N> {
         self.unsent_queue.borrow_mut().push_back(buf);
         self.unsent_seq_no.modify(|s| s + SeqNumber::from(buf_len));
 
+        // Queued data may now fill up a full-sized segment.  If our peer's window is closed instead, this starts
+        // probing it.
+        self.send_unsent(cb, cb.now());
+
         Ok(())
     }
 
+    /// Sends as much queued data as both our peer's window and the congestion window allow.
+    pub fn send_unsent(&self, cb: &ControlBlock<N>, now: Instant) {
+        while let Some(top_size) = self.top_size_unsent() {
+            let send_unacknowledged: SeqNumber = self.send_unacked.get();
+            let send_next: SeqNumber = self.send_next.get();
+            let sent_data: u32 = (send_next - send_unacknowledged).into();
+
+            cb.congestion_control_on_cwnd_check_before_send();
+            let effective_cwnd: u32 =
+                cb.congestion_control_get_cwnd() + cb.congestion_control_get_limited_transmit_cwnd_increase();
+            let available: u32 = cmp::min(self.send_window.get(), effective_cwnd).saturating_sub(sent_data);
+
+            // The end-of-send marker takes no room in the window.
+            let mut header: TcpHeader = cb.tcp_header();
+            let segment_size: usize = self.segment_size(&header);
+            let max_size: usize = cmp::min(self.unsent_bytes(), cmp::min(segment_size, available as usize));
+            if top_size != 0 && max_size == 0 {
+                break;
+            }
+            // Hold back a partial segment, unless the user is done sending and it is all that is left.
+            if top_size != 0 && !self.fin_queued() && self.should_delay(cb, max_size, segment_size) {
+                trace!("Holding back {} bytes", max_size);
+                break;
+            }
+            // Leave the rest to the pacing timer.
+            if self.should_pace(cb, now) {
+                trace!("Pacing {} bytes", max_size);
+                break;
+            }
+            let buf: DemiBuffer = self.pop_unsent(max_size).expect("unsent queue should not be empty");
+            let mut buf_len: u32 = buf.len() as u32;
+
+            let rto: Duration = cb.rto();
+            cb.congestion_control_on_send(rto, sent_data);
+
+            header.seq_num = send_next;
+            if buf_len == 0 {
+                // This buffer is the end-of-send marker.
+                header.fin = true;
+                buf_len = 1;
+            }
+            trace!("Send queued data");
+            cb.emit(header, Some(buf.clone()), cb.remote_link_addr);
+            self.on_segment_sent(send_next, buf.len(), segment_size);
+            self.on_paced_send(cb, now, buf.len());
+            self.send_next.modify(|s| s + SeqNumber::from(buf_len));
+
+            let unacked_segment = UnackedSegment {
+                bytes: buf,
+                initial_tx: Some(now),
+            };
+            self.unacked_queue.borrow_mut().push_back(unacked_segment);
+
+            if cb.get_retransmit_deadline().is_none() {
+                cb.set_retransmit_deadline(Some(now + rto));
+            }
+        }
+
+        self.update_persist_timer(cb, now);
+    }
+
+    // Checks if a segment of `size` bytes should wait for more data to fill it up to `segment_size`.
+    fn should_delay(&self, cb: &ControlBlock<N>, size: usize, segment_size: usize) -> bool {
+        if size >= segment_size {
+            return false;
+        }
+        if cb.get_cork() {
+            return true;
+        }
+        // This is Minshall's variant of Nagle's algorithm (RFC 896): at most one partial segment is in flight.  Unlike
+        // the original, it does not wait on full-sized segments, whose ACK our peer may delay (RFC 1122 Section
+        // 4.2.3.2) when an odd number of them is outstanding.
+        !cb.get_nodelay() && self.small_segment_end.get() > self.send_unacked.get()
+    }
+
+    // Remembers the end of partial segments, for Nagle's algorithm.
+    fn on_segment_sent(&self, seq_no: SeqNumber, size: usize, segment_size: usize) {
+        if size > 0 && size < segment_size {
+            self.small_segment_end.set(seq_no + SeqNumber::from(size as u32));
+        }
+    }
+
+    // Rate at which new data should be sent, in bytes per second, if it should be paced at all.  Rate-based congestion
+    // control algorithms tell us their rate.  For the others, we spread the congestion window over the smoothed RTT,
+    // once we have measured it.
+    fn pacing_rate(&self, cb: &ControlBlock<N>) -> Option<u64> {
+        if !cb.get_pacing() {
+            return None;
+        }
+        let rate: Option<u64> = cb
+            .congestion_control_get_pacing_rate()
+            .or_else(|| cb.srtt().map(|srtt| Pacer::window_rate(cb.congestion_control_get_cwnd(), srtt)));
+        match (rate, cb.get_max_pacing_rate()) {
+            (Some(rate), Some(max_rate)) => Some(cmp::min(rate, max_rate)),
+            (rate, max_rate) => rate.or(max_rate),
+        }
+    }
+
+    // Checks if new data has to wait for the pacer, and arms the pacing timer if so.
+    fn should_pace(&self, cb: &ControlBlock<N>, now: Instant) -> bool {
+        if !cb.get_pacing() {
+            return false;
+        }
+        match self.pacer.borrow().release_time(now) {
+            Some(release_time) => {
+                if self.pacing_deadline.get() != Some(release_time) {
+                    self.pacing_deadline.set(Some(release_time));
+                }
+                true
+            },
+            None => false,
+        }
+    }
+
+    // Spaces the next segment from the one we just sent.  Retransmissions and window probes are not paced.
+    fn on_paced_send(&self, cb: &ControlBlock<N>, now: Instant, size: usize) {
+        if let Some(rate) = self.pacing_rate(cb) {
+            self.pacer.borrow_mut().on_send(now, size, rate);
+        }
+    }
+
+    pub fn get_pacing_deadline(&self) -> Option<Instant> {
+        self.pacing_deadline.get()
+    }
+
+    /// Sends what the pacer held back, now that it is due.
+    pub fn on_pacing_timeout(&self, cb: &ControlBlock<N>, now: Instant) {
+        self.pacing_deadline.set(None);
+        self.send_unsent(cb, now);
+    }
+
+    // Number of bytes of data waiting in the unsent queue.
+    fn unsent_bytes(&self) -> usize {
+        self.unsent_queue.borrow().iter().map(|buf| buf.len()).sum()
+    }
+
+    // Checks if the end-of-send marker is waiting in the unsent queue.
+    fn fin_queued(&self) -> bool {
+        self.unsent_queue.borrow().back().map_or(false, |buf| buf.len() == 0)
+    }
+
+    // Starts the persist timer when queued data is blocked by a closed window with nothing in flight, as there is
+    // then nothing left to elicit a window update from our peer (RFC 9293 Section 3.8.6.1).  Stops it once the window
+    // opens.
+    fn update_persist_timer(&self, cb: &ControlBlock<N>, now: Instant) {
+        if self.send_window.get() != 0 {
+            if self.persist_deadline.get().is_some() {
+                debug!("Window reopened, leaving persist mode");
+                self.persist_deadline.set(None);
+            }
+            self.persist_timer.borrow_mut().reset();
+            return;
+        }
+
+        let nothing_in_flight: bool = self.send_next.get() == self.send_unacked.get();
+        if nothing_in_flight && !self.unsent_queue.borrow().is_empty() && self.persist_deadline.get().is_none() {
+            debug!("Zero window, entering persist mode");
+            let timeout: Duration = self.persist_timer.borrow_mut().next_timeout(cb.rto());
+            self.persist_deadline.set(Some(now + timeout));
+        }
+    }
+
+    pub fn get_persist_deadline(&self) -> Option<Instant> {
+        self.persist_deadline.get()
+    }
+
+    /// Sends a window probe, and schedules the next one.
+    pub fn on_persist_timeout(&self, cb: &ControlBlock<N>, now: Instant) {
+        if self.send_window.get() != 0 {
+            self.persist_deadline.set(None);
+            return;
+        }
+
+        // The probe carries the next byte of data, so that our peer can accept it as soon as it has room.  If the
+        // previous probe went unanswered, send the same byte again.
+        let send_unacknowledged: SeqNumber = self.send_unacked.get();
+        let send_next: SeqNumber = self.send_next.get();
+        let (seq_no, data): (SeqNumber, DemiBuffer) = if send_next == send_unacknowledged {
+            match self.top_size_unsent() {
+                Some(top_size) if top_size > 0 => (),
+                // Nothing to probe with, or only the FIN, which does not need any window.
+                _ => {
+                    self.persist_deadline.set(None);
+                    return;
+                },
+            }
+            let buf: DemiBuffer = self.pop_unsent(1).expect("unsent queue should not be empty");
+            self.send_next.modify(|s| s + SeqNumber::from(1));
+            // The probe may sit unanswered for a while, so it is useless as an RTT sample.
+            let unacked_segment = UnackedSegment {
+                bytes: buf.clone(),
+                initial_tx: None,
+            };
+            self.unacked_queue.borrow_mut().push_back(unacked_segment);
+            (send_next, buf)
+        } else {
+            match self.unacked_queue.borrow().front() {
+                Some(segment) => (send_unacknowledged, segment.bytes.clone()),
+                None => return warn!("Window probe with empty unacknowledged queue?"),
+            }
+        };
+
+        trace!("Sending window probe at {}", seq_no);
+        let mut header: TcpHeader = cb.tcp_header();
+        header.seq_num = seq_no;
+        cb.emit(header, Some(data), cb.remote_link_addr);
+
+        let timeout: Duration = self.persist_timer.borrow_mut().next_timeout(cb.rto());
+        self.persist_deadline.set(Some(now + timeout));
+    }
+
     /// Retransmits the earliest segment that has not (yet) been acknowledged by our peer.
     pub fn retransmit(&self, cb: &ControlBlock<N>) {
+        // Our peer may have discarded data it had SACKed, so start over (RFC 2018 Section 8).
+        self.scoreboard.borrow_mut().clear(self.send_unacked.get());
+
         // Check that we have an unacknowledged segment.
         if let Some(segment) = self.unacked_queue.borrow_mut().front_mut() {
             // We're retransmitting this, so we can no longer use an ACK for it as an RTT measurement (as we can't tell
@@ -303,9 +622,11 @@ impl<const N: usize> Sender<N> {
             if let Some(segment) = self.unacked_queue.borrow_mut().front_mut() {
                 // Add sample for RTO if we have an initial transmit time.
                 // Note that in the case of repacketization, an ack for the first byte is enough for the time sample.
-                // TODO: TCP timestamp support.
+                // With the timestamps option, samples are taken from the echoed timestamps instead.
                 if let Some(initial_tx) = segment.initial_tx {
-                    cb.rto_add_sample(now - initial_tx);
+                    if !cb.has_timestamps() {
+                        cb.rto_add_sample(now - initial_tx);
+                    }
                 }
 
                 if segment.bytes.len() > bytes_remaining {
@@ -338,6 +659,95 @@ impl<const N: usize> Sender<N> {
         }
     }
 
+    /// Processes the SACK blocks carried by an ACK, and retransmits the segments they reveal as lost.
+    /// This implements the loss recovery algorithm of RFC 6675, with segment granularity.
+    pub fn on_sack(&self, cb: &ControlBlock<N>, header: &TcpHeader) {
+        let send_unacked: SeqNumber = self.send_unacked.get();
+        let send_next: SeqNumber = self.send_next.get();
+
+        {
+            let mut scoreboard: RefMut<Scoreboard> = self.scoreboard.borrow_mut();
+            scoreboard.advance(send_unacked);
+            for option in header.iter_options() {
+                if let TcpOptions2::SelectiveAcknowlegement { num_sacks, sacks } = option {
+                    scoreboard.update(send_unacked, send_next, &sacks[..*num_sacks]);
+                }
+            }
+
+            if !scoreboard.in_recovery() {
+                // RFC 6675 Section 5: Enter loss recovery on the third duplicate ACK, or as soon as SND.UNA is deemed
+                // lost.
+                if scoreboard.is_empty() || send_unacked == send_next {
+                    return;
+                }
+                if cb.congestion_control_get_duplicate_ack_count() < 3 && !scoreboard.is_lost(send_unacked, self.mss.get()) {
+                    return;
+                }
+                debug!("Entering SACK recovery (send_unacked={}, send_next={})", send_unacked, send_next);
+                scoreboard.enter_recovery(send_unacked, send_next);
+                cb.congestion_control_on_fast_retransmit();
+            }
+        }
+
+        // RFC 6675 Section 5 (C): Retransmit lost segments while the congestion window allows it.
+        let cwnd: u32 = cb.congestion_control_get_cwnd();
+        while cwnd.saturating_sub(self.pipe()) >= self.mss.get() as u32 {
+            let (seq_no, data): (SeqNumber, DemiBuffer) = match self.next_seg() {
+                Some(segment) => segment,
+                None => break,
+            };
+            let len: u32 = cmp::max(data.len(), 1) as u32;
+
+            let mut header: TcpHeader = cb.tcp_header();
+            header.seq_num = seq_no;
+            if data.len() == 0 {
+                // This buffer is the end-of-send marker.  Retransmit the FIN.
+                header.fin = true;
+            }
+            trace!("SACK retransmission of [{}, {})", seq_no, seq_no + SeqNumber::from(len));
+            cb.emit(header, Some(data), cb.remote_link_addr);
+            self.scoreboard.borrow_mut().set_high_rxt(seq_no + SeqNumber::from(len));
+        }
+    }
+
+    // Finds the first lost segment that was neither SACKed nor retransmitted yet (NextSeg() in RFC 6675, rule 1).
+    fn next_seg(&self) -> Option<(SeqNumber, DemiBuffer)> {
+        let scoreboard = self.scoreboard.borrow();
+        let mut unacked_queue = self.unacked_queue.borrow_mut();
+        let mut start: SeqNumber = self.send_unacked.get();
+        for segment in unacked_queue.iter_mut() {
+            let end: SeqNumber = start + SeqNumber::from(seq_len(segment));
+            if end > scoreboard.get_high_rxt() && !scoreboard.is_sacked(start, end) && scoreboard.is_lost(start, self.mss.get()) {
+                // Karn's algorithm: an ACK for this segment can no longer be used as an RTT sample.
+                segment.initial_tx.take();
+                return Some((start, segment.bytes.clone()));
+            }
+            start = end;
+        }
+        None
+    }
+
+    // Estimates the number of bytes still in flight (SetPipe() in RFC 6675 Section 4).
+    fn pipe(&self) -> u32 {
+        let scoreboard = self.scoreboard.borrow();
+        let mut pipe: u32 = 0;
+        let mut start: SeqNumber = self.send_unacked.get();
+        for segment in self.unacked_queue.borrow().iter() {
+            let len: u32 = seq_len(segment);
+            let end: SeqNumber = start + SeqNumber::from(len);
+            if !scoreboard.is_sacked(start, end) {
+                if !scoreboard.is_lost(start, self.mss.get()) {
+                    pipe += len;
+                }
+                if end <= scoreboard.get_high_rxt() {
+                    pipe += len;
+                }
+            }
+            start = end;
+        }
+        pipe
+    }
+
     pub fn pop_one_unsent_byte(&self) -> Option<DemiBuffer> {
         let mut queue = self.unsent_queue.borrow_mut();
 
@@ -355,7 +765,6 @@ impl<const N: usize> Sender<N> {
     }
 
     pub fn pop_unsent(&self, max_bytes: usize) -> Option<DemiBuffer> {
-        // TODO: Use a scatter/gather array to coalesce multiple buffers into a single segment.
         let mut unsent_queue = self.unsent_queue.borrow_mut();
         let mut buf: DemiBuffer = unsent_queue.pop_front()?;
         let buf_len: usize = buf.len();
@@ -372,6 +781,32 @@ impl<const N: usize> Sender<N> {
             unsent_queue.push_front(buf);
             buf = cloned_buf;
         }
+
+        // Coalesce small buffers into a single segment, up to the end-of-send marker.
+        // TODO: Use a scatter/gather array instead of copying.
+        let mut size: usize = buf.len();
+        for next in unsent_queue.iter() {
+            if size == 0 || size >= max_bytes || next.len() == 0 {
+                break;
+            }
+            size = cmp::min(size + next.len(), max_bytes);
+        }
+        if size > buf.len() {
+            let mut segment: DemiBuffer = DemiBuffer::new(size as u16);
+            let mut offset: usize = buf.len();
+            segment[..offset].copy_from_slice(&buf[..]);
+            while offset < size {
+                let mut next: DemiBuffer = unsent_queue.pop_front().expect("unsent queue should not be empty");
+                let n: usize = cmp::min(next.len(), size - offset);
+                segment[offset..offset + n].copy_from_slice(&next[..n]);
+                if n < next.len() {
+                    next.adjust(n).expect("'next' should contain at least 'n' bytes");
+                    unsent_queue.push_front(next);
+                }
+                offset += n;
+            }
+            buf = segment;
+        }
         Some(buf)
     }
 
@@ -403,6 +838,6 @@ impl<const N: usize> Sender<N> {
     }
 
     pub fn remote_mss(&self) -> usize {
-        self.mss
+        self.mss.get()
     }
 }
@@ -0,0 +1,136 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+// TCP Timestamps option state.
+// See RFC 7323 for details on Round-Trip Time Measurement (RTTM) and Protection Against Wrapped Sequences (PAWS).
+
+use crate::inetstack::protocols::tcp::segment::{
+    TcpHeader,
+    TcpOptions2,
+};
+use ::std::time::{
+    Duration,
+    Instant,
+};
+
+/// A timestamp older than this is no longer trusted for PAWS (RFC 7323 Section 5.5).
+const PAWS_IDLE_LIMIT: Duration = Duration::from_secs(24 * 24 * 60 * 60);
+
+#[derive(Clone, Copy, Debug)]
+pub struct Timestamps {
+    // Instant at which our timestamp clock read `offset`.
+    base: Instant,
+
+    // Initial value of our timestamp clock.  Randomized per connection, as recommended by RFC 7323 Section 5.4.
+    offset: u32,
+
+    // Timestamp to echo in our next segment.  In RFC 7323 terms, this is TS.Recent.
+    recent: u32,
+
+    // When `recent` was last updated.
+    recent_age: Instant,
+}
+
+impl Timestamps {
+    pub fn new(base: Instant, offset: u32, recent: u32) -> Self {
+        Self {
+            base,
+            offset,
+            recent,
+            recent_age: base,
+        }
+    }
+
+    /// Reads our timestamp clock, which ticks every millisecond.
+    pub fn now(&self, now: Instant) -> u32 {
+        let elapsed: u128 = now.saturating_duration_since(self.base).as_millis();
+        self.offset.wrapping_add(elapsed as u32)
+    }
+
+    pub fn get_recent(&self) -> u32 {
+        self.recent
+    }
+
+    /// Remembers the timestamp of a segment, unless it is older than the one we already have.
+    pub fn update_recent(&mut self, tsval: u32, now: Instant) {
+        if !is_before(tsval, self.recent) {
+            self.recent = tsval;
+            self.recent_age = now;
+        }
+    }
+
+    /// Checks if a segment carries an old timestamp, and thus belongs to a previous wrap of the sequence space (PAWS).
+    pub fn is_old(&self, tsval: u32, now: Instant) -> bool {
+        is_before(tsval, self.recent) && now.saturating_duration_since(self.recent_age) < PAWS_IDLE_LIMIT
+    }
+
+    /// Computes the round-trip time from an echoed timestamp (RFC 7323 Section 4).
+    pub fn rtt(&self, tsecr: u32, now: Instant) -> Duration {
+        Duration::from_millis(self.now(now).wrapping_sub(tsecr) as u64)
+    }
+}
+
+/// Compares two timestamps, considering that they wrap around.
+fn is_before(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) < 0
+}
+
+/// Looks for the timestamps option in a TCP header, and returns its values (TSval and TSecr).
+pub fn find_timestamp_option(header: &TcpHeader) -> Option<(u32, u32)> {
+    header.iter_options().find_map(|option| match option {
+        TcpOptions2::Timestamp {
+            sender_timestamp,
+            echo_timestamp,
+        } => Some((*sender_timestamp, *echo_timestamp)),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Timestamps;
+    use ::anyhow::Result;
+    use ::std::time::{
+        Duration,
+        Instant,
+    };
+
+    // Test that our clock and RTT samples survive a wrap around.
+    #[test]
+    fn clock() -> Result<()> {
+        let base: Instant = Instant::now();
+        let timestamps: Timestamps = Timestamps::new(base, u32::MAX - 10, 0);
+
+        crate::ensure_eq!(timestamps.now(base), u32::MAX - 10);
+        crate::ensure_eq!(timestamps.now(base + Duration::from_millis(20)), 9);
+        crate::ensure_eq!(
+            timestamps.rtt(u32::MAX - 5, base + Duration::from_millis(20)),
+            Duration::from_millis(15)
+        );
+
+        Ok(())
+    }
+
+    // Test that segments with old timestamps are detected, and only while TS.Recent is fresh.
+    #[test]
+    fn paws() -> Result<()> {
+        let base: Instant = Instant::now();
+        let mut timestamps: Timestamps = Timestamps::new(base, 0, u32::MAX - 1);
+
+        // Newer, across the wrap.
+        crate::ensure_eq!(timestamps.is_old(1, base), false);
+        timestamps.update_recent(1, base);
+        crate::ensure_eq!(timestamps.get_recent(), 1);
+
+        // Older ones never move TS.Recent back.
+        crate::ensure_eq!(timestamps.is_old(u32::MAX - 1, base), true);
+        timestamps.update_recent(u32::MAX - 1, base);
+        crate::ensure_eq!(timestamps.get_recent(), 1);
+
+        // After a long idle period, TS.Recent is no longer trusted.
+        let later: Instant = base + Duration::from_secs(25 * 24 * 60 * 60);
+        crate::ensure_eq!(timestamps.is_old(u32::MAX - 1, later), false);
+
+        Ok(())
+    }
+}
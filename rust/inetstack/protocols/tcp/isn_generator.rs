@@ -1,41 +1,97 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT license.
 
-use crate::inetstack::protocols::tcp::SeqNumber;
-#[allow(unused_imports)]
+use crate::inetstack::protocols::tcp::{
+    constants::FALLBACK_MSS,
+    SeqNumber,
+};
 use std::{
-    hash::Hasher,
-    net::SocketAddrV4,
+    collections::hash_map::{
+        DefaultHasher,
+        RandomState,
+    },
+    hash::{
+        BuildHasher,
+        Hasher,
+    },
+    net::{
+        IpAddr,
+        SocketAddr,
+    },
     num::Wrapping,
+    time::{
+        Duration,
+        Instant,
+    },
 };
 
+/// MSS values that a SYN cookie can encode.  Peers get the largest one that does not exceed what they advertised.
+const COOKIE_MSS_TABLE: [usize; 4] = [FALLBACK_MSS, 1300, 1440, 1460];
+
+/// How often the time counter in SYN cookies ticks.
+const COOKIE_PERIOD: Duration = Duration::from_secs(64);
+
+/// How many ticks of the time counter a SYN cookie stays valid for.
+const MAX_COOKIE_AGE: u32 = 1;
+
+/// How long after sending our last SYN cookie we still accept ACKs that carry one.  Beyond that, no genuine cookie
+/// can be alive, so we do not even look at the ACK.
+const COOKIE_LIFETIME: Duration = Duration::from_secs(COOKIE_PERIOD.as_secs() * (MAX_COOKIE_AGE as u64 + 1));
+
+// Layout of a SYN cookie: [ counter (5 bits) ][ MSS index (2 bits) ][ window scale (4 bits) ][ SACK (1 bit) ][ MAC (20 bits) ]
+const COOKIE_COUNTER_SHIFT: u32 = 27;
+const COOKIE_MSS_SHIFT: u32 = 25;
+const COOKIE_WINDOW_SCALE_SHIFT: u32 = 21;
+const COOKIE_SACK_SHIFT: u32 = 20;
+const COOKIE_MAC_MASK: u32 = (1 << COOKIE_SACK_SHIFT) - 1;
+// Window scale value that stands for "no window scale option".
+const COOKIE_NO_WINDOW_SCALE: u32 = 0xf;
+
+/// Connection parameters that a SYN cookie remembers for us, as we keep no state for the connection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SynCookie {
+    pub mss: usize,
+    pub window_scale: Option<u8>,
+    pub sack_permitted: bool,
+}
+
 #[allow(dead_code)]
 pub struct IsnGenerator {
     nonce: u32,
     counter: Wrapping<u16>,
+    // Time origin of the SYN cookie counter.
+    epoch: Instant,
+    // Secret key of the SYN cookie MAC.  Each generator draws its own.
+    cookie_key: RandomState,
+    // When we last answered a SYN with a cookie.
+    last_cookie: Option<Instant>,
 }
 
 impl IsnGenerator {
-    pub fn new(nonce: u32) -> Self {
+    /// Creates a generator whose SYN cookie counter starts at `now`.
+    pub fn new(nonce: u32, now: Instant) -> Self {
         Self {
             nonce,
             counter: Wrapping(0),
+            epoch: now,
+            cookie_key: RandomState::new(),
+            last_cookie: None,
         }
     }
 
     #[cfg(test)]
-    pub fn generate(&mut self, _local: &SocketAddrV4, _remote: &SocketAddrV4) -> SeqNumber {
+    pub fn generate(&mut self, _local: &SocketAddr, _remote: &SocketAddr) -> SeqNumber {
         SeqNumber::from(0)
     }
 
     #[cfg(not(test))]
-    pub fn generate(&mut self, local: &SocketAddrV4, remote: &SocketAddrV4) -> SeqNumber {
+    pub fn generate(&mut self, local: &SocketAddr, remote: &SocketAddr) -> SeqNumber {
         let crc: crc::Crc<u32> = crc::Crc::<u32>::new(&crc::CRC_32_CKSUM);
         let mut digest = crc.digest();
-        digest.update(&remote.ip().octets());
+        digest.update(&addr_octets(remote));
         let remote_port: u16 = remote.port().into();
         digest.update(&remote_port.to_be_bytes());
-        digest.update(&local.ip().octets());
+        digest.update(&addr_octets(local));
         let local_port: u16 = local.port().into();
         digest.update(&local_port.to_be_bytes());
         digest.update(&self.nonce.to_be_bytes());
@@ -44,4 +100,230 @@ impl IsnGenerator {
         self.counter += Wrapping(1);
         isn
     }
+
+    /// Generates an ISN that encodes `cookie`, for a SYN that we do not want to keep any state for.
+    /// See RFC 4987 Section 3.6 for details on SYN cookies.
+    pub fn generate_cookie(
+        &mut self,
+        local: &SocketAddr,
+        remote: &SocketAddr,
+        remote_isn: SeqNumber,
+        cookie: SynCookie,
+        now: Instant,
+    ) -> SeqNumber {
+        let counter: u32 = self.cookie_counter(now);
+        let mss_index: u32 = COOKIE_MSS_TABLE
+            .iter()
+            .rposition(|mss| *mss <= cookie.mss)
+            .unwrap_or(0) as u32;
+        let window_scale: u32 = match cookie.window_scale {
+            Some(w) => (w as u32).min(COOKIE_NO_WINDOW_SCALE - 1),
+            None => COOKIE_NO_WINDOW_SCALE,
+        };
+        let sack: u32 = cookie.sack_permitted as u32;
+
+        let bits: u32 = ((counter & 0x1f) << COOKIE_COUNTER_SHIFT)
+            | (mss_index << COOKIE_MSS_SHIFT)
+            | (window_scale << COOKIE_WINDOW_SCALE_SHIFT)
+            | (sack << COOKIE_SACK_SHIFT);
+        self.last_cookie = Some(now);
+        SeqNumber::from(bits | self.cookie_mac(local, remote, remote_isn, counter, bits))
+    }
+
+    /// Checks an ISN generated by `generate_cookie`, and returns what it encodes if it is genuine and recent enough.
+    /// Unless we sent a cookie within the last `COOKIE_LIFETIME`, every ISN is rejected.
+    pub fn check_cookie(
+        &self,
+        local: &SocketAddr,
+        remote: &SocketAddr,
+        remote_isn: SeqNumber,
+        local_isn: SeqNumber,
+        now: Instant,
+    ) -> Option<SynCookie> {
+        match self.last_cookie {
+            Some(sent) if now.saturating_duration_since(sent) < COOKIE_LIFETIME => (),
+            _ => return None,
+        }
+
+        let value: u32 = u32::from(local_isn);
+        let current: u32 = self.cookie_counter(now);
+        let age: u32 = current.wrapping_sub(value >> COOKIE_COUNTER_SHIFT) & 0x1f;
+        if age > MAX_COOKIE_AGE || age > current {
+            return None;
+        }
+        let counter: u32 = current - age;
+
+        let bits: u32 = value & !COOKIE_MAC_MASK;
+        if value & COOKIE_MAC_MASK != self.cookie_mac(local, remote, remote_isn, counter, bits) {
+            return None;
+        }
+
+        let mss_index: usize = ((value >> COOKIE_MSS_SHIFT) & 0x3) as usize;
+        let window_scale: u32 = (value >> COOKIE_WINDOW_SCALE_SHIFT) & 0xf;
+        Some(SynCookie {
+            mss: COOKIE_MSS_TABLE[mss_index],
+            window_scale: if window_scale == COOKIE_NO_WINDOW_SCALE {
+                None
+            } else {
+                Some(window_scale as u8)
+            },
+            sack_permitted: (value >> COOKIE_SACK_SHIFT) & 0x1 != 0,
+        })
+    }
+
+    fn cookie_counter(&self, now: Instant) -> u32 {
+        (now.saturating_duration_since(self.epoch).as_secs() / COOKIE_PERIOD.as_secs()) as u32
+    }
+
+    /// Authenticates the connection and the parameters encoded in a SYN cookie.  This is a SipHash keyed with a random
+    /// secret, so unlike a CRC, it cannot be computed nor adjusted without knowing the key.
+    fn cookie_mac(&self, local: &SocketAddr, remote: &SocketAddr, remote_isn: SeqNumber, counter: u32, bits: u32) -> u32 {
+        let mut hasher: DefaultHasher = self.cookie_key.build_hasher();
+        hasher.write(&addr_octets(remote));
+        hasher.write(&remote.port().to_be_bytes());
+        hasher.write(&addr_octets(local));
+        hasher.write(&local.port().to_be_bytes());
+        hasher.write(&u32::from(remote_isn).to_be_bytes());
+        hasher.write(&counter.to_be_bytes());
+        hasher.write(&bits.to_be_bytes());
+        (hasher.finish() as u32) & COOKIE_MAC_MASK
+    }
+}
+
+/// Returns the octets that identify the address of `addr` in a digest.  IPv4 addresses are mapped into IPv6 ones, so
+/// both versions hash the same amount of bytes.
+fn addr_octets(addr: &SocketAddr) -> [u8; 16] {
+    match addr.ip() {
+        IpAddr::V4(ip) => ip.to_ipv6_mapped().octets(),
+        IpAddr::V6(ip) => ip.octets(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        IsnGenerator,
+        SynCookie,
+        COOKIE_LIFETIME,
+        COOKIE_PERIOD,
+    };
+    use crate::inetstack::protocols::tcp::SeqNumber;
+    use ::anyhow::Result;
+    use ::std::{
+        net::{
+            Ipv4Addr,
+            SocketAddr,
+        },
+        time::Instant,
+    };
+
+    fn endpoints() -> (SocketAddr, SocketAddr) {
+        (
+            SocketAddr::new(Ipv4Addr::new(10, 0, 0, 1).into(), 80),
+            SocketAddr::new(Ipv4Addr::new(10, 0, 0, 2).into(), 4242),
+        )
+    }
+
+    // Test that a SYN cookie gives back what was encoded in it.
+    #[test]
+    fn cookie_roundtrip() -> Result<()> {
+        let (local, remote): (SocketAddr, SocketAddr) = endpoints();
+        let now: Instant = Instant::now();
+        let mut isn_generator: IsnGenerator = IsnGenerator::new(7, now);
+        let remote_isn: SeqNumber = SeqNumber::from(0xdeadbeef);
+
+        let cookie: SynCookie = SynCookie {
+            mss: 1450,
+            window_scale: Some(7),
+            sack_permitted: true,
+        };
+        let local_isn: SeqNumber = isn_generator.generate_cookie(&local, &remote, remote_isn, cookie, now);
+        let expected: SynCookie = SynCookie { mss: 1440, ..cookie };
+        crate::ensure_eq!(
+            isn_generator.check_cookie(&local, &remote, remote_isn, local_isn, now + COOKIE_PERIOD),
+            Some(expected)
+        );
+
+        let cookie: SynCookie = SynCookie {
+            mss: 100,
+            window_scale: None,
+            sack_permitted: false,
+        };
+        let local_isn: SeqNumber = isn_generator.generate_cookie(&local, &remote, remote_isn, cookie, now);
+        let expected: SynCookie = SynCookie { mss: 536, ..cookie };
+        crate::ensure_eq!(
+            isn_generator.check_cookie(&local, &remote, remote_isn, local_isn, now),
+            Some(expected)
+        );
+
+        Ok(())
+    }
+
+    // Test that SYN cookies are bound to the connection and expire.
+    #[test]
+    fn cookie_rejected() -> Result<()> {
+        let (local, remote): (SocketAddr, SocketAddr) = endpoints();
+        let now: Instant = Instant::now();
+        let mut isn_generator: IsnGenerator = IsnGenerator::new(7, now);
+        let remote_isn: SeqNumber = SeqNumber::from(1000);
+        let cookie: SynCookie = SynCookie {
+            mss: 1460,
+            window_scale: Some(0),
+            sack_permitted: false,
+        };
+        let local_isn: SeqNumber = isn_generator.generate_cookie(&local, &remote, remote_isn, cookie, now);
+
+        let other: SocketAddr = SocketAddr::new(remote.ip(), remote.port() + 1);
+        crate::ensure_eq!(isn_generator.check_cookie(&local, &other, remote_isn, local_isn, now), None);
+        crate::ensure_eq!(
+            isn_generator.check_cookie(&local, &remote, SeqNumber::from(1001), local_isn, now),
+            None
+        );
+        crate::ensure_eq!(
+            isn_generator.check_cookie(&local, &remote, remote_isn, local_isn + SeqNumber::from(1), now),
+            None
+        );
+        crate::ensure_eq!(
+            isn_generator.check_cookie(&local, &remote, remote_isn, local_isn, now + 2 * COOKIE_PERIOD),
+            None
+        );
+
+        Ok(())
+    }
+
+    // Test that SYN cookies only hold with the key of the generator that made them, and only shortly after we sent one.
+    #[test]
+    fn cookie_needs_key_and_recent_cookie() -> Result<()> {
+        let (local, remote): (SocketAddr, SocketAddr) = endpoints();
+        let now: Instant = Instant::now();
+        let mut isn_generator: IsnGenerator = IsnGenerator::new(7, now);
+        let mut other_generator: IsnGenerator = IsnGenerator::new(7, now);
+        let remote_isn: SeqNumber = SeqNumber::from(1000);
+        let cookie: SynCookie = SynCookie {
+            mss: 1460,
+            window_scale: None,
+            sack_permitted: false,
+        };
+
+        let local_isn: SeqNumber = isn_generator.generate_cookie(&local, &remote, remote_isn, cookie, now);
+        crate::ensure_eq!(
+            isn_generator.check_cookie(&local, &remote, remote_isn, local_isn, now),
+            Some(cookie)
+        );
+
+        // Another generator draws another key.
+        other_generator.generate_cookie(&local, &remote, remote_isn, cookie, now);
+        crate::ensure_eq!(other_generator.check_cookie(&local, &remote, remote_isn, local_isn, now), None);
+
+        // Without a cookie sent recently, even a genuine one is rejected.
+        isn_generator.last_cookie = None;
+        crate::ensure_eq!(isn_generator.check_cookie(&local, &remote, remote_isn, local_isn, now), None);
+        isn_generator.last_cookie = Some(now);
+        crate::ensure_eq!(
+            isn_generator.check_cookie(&local, &remote, remote_isn, local_isn, now + COOKIE_LIFETIME),
+            None
+        );
+
+        Ok(())
+    }
 }
@@ -11,6 +11,10 @@ pub mod peer;
 pub mod queue;
 pub mod segment;
 mod sequence_number;
+pub mod time_wait;
+
+#[cfg(test)]
+mod tests;
 
 pub use self::{
     established::congestion_control,
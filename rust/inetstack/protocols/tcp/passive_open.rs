@@ -4,21 +4,23 @@
 use super::{
     constants::FALLBACK_MSS,
     established::ControlBlock,
-    isn_generator::IsnGenerator,
+    isn_generator::{
+        IsnGenerator,
+        SynCookie,
+    },
 };
 use crate::{
     QDesc,
     inetstack::protocols::{
-        ethernet2::{
-            EtherType2,
-            Ethernet2Header,
+        ethernet2::Ethernet2Header,
+        ip::{
+            IpHeader,
+            IpProtocol,
         },
-        ip::IpProtocol,
-        ipv4::Ipv4Header,
         tcp::{
             established::{
                 congestion_control,
-                congestion_control::CongestionControl,
+                Timestamps,
             },
             segment::{
                 TcpHeader,
@@ -32,14 +34,16 @@ use crate::{
         fail::Fail,
         network::{
             config::TcpConfig,
+            socket::{
+                SocketOption,
+                SocketOptionName,
+            },
             NetworkRuntime,
         },
+        timer::TimerRc,
     },
 };
-use ::libc::{
-    EBADMSG,
-    ECONNREFUSED,
-};
+use ::libc::EBADMSG;
 use ::std::{
     cell::RefCell,
     collections::{
@@ -48,7 +52,7 @@ use ::std::{
         VecDeque,
     },
     convert::TryInto,
-    net::SocketAddrV4,
+    net::SocketAddr,
     rc::Rc,
     sync::Arc,
     task::{
@@ -56,6 +60,10 @@ use ::std::{
         Poll,
         Waker,
     },
+    time::{
+        Duration,
+        Instant,
+    },
 };
 
 struct InflightAccept {
@@ -64,11 +72,20 @@ struct InflightAccept {
     header_window_size: u16,
     remote_window_scale: Option<u8>,
     mss: usize,
+    sack_permitted: bool,
+    timestamps: Option<Timestamps>,
+    ecn: bool,
+    // Headers of the SYN, which we answer again if our SYN+ACK goes unanswered.
+    eth_header: Ethernet2Header,
+    ip_header: IpHeader,
+    // When we next retransmit our SYN+ACK, and how many more times we do so before giving up on the handshake.
+    deadline: Instant,
+    retries: usize,
 }
 
 struct ReadySockets<const N: usize> {
     ready: VecDeque<Result<ControlBlock<N>, Fail>>,
-    endpoints: HashSet<SocketAddrV4>,
+    endpoints: HashSet<SocketAddr>,
     waker: Option<Waker>,
 }
 
@@ -108,22 +125,24 @@ impl<const N: usize> ReadySockets<N> {
 }
 
 pub struct PassiveSocket<const N: usize> {
-    inflight: HashMap<SocketAddrV4, InflightAccept>,
+    inflight: HashMap<SocketAddr, InflightAccept>,
     ready: Rc<RefCell<ReadySockets<N>>>,
 
     max_backlog: usize,
     isn_generator: IsnGenerator,
 
-    local: SocketAddrV4,
+    local: SocketAddr,
     rt: Option<Arc<dyn NetworkRuntime<N>>>,
+    clock: TimerRc,
     tcp_config: TcpConfig,
 }
 
 impl<const N: usize> PassiveSocket<N> {
     pub fn new(
-        local: SocketAddrV4,
+        local: SocketAddr,
         max_backlog: usize,
         rt: Option<Arc<dyn NetworkRuntime<N>>>,
+        clock: TimerRc,
         tcp_config: TcpConfig,
         nonce: u32,
     ) -> Self {
@@ -137,24 +156,64 @@ impl<const N: usize> PassiveSocket<N> {
             inflight: HashMap::new(),
             ready,
             max_backlog,
-            isn_generator: IsnGenerator::new(nonce),
+            isn_generator: IsnGenerator::new(nonce, clock.now()),
             local,
             rt,
+            clock,
             tcp_config,
         }
     }
 
     /// Returns the address that the socket is bound to.
-    pub fn endpoint(&self) -> SocketAddrV4 {
+    pub fn endpoint(&self) -> SocketAddr {
         self.local
     }
 
+    /// Gets the value of a socket option. Accepted connections inherit it.
+    pub fn get_option(&self, name: SocketOptionName) -> SocketOption {
+        self.tcp_config.get_option(name)
+    }
+
+    /// Sets a socket option. Only connections accepted afterwards are affected.
+    pub fn set_option(&mut self, option: SocketOption) -> Result<(), Fail> {
+        self.tcp_config = self.tcp_config.clone().set_option(option)?;
+        Ok(())
+    }
+
+    /// Retransmits our SYN+ACK to peers that did not complete their handshake in time, and gives up on the handshakes
+    /// that ran out of retries, so that they do not take room in the backlog forever.  Returns how many were given up.
+    pub fn poll_handshakes(&mut self) -> usize {
+        let now: Instant = self.clock.now();
+        let timeout: Duration = self.tcp_config.get_handshake_timeout();
+
+        let before: usize = self.inflight.len();
+        self.inflight.retain(|_, accept| accept.deadline > now || accept.retries > 0);
+        let nr_expired: usize = before - self.inflight.len();
+
+        let due: Vec<SocketAddr> = self
+            .inflight
+            .iter_mut()
+            .filter(|(_, accept)| accept.deadline <= now)
+            .map(|(remote, accept)| {
+                accept.retries -= 1;
+                accept.deadline = now + timeout;
+                *remote
+            })
+            .collect();
+        for remote in due {
+            debug!("Retransmitting SYN+ACK to {:?}", remote);
+            self.send_syn_ack(remote, &self.inflight[&remote]);
+        }
+
+        nr_expired
+    }
+
     pub fn poll_accept(&mut self, ctx: &mut Context) -> Poll<Result<ControlBlock<N>, Fail>> {
         self.ready.borrow_mut().poll(ctx)
     }
 
-    pub fn receive(&mut self, qd: QDesc, eth_header: &Ethernet2Header, ip_header: &Ipv4Header, header: &TcpHeader) -> Result<Option<*mut ControlBlock<N>>, Fail> {
-        let remote = SocketAddrV4::new(ip_header.get_src_addr(), header.src_port);
+    pub fn receive(&mut self, qd: QDesc, eth_header: &Ethernet2Header, ip_header: &IpHeader, header: &TcpHeader) -> Result<Option<*mut ControlBlock<N>>, Fail> {
+        let remote = SocketAddr::new(ip_header.get_src_addr(), header.src_port);
         if self.ready.borrow().endpoints.contains(&remote) {
             // TODO: What should we do if a packet shows up for a connection that hasn't been `accept`ed yet?
             return Ok(None);
@@ -167,59 +226,42 @@ impl<const N: usize> PassiveSocket<N> {
                 return Err(Fail::new(EBADMSG, "expeting ACK"));
             }
             debug!("Received ACK: {:?}", header);
-            let &InflightAccept {
-                local_isn,
-                remote_isn,
-                header_window_size,
-                remote_window_scale,
-                mss,
-                ..
-            } = self.inflight.get(&remote).unwrap();
+            let local_isn: SeqNumber = self.inflight.get(&remote).unwrap().local_isn;
             if header.ack_num != local_isn + SeqNumber::from(1) {
                 return Err(Fail::new(EBADMSG, "invalid SYN+ACK seq num"));
             }
 
-            let (local_window_scale, remote_window_scale) = match remote_window_scale {
-                Some(w) => (self.tcp_config.get_window_scale() as u32, w),
-                None => (0, 0),
+            let accept: InflightAccept = self.inflight.remove(&remote).unwrap();
+            return Ok(Some(self.establish(qd, eth_header, remote, accept, header)));
+        }
+
+        // Otherwise, this may be the ACK that completes a handshake we answered with a SYN cookie.
+        if header.ack && !header.syn && !header.rst {
+            let remote_isn: SeqNumber = header.seq_num - SeqNumber::from(1);
+            let local_isn: SeqNumber = header.ack_num - SeqNumber::from(1);
+            let now: Instant = self.clock.now();
+            let cookie: SynCookie = match self.isn_generator.check_cookie(&self.local, &remote, remote_isn, local_isn, now) {
+                Some(cookie) => cookie,
+                None => return Err(Fail::new(EBADMSG, "invalid flags")),
             };
-            let remote_window_size = (header_window_size)
-                .checked_shl(remote_window_scale as u32)
-                .expect("TODO: Window size overflow")
-                .try_into()
-                .expect("TODO: Window size overflow");
-            let local_window_size = (self.tcp_config.get_receive_window_size() as u32)
-                .checked_shl(local_window_scale as u32)
-                .expect("TODO: Window size overflow");
-            info!(
-                "Window sizes: local {}, remote {}",
-                local_window_size, remote_window_size
-            );
-            info!(
-                "Window scale: local {}, remote {}",
-                local_window_scale, remote_window_scale
-            );
-
-            self.inflight.remove(&remote);
-            let cb = Box::into_raw(Box::new(ControlBlock::new(
-                qd,
-                self.local,
-                remote,
-                eth_header.dst_addr(),
-                eth_header.src_addr(),
-                self.tcp_config.clone(),
-                remote_isn + SeqNumber::from(1),
-                self.tcp_config.get_ack_delay_timeout(),
-                local_window_size,
-                local_window_scale,
-                local_isn + SeqNumber::from(1),
-                remote_window_size,
-                remote_window_scale,
-                mss,
-                congestion_control::None::new,
-                None,
-            )));
-            return Ok(Some(cb));
+            debug!("Received ACK with valid SYN cookie: {:?}", cookie);
+
+            // Our peer is scaling its window already, so we take it from this segment rather than from the SYN.
+            let accept: InflightAccept = InflightAccept {
+                local_isn,
+                remote_isn,
+                header_window_size: header.window_size,
+                remote_window_scale: cookie.window_scale,
+                mss: cookie.mss,
+                sack_permitted: cookie.sack_permitted,
+                timestamps: None,
+                ecn: false,
+                eth_header: eth_header.clone(),
+                ip_header: *ip_header,
+                deadline: now,
+                retries: 0,
+            };
+            return Ok(Some(self.establish(qd, eth_header, remote, accept, header)));
         }
 
         // Otherwise, start a new connection.
@@ -227,31 +269,147 @@ impl<const N: usize> PassiveSocket<N> {
             return Err(Fail::new(EBADMSG, "invalid flags"));
         }
         debug!("Received SYN: {:?}", header);
+        let remote_isn = header.seq_num;
+
+        let mut remote_window_scale = None;
+        let mut mss = FALLBACK_MSS;
+        let mut sack_permitted = false;
+        let mut remote_timestamp = None;
+        for option in header.iter_options() {
+            match option {
+                TcpOptions2::WindowScale(w) => {
+                    info!("Received window scale: {:?}", w);
+                    remote_window_scale = Some(*w);
+                },
+                TcpOptions2::MaximumSegmentSize(m) => {
+                    info!("Received advertised MSS: {}", m);
+                    mss = *m as usize;
+                },
+                TcpOptions2::SelectiveAcknowlegementPermitted => {
+                    info!("Received SACK permitted");
+                    sack_permitted = self.tcp_config.get_sack();
+                },
+                TcpOptions2::Timestamp { sender_timestamp, .. } => {
+                    info!("Received timestamp: {}", sender_timestamp);
+                    if self.tcp_config.get_timestamps() {
+                        remote_timestamp = Some(*sender_timestamp);
+                    }
+                },
+                _ => continue,
+            }
+        }
+
+        // Our peer asks for ECN with an ECN-setup SYN.  We agree by setting ECE alone in our SYN+ACK.
+        let ecn: bool = self.tcp_config.get_ecn() && header.is_ecn_setup_syn();
+
         if inflight_len + self.ready.borrow().len() >= self.max_backlog {
-            // TODO: Should we send a RST here?
-            return Err(Fail::new(ECONNREFUSED, "connection refused"));
+            // The backlog is full, so answer with a SYN cookie instead of keeping any state for this connection.
+            // Timestamps and ECN cannot be carried by the cookie, so we do not negotiate them (RFC 4987 Section 3.6).
+            let cookie: SynCookie = SynCookie {
+                mss,
+                window_scale: remote_window_scale,
+                sack_permitted,
+            };
+            let now: Instant = self.clock.now();
+            let local_isn: SeqNumber = self.isn_generator.generate_cookie(&self.local, &remote, remote_isn, cookie, now);
+            debug!("Backlog is full, answering with a SYN cookie: {:?}", cookie);
+            let accept: InflightAccept = InflightAccept {
+                local_isn,
+                remote_isn,
+                header_window_size: header.window_size,
+                remote_window_scale,
+                mss,
+                sack_permitted,
+                timestamps: None,
+                ecn: false,
+                eth_header: eth_header.clone(),
+                ip_header: *ip_header,
+                deadline: now,
+                retries: 0,
+            };
+            self.send_syn_ack(remote, &accept);
+            return Ok(None);
         }
         let local_isn = self.isn_generator.generate(&self.local, &remote);
-        let remote_isn = header.seq_num;
 
-        let mut tcp_hdr = TcpHeader::new(header.dst_port, header.src_port);
+        // Our timestamp clock starts at the ISN, which is already unpredictable (RFC 7323 Section 5.4).
+        let now: Instant = self.clock.now();
+        let timestamps = remote_timestamp.map(|tsval| Timestamps::new(now, u32::from(local_isn), tsval));
+        let accept = InflightAccept {
+            local_isn,
+            remote_isn,
+            header_window_size: header.window_size,
+            remote_window_scale,
+            mss,
+            sack_permitted,
+            timestamps,
+            ecn,
+            eth_header: eth_header.clone(),
+            ip_header: *ip_header,
+            deadline: now + self.tcp_config.get_handshake_timeout(),
+            retries: self.tcp_config.get_handshake_retries(),
+        };
+        self.send_syn_ack(remote, &accept);
+        self.inflight.insert(remote, accept);
+        Ok(None)
+    }
+
+    /// Answers the SYN of `remote` with a SYN+ACK that offers what `accept` negotiated.
+    fn send_syn_ack(&self, remote: SocketAddr, accept: &InflightAccept) {
+        let InflightAccept {
+            local_isn,
+            remote_isn,
+            remote_window_scale,
+            sack_permitted,
+            timestamps,
+            ecn,
+            eth_header,
+            ip_header,
+            ..
+        } = accept;
+
+        let mut tcp_hdr = TcpHeader::new(self.local.port(), remote.port());
         tcp_hdr.syn = true;
-        tcp_hdr.seq_num = local_isn;
+        tcp_hdr.seq_num = *local_isn;
         tcp_hdr.ack = true;
-        tcp_hdr.ack_num = remote_isn + SeqNumber::from(1);
-        tcp_hdr.window_size = 0xffff as u16;
+        tcp_hdr.ack_num = *remote_isn + SeqNumber::from(1);
+        // The window of a SYN segment is never scaled (RFC 7323 Section 2.2).
+        tcp_hdr.window_size = self.tcp_config.get_receive_window_size();
+        if *ecn {
+            tcp_hdr.ece = true;
+            info!("Accepting ECN");
+        }
 
-        let mss = 0xffff as u16;
+        let mss: u16 = self.tcp_config.get_advertised_mss() as u16;
         tcp_hdr.push_option(TcpOptions2::MaximumSegmentSize(mss));
         info!("Advertising MSS: {}", mss);
 
-        tcp_hdr.push_option(TcpOptions2::WindowScale(0));
-        info!("Advertising window scale: {}", 0);
+        // We may only scale our window if our peer offered to scale its own (RFC 7323 Section 2.2), which is also
+        // when `establish()` scales it.
+        if remote_window_scale.is_some() {
+            let window_scale: u8 = self.tcp_config.get_window_scale();
+            tcp_hdr.push_option(TcpOptions2::WindowScale(window_scale));
+            info!("Advertising window scale: {}", window_scale);
+        }
+
+        if *sack_permitted {
+            tcp_hdr.push_option(TcpOptions2::SelectiveAcknowlegementPermitted);
+            info!("Advertising SACK permitted");
+        }
+
+        if let Some(timestamps) = timestamps {
+            tcp_hdr.push_option(TcpOptions2::Timestamp {
+                sender_timestamp: timestamps.now(self.clock.now()),
+                echo_timestamp: timestamps.get_recent(),
+            });
+            info!("Advertising timestamps");
+        }
 
         debug!("Sending SYN+ACK: {:?}", tcp_hdr);
+        let ip_hdr: IpHeader = IpHeader::new(ip_header.get_dest_addr(), ip_header.get_src_addr(), IpProtocol::TCP);
         let segment = TcpSegment {
-            ethernet2_hdr: Ethernet2Header::new(eth_header.src_addr(), eth_header.dst_addr(), EtherType2::Ipv4),
-            ipv4_hdr: Ipv4Header::new(ip_header.get_dest_addr(), ip_header.get_src_addr(), IpProtocol::TCP),
+            ethernet2_hdr: Ethernet2Header::new(eth_header.src_addr(), eth_header.dst_addr(), ip_hdr.get_ether_type()),
+            ip_hdr,
             tcp_hdr,
             data: None,
             tx_checksum_offload: true,
@@ -262,30 +420,83 @@ impl<const N: usize> PassiveSocket<N> {
             Some(rt) => rt.transmit(Box::new(segment)),
             None => todo!(),
         }
+    }
 
-        let mut remote_window_scale = None;
-        let mut mss = FALLBACK_MSS;
-        for option in header.iter_options() {
-            match option {
-                TcpOptions2::WindowScale(w) => {
-                    info!("Received window scale: {:?}", w);
-                    remote_window_scale = Some(*w);
-                },
-                TcpOptions2::MaximumSegmentSize(m) => {
-                    info!("Received advertised MSS: {}", m);
-                    mss = *m as usize;
-                },
-                _ => continue,
-            }
-        }
-        let accept = InflightAccept {
+    /// Sets up the control block of a connection whose handshake was completed by `header`.
+    fn establish(
+        &self,
+        qd: QDesc,
+        eth_header: &Ethernet2Header,
+        remote: SocketAddr,
+        accept: InflightAccept,
+        header: &TcpHeader,
+    ) -> *mut ControlBlock<N> {
+        let InflightAccept {
             local_isn,
             remote_isn,
-            header_window_size: header.window_size,
+            header_window_size,
             remote_window_scale,
             mss,
+            sack_permitted,
+            mut timestamps,
+            ecn,
+            ..
+        } = accept;
+
+        let (local_window_scale, remote_window_scale) = match remote_window_scale {
+            Some(w) => (self.tcp_config.get_window_scale() as u32, w),
+            None => (0, 0),
         };
-        self.inflight.insert(remote, accept);
-        Ok(None)
+        let remote_window_size = (header_window_size)
+            .checked_shl(remote_window_scale as u32)
+            .expect("TODO: Window size overflow")
+            .try_into()
+            .expect("TODO: Window size overflow");
+        let local_window_size = (self.tcp_config.get_receive_window_size() as u32)
+            .checked_shl(local_window_scale as u32)
+            .expect("TODO: Window size overflow");
+        info!(
+            "Window sizes: local {}, remote {}",
+            local_window_size, remote_window_size
+        );
+        info!(
+            "Window scale: local {}, remote {}",
+            local_window_scale, remote_window_scale
+        );
+
+        // The handshake ACK carries the first timestamp to echo.
+        if let Some(ref mut timestamps) = timestamps {
+            for option in header.iter_options() {
+                if let TcpOptions2::Timestamp { sender_timestamp, .. } = option {
+                    timestamps.update_recent(*sender_timestamp, self.clock.now());
+                }
+            }
+        }
+
+        // Connections answer to the link-layer address that their handshake came from, rather than to the one that ARP
+        // resolves for the next hop of their peer, which would take a query on the path that sets them up.  For an
+        // off-link peer, that is the router which forwarded the handshake, so this only holds as long as routes are
+        // symmetric.
+        Box::into_raw(Box::new(ControlBlock::new(
+            qd,
+            self.local,
+            remote,
+            eth_header.dst_addr(),
+            eth_header.src_addr(),
+            self.tcp_config.clone(),
+            remote_isn + SeqNumber::from(1),
+            self.tcp_config.get_ack_delay_timeout(),
+            local_window_size,
+            local_window_scale,
+            local_isn + SeqNumber::from(1),
+            remote_window_size,
+            remote_window_scale,
+            mss,
+            sack_permitted,
+            timestamps,
+            ecn,
+            congestion_control::constructor(self.tcp_config.get_congestion_control()),
+            None,
+        )))
     }
 }
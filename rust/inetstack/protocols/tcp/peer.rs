@@ -12,7 +12,11 @@ use super::{
 };
 use crate::{
     inetstack::protocols::{
-        ipv4::Ipv4Header,
+        icmpv4::Icmpv4Error,
+        ip::{
+            IpHeader,
+            IpProtocol,
+        },
         tcp::{
             established::ControlBlock,
             operations::{
@@ -35,7 +39,8 @@ use crate::{
 use ::std::{
     cell::RefCell,
     net::{
-        SocketAddrV4,
+        IpAddr,
+        SocketAddr,
     },
     rc::Rc,
     task::{
@@ -53,7 +58,7 @@ use crate::timer;
 //==============================================================================
 
 pub enum Socket<const N: usize> {
-    Inactive(Option<SocketAddrV4>),
+    Inactive(Option<SocketAddr>),
     Listening(PassiveSocket<N>),
     // Connecting(ActiveOpenSocket<N>),
     Established(EstablishedSocket<N>),
@@ -62,8 +67,8 @@ pub enum Socket<const N: usize> {
 
 #[derive(PartialEq, Eq, Hash)]
 pub enum SocketId {
-    Active(SocketAddrV4, SocketAddrV4),
-    Passive(SocketAddrV4),
+    Active(SocketAddr, SocketAddr),
+    Passive(SocketAddr),
 }
 
 //==============================================================================
@@ -97,14 +102,23 @@ impl<const N: usize> TcpPeer<N> {
         Err(Fail::new(libc::ENOTSUP, "Not supported"))
     }
 
-    pub fn bind(&self, _qd: QDesc, mut _addr: SocketAddrV4) -> Result<(), Fail> {
+    pub fn bind(&self, _qd: QDesc, mut _addr: SocketAddr) -> Result<(), Fail> {
         Err(Fail::new(libc::ENOTSUP, "Not supported"))
     }
 
-    pub fn receive(&self, cb: *mut ControlBlock<RECEIVE_BATCH_SIZE>, ip_header: &Ipv4Header, buf: DemiBuffer) -> Result<(), Fail> {
+    pub fn receive(&self, cb: *mut ControlBlock<RECEIVE_BATCH_SIZE>, ip_header: &IpHeader, buf: DemiBuffer) -> Result<(), Fail> {
         self.inner.borrow().receive(cb, ip_header, buf)
     }
 
+    /// Hands an ICMP error about one of our segments over to the connection that sent it.
+    pub fn receive_icmp_error(&self, cb: *mut ControlBlock<RECEIVE_BATCH_SIZE>, error: &Icmpv4Error) -> Result<(), Fail> {
+        if error.get_protocol() != IpProtocol::TCP {
+            return Err(Fail::new(libc::ENOTSUP, "ICMP error is not about TCP"));
+        }
+        unsafe { (*cb).receive_icmp_error(error) };
+        Ok(())
+    }
+
     // Marks the target socket as passive.
     pub fn listen(&self, _qd: QDesc, _backlog: usize) -> Result<(), Fail> {
         Err(Fail::new(libc::ENOTSUP, "Not supported"))
@@ -125,11 +139,11 @@ impl<const N: usize> TcpPeer<N> {
         _qd: QDesc,
         _new_qd: QDesc,
         _ctx: &mut Context,
-    ) -> Poll<Result<(QDesc, SocketAddrV4), Fail>> {
+    ) -> Poll<Result<(QDesc, SocketAddr), Fail>> {
         Poll::Ready(Err(Fail::new(libc::ENOTSUP, "Not supported")))
     }
 
-    // pub fn connect(&self, _qd: QDesc, _remote: SocketAddrV4) -> Result<ConnectFuture<N>, Fail> {
+    // pub fn connect(&self, _qd: QDesc, _remote: SocketAddr) -> Result<ConnectFuture<N>, Fail> {
     //     Err(Fail::new(libc::ENOTSUP, "Not supported"))
     // }
 
@@ -177,7 +191,7 @@ impl<const N: usize> TcpPeer<N> {
         unsafe { Ok((*cb).rto()) }
     }
 
-    pub fn endpoints(&self,  cb: *mut ControlBlock<N>) -> Result<(SocketAddrV4, SocketAddrV4), Fail> {
+    pub fn endpoints(&self,  cb: *mut ControlBlock<N>) -> Result<(SocketAddr, SocketAddr), Fail> {
         unsafe { Ok(((*cb).get_local(), (*cb).get_remote())) }
     }
 }
@@ -191,17 +205,21 @@ impl<const N: usize> Inner<N> {
         }
     }
 
-    fn receive(&self, cb: *mut ControlBlock<RECEIVE_BATCH_SIZE>, ip_hdr: &Ipv4Header, buf: DemiBuffer) -> Result<(), Fail> {
+    fn receive(&self, cb: *mut ControlBlock<RECEIVE_BATCH_SIZE>, ip_hdr: &IpHeader, buf: DemiBuffer) -> Result<(), Fail> {
         let (mut tcp_hdr, data) = TcpHeader::parse(ip_hdr, buf, self.tcp_config.get_rx_checksum_offload())?;
         debug!("TCP received {:?}", tcp_hdr);
-        // let local = SocketAddrV4::new(ip_hdr.get_dest_addr(), tcp_hdr.dst_port);
-        let remote = SocketAddrV4::new(ip_hdr.get_src_addr(), tcp_hdr.src_port);
+        // let local = SocketAddr::new(ip_hdr.get_dest_addr(), tcp_hdr.dst_port);
+        let remote = SocketAddr::new(ip_hdr.get_src_addr(), tcp_hdr.src_port);
 
-        if remote.ip().is_broadcast() || remote.ip().is_multicast() || remote.ip().is_unspecified() {
+        let is_broadcast: bool = match remote.ip() {
+            IpAddr::V4(addr) => addr.is_broadcast(),
+            IpAddr::V6(_) => false,
+        };
+        if is_broadcast || remote.ip().is_multicast() || remote.ip().is_unspecified() {
             return Err(Fail::new(libc::EINVAL, "invalid address type"));
         }
 
-        unsafe { (*cb).receive(&mut tcp_hdr, data) };
+        unsafe { (*cb).receive(&mut tcp_hdr, data, ip_hdr.get_ecn()) };
 
         Ok(())
     }
@@ -7,9 +7,18 @@
 
 use super::peer::Socket;
 use crate::runtime::{
+    fail::Fail,
+    network::{
+        config::TcpConfig,
+        socket::{
+            SocketOption,
+            SocketOptionName,
+        },
+    },
     queue::IoQueue,
     QType,
 };
+use ::std::net::SocketAddr;
 
 //======================================================================================================================
 // Structures
@@ -18,6 +27,8 @@ use crate::runtime::{
 /// Per-queue metadata for the TCP socket.
 pub struct TcpQueue<const N: usize> {
     socket: Socket<N>,
+    /// Options for a socket that is not listening nor connected yet.
+    tcp_config: TcpConfig,
 }
 
 //======================================================================================================================
@@ -26,11 +37,22 @@ pub struct TcpQueue<const N: usize> {
 
 impl<const N: usize> TcpQueue<N> {
     pub fn new() -> Self {
+        Self::with_config(TcpConfig::default())
+    }
+
+    /// Creates a queue whose socket starts with the given options.
+    pub fn with_config(tcp_config: TcpConfig) -> Self {
         Self {
             socket: Socket::Inactive(None),
+            tcp_config,
         }
     }
 
+    /// Gets the options that a listening or connecting socket should start with.
+    pub fn get_tcp_config(&self) -> &TcpConfig {
+        &self.tcp_config
+    }
+
     /// Get/borrow reference to underlying TCP socket data structure.
     pub fn get_socket(&self) -> &Socket<N> {
         &self.socket
@@ -45,6 +67,45 @@ impl<const N: usize> TcpQueue<N> {
     pub fn set_socket(&mut self, s: Socket<N>) {
         self.socket = s;
     }
+
+    /// Sets a socket option.
+    pub fn setsockopt(&mut self, option: SocketOption) -> Result<(), Fail> {
+        match &mut self.socket {
+            Socket::Inactive(_) => {
+                self.tcp_config = self.tcp_config.clone().set_option(option)?;
+                Ok(())
+            },
+            Socket::Listening(socket) => socket.set_option(option),
+            Socket::Established(socket) | Socket::Closing(socket) => socket.set_option(option),
+        }
+    }
+
+    /// Gets the value of a socket option.
+    pub fn getsockopt(&self, name: SocketOptionName) -> SocketOption {
+        match &self.socket {
+            Socket::Inactive(_) => self.tcp_config.get_option(name),
+            Socket::Listening(socket) => socket.get_option(name),
+            Socket::Established(socket) | Socket::Closing(socket) => socket.get_option(name),
+        }
+    }
+
+    /// Gets the local address of the socket.
+    pub fn getsockname(&self) -> Result<SocketAddr, Fail> {
+        match &self.socket {
+            Socket::Inactive(Some(local)) => Ok(*local),
+            Socket::Inactive(None) => Err(Fail::new(libc::EINVAL, "socket is not bound")),
+            Socket::Listening(socket) => Ok(socket.endpoint()),
+            Socket::Established(socket) | Socket::Closing(socket) => Ok(socket.endpoints().0),
+        }
+    }
+
+    /// Gets the remote address of the socket.
+    pub fn getpeername(&self) -> Result<SocketAddr, Fail> {
+        match &self.socket {
+            Socket::Established(socket) | Socket::Closing(socket) => Ok(socket.endpoints().1),
+            _ => Err(Fail::new(libc::ENOTCONN, "socket is not connected")),
+        }
+    }
 }
 
 //======================================================================================================================
@@ -4,8 +4,10 @@
 use crate::{
     inetstack::protocols::{
         ethernet2::Ethernet2Header,
-        ip::IpProtocol,
-        ipv4::Ipv4Header,
+        ip::{
+            IpHeader,
+            IpProtocol,
+        },
         tcp::SeqNumber,
     },
     runtime::{
@@ -30,7 +32,7 @@ pub const MAX_TCP_OPTIONS: usize = 5;
 
 pub struct TcpSegment {
     pub ethernet2_hdr: Ethernet2Header,
-    pub ipv4_hdr: Ipv4Header,
+    pub ip_hdr: IpHeader,
     pub tcp_hdr: TcpHeader,
     pub data: Option<DemiBuffer>,
     pub tx_checksum_offload: bool,
@@ -38,7 +40,7 @@ pub struct TcpSegment {
 
 impl PacketBuf for TcpSegment {
     fn header_size(&self) -> usize {
-        self.ethernet2_hdr.compute_size() + self.ipv4_hdr.compute_size() + self.tcp_hdr.compute_size()
+        self.ethernet2_hdr.compute_size() + self.ip_hdr.compute_size() + self.tcp_hdr.compute_size()
     }
 
     fn body_size(&self) -> usize {
@@ -50,7 +52,7 @@ impl PacketBuf for TcpSegment {
 
     fn write_header(&self, buf: &mut [u8]) {
         let eth_hdr_size: usize = self.ethernet2_hdr.compute_size();
-        let ipv4_hdr_size: usize = self.ipv4_hdr.compute_size();
+        let ip_hdr_size: usize = self.ip_hdr.compute_size();
         let tcp_hdr_size: usize = self.tcp_hdr.compute_size();
         let mut cur_pos: usize = 0;
 
@@ -58,10 +60,10 @@ impl PacketBuf for TcpSegment {
             .serialize(&mut buf[cur_pos..(cur_pos + eth_hdr_size)]);
         cur_pos += eth_hdr_size;
 
-        let ipv4_payload_len = tcp_hdr_size + self.body_size();
-        self.ipv4_hdr
-            .serialize(&mut buf[cur_pos..(cur_pos + ipv4_hdr_size)], ipv4_payload_len);
-        cur_pos += ipv4_hdr_size;
+        let ip_payload_len = tcp_hdr_size + self.body_size();
+        self.ip_hdr
+            .serialize(&mut buf[cur_pos..(cur_pos + ip_hdr_size)], ip_payload_len);
+        cur_pos += ip_hdr_size;
 
         let payload: &[u8] = match &self.data {
             Some(buf) => &buf[..],
@@ -69,7 +71,7 @@ impl PacketBuf for TcpSegment {
         };
         self.tcp_hdr.serialize(
             &mut buf[cur_pos..(cur_pos + tcp_hdr_size)],
-            &self.ipv4_hdr,
+            &self.ip_hdr,
             payload,
             self.tx_checksum_offload,
         );
@@ -223,7 +225,7 @@ impl TcpHeader {
     }
 
     pub fn parse(
-        ipv4_header: &Ipv4Header,
+        ip_header: &IpHeader,
         mut buf: DemiBuffer,
         rx_checksum_offload: bool,
     ) -> Result<(Self, DemiBuffer), Fail> {
@@ -264,7 +266,7 @@ impl TcpHeader {
 
         if !rx_checksum_offload {
             let checksum: u16 = u16::from_be_bytes([hdr_buf[16], hdr_buf[17]]);
-            if checksum != tcp_checksum(ipv4_header, hdr_buf, data_buf) {
+            if checksum != tcp_checksum(ip_header, hdr_buf, data_buf) {
                 return Err(Fail::new(EBADMSG, "TCP checksum mismatch"));
             }
         }
@@ -388,7 +390,7 @@ impl TcpHeader {
         Ok((header, buf))
     }
 
-    pub fn serialize(&self, buf: &mut [u8], ipv4_hdr: &Ipv4Header, data: &[u8], tx_checksum_offload: bool) {
+    pub fn serialize(&self, buf: &mut [u8], ip_hdr: &IpHeader, data: &[u8], tx_checksum_offload: bool) {
         let fixed_buf: &mut [u8; MIN_TCP_HEADER_SIZE] = (&mut buf[..MIN_TCP_HEADER_SIZE]).try_into().unwrap();
         fixed_buf[0..2].copy_from_slice(&self.src_port.to_be_bytes());
         fixed_buf[2..4].copy_from_slice(&self.dst_port.to_be_bytes());
@@ -447,7 +449,7 @@ impl TcpHeader {
 
         // Alright, we've fully filled out the header, time to compute the checksum.
         if !tx_checksum_offload {
-            let checksum: u16 = tcp_checksum(ipv4_hdr, &buf[..], data);
+            let checksum: u16 = tcp_checksum(ip_hdr, &buf[..], data);
             buf[16..18].copy_from_slice(&checksum.to_be_bytes());
         } else {
             buf[16] = 0;
@@ -471,6 +473,11 @@ impl TcpHeader {
         size.wrapping_add(3) & !0x3
     }
 
+    /// Checks if this is an ECN-setup SYN, i.e. a SYN asking to use ECN on the connection (RFC 3168 Section 6.1.1).
+    pub fn is_ecn_setup_syn(&self) -> bool {
+        self.syn && !self.ack && self.ece && self.cwr
+    }
+
     pub fn iter_options(&self) -> impl Iterator<Item = &TcpOptions2> {
         (0..self.num_options).map(move |i| &self.option_list[i])
     }
@@ -481,25 +488,12 @@ impl TcpHeader {
     }
 }
 
-fn tcp_checksum(ipv4_header: &Ipv4Header, header: &[u8], data: &[u8]) -> u16 {
+fn tcp_checksum(ip_header: &IpHeader, header: &[u8], data: &[u8]) -> u16 {
     let mut state: u32 = 0xffff;
 
-    // First, fold in a "pseudo-IP" header of...
-    // 1) Source address (4 bytes)
-    let src_octets: [u8; 4] = ipv4_header.get_src_addr().octets();
-    state += u16::from_be_bytes([src_octets[0], src_octets[1]]) as u32;
-    state += u16::from_be_bytes([src_octets[2], src_octets[3]]) as u32;
-
-    // 2) Destination address (4 bytes)
-    let dst_octets: [u8; 4] = ipv4_header.get_dest_addr().octets();
-    state += u16::from_be_bytes([dst_octets[0], dst_octets[1]]) as u32;
-    state += u16::from_be_bytes([dst_octets[2], dst_octets[3]]) as u32;
-
-    // 3) 1 byte of zeros and TCP protocol number (1 byte)
-    state += u16::from_be_bytes([0, IpProtocol::TCP as u8]) as u32;
-
-    // 4) TCP segment length (2 bytes)
-    state += (header.len() + data.len()) as u32;
+    // First, fold in a "pseudo-IP" header of source and destination addresses, TCP protocol number and TCP segment
+    // length.
+    state += ip_header.pseudo_header_sum(IpProtocol::TCP, header.len() + data.len());
 
     let fixed_header: &[u8; MIN_TCP_HEADER_SIZE] = header[..MIN_TCP_HEADER_SIZE].try_into().unwrap();
 
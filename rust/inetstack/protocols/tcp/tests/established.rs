@@ -0,0 +1,401 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+use super::{
+    bob_ack,
+    connection_setup,
+    new_clock,
+    parse_segment,
+    ALICE_ISN,
+};
+use crate::{
+    inetstack::{
+        protocols::{
+            ip::Ecn,
+            tcp::{
+                established::{
+                    ControlBlock,
+                    State,
+                },
+                segment::TcpHeader,
+                SeqNumber,
+            },
+        },
+        test_helpers::{
+            TestRuntime,
+            ALICE_IPV4,
+            ALICE_MAC,
+        },
+    },
+    runtime::{
+        memory::DemiBuffer,
+        network::{
+            config::TcpConfig,
+            consts::RECEIVE_BATCH_SIZE,
+            socket::SocketOption,
+        },
+        timer::TimerRc,
+    },
+};
+use ::anyhow::Result;
+use ::std::{
+    sync::Arc,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+//=============================================================================
+
+/// Cooks a buffer.
+fn cook_buffer(size: usize, stamp: Option<u8>) -> DemiBuffer {
+    assert!(size < u16::MAX as usize);
+    let mut buf: DemiBuffer = DemiBuffer::new(size as u16);
+    for i in 0..size {
+        buf[i] = stamp.unwrap_or(i as u8);
+    }
+    buf
+}
+
+/// Advances the clock until the persist timer of `cb`, which must be armed, expires and services the timers, as a
+/// worker does when the dispatcher hands it the connection.  Returns when the timer fired.
+fn fire_persist_timer(clock: &TimerRc, cb: &ControlBlock<RECEIVE_BATCH_SIZE>) -> Result<Instant> {
+    let now: Instant = match cb.get_persist_deadline() {
+        Some(deadline) => deadline,
+        None => anyhow::bail!("persist timer should be armed"),
+    };
+    // Workers publish the timers after every push and receive, which tests call directly.
+    cb.publish_timers();
+    crate::ensure_eq!(cb.has_expired_timers(now), true);
+    clock.advance_clock(now);
+    cb.poll_timers();
+    Ok(now)
+}
+
+//=============================================================================
+
+/// Tests that a connection recovers from a lost window update: our peer's window is closed, the segment that opens it
+/// gets lost, and a window probe brings the update back.
+#[test]
+fn test_lost_window_update() -> Result<()> {
+    let now: Instant = Instant::now();
+    let rt: Arc<TestRuntime> = Arc::new(TestRuntime::new(ALICE_MAC, ALICE_IPV4));
+    let clock: TimerRc = new_clock(now);
+    let cb: Box<ControlBlock<RECEIVE_BATCH_SIZE>> = connection_setup(&rt, &clock, TcpConfig::default(), 0);
+    let send_next: SeqNumber = SeqNumber::from(ALICE_ISN + 1);
+
+    // Our peer's window is closed, so the data has to wait.
+    cb.send(cook_buffer(16, None))?;
+    crate::ensure_eq!(rt.pop_frame_unchecked().is_none(), true);
+    crate::ensure_eq!(cb.get_persist_deadline(), Some(now + cb.rto()));
+
+    // Our peer makes room and opens its window, but the update gets lost on its way.
+    let _lost_update: TcpHeader = bob_ack(send_next, 1024);
+
+    // The persist timer probes the window with the first byte of data.
+    let now: Instant = fire_persist_timer(&clock, &cb)?;
+    let (header, payload): (TcpHeader, DemiBuffer) = parse_segment(rt.pop_frame())?;
+    crate::ensure_eq!(header.seq_num, send_next);
+    crate::ensure_eq!(payload[..], [0]);
+    crate::ensure_eq!(rt.pop_frame_unchecked().is_none(), true);
+
+    // Our peer accepts the probe, and advertises its open window again.
+    let now: Instant = now + Duration::from_millis(10);
+    clock.advance_clock(now);
+    let mut ack: TcpHeader = bob_ack(send_next + SeqNumber::from(1), 1024);
+    cb.receive(&mut ack, DemiBuffer::new(0), Ecn::NotEct);
+
+    // The rest of the data goes out, and probing stops.
+    let (header, payload): (TcpHeader, DemiBuffer) = parse_segment(rt.pop_frame())?;
+    crate::ensure_eq!(header.seq_num, send_next + SeqNumber::from(1));
+    crate::ensure_eq!(payload[..], cook_buffer(16, None)[1..]);
+    crate::ensure_eq!(rt.pop_frame_unchecked().is_none(), true);
+    crate::ensure_eq!(cb.get_persist_deadline(), None);
+
+    Ok(())
+}
+
+/// Tests that unanswered window probes keep going with exponential backoff, and keep probing with the same byte.
+#[test]
+fn test_window_probe_backoff() -> Result<()> {
+    let now: Instant = Instant::now();
+    let rt: Arc<TestRuntime> = Arc::new(TestRuntime::new(ALICE_MAC, ALICE_IPV4));
+    let clock: TimerRc = new_clock(now);
+    let cb: Box<ControlBlock<RECEIVE_BATCH_SIZE>> = connection_setup(&rt, &clock, TcpConfig::default(), 0);
+    let send_next: SeqNumber = SeqNumber::from(ALICE_ISN + 1);
+    let rto: Duration = cb.rto();
+
+    cb.send(cook_buffer(16, None))?;
+    let mut timeout: Duration = rto;
+    for _ in 0..3 {
+        let now: Instant = fire_persist_timer(&clock, &cb)?;
+        let (header, payload): (TcpHeader, DemiBuffer) = parse_segment(rt.pop_frame())?;
+        crate::ensure_eq!(header.seq_num, send_next);
+        crate::ensure_eq!(payload[..], [0]);
+
+        // Our peer's window is still closed, so it does not take the byte.
+        let mut ack: TcpHeader = bob_ack(send_next, 0);
+        cb.receive(&mut ack, DemiBuffer::new(0), Ecn::NotEct);
+        crate::ensure_eq!(rt.pop_frame_unchecked().is_none(), true);
+
+        timeout *= 2;
+        crate::ensure_eq!(cb.get_persist_deadline(), Some(now + timeout));
+    }
+
+    Ok(())
+}
+
+/// Tests that an abort that another core requested is carried out by the next timer sweep, and not before.
+#[test]
+fn test_requested_abort() -> Result<()> {
+    let now: Instant = Instant::now();
+    let rt: Arc<TestRuntime> = Arc::new(TestRuntime::new(ALICE_MAC, ALICE_IPV4));
+    let clock: TimerRc = new_clock(now);
+    let cb: Box<ControlBlock<RECEIVE_BATCH_SIZE>> = connection_setup(&rt, &clock, TcpConfig::default(), 1024);
+    crate::ensure_eq!(cb.has_expired_timers(now), false);
+
+    // The dispatcher only flags the connection, so that it gets handed to a worker.
+    cb.request_abort();
+    crate::ensure_eq!(cb.get_state(), State::Established);
+    crate::ensure_eq!(cb.has_expired_timers(now), true);
+
+    cb.poll_timers();
+    crate::ensure_eq!(cb.get_state(), State::Closed);
+    crate::ensure_eq!(cb.has_expired_timers(now), false);
+    crate::ensure_eq!(rt.pop_frame_unchecked().is_none(), true);
+    match cb.send(cook_buffer(16, None)) {
+        Err(e) => crate::ensure_eq!(e.errno, libc::ECONNRESET),
+        Ok(()) => anyhow::bail!("send should fail on an aborted connection"),
+    }
+
+    Ok(())
+}
+
+/// Tests that data that the pacer held back goes out once the pacing timer expires, even though nothing is in flight
+/// for an ACK to clock it out.
+#[test]
+fn test_paced_data_goes_out_on_timer() -> Result<()> {
+    let now: Instant = Instant::now();
+    let rt: Arc<TestRuntime> = Arc::new(TestRuntime::new(ALICE_MAC, ALICE_IPV4));
+    let clock: TimerRc = new_clock(now);
+    // 500 bytes at 1 MB/s take half a millisecond.
+    let tcp_config: TcpConfig = TcpConfig::default()
+        .set_nodelay(true)
+        .set_pacing(true)
+        .set_max_pacing_rate(Some(1_000_000));
+    let cb: Box<ControlBlock<RECEIVE_BATCH_SIZE>> = connection_setup(&rt, &clock, tcp_config, 1024);
+    let send_next: SeqNumber = SeqNumber::from(ALICE_ISN + 1);
+
+    // The first segment leaves right away, and our peer acknowledges it before the next one may leave.
+    cb.send(cook_buffer(500, Some(1)))?;
+    let (header, _): (TcpHeader, DemiBuffer) = parse_segment(rt.pop_frame())?;
+    crate::ensure_eq!(header.seq_num, send_next);
+    let now: Instant = now + Duration::from_micros(100);
+    clock.advance_clock(now);
+    let send_next: SeqNumber = send_next + SeqNumber::from(500);
+    let mut ack: TcpHeader = bob_ack(send_next, 1024);
+    cb.receive(&mut ack, DemiBuffer::new(0), Ecn::NotEct);
+    crate::ensure_eq!(rt.pop_frame_unchecked().is_none(), true);
+
+    // The next segment is paced, and nothing else would send it.
+    cb.send(cook_buffer(500, Some(2)))?;
+    cb.publish_timers();
+    crate::ensure_eq!(rt.pop_frame_unchecked().is_none(), true);
+    let deadline: Instant = match cb.get_pacing_deadline() {
+        Some(deadline) => deadline,
+        None => anyhow::bail!("pacing timer should be armed"),
+    };
+    crate::ensure_eq!(deadline > now, true);
+    crate::ensure_eq!(cb.has_expired_timers(now), false);
+
+    // The timer sweep sends it once it is due.
+    crate::ensure_eq!(cb.has_expired_timers(deadline), true);
+    clock.advance_clock(deadline);
+    cb.poll_timers();
+    let (header, payload): (TcpHeader, DemiBuffer) = parse_segment(rt.pop_frame())?;
+    crate::ensure_eq!(header.seq_num, send_next);
+    crate::ensure_eq!(payload[..], cook_buffer(500, Some(2))[..]);
+    crate::ensure_eq!(rt.pop_frame_unchecked().is_none(), true);
+    crate::ensure_eq!(cb.get_pacing_deadline(), None);
+
+    Ok(())
+}
+
+/// Tests that a connection that was closed with a linger timeout gets reset once the timeout expires with data that our
+/// peer did not acknowledge (SO_LINGER).
+#[test]
+fn test_linger_timeout() -> Result<()> {
+    let now: Instant = Instant::now();
+    let rt: Arc<TestRuntime> = Arc::new(TestRuntime::new(ALICE_MAC, ALICE_IPV4));
+    let clock: TimerRc = new_clock(now);
+    let linger: Duration = Duration::from_millis(100);
+    let tcp_config: TcpConfig = TcpConfig::default().set_linger(Some(linger));
+    let cb: Box<ControlBlock<RECEIVE_BATCH_SIZE>> = connection_setup(&rt, &clock, tcp_config, 1024);
+
+    // Our peer never acknowledges the data, nor the FIN that follows it.
+    cb.send(cook_buffer(16, None))?;
+    cb.close()?;
+    while rt.pop_frame_unchecked().is_some() {}
+    let deadline: Instant = now + linger;
+    crate::ensure_eq!(cb.get_linger_deadline(), Some(deadline));
+    crate::ensure_eq!(cb.has_expired_timers(deadline - Duration::from_millis(1)), false);
+
+    // The timer sweep resets the connection once the timeout expires.
+    crate::ensure_eq!(cb.has_expired_timers(deadline), true);
+    clock.advance_clock(deadline);
+    cb.poll_timers();
+    let (header, _): (TcpHeader, DemiBuffer) = parse_segment(rt.pop_frame())?;
+    crate::ensure_eq!(header.rst, true);
+    crate::ensure_eq!(cb.get_state(), State::Closed);
+    crate::ensure_eq!(cb.get_linger_deadline(), None);
+    match cb.send(cook_buffer(16, None)) {
+        Err(e) => crate::ensure_eq!(e.errno, libc::ETIMEDOUT),
+        Ok(()) => anyhow::bail!("send should fail on a connection that was reset"),
+    }
+
+    Ok(())
+}
+
+/// Tests that small pushes go out right away by default, each in its own segment, as Nagle's algorithm is off.
+#[test]
+fn test_nodelay_by_default() -> Result<()> {
+    let rt: Arc<TestRuntime> = Arc::new(TestRuntime::new(ALICE_MAC, ALICE_IPV4));
+    let clock: TimerRc = new_clock(Instant::now());
+    let cb: Box<ControlBlock<RECEIVE_BATCH_SIZE>> = connection_setup(&rt, &clock, TcpConfig::default(), 4096);
+    let send_next: SeqNumber = SeqNumber::from(ALICE_ISN + 1);
+
+    for i in 0..3 {
+        cb.send(cook_buffer(16, Some(i)))?;
+        let (header, payload): (TcpHeader, DemiBuffer) = parse_segment(rt.pop_frame())?;
+        crate::ensure_eq!(header.seq_num, send_next + SeqNumber::from(16 * i as u32));
+        crate::ensure_eq!(payload[..], cook_buffer(16, Some(i))[..]);
+    }
+    crate::ensure_eq!(rt.pop_frame_unchecked().is_none(), true);
+
+    Ok(())
+}
+
+/// Tests that Nagle's algorithm holds small pushes back while a partial segment is in flight, and sends them in a
+/// single segment once it is acknowledged.
+#[test]
+fn test_nagle_coalesces_small_pushes() -> Result<()> {
+    let now: Instant = Instant::now();
+    let rt: Arc<TestRuntime> = Arc::new(TestRuntime::new(ALICE_MAC, ALICE_IPV4));
+    let clock: TimerRc = new_clock(now);
+    let tcp_config: TcpConfig = TcpConfig::default().set_nodelay(false);
+    let cb: Box<ControlBlock<RECEIVE_BATCH_SIZE>> = connection_setup(&rt, &clock, tcp_config, 4096);
+    let send_next: SeqNumber = SeqNumber::from(ALICE_ISN + 1);
+
+    // Nothing is in flight, so the first push goes out right away.
+    cb.send(cook_buffer(16, Some(1)))?;
+    let (header, _): (TcpHeader, DemiBuffer) = parse_segment(rt.pop_frame())?;
+    crate::ensure_eq!(header.seq_num, send_next);
+
+    // The next ones wait for it to be acknowledged.
+    cb.send(cook_buffer(16, Some(2)))?;
+    cb.send(cook_buffer(16, Some(3)))?;
+    crate::ensure_eq!(rt.pop_frame_unchecked().is_none(), true);
+
+    clock.advance_clock(now + Duration::from_millis(1));
+    let mut ack: TcpHeader = bob_ack(send_next + SeqNumber::from(16), 4096);
+    cb.receive(&mut ack, DemiBuffer::new(0), Ecn::NotEct);
+    let (header, payload): (TcpHeader, DemiBuffer) = parse_segment(rt.pop_frame())?;
+    crate::ensure_eq!(header.seq_num, send_next + SeqNumber::from(16));
+    crate::ensure_eq!(payload[..16], cook_buffer(16, Some(2))[..]);
+    crate::ensure_eq!(payload[16..], cook_buffer(16, Some(3))[..]);
+    crate::ensure_eq!(rt.pop_frame_unchecked().is_none(), true);
+
+    Ok(())
+}
+
+/// Tests that Nagle's algorithm only waits on partial segments (Minshall's variant), so that a partial segment after
+/// full-sized ones does not stall on the ACK that our peer may delay, and full-sized segments are never held back.
+#[test]
+fn test_nagle_minshall() -> Result<()> {
+    let rt: Arc<TestRuntime> = Arc::new(TestRuntime::new(ALICE_MAC, ALICE_IPV4));
+    let clock: TimerRc = new_clock(Instant::now());
+    let tcp_config: TcpConfig = TcpConfig::default().set_nodelay(false);
+    let cb: Box<ControlBlock<RECEIVE_BATCH_SIZE>> = connection_setup(&rt, &clock, tcp_config, 4096);
+    let mss: usize = cb.get_mss();
+    let send_next: SeqNumber = SeqNumber::from(ALICE_ISN + 1);
+
+    // A full-sized segment does not hold back the partial one after it, although neither is acknowledged.
+    cb.send(cook_buffer(mss, Some(1)))?;
+    cb.send(cook_buffer(16, Some(2)))?;
+    let (header, payload): (TcpHeader, DemiBuffer) = parse_segment(rt.pop_frame())?;
+    crate::ensure_eq!(header.seq_num, send_next);
+    crate::ensure_eq!(payload.len(), mss);
+    let (header, payload): (TcpHeader, DemiBuffer) = parse_segment(rt.pop_frame())?;
+    crate::ensure_eq!(header.seq_num, send_next + SeqNumber::from(mss as u32));
+    crate::ensure_eq!(payload.len(), 16);
+
+    // With that partial segment in flight, another full-sized one still goes out.
+    cb.send(cook_buffer(mss, Some(3)))?;
+    let (header, payload): (TcpHeader, DemiBuffer) = parse_segment(rt.pop_frame())?;
+    crate::ensure_eq!(header.seq_num, send_next + SeqNumber::from(mss as u32 + 16));
+    crate::ensure_eq!(payload.len(), mss);
+
+    // But another partial one does not.
+    cb.send(cook_buffer(16, Some(4)))?;
+    crate::ensure_eq!(rt.pop_frame_unchecked().is_none(), true);
+
+    Ok(())
+}
+
+/// Tests that a corked connection holds partial segments back, sends full-sized ones as soon as queued data fills them
+/// up, and sends the rest once it is uncorked.
+#[test]
+fn test_cork() -> Result<()> {
+    let rt: Arc<TestRuntime> = Arc::new(TestRuntime::new(ALICE_MAC, ALICE_IPV4));
+    let clock: TimerRc = new_clock(Instant::now());
+    let tcp_config: TcpConfig = TcpConfig::default().set_cork(true);
+    let mut cb: Box<ControlBlock<RECEIVE_BATCH_SIZE>> = connection_setup(&rt, &clock, tcp_config, 4096);
+    let mss: usize = cb.get_mss();
+    let send_next: SeqNumber = SeqNumber::from(ALICE_ISN + 1);
+
+    // Even with nothing in flight, a partial segment waits.
+    cb.send(cook_buffer(mss / 2, Some(1)))?;
+    crate::ensure_eq!(rt.pop_frame_unchecked().is_none(), true);
+
+    // Once queued data fills up a segment, it goes out.
+    cb.send(cook_buffer(mss / 2 + 16, Some(2)))?;
+    let (header, payload): (TcpHeader, DemiBuffer) = parse_segment(rt.pop_frame())?;
+    crate::ensure_eq!(header.seq_num, send_next);
+    crate::ensure_eq!(payload.len(), mss);
+    crate::ensure_eq!(rt.pop_frame_unchecked().is_none(), true);
+
+    // The rest goes out when the connection is uncorked.
+    let rest: usize = mss / 2 + (mss / 2 + 16) - mss;
+    cb.set_option(SocketOption::Cork(false))?;
+    let (header, payload): (TcpHeader, DemiBuffer) = parse_segment(rt.pop_frame())?;
+    crate::ensure_eq!(header.seq_num, send_next + SeqNumber::from(mss as u32));
+    crate::ensure_eq!(payload.len(), rest);
+    crate::ensure_eq!(rt.pop_frame_unchecked().is_none(), true);
+
+    Ok(())
+}
+
+/// Tests that closing a corked connection sends what it held back, followed by the FIN.
+#[test]
+fn test_cork_close() -> Result<()> {
+    let rt: Arc<TestRuntime> = Arc::new(TestRuntime::new(ALICE_MAC, ALICE_IPV4));
+    let clock: TimerRc = new_clock(Instant::now());
+    let tcp_config: TcpConfig = TcpConfig::default().set_cork(true);
+    let cb: Box<ControlBlock<RECEIVE_BATCH_SIZE>> = connection_setup(&rt, &clock, tcp_config, 4096);
+    let send_next: SeqNumber = SeqNumber::from(ALICE_ISN + 1);
+
+    cb.send(cook_buffer(16, None))?;
+    crate::ensure_eq!(rt.pop_frame_unchecked().is_none(), true);
+
+    cb.close()?;
+    let (header, payload): (TcpHeader, DemiBuffer) = parse_segment(rt.pop_frame())?;
+    crate::ensure_eq!(header.seq_num, send_next);
+    crate::ensure_eq!(payload[..], cook_buffer(16, None)[..]);
+    let (header, payload): (TcpHeader, DemiBuffer) = parse_segment(rt.pop_frame())?;
+    crate::ensure_eq!(header.fin, true);
+    crate::ensure_eq!(header.seq_num, send_next + SeqNumber::from(16));
+    crate::ensure_eq!(payload.len(), 0);
+
+    Ok(())
+}
@@ -0,0 +1,183 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+use super::{
+    bob_ack,
+    connection_setup,
+    new_clock,
+    parse_segment,
+    ALICE_ISN,
+};
+use crate::{
+    inetstack::{
+        protocols::{
+            ip::Ecn,
+            tcp::{
+                established::{
+                    ControlBlock,
+                    State,
+                },
+                segment::TcpHeader,
+                SeqNumber,
+            },
+        },
+        test_helpers::{
+            TestRuntime,
+            ALICE_IPV4,
+            ALICE_MAC,
+        },
+    },
+    runtime::{
+        fail::Fail,
+        memory::DemiBuffer,
+        network::{
+            config::TcpConfig,
+            consts::RECEIVE_BATCH_SIZE,
+        },
+        timer::TimerRc,
+    },
+};
+use ::anyhow::Result;
+use ::std::{
+    sync::Arc,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+//=============================================================================
+
+const KEEPALIVE_IDLE: Duration = Duration::from_secs(10);
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(1);
+const KEEPALIVE_PROBES: usize = 3;
+
+/// Builds the configuration of a connection with keepalive enabled.
+fn keepalive_config() -> TcpConfig {
+    TcpConfig::default()
+        .set_keepalive(true)
+        .set_keepalive_idle(KEEPALIVE_IDLE)
+        .set_keepalive_interval(KEEPALIVE_INTERVAL)
+        .set_keepalive_probes(KEEPALIVE_PROBES)
+}
+
+/// Advances the clock to `now` and services the timers, as a worker does when the dispatcher hands it the connection.
+fn poll_at(clock: &TimerRc, cb: &ControlBlock<RECEIVE_BATCH_SIZE>, now: Instant) -> Result<()> {
+    crate::ensure_eq!(cb.has_expired_timers(now), true);
+    clock.advance_clock(now);
+    cb.poll_timers();
+    Ok(())
+}
+
+/// Checks that Alice sent a keepalive probe, which carries the sequence number before the oldest unacknowledged one.
+fn expect_probe(rt: &TestRuntime) -> Result<()> {
+    let (header, payload): (TcpHeader, DemiBuffer) = parse_segment(rt.pop_frame())?;
+    crate::ensure_eq!(header.ack, true);
+    crate::ensure_eq!(header.seq_num, SeqNumber::from(ALICE_ISN));
+    crate::ensure_eq!(payload.len(), 0);
+    crate::ensure_eq!(rt.pop_frame_unchecked().is_none(), true);
+    Ok(())
+}
+
+//=============================================================================
+
+/// Tests that an idle connection gets probed once the idle time elapses, and that an answer pushes the next probe back.
+#[test]
+fn test_keepalive_probe() -> Result<()> {
+    let now: Instant = Instant::now();
+    let rt: Arc<TestRuntime> = Arc::new(TestRuntime::new(ALICE_MAC, ALICE_IPV4));
+    let clock: TimerRc = new_clock(now);
+    let cb: Box<ControlBlock<RECEIVE_BATCH_SIZE>> = connection_setup(&rt, &clock, keepalive_config(), 1024);
+    let deadline: Instant = match cb.get_keepalive_deadline() {
+        Some(deadline) => deadline,
+        None => anyhow::bail!("keepalive timer should be armed"),
+    };
+
+    // Nothing is due before the connection has been idle for long enough.
+    let before: Instant = deadline - Duration::from_millis(1);
+    crate::ensure_eq!(cb.has_expired_timers(before), false);
+    clock.advance_clock(before);
+    cb.poll_timers();
+    crate::ensure_eq!(rt.pop_frame_unchecked().is_none(), true);
+
+    // The first probe goes out once it is.
+    poll_at(&clock, &cb, deadline)?;
+    expect_probe(&rt)?;
+    crate::ensure_eq!(cb.has_expired_timers(deadline), false);
+    crate::ensure_eq!(cb.get_keepalive_deadline(), Some(deadline + KEEPALIVE_INTERVAL));
+
+    // Our peer answers, so the connection is idle again from now on.
+    let now: Instant = deadline + Duration::from_millis(10);
+    clock.advance_clock(now);
+    let mut ack: TcpHeader = bob_ack(SeqNumber::from(ALICE_ISN + 1), 1024);
+    cb.receive(&mut ack, DemiBuffer::new(0), Ecn::NotEct);
+    cb.poll_timers();
+    crate::ensure_eq!(cb.get_keepalive_deadline(), Some(now + KEEPALIVE_IDLE));
+    crate::ensure_eq!(cb.has_expired_timers(deadline + KEEPALIVE_INTERVAL), false);
+    crate::ensure_eq!(cb.has_expired_timers(now + KEEPALIVE_IDLE), true);
+
+    Ok(())
+}
+
+/// Tests that a connection gets aborted once our peer leaves the configured number of probes unanswered, so that the
+/// dispatcher reaps it.
+#[test]
+fn test_keepalive_probe_limit() -> Result<()> {
+    let now: Instant = Instant::now();
+    let rt: Arc<TestRuntime> = Arc::new(TestRuntime::new(ALICE_MAC, ALICE_IPV4));
+    let clock: TimerRc = new_clock(now);
+    let cb: Box<ControlBlock<RECEIVE_BATCH_SIZE>> = connection_setup(&rt, &clock, keepalive_config(), 1024);
+    let mut now: Instant = match cb.get_keepalive_deadline() {
+        Some(deadline) => deadline,
+        None => anyhow::bail!("keepalive timer should be armed"),
+    };
+
+    for _ in 0..KEEPALIVE_PROBES {
+        poll_at(&clock, &cb, now)?;
+        expect_probe(&rt)?;
+        crate::ensure_eq!(cb.get_state(), State::Established);
+        now += KEEPALIVE_INTERVAL;
+    }
+
+    // No answer came back, so the connection is dropped rather than probed again.
+    poll_at(&clock, &cb, now)?;
+    crate::ensure_eq!(rt.pop_frame_unchecked().is_none(), true);
+    crate::ensure_eq!(cb.get_state(), State::Closed);
+    crate::ensure_eq!(cb.get_keepalive_deadline(), None);
+    crate::ensure_eq!(cb.has_expired_timers(now + KEEPALIVE_IDLE), false);
+    match cb.send(DemiBuffer::new(16)) {
+        Err(e) => crate::ensure_eq!(e.errno, libc::ETIMEDOUT),
+        Ok(()) => anyhow::bail!("send should fail on a connection that timed out"),
+    }
+
+    Ok(())
+}
+
+/// Tests that aborting a connection disarms its timers, so that it is not handed to a worker anymore.
+#[test]
+fn test_abort_disarms_keepalive() -> Result<()> {
+    let now: Instant = Instant::now();
+    let rt: Arc<TestRuntime> = Arc::new(TestRuntime::new(ALICE_MAC, ALICE_IPV4));
+    let clock: TimerRc = new_clock(now);
+    let cb: Box<ControlBlock<RECEIVE_BATCH_SIZE>> = connection_setup(&rt, &clock, keepalive_config(), 1024);
+    let deadline: Instant = match cb.get_keepalive_deadline() {
+        Some(deadline) => deadline,
+        None => anyhow::bail!("keepalive timer should be armed"),
+    };
+
+    cb.abort(Fail::new(libc::ECONNRESET, "connection reset"));
+    cb.publish_timers();
+    crate::ensure_eq!(cb.get_state(), State::Closed);
+    crate::ensure_eq!(cb.has_expired_timers(deadline), false);
+
+    // Nothing goes out, even long after the probe would have.
+    clock.advance_clock(deadline + KEEPALIVE_IDLE);
+    cb.poll_timers();
+    crate::ensure_eq!(rt.pop_frame_unchecked().is_none(), true);
+    match cb.send(DemiBuffer::new(16)) {
+        Err(e) => crate::ensure_eq!(e.errno, libc::ECONNRESET),
+        Ok(()) => anyhow::bail!("send should fail on an aborted connection"),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,130 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+mod established;
+mod keepalive;
+mod passive_open;
+
+use crate::{
+    inetstack::{
+        protocols::{
+            ethernet2::{
+                EtherType2,
+                Ethernet2Header,
+            },
+            ip::IpHeader,
+            ipv4::Ipv4Header,
+            tcp::{
+                congestion_control,
+                constants::MIN_MSS,
+                established::ControlBlock,
+                segment::TcpHeader,
+                SeqNumber,
+            },
+        },
+        test_helpers::{
+            TestRuntime,
+            ALICE_IPV4,
+            ALICE_MAC,
+            BOB_IPV4,
+            BOB_MAC,
+        },
+    },
+    runtime::{
+        memory::DemiBuffer,
+        network::{
+            config::TcpConfig,
+            consts::RECEIVE_BATCH_SIZE,
+        },
+        timer::{
+            Timer,
+            TimerRc,
+        },
+        QDesc,
+    },
+};
+use ::anyhow::Result;
+use ::std::{
+    net::{
+        SocketAddr,
+        SocketAddrV4,
+    },
+    rc::Rc,
+    sync::Arc,
+    time::Instant,
+};
+
+//=============================================================================
+
+pub const ALICE_PORT: u16 = 80;
+pub const BOB_PORT: u16 = 49152;
+
+/// Initial sequence number of Alice, who owns the connection under test.
+pub const ALICE_ISN: u32 = 1000;
+/// Initial sequence number of Bob, the peer that tests play.
+pub const BOB_ISN: u32 = 5000;
+
+//=============================================================================
+
+/// Creates the established end of a connection from Alice to Bob, as passive open would after the handshake.  Bob
+/// announced `bob_window` bytes of receive window in its handshake segment.
+pub fn connection_setup(
+    rt: &Arc<TestRuntime>,
+    clock: &TimerRc,
+    tcp_config: TcpConfig,
+    bob_window: u32,
+) -> Box<ControlBlock<RECEIVE_BATCH_SIZE>> {
+    let local: SocketAddr = SocketAddr::V4(SocketAddrV4::new(ALICE_IPV4, ALICE_PORT));
+    let remote: SocketAddr = SocketAddr::V4(SocketAddrV4::new(BOB_IPV4, BOB_PORT));
+    let mut cb: Box<ControlBlock<RECEIVE_BATCH_SIZE>> = Box::new(ControlBlock::new(
+        QDesc::from(0u32),
+        local,
+        remote,
+        ALICE_MAC,
+        BOB_MAC,
+        tcp_config.clone(),
+        SeqNumber::from(BOB_ISN + 1),
+        tcp_config.get_ack_delay_timeout(),
+        tcp_config.get_receive_window_size() as u32,
+        0,
+        SeqNumber::from(ALICE_ISN + 1),
+        bob_window,
+        0,
+        MIN_MSS,
+        false,
+        None,
+        false,
+        congestion_control::constructor(tcp_config.get_congestion_control()),
+        None,
+    ));
+    cb.set_rt(rt.clone());
+    cb.set_clock(clock.clone());
+    cb
+}
+
+/// Creates a clock for a connection, starting at `now`.
+pub fn new_clock(now: Instant) -> TimerRc {
+    TimerRc(Rc::new(Timer::new(now)))
+}
+
+/// Builds a segment from Bob that acknowledges everything up to `ack_num` and advertises `window_size` bytes.
+pub fn bob_ack(ack_num: SeqNumber, window_size: u16) -> TcpHeader {
+    let mut header: TcpHeader = TcpHeader::new(BOB_PORT, ALICE_PORT);
+    header.seq_num = SeqNumber::from(BOB_ISN + 1);
+    header.ack = true;
+    header.ack_num = ack_num;
+    header.window_size = window_size;
+    header
+}
+
+/// Parses a frame that Alice sent to Bob, and returns its TCP header and payload.
+pub fn parse_segment(bytes: DemiBuffer) -> Result<(TcpHeader, DemiBuffer)> {
+    let (eth2_header, eth2_payload): (Ethernet2Header, DemiBuffer) = Ethernet2Header::parse(bytes)?;
+    crate::ensure_eq!(eth2_header.src_addr(), ALICE_MAC);
+    crate::ensure_eq!(eth2_header.dst_addr(), BOB_MAC);
+    crate::ensure_eq!(eth2_header.ether_type(), EtherType2::Ipv4);
+    let (ipv4_header, ipv4_payload): (Ipv4Header, DemiBuffer) = Ipv4Header::parse(eth2_payload)?;
+    crate::ensure_eq!(ipv4_header.get_src_addr(), ALICE_IPV4);
+    crate::ensure_eq!(ipv4_header.get_dest_addr(), BOB_IPV4);
+    Ok(TcpHeader::parse(&IpHeader::V4(ipv4_header), ipv4_payload, false)?)
+}
@@ -0,0 +1,288 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+use super::{
+    new_clock,
+    parse_segment,
+    ALICE_PORT,
+    BOB_ISN,
+    BOB_PORT,
+};
+use crate::{
+    inetstack::{
+        protocols::{
+            ethernet2::{
+                EtherType2,
+                Ethernet2Header,
+            },
+            ip::{
+                IpHeader,
+                IpProtocol,
+            },
+            ipv4::Ipv4Header,
+            tcp::{
+                established::ControlBlock,
+                passive_open::PassiveSocket,
+                segment::{
+                    TcpHeader,
+                    TcpOptions2,
+                },
+                SeqNumber,
+            },
+        },
+        test_helpers::{
+            TestRuntime,
+            ALICE_IPV4,
+            ALICE_MAC,
+            BOB_IPV4,
+            BOB_MAC,
+        },
+    },
+    runtime::{
+        fail::Fail,
+        memory::DemiBuffer,
+        network::{
+            config::TcpConfig,
+            consts::RECEIVE_BATCH_SIZE,
+            types::MacAddress,
+            NetworkRuntime,
+        },
+        timer::TimerRc,
+        QDesc,
+    },
+};
+use ::anyhow::Result;
+use ::std::{
+    net::{
+        IpAddr,
+        Ipv4Addr,
+        SocketAddr,
+        SocketAddrV4,
+    },
+    sync::Arc,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+//=============================================================================
+
+/// Router that forwards the segments of peers outside of the local network.
+const ROUTER_MAC: MacAddress = MacAddress::new([0x02, 0x00, 0x00, 0x00, 0x00, 0xfe]);
+/// Peer outside of the local network.
+const CARRIE_IPV4: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 1);
+
+//=============================================================================
+
+/// Creates a listening socket of Alice, which answers with SYN cookies once `backlog` handshakes are pending.
+fn listen(
+    rt: &Arc<TestRuntime>,
+    clock: &TimerRc,
+    backlog: usize,
+    tcp_config: TcpConfig,
+) -> PassiveSocket<RECEIVE_BATCH_SIZE> {
+    let local: SocketAddr = SocketAddr::V4(SocketAddrV4::new(ALICE_IPV4, ALICE_PORT));
+    let rt: Arc<dyn NetworkRuntime<RECEIVE_BATCH_SIZE>> = rt.clone();
+    PassiveSocket::new(local, backlog, Some(rt), clock.clone(), tcp_config, 0)
+}
+
+/// Builds a SYN from Bob, which advertises an MSS of 1460 bytes and offers to scale its window by `window_scale`.
+fn bob_syn(window_scale: Option<u8>) -> TcpHeader {
+    let mut header: TcpHeader = TcpHeader::new(BOB_PORT, ALICE_PORT);
+    header.syn = true;
+    header.seq_num = SeqNumber::from(BOB_ISN);
+    header.window_size = 0xffff;
+    header.push_option(TcpOptions2::MaximumSegmentSize(1460));
+    if let Some(window_scale) = window_scale {
+        header.push_option(TcpOptions2::WindowScale(window_scale));
+    }
+    header
+}
+
+/// Hands a segment from Bob to the listening socket of Alice, and returns the connection that it established, if any.
+fn receive(
+    socket: &mut PassiveSocket<RECEIVE_BATCH_SIZE>,
+    header: &TcpHeader,
+) -> Result<Option<Box<ControlBlock<RECEIVE_BATCH_SIZE>>>, Fail> {
+    let eth_header: Ethernet2Header = Ethernet2Header::new(ALICE_MAC, BOB_MAC, EtherType2::Ipv4);
+    let ip_header: IpHeader = IpHeader::new(IpAddr::V4(BOB_IPV4), IpAddr::V4(ALICE_IPV4), IpProtocol::TCP);
+    let cb: Option<*mut ControlBlock<RECEIVE_BATCH_SIZE>> =
+        socket.receive(QDesc::from(0u32), &eth_header, &ip_header, header)?;
+    Ok(cb.map(|cb| unsafe { Box::from_raw(cb) }))
+}
+
+/// Returns the MSS and window scale that a SYN+ACK advertises.
+fn syn_ack_options(header: &TcpHeader) -> (Option<u16>, Option<u8>) {
+    let mut mss: Option<u16> = None;
+    let mut window_scale: Option<u8> = None;
+    for option in header.iter_options() {
+        match option {
+            TcpOptions2::MaximumSegmentSize(m) => mss = Some(*m),
+            TcpOptions2::WindowScale(w) => window_scale = Some(*w),
+            _ => continue,
+        }
+    }
+    (mss, window_scale)
+}
+
+//=============================================================================
+
+/// Tests that the SYN+ACK advertises the configured MSS, receive window and window scale, which are what the
+/// established connection then uses.
+#[test]
+fn test_syn_ack_options() -> Result<()> {
+    let rt: Arc<TestRuntime> = Arc::new(TestRuntime::new(ALICE_MAC, ALICE_IPV4));
+    let clock: TimerRc = new_clock(Instant::now());
+    let tcp_config: TcpConfig = TcpConfig::default()
+        .set_advertised_mss(1200)
+        .set_receive_window_size(8192)
+        .set_window_scale(5);
+    let mut socket: PassiveSocket<RECEIVE_BATCH_SIZE> = listen(&rt, &clock, 16, tcp_config);
+
+    receive(&mut socket, &bob_syn(Some(2)))?;
+    let (header, payload): (TcpHeader, DemiBuffer) = parse_segment(rt.pop_frame())?;
+    crate::ensure_eq!(header.syn, true);
+    crate::ensure_eq!(header.ack, true);
+    crate::ensure_eq!(header.ack_num, SeqNumber::from(BOB_ISN + 1));
+    crate::ensure_eq!(header.window_size, 8192);
+    crate::ensure_eq!(syn_ack_options(&header), (Some(1200), Some(5)));
+    crate::ensure_eq!(payload.len(), 0);
+
+    Ok(())
+}
+
+/// Tests that the SYN+ACK does not offer to scale our window when our peer did not offer to scale its own.
+#[test]
+fn test_syn_ack_without_window_scale() -> Result<()> {
+    let rt: Arc<TestRuntime> = Arc::new(TestRuntime::new(ALICE_MAC, ALICE_IPV4));
+    let clock: TimerRc = new_clock(Instant::now());
+    let tcp_config: TcpConfig = TcpConfig::default().set_window_scale(5);
+    let mut socket: PassiveSocket<RECEIVE_BATCH_SIZE> = listen(&rt, &clock, 16, tcp_config.clone());
+
+    receive(&mut socket, &bob_syn(None))?;
+    let (header, _): (TcpHeader, DemiBuffer) = parse_segment(rt.pop_frame())?;
+    crate::ensure_eq!(header.syn, true);
+    crate::ensure_eq!(
+        syn_ack_options(&header),
+        (Some(tcp_config.get_advertised_mss() as u16), None)
+    );
+
+    Ok(())
+}
+
+/// Tests that SYN cookies age by the clock of the runtime, rather than by the system clock.
+#[test]
+fn test_syn_cookie_follows_clock() -> Result<()> {
+    let now: Instant = Instant::now();
+    let rt: Arc<TestRuntime> = Arc::new(TestRuntime::new(ALICE_MAC, ALICE_IPV4));
+    let clock: TimerRc = new_clock(now);
+    // Without room for any pending handshake, every SYN is answered with a cookie.
+    let mut socket: PassiveSocket<RECEIVE_BATCH_SIZE> = listen(&rt, &clock, 0, TcpConfig::default());
+
+    crate::ensure_eq!(receive(&mut socket, &bob_syn(None))?.is_none(), true);
+    let (syn_ack, _): (TcpHeader, DemiBuffer) = parse_segment(rt.pop_frame())?;
+    let mut ack: TcpHeader = TcpHeader::new(BOB_PORT, ALICE_PORT);
+    ack.seq_num = SeqNumber::from(BOB_ISN + 1);
+    ack.ack = true;
+    ack.ack_num = syn_ack.seq_num + SeqNumber::from(1);
+    ack.window_size = 0xffff;
+
+    // The cookie holds shortly after we sent it.
+    clock.advance_clock(now + Duration::from_secs(1));
+    crate::ensure_eq!(receive(&mut socket, &ack)?.is_some(), true);
+
+    // It does not anymore once the clock says that it is stale, however little time actually went by.
+    clock.advance_clock(now + Duration::from_secs(600));
+    crate::ensure_eq!(receive(&mut socket, &ack).is_err(), true);
+
+    Ok(())
+}
+
+/// Tests that an unanswered SYN+ACK gets retransmitted for the configured number of times, after which the handshake
+/// is given up on and does not complete anymore.
+#[test]
+fn test_handshake_timeout() -> Result<()> {
+    let now: Instant = Instant::now();
+    let rt: Arc<TestRuntime> = Arc::new(TestRuntime::new(ALICE_MAC, ALICE_IPV4));
+    let clock: TimerRc = new_clock(now);
+    let timeout: Duration = Duration::from_secs(1);
+    let tcp_config: TcpConfig = TcpConfig::default()
+        .set_handshake_timeout(timeout)
+        .set_handshake_retries(2);
+    let mut socket: PassiveSocket<RECEIVE_BATCH_SIZE> = listen(&rt, &clock, 16, tcp_config);
+
+    receive(&mut socket, &bob_syn(None))?;
+    let (syn_ack, _): (TcpHeader, DemiBuffer) = parse_segment(rt.pop_frame())?;
+
+    // Nothing goes out before the timeout.
+    clock.advance_clock(now + timeout - Duration::from_millis(1));
+    crate::ensure_eq!(socket.poll_handshakes(), 0);
+    crate::ensure_eq!(rt.pop_frame_unchecked().is_none(), true);
+
+    // Each timeout resends the same SYN+ACK, as long as retries are left.
+    for i in 1..=2 {
+        clock.advance_clock(now + timeout * i);
+        crate::ensure_eq!(socket.poll_handshakes(), 0);
+        let (header, _): (TcpHeader, DemiBuffer) = parse_segment(rt.pop_frame())?;
+        crate::ensure_eq!(header.syn, true);
+        crate::ensure_eq!(header.ack, true);
+        crate::ensure_eq!(header.seq_num, syn_ack.seq_num);
+        crate::ensure_eq!(header.ack_num, SeqNumber::from(BOB_ISN + 1));
+    }
+
+    // Then the handshake is dropped without another SYN+ACK.
+    clock.advance_clock(now + timeout * 3);
+    crate::ensure_eq!(socket.poll_handshakes(), 1);
+    crate::ensure_eq!(rt.pop_frame_unchecked().is_none(), true);
+
+    // So an ACK that comes in too late does not complete it.
+    let mut ack: TcpHeader = TcpHeader::new(BOB_PORT, ALICE_PORT);
+    ack.seq_num = SeqNumber::from(BOB_ISN + 1);
+    ack.ack = true;
+    ack.ack_num = syn_ack.seq_num + SeqNumber::from(1);
+    ack.window_size = 0xffff;
+    crate::ensure_eq!(receive(&mut socket, &ack).is_err(), true);
+
+    Ok(())
+}
+
+/// Tests that a connection with an off-link peer answers to the router that forwarded its handshake.  Connections do
+/// not look their peer up in the routing table, so they rely on routes being symmetric.
+#[test]
+fn test_off_link_peer() -> Result<()> {
+    let rt: Arc<TestRuntime> = Arc::new(TestRuntime::new(ALICE_MAC, ALICE_IPV4));
+    let clock: TimerRc = new_clock(Instant::now());
+    let mut socket: PassiveSocket<RECEIVE_BATCH_SIZE> = listen(&rt, &clock, 16, TcpConfig::default());
+    let eth_header: Ethernet2Header = Ethernet2Header::new(ALICE_MAC, ROUTER_MAC, EtherType2::Ipv4);
+    let ip_header: IpHeader = IpHeader::new(IpAddr::V4(CARRIE_IPV4), IpAddr::V4(ALICE_IPV4), IpProtocol::TCP);
+
+    // The SYN+ACK goes back through the router.
+    socket.receive(QDesc::from(0u32), &eth_header, &ip_header, &bob_syn(None))?;
+    let (syn_ack_eth_header, payload): (Ethernet2Header, DemiBuffer) = Ethernet2Header::parse(rt.pop_frame())?;
+    crate::ensure_eq!(syn_ack_eth_header.dst_addr(), ROUTER_MAC);
+    let (syn_ack_ip_header, payload): (Ipv4Header, DemiBuffer) = Ipv4Header::parse(payload)?;
+    crate::ensure_eq!(syn_ack_ip_header.get_dest_addr(), CARRIE_IPV4);
+    let (syn_ack, _): (TcpHeader, DemiBuffer) = TcpHeader::parse(&IpHeader::V4(syn_ack_ip_header), payload, false)?;
+
+    // So does the data of the connection that the handshake sets up.
+    let mut ack: TcpHeader = TcpHeader::new(BOB_PORT, ALICE_PORT);
+    ack.seq_num = SeqNumber::from(BOB_ISN + 1);
+    ack.ack = true;
+    ack.ack_num = syn_ack.seq_num + SeqNumber::from(1);
+    ack.window_size = 0xffff;
+    let mut cb: Box<ControlBlock<RECEIVE_BATCH_SIZE>> =
+        match socket.receive(QDesc::from(0u32), &eth_header, &ip_header, &ack)? {
+            Some(cb) => unsafe { Box::from_raw(cb) },
+            None => anyhow::bail!("handshake should complete"),
+        };
+    crate::ensure_eq!(cb.remote_link_addr, ROUTER_MAC);
+    cb.set_rt(rt.clone());
+    cb.set_clock(clock.clone());
+    cb.send(DemiBuffer::new(16))?;
+    let (data_eth_header, _): (Ethernet2Header, DemiBuffer) = Ethernet2Header::parse(rt.pop_frame())?;
+    crate::ensure_eq!(data_eth_header.dst_addr(), ROUTER_MAC);
+    crate::ensure_eq!(data_eth_header.src_addr(), ALICE_MAC);
+
+    Ok(())
+}
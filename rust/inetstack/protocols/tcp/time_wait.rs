@@ -0,0 +1,290 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+// Connections in TIME-WAIT.
+// See RFC 9293 Section 3.6.1 for why we keep them around, and RFC 1337 for why RSTs must not cut the wait short.
+
+use crate::inetstack::protocols::tcp::{
+    segment::TcpHeader,
+    SeqNumber,
+};
+use ::std::{
+    collections::HashMap,
+    net::SocketAddr,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// What we remember about a connection in TIME-WAIT, once its control block is gone.
+#[derive(Debug)]
+struct TimeWaitEntry {
+    // SND.NXT, including our FIN.
+    send_next: SeqNumber,
+    // RCV.NXT, including our peer's FIN.
+    receive_next: SeqNumber,
+    // When this entry can be dropped.
+    expiry: Instant,
+}
+
+/// What to do with a segment that hit a connection in TIME-WAIT.
+#[derive(Debug, PartialEq, Eq)]
+pub enum TimeWaitVerdict {
+    /// Answer with an ACK carrying these sequence and acknowledgment numbers.
+    Ack(SeqNumber, SeqNumber),
+    /// The connection was dropped to let a new incarnation in.  Hand the segment to the listening socket.
+    Reopen,
+    /// Drop the segment silently.
+    Drop,
+}
+
+/// Connections that were closed by us first, keyed by (local, remote) address.
+pub struct TimeWaitTable {
+    entries: HashMap<(SocketAddr, SocketAddr), TimeWaitEntry>,
+    // How long an entry stays in the table (2 MSL).
+    timeout: Duration,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+impl TimeWaitTable {
+    pub fn new(msl: Duration) -> Self {
+        Self {
+            entries: HashMap::new(),
+            timeout: 2 * msl,
+        }
+    }
+
+    /// Starts tracking a connection that entered TIME-WAIT.  `expiry` is when the connection's own 2 MSL timer would
+    /// have fired, if it had one running.
+    pub fn insert(
+        &mut self,
+        local: SocketAddr,
+        remote: SocketAddr,
+        send_next: SeqNumber,
+        receive_next: SeqNumber,
+        expiry: Instant,
+    ) {
+        let entry: TimeWaitEntry = TimeWaitEntry {
+            send_next,
+            receive_next,
+            expiry,
+        };
+        self.entries.insert((local, remote), entry);
+    }
+
+    pub fn contains(&self, local: SocketAddr, remote: SocketAddr) -> bool {
+        self.entries.contains_key(&(local, remote))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Drops all entries whose 2 MSL wait is over, and returns how many were dropped.
+    pub fn expire(&mut self, now: Instant) -> usize {
+        let before: usize = self.entries.len();
+        self.entries.retain(|_, entry| entry.expiry > now);
+        before - self.entries.len()
+    }
+
+    /// Handles a segment for a connection in TIME-WAIT.  Returns `None` if there is no such connection.
+    pub fn receive(
+        &mut self,
+        local: SocketAddr,
+        remote: SocketAddr,
+        header: &TcpHeader,
+        reuse_addr: bool,
+        now: Instant,
+    ) -> Option<TimeWaitVerdict> {
+        let key: (SocketAddr, SocketAddr) = (local, remote);
+        let entry: &mut TimeWaitEntry = self.entries.get_mut(&key)?;
+
+        if entry.expiry <= now {
+            self.entries.remove(&key);
+            return None;
+        }
+
+        // RFC 1337: Ignore RSTs, as they would otherwise cut the 2 MSL wait short.
+        if header.rst {
+            return Some(TimeWaitVerdict::Drop);
+        }
+
+        // A new SYN beyond anything the old incarnation could have sent may reuse the address pair right away.
+        // This is what BSD-derived stacks do (RFC 6191 refines it with timestamps).
+        if header.syn && !header.ack {
+            if reuse_addr && header.seq_num > entry.receive_next {
+                self.entries.remove(&key);
+                return Some(TimeWaitVerdict::Reopen);
+            }
+            return Some(TimeWaitVerdict::Ack(entry.send_next, entry.receive_next));
+        }
+
+        // Our peer retransmitted its FIN, so our last ACK got lost.  ACK it again and restart the 2 MSL timeout.  Only
+        // a FIN in the sequence number it originally took does this, so stray or forged FINs cannot hold us here.
+        if header.fin {
+            if header.seq_num + SeqNumber::from(1) == entry.receive_next {
+                entry.expiry = now + self.timeout;
+            }
+            return Some(TimeWaitVerdict::Ack(entry.send_next, entry.receive_next));
+        }
+
+        // Stray ACKs of our FIN need no answer.  Anything else (e.g. old data) gets an ACK.
+        if header.ack && header.seq_num == entry.receive_next {
+            return Some(TimeWaitVerdict::Drop);
+        }
+        Some(TimeWaitVerdict::Ack(entry.send_next, entry.receive_next))
+    }
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        TimeWaitTable,
+        TimeWaitVerdict,
+    };
+    use crate::inetstack::protocols::tcp::{
+        segment::TcpHeader,
+        SeqNumber,
+    };
+    use ::anyhow::Result;
+    use ::std::{
+        net::{
+            Ipv4Addr,
+            SocketAddr,
+        },
+        time::{
+            Duration,
+            Instant,
+        },
+    };
+
+    const MSL: Duration = Duration::from_secs(30);
+
+    fn endpoints() -> (SocketAddr, SocketAddr) {
+        (
+            SocketAddr::new(Ipv4Addr::new(10, 0, 0, 1).into(), 80),
+            SocketAddr::new(Ipv4Addr::new(10, 0, 0, 2).into(), 4242),
+        )
+    }
+
+    fn table(now: Instant) -> TimeWaitTable {
+        let (local, remote): (SocketAddr, SocketAddr) = endpoints();
+        let mut table: TimeWaitTable = TimeWaitTable::new(MSL);
+        table.insert(local, remote, SeqNumber::from(100), SeqNumber::from(200), now + 2 * MSL);
+        table
+    }
+
+    // Test that retransmitted FINs are ACK'd again and restart the 2 MSL timeout.
+    #[test]
+    fn fin_restarts_timeout() -> Result<()> {
+        let now: Instant = Instant::now();
+        let (local, remote): (SocketAddr, SocketAddr) = endpoints();
+        let mut table: TimeWaitTable = table(now);
+
+        let mut header: TcpHeader = TcpHeader::new(remote.port(), local.port());
+        header.seq_num = SeqNumber::from(199);
+        header.ack = true;
+        header.fin = true;
+        let later: Instant = now + MSL;
+        crate::ensure_eq!(
+            table.receive(local, remote, &header, false, later),
+            Some(TimeWaitVerdict::Ack(SeqNumber::from(100), SeqNumber::from(200)))
+        );
+
+        // The original deadline has passed, but the restarted one has not.
+        crate::ensure_eq!(table.expire(now + 2 * MSL), 0);
+        crate::ensure_eq!(table.expire(later + 2 * MSL), 1);
+        crate::ensure_eq!(table.len(), 0);
+
+        Ok(())
+    }
+
+    // Test that a FIN in any other sequence number gets an ACK, but does not restart the timeout.
+    #[test]
+    fn stray_fin_keeps_timeout() -> Result<()> {
+        let now: Instant = Instant::now();
+        let (local, remote): (SocketAddr, SocketAddr) = endpoints();
+        let mut table: TimeWaitTable = table(now);
+
+        let mut header: TcpHeader = TcpHeader::new(remote.port(), local.port());
+        header.fin = true;
+        header.ack = true;
+        header.ack_num = SeqNumber::from(100);
+        let later: Instant = now + MSL;
+        for seq_num in [150, 200, 5000] {
+            header.seq_num = SeqNumber::from(seq_num);
+            crate::ensure_eq!(
+                table.receive(local, remote, &header, true, later),
+                Some(TimeWaitVerdict::Ack(SeqNumber::from(100), SeqNumber::from(200)))
+            );
+        }
+
+        crate::ensure_eq!(table.expire(now + 2 * MSL), 1);
+        crate::ensure_eq!(table.len(), 0);
+
+        Ok(())
+    }
+
+    // Test that RSTs do not end TIME-WAIT early.
+    #[test]
+    fn rst_ignored() -> Result<()> {
+        let now: Instant = Instant::now();
+        let (local, remote): (SocketAddr, SocketAddr) = endpoints();
+        let mut table: TimeWaitTable = table(now);
+
+        let mut header: TcpHeader = TcpHeader::new(remote.port(), local.port());
+        header.seq_num = SeqNumber::from(200);
+        header.rst = true;
+        crate::ensure_eq!(
+            table.receive(local, remote, &header, true, now),
+            Some(TimeWaitVerdict::Drop)
+        );
+        crate::ensure_eq!(table.contains(local, remote), true);
+
+        Ok(())
+    }
+
+    // Test that a new SYN reopens the address pair only if address reuse is allowed.
+    #[test]
+    fn syn_reuse() -> Result<()> {
+        let now: Instant = Instant::now();
+        let (local, remote): (SocketAddr, SocketAddr) = endpoints();
+        let mut table: TimeWaitTable = table(now);
+
+        let mut header: TcpHeader = TcpHeader::new(remote.port(), local.port());
+        header.seq_num = SeqNumber::from(5000);
+        header.syn = true;
+        crate::ensure_eq!(
+            table.receive(local, remote, &header, false, now),
+            Some(TimeWaitVerdict::Ack(SeqNumber::from(100), SeqNumber::from(200)))
+        );
+
+        // An old SYN never reopens.
+        header.seq_num = SeqNumber::from(150);
+        crate::ensure_eq!(
+            table.receive(local, remote, &header, true, now),
+            Some(TimeWaitVerdict::Ack(SeqNumber::from(100), SeqNumber::from(200)))
+        );
+
+        header.seq_num = SeqNumber::from(5000);
+        crate::ensure_eq!(
+            table.receive(local, remote, &header, true, now),
+            Some(TimeWaitVerdict::Reopen)
+        );
+        crate::ensure_eq!(table.contains(local, remote), false);
+
+        Ok(())
+    }
+}
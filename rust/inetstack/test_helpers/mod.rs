@@ -0,0 +1,18 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+pub mod runtime;
+
+pub use self::runtime::TestRuntime;
+
+use crate::runtime::network::types::MacAddress;
+use ::std::net::Ipv4Addr;
+
+//==============================================================================
+// Constants
+//==============================================================================
+
+pub const ALICE_MAC: MacAddress = MacAddress::new([0x12, 0x23, 0x45, 0x67, 0x89, 0xab]);
+pub const ALICE_IPV4: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 1);
+pub const BOB_MAC: MacAddress = MacAddress::new([0xab, 0x89, 0x67, 0x45, 0x23, 0x12]);
+pub const BOB_IPV4: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 2);
@@ -0,0 +1,89 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+use crate::{
+    inetstack::protocols::tcp::established::ControlBlock,
+    runtime::{
+        libdpdk::rte_mbuf,
+        logging,
+        memory::DemiBuffer,
+        network::{
+            consts::RECEIVE_BATCH_SIZE,
+            types::MacAddress,
+            NetworkRuntime,
+            PacketBuf,
+        },
+    },
+};
+use ::arrayvec::ArrayVec;
+use ::std::{
+    cell::RefCell,
+    collections::VecDeque,
+    net::Ipv4Addr,
+};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Network runtime that keeps the frames it transmits, so that tests can inspect them, drop them, or hand them over to
+/// the other end of a connection.
+pub struct TestRuntime {
+    pub link_addr: MacAddress,
+    pub ipv4_addr: Ipv4Addr,
+    outgoing: RefCell<VecDeque<DemiBuffer>>,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+impl TestRuntime {
+    pub fn new(link_addr: MacAddress, ipv4_addr: Ipv4Addr) -> Self {
+        logging::initialize();
+
+        Self {
+            link_addr,
+            ipv4_addr,
+            outgoing: RefCell::new(VecDeque::new()),
+        }
+    }
+
+    pub fn pop_frame(&self) -> DemiBuffer {
+        self.outgoing
+            .borrow_mut()
+            .pop_front()
+            .expect("pop_front didn't return an outgoing frame")
+    }
+
+    pub fn pop_frame_unchecked(&self) -> Option<DemiBuffer> {
+        self.outgoing.borrow_mut().pop_front()
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+impl NetworkRuntime<RECEIVE_BATCH_SIZE> for TestRuntime {
+    fn transmit(&self, pkt: Box<dyn PacketBuf>) {
+        let header_size: usize = pkt.header_size();
+        let body_size: usize = pkt.body_size();
+
+        // The packet header and body must fit into whatever physical media we're transmitting over.
+        // For this test harness, we 2^16 bytes (u16::MAX) as our limit.
+        assert!(header_size + body_size < u16::MAX as usize);
+
+        let mut buf: DemiBuffer = DemiBuffer::new((header_size + body_size) as u16);
+        pkt.write_header(&mut buf[..header_size]);
+        if let Some(body) = pkt.take_body() {
+            buf[header_size..].copy_from_slice(&body[..]);
+        }
+        self.outgoing.borrow_mut().push_back(buf);
+    }
+
+    /// Frames are handed over to connections directly by the tests, so there is never anything to receive.
+    fn receive(&self) -> ArrayVec<(*mut rte_mbuf, *mut ControlBlock<RECEIVE_BATCH_SIZE>), RECEIVE_BATCH_SIZE> {
+        ArrayVec::new()
+    }
+}
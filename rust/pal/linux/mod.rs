@@ -15,7 +15,10 @@ use ::std::{
     mem,
     net::{
         Ipv4Addr,
+        Ipv6Addr,
+        SocketAddr,
         SocketAddrV4,
+        SocketAddrV6,
     },
     os::unix::prelude::RawFd,
 };
@@ -97,8 +100,41 @@ pub fn socketaddrv4_to_sockaddr(addr: &SocketAddrV4) -> libc::sockaddr {
     unsafe { mem::transmute::<libc::sockaddr_in, libc::sockaddr>(sin) }
 }
 
+/// Converts a [std::net::SocketAddr] to the [libc::sockaddr] that the results of accept and pop carry.  It only has
+/// room for IPv4 addresses, so IPv6 ones come out unspecified (`AF_UNSPEC`), and callers look them up with
+/// `demi_getpeername()` instead.
+pub fn socketaddr_to_sockaddr(addr: &SocketAddr) -> libc::sockaddr {
+    match addr {
+        SocketAddr::V4(addr) => socketaddrv4_to_sockaddr(addr),
+        SocketAddr::V6(_) => unsafe { mem::zeroed() },
+    }
+}
+
 /// Converts a [libc::sockaddr] to a [std::net::SocketAddrV4].
 pub fn sockaddr_to_socketaddrv4(saddr: &libc::sockaddr) -> SocketAddrV4 {
     let sin: libc::sockaddr_in = unsafe { mem::transmute::<libc::sockaddr, libc::sockaddr_in>(saddr.to_owned()) };
     sockaddr_in_to_socketaddrv4(&sin)
 }
+
+/// Converts a [std::net::SocketAddrV6] to a [libc::sockaddr_in6].
+pub fn socketaddrv6_to_sockaddr_in6(addr: &SocketAddrV6) -> libc::sockaddr_in6 {
+    libc::sockaddr_in6 {
+        sin6_family: libc::AF_INET6 as libc::sa_family_t,
+        sin6_port: u16::to_be(addr.port()),
+        sin6_flowinfo: u32::to_be(addr.flowinfo()),
+        sin6_addr: libc::in6_addr {
+            s6_addr: addr.ip().octets(),
+        },
+        sin6_scope_id: addr.scope_id(),
+    }
+}
+
+/// Converts a [libc::sockaddr_in6] to a [std::net::SocketAddrV6].
+pub fn sockaddr_in6_to_socketaddrv6(sin6: &libc::sockaddr_in6) -> SocketAddrV6 {
+    SocketAddrV6::new(
+        Ipv6Addr::from(sin6.sin6_addr.s6_addr),
+        u16::from_be(sin6.sin6_port),
+        u32::from_be(sin6.sin6_flowinfo),
+        sin6.sin6_scope_id,
+    )
+}
@@ -353,6 +353,11 @@ impl DemiBuffer {
         self.as_metadata().data_len as usize
     }
 
+    /// Returns the allocated size of the buffer that holds the data of the `DemiBuffer`, which the data may not fill.
+    pub fn capacity(&self) -> usize {
+        self.as_metadata().buf_len as usize
+    }
+
     /// Removes `nbytes` bytes from the beginning of the `DemiBuffer` chain.
     // Note: If `nbytes` is greater than the length of the first segment in the chain, then this function will fail and
     // return an error, rather than remove the remaining bytes from subsequent segments in the chain.  This is to match
@@ -541,6 +546,12 @@ impl DemiBuffer {
         }
     }
 
+    /// Returns a raw pointer to the start of the underlying data buffer, i.e. before any headroom.
+    // Note: Clones of a `DemiBuffer` share the same data buffer, so they all return the same pointer.
+    pub fn buffer_start(&self) -> *mut u8 {
+        self.as_metadata().buf_addr
+    }
+
     /// Consumes the `DemiBuffer`, returning a raw token (useful for FFI) that can be used with `from_raw()`.
     // Note the type of the token is arbitrary, it should be treated as an opaque value.
     pub fn into_raw(self) -> NonNull<u8> {
@@ -1012,6 +1023,7 @@ mod tests {
         // Remove 7 bytes from the beginning of the data area.  Length should now be 35.
         crate::ensure_eq!(buf.adjust(7).is_ok(), true);
         crate::ensure_eq!(buf.len(), 35);
+        crate::ensure_eq!(buf.as_ptr() as usize - buf.buffer_start() as usize, 7);
 
         // Remove 7 bytes from the end of the data area.  Length should now be 28.
         crate::ensure_eq!(buf.trim(7).is_ok(), true);
@@ -29,6 +29,8 @@ pub struct ArpConfig {
     initial_values: HashMap<Ipv4Addr, MacAddress>,
     /// Disable ARP?
     disable_arp: bool,
+    /// Maximum Number of Packets Waiting for the Same Resolution
+    pending_queue_len: usize,
 }
 
 //==============================================================================
@@ -44,6 +46,7 @@ impl ArpConfig {
         retry_count: Option<usize>,
         initial_values: Option<HashMap<Ipv4Addr, MacAddress>>,
         disable_arp: Option<bool>,
+        pending_queue_len: Option<usize>,
     ) -> Self {
         let mut config: ArpConfig = Self::default();
 
@@ -62,6 +65,9 @@ impl ArpConfig {
         if let Some(disable_arp) = disable_arp {
             config.set_disable_arp(disable_arp);
         }
+        if let Some(pending_queue_len) = pending_queue_len {
+            config.set_pending_queue_len(pending_queue_len);
+        }
 
         config
     }
@@ -91,6 +97,11 @@ impl ArpConfig {
         self.disable_arp
     }
 
+    /// Gets the maximum number of packets that wait for the same resolution in the target [ArpConfig].
+    pub fn get_pending_queue_len(&self) -> usize {
+        self.pending_queue_len
+    }
+
     /// Sets the time to live for entries of the ARP Cache in the target [ArpConfig].
     fn set_cache_ttl(&mut self, cache_ttl: Duration) {
         self.cache_ttl = cache_ttl
@@ -115,6 +126,11 @@ impl ArpConfig {
     fn set_disable_arp(&mut self, disable_arp: bool) {
         self.disable_arp = disable_arp
     }
+
+    /// Sets the maximum number of packets that wait for the same resolution in the target [ArpConfig].
+    fn set_pending_queue_len(&mut self, pending_queue_len: usize) {
+        self.pending_queue_len = pending_queue_len
+    }
 }
 
 //==============================================================================
@@ -131,6 +147,7 @@ impl Default for ArpConfig {
             retry_count: 5,
             initial_values: HashMap::new(),
             disable_arp: false,
+            pending_queue_len: 8,
         }
     }
 }
@@ -157,6 +174,7 @@ mod tests {
         crate::ensure_eq!(config.get_retry_count(), 5);
         crate::ensure_eq!(config.get_initial_values(), &HashMap::new());
         crate::ensure_eq!(config.get_disable_arp(), false);
+        crate::ensure_eq!(config.get_pending_queue_len(), 8);
 
         Ok(())
     }
@@ -2,6 +2,7 @@
 // Licensed under the MIT license.
 
 mod arp;
+mod ndp;
 mod tcp;
 mod udp;
 
@@ -11,6 +12,7 @@ mod udp;
 
 pub use self::{
     arp::ArpConfig,
+    ndp::NdpConfig,
     tcp::TcpConfig,
     udp::UdpConfig,
 };
@@ -0,0 +1,164 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use crate::runtime::network::types::MacAddress;
+use ::std::{
+    collections::HashMap,
+    net::Ipv6Addr,
+    time::Duration,
+};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// NDP Configuration Descriptor
+#[derive(Clone, Debug)]
+pub struct NdpConfig {
+    /// Time to Live for Neighbor Cache
+    cache_ttl: Duration,
+    /// Timeout for Neighbor Solicitations
+    request_timeout: Duration,
+    /// Retry Count for Neighbor Solicitations
+    retry_count: usize,
+    /// Initial Values for Neighbor Cache
+    initial_values: HashMap<Ipv6Addr, MacAddress>,
+    /// Disable NDP?
+    disable_ndp: bool,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for NDP Configuration Descriptor
+impl NdpConfig {
+    /// Creates an NDP Configuration Descriptor.
+    pub fn new(
+        cache_ttl: Option<Duration>,
+        request_timeout: Option<Duration>,
+        retry_count: Option<usize>,
+        initial_values: Option<HashMap<Ipv6Addr, MacAddress>>,
+        disable_ndp: Option<bool>,
+    ) -> Self {
+        let mut config: NdpConfig = Self::default();
+
+        if let Some(cache_ttl) = cache_ttl {
+            config.set_cache_ttl(cache_ttl);
+        }
+        if let Some(request_timeout) = request_timeout {
+            config.set_request_timeout(request_timeout);
+        }
+        if let Some(retry_count) = retry_count {
+            config.set_retry_count(retry_count);
+        }
+        if let Some(initial_values) = initial_values {
+            config.set_initial_values(initial_values);
+        }
+        if let Some(disable_ndp) = disable_ndp {
+            config.set_disable_ndp(disable_ndp);
+        }
+
+        config
+    }
+
+    /// Gets the time to live for entries of the Neighbor Cache in the target [NdpConfig].
+    pub fn get_cache_ttl(&self) -> Duration {
+        self.cache_ttl
+    }
+
+    /// Gets the request timeout for Neighbor Solicitations in the target [NdpConfig].
+    pub fn get_request_timeout(&self) -> Duration {
+        self.request_timeout
+    }
+
+    /// Gets the retry count for Neighbor Solicitations in the target [NdpConfig].
+    pub fn get_retry_count(&self) -> usize {
+        self.retry_count
+    }
+
+    /// Gets the initial values for the Neighbor Cache in the target [NdpConfig].
+    pub fn get_initial_values(&self) -> &HashMap<Ipv6Addr, MacAddress> {
+        &self.initial_values
+    }
+
+    /// Gets the disable option of the NDP in the target [NdpConfig].
+    pub fn get_disable_ndp(&self) -> bool {
+        self.disable_ndp
+    }
+
+    /// Sets the time to live for entries of the Neighbor Cache in the target [NdpConfig].
+    fn set_cache_ttl(&mut self, cache_ttl: Duration) {
+        self.cache_ttl = cache_ttl
+    }
+
+    /// Sets the request timeout for Neighbor Solicitations in the target [NdpConfig].
+    fn set_request_timeout(&mut self, request_timeout: Duration) {
+        self.request_timeout = request_timeout
+    }
+
+    /// Sets the retry count for Neighbor Solicitations in the target [NdpConfig].
+    fn set_retry_count(&mut self, retry_count: usize) {
+        self.retry_count = retry_count
+    }
+
+    /// Sets the initial values for the Neighbor Cache in the target [NdpConfig].
+    fn set_initial_values(&mut self, initial_values: HashMap<Ipv6Addr, MacAddress>) {
+        self.initial_values = initial_values;
+    }
+
+    /// Sets the disable option of the NDP in the target [NdpConfig].
+    fn set_disable_ndp(&mut self, disable_ndp: bool) {
+        self.disable_ndp = disable_ndp
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+/// Default Trait Implementation for NDP Configuration Descriptor
+impl Default for NdpConfig {
+    /// Creates an NDP Configuration Descriptor with the default values.
+    fn default() -> Self {
+        NdpConfig {
+            // These follow REACHABLE_TIME, RETRANS_TIMER and MAX_MULTICAST_SOLICIT in RFC 4861 Section 10.
+            cache_ttl: Duration::from_secs(30),
+            request_timeout: Duration::from_secs(1),
+            retry_count: 2,
+            initial_values: HashMap::new(),
+            disable_ndp: false,
+        }
+    }
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::runtime::network::config::NdpConfig;
+    use ::anyhow::Result;
+    use ::std::{
+        collections::HashMap,
+        time::Duration,
+    };
+
+    /// Tests default instantiation for [NdpConfig].
+    #[test]
+    fn test_ndp_config_default() -> Result<()> {
+        let config: NdpConfig = NdpConfig::default();
+        crate::ensure_eq!(config.get_cache_ttl(), Duration::from_secs(30));
+        crate::ensure_eq!(config.get_request_timeout(), Duration::from_secs(1));
+        crate::ensure_eq!(config.get_retry_count(), 2);
+        crate::ensure_eq!(config.get_initial_values(), &HashMap::new());
+        crate::ensure_eq!(config.get_disable_ndp(), false);
+
+        Ok(())
+    }
+}
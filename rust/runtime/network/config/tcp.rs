@@ -5,10 +5,21 @@
 // Imports
 //==============================================================================
 
-use crate::runtime::network::consts::{
-    DEFAULT_MSS,
-    MAX_MSS,
-    MIN_MSS,
+use crate::runtime::{
+    fail::Fail,
+    network::{
+        consts::{
+            DEFAULT_MSS,
+            MAX_MSS,
+            MAX_WINDOW_SCALE,
+            MIN_MSS,
+        },
+        socket::{
+            CongestionAlgorithm,
+            SocketOption,
+            SocketOptionName,
+        },
+    },
 };
 use ::std::time::Duration;
 
@@ -35,6 +46,38 @@ pub struct TcpConfig {
     rx_checksum_offload: bool,
     /// Offload Checksum to Hardware When Sending?
     tx_checksum_offload: bool,
+    /// Send Buffer Size
+    send_buffer_size: usize,
+    /// Disable Nagle's Algorithm?
+    nodelay: bool,
+    /// Hold Back Partial Segments Until Uncorked?
+    cork: bool,
+    /// Send Keepalive Probes?
+    keepalive: bool,
+    /// Idle Time Before the First Keepalive Probe
+    keepalive_idle: Duration,
+    /// Time Between Keepalive Probes
+    keepalive_interval: Duration,
+    /// Number of Unanswered Keepalive Probes Before Dropping the Connection
+    keepalive_probes: usize,
+    /// Linger Timeout on Close
+    linger: Option<Duration>,
+    /// Maximum Segment Lifetime (MSL), Connections Stay in TIME-WAIT for Twice This Long
+    msl: Duration,
+    /// Allow Reuse of Local Addresses?
+    reuse_addr: bool,
+    /// Negotiate Selective Acknowledgments?
+    sack: bool,
+    /// Negotiate Timestamps?
+    timestamps: bool,
+    /// Congestion Control Algorithm
+    congestion_control: CongestionAlgorithm,
+    /// Negotiate Explicit Congestion Notification?
+    ecn: bool,
+    /// Pace Outgoing Segments?
+    pacing: bool,
+    /// Upper Bound on the Pacing Rate, in Bytes per Second
+    max_pacing_rate: Option<u64>,
 }
 
 //==============================================================================
@@ -124,8 +167,156 @@ impl TcpConfig {
         self.rx_checksum_offload
     }
 
+    /// Gets the send buffer size in the target [TcpConfig].
+    pub fn get_send_buffer_size(&self) -> usize {
+        self.send_buffer_size
+    }
+
+    /// Gets the no delay option in the target [TcpConfig].
+    pub fn get_nodelay(&self) -> bool {
+        self.nodelay
+    }
+
+    /// Gets the cork option in the target [TcpConfig].
+    pub fn get_cork(&self) -> bool {
+        self.cork
+    }
+
+    /// Gets the keepalive option in the target [TcpConfig].
+    pub fn get_keepalive(&self) -> bool {
+        self.keepalive
+    }
+
+    /// Gets the keepalive idle time in the target [TcpConfig].
+    pub fn get_keepalive_idle(&self) -> Duration {
+        self.keepalive_idle
+    }
+
+    /// Gets the keepalive probe interval in the target [TcpConfig].
+    pub fn get_keepalive_interval(&self) -> Duration {
+        self.keepalive_interval
+    }
+
+    /// Gets the number of keepalive probes in the target [TcpConfig].
+    pub fn get_keepalive_probes(&self) -> usize {
+        self.keepalive_probes
+    }
+
+    /// Gets the linger timeout in the target [TcpConfig].
+    pub fn get_linger(&self) -> Option<Duration> {
+        self.linger
+    }
+
+    /// Gets the maximum segment lifetime in the target [TcpConfig].
+    pub fn get_msl(&self) -> Duration {
+        self.msl
+    }
+
+    /// Gets the address reuse option in the target [TcpConfig].
+    pub fn get_reuse_addr(&self) -> bool {
+        self.reuse_addr
+    }
+
+    /// Gets the selective acknowledgment option in the target [TcpConfig].
+    pub fn get_sack(&self) -> bool {
+        self.sack
+    }
+
+    /// Gets the timestamps option in the target [TcpConfig].
+    pub fn get_timestamps(&self) -> bool {
+        self.timestamps
+    }
+
+    /// Gets the congestion control algorithm in the target [TcpConfig].
+    pub fn get_congestion_control(&self) -> CongestionAlgorithm {
+        self.congestion_control
+    }
+
+    /// Gets the explicit congestion notification option in the target [TcpConfig].
+    pub fn get_ecn(&self) -> bool {
+        self.ecn
+    }
+
+    /// Gets the pacing option in the target [TcpConfig].
+    pub fn get_pacing(&self) -> bool {
+        self.pacing
+    }
+
+    /// Gets the maximum pacing rate in the target [TcpConfig].
+    pub fn get_max_pacing_rate(&self) -> Option<u64> {
+        self.max_pacing_rate
+    }
+
+    /// Gets the value of a socket option in the target [TcpConfig].
+    pub fn get_option(&self, name: SocketOptionName) -> SocketOption {
+        match name {
+            SocketOptionName::ReceiveBuffer => {
+                SocketOption::ReceiveBuffer((self.receive_window_size as usize) << self.window_scale)
+            },
+            SocketOptionName::SendBuffer => SocketOption::SendBuffer(self.send_buffer_size),
+            SocketOptionName::KeepAlive => SocketOption::KeepAlive(self.keepalive),
+            SocketOptionName::Linger => SocketOption::Linger(self.linger),
+            SocketOptionName::ReuseAddress => SocketOption::ReuseAddress(self.reuse_addr),
+            SocketOptionName::NoDelay => SocketOption::NoDelay(self.nodelay),
+            SocketOptionName::Cork => SocketOption::Cork(self.cork),
+            SocketOptionName::MaxSegment => SocketOption::MaxSegment(self.advertised_mss),
+            SocketOptionName::KeepIdle => SocketOption::KeepIdle(self.keepalive_idle),
+            SocketOptionName::KeepInterval => SocketOption::KeepInterval(self.keepalive_interval),
+            SocketOptionName::KeepCount => SocketOption::KeepCount(self.keepalive_probes),
+            SocketOptionName::Congestion => SocketOption::Congestion(self.congestion_control),
+            SocketOptionName::MaxPacingRate => SocketOption::MaxPacingRate(self.max_pacing_rate),
+        }
+    }
+
+    /// Sets a socket option in the target [TcpConfig].
+    pub fn set_option(self, option: SocketOption) -> Result<Self, Fail> {
+        match option {
+            SocketOption::ReceiveBuffer(size) => {
+                if size == 0 {
+                    return Err(Fail::new(libc::EINVAL, "receive buffer size should be positive"));
+                }
+                // Pick the smallest window scale that fits the requested size.
+                let mut scale: u8 = 0;
+                while (size >> scale) > 0xffff && scale < MAX_WINDOW_SCALE {
+                    scale += 1;
+                }
+                let window: u16 = (size >> scale).clamp(1, 0xffff) as u16;
+                Ok(self.set_window_scale(scale).set_receive_window_size(window))
+            },
+            SocketOption::SendBuffer(size) => {
+                if size == 0 {
+                    return Err(Fail::new(libc::EINVAL, "send buffer size should be positive"));
+                }
+                Ok(self.set_send_buffer_size(size))
+            },
+            SocketOption::KeepAlive(value) => Ok(self.set_keepalive(value)),
+            SocketOption::Linger(value) => Ok(self.set_linger(value)),
+            SocketOption::ReuseAddress(value) => Ok(self.set_reuse_addr(value)),
+            SocketOption::NoDelay(value) => Ok(self.set_nodelay(value)),
+            SocketOption::Cork(value) => Ok(self.set_cork(value)),
+            SocketOption::MaxSegment(mss) => {
+                if !(MIN_MSS..=MAX_MSS).contains(&mss) {
+                    return Err(Fail::new(libc::EINVAL, "maximum segment size is out of range"));
+                }
+                Ok(self.set_advertised_mss(mss))
+            },
+            SocketOption::KeepIdle(value) | SocketOption::KeepInterval(value) if value < Duration::from_secs(1) => {
+                Err(Fail::new(libc::EINVAL, "keepalive timers should be at least one second"))
+            },
+            SocketOption::KeepIdle(value) => Ok(self.set_keepalive_idle(value)),
+            SocketOption::KeepInterval(value) => Ok(self.set_keepalive_interval(value)),
+            SocketOption::KeepCount(0) => Err(Fail::new(libc::EINVAL, "keepalive probe count should be positive")),
+            SocketOption::KeepCount(value) => Ok(self.set_keepalive_probes(value)),
+            SocketOption::Congestion(value) => Ok(self.set_congestion_control(value)),
+            SocketOption::MaxPacingRate(Some(0)) => Err(Fail::new(libc::EINVAL, "pacing rate should be positive")),
+            // Like on Linux, capping the rate of a socket turns pacing on for it.
+            SocketOption::MaxPacingRate(Some(rate)) => Ok(self.set_pacing(true).set_max_pacing_rate(Some(rate))),
+            SocketOption::MaxPacingRate(None) => Ok(self.set_max_pacing_rate(None)),
+        }
+    }
+
     /// Sets the advertised maximum segment size in the target [TcpConfig].
-    fn set_advertised_mss(mut self, value: usize) -> Self {
+    pub fn set_advertised_mss(mut self, value: usize) -> Self {
         assert!(value >= MIN_MSS);
         assert!(value <= MAX_MSS);
         self.advertised_mss = value;
@@ -133,38 +324,140 @@ impl TcpConfig {
     }
 
     /// Sets the number of TCP handshake retries in the target [TcpConfig].
-    fn set_handshake_retries(mut self, value: usize) -> Self {
+    pub fn set_handshake_retries(mut self, value: usize) -> Self {
         assert!(value > 0);
         self.handshake_retries = value;
         self
     }
 
     /// Sets the handshake TCP timeout in the target [TcpConfig].
-    fn set_handshake_timeout(mut self, value: Duration) -> Self {
+    pub fn set_handshake_timeout(mut self, value: Duration) -> Self {
         assert!(value > Duration::new(0, 0));
         self.handshake_timeout = value;
         self
     }
 
     /// Sets the receiver window size in the target [TcpConfig].
-    fn set_receive_window_size(mut self, value: u16) -> Self {
+    pub fn set_receive_window_size(mut self, value: u16) -> Self {
         assert!(value > 0);
         self.receive_window_size = value;
         self
     }
 
-    /// Gets the window scale in the target [TcpConfig]
-    fn set_window_scale(mut self, value: u8) -> Self {
+    /// Sets the window scale in the target [TcpConfig]
+    pub fn set_window_scale(mut self, value: u8) -> Self {
         self.window_scale = value;
         self
     }
 
     /// Sets the acknowledgement delay timeout in the target [TcpConfig].
-    fn set_ack_delay_timeout(mut self, value: Duration) -> Self {
+    pub fn set_ack_delay_timeout(mut self, value: Duration) -> Self {
         assert!(value <= Duration::from_millis(500));
         self.ack_delay_timeout = value;
         self
     }
+
+    /// Sets the send buffer size in the target [TcpConfig].
+    pub fn set_send_buffer_size(mut self, value: usize) -> Self {
+        assert!(value > 0);
+        self.send_buffer_size = value;
+        self
+    }
+
+    /// Sets the no delay option in the target [TcpConfig].
+    pub fn set_nodelay(mut self, value: bool) -> Self {
+        self.nodelay = value;
+        self
+    }
+
+    /// Sets the cork option in the target [TcpConfig].
+    pub fn set_cork(mut self, value: bool) -> Self {
+        self.cork = value;
+        self
+    }
+
+    /// Sets the keepalive option in the target [TcpConfig].
+    pub fn set_keepalive(mut self, value: bool) -> Self {
+        self.keepalive = value;
+        self
+    }
+
+    /// Sets the keepalive idle time in the target [TcpConfig].
+    pub fn set_keepalive_idle(mut self, value: Duration) -> Self {
+        assert!(value > Duration::new(0, 0));
+        self.keepalive_idle = value;
+        self
+    }
+
+    /// Sets the keepalive probe interval in the target [TcpConfig].
+    pub fn set_keepalive_interval(mut self, value: Duration) -> Self {
+        assert!(value > Duration::new(0, 0));
+        self.keepalive_interval = value;
+        self
+    }
+
+    /// Sets the number of keepalive probes in the target [TcpConfig].
+    pub fn set_keepalive_probes(mut self, value: usize) -> Self {
+        assert!(value > 0);
+        self.keepalive_probes = value;
+        self
+    }
+
+    /// Sets the linger timeout in the target [TcpConfig].
+    pub fn set_linger(mut self, value: Option<Duration>) -> Self {
+        self.linger = value;
+        self
+    }
+
+    /// Sets the maximum segment lifetime in the target [TcpConfig].
+    pub fn set_msl(mut self, value: Duration) -> Self {
+        assert!(value > Duration::new(0, 0));
+        self.msl = value;
+        self
+    }
+
+    /// Sets the address reuse option in the target [TcpConfig].
+    pub fn set_reuse_addr(mut self, value: bool) -> Self {
+        self.reuse_addr = value;
+        self
+    }
+
+    /// Sets the selective acknowledgment option in the target [TcpConfig].
+    pub fn set_sack(mut self, value: bool) -> Self {
+        self.sack = value;
+        self
+    }
+
+    /// Sets the timestamps option in the target [TcpConfig].
+    pub fn set_timestamps(mut self, value: bool) -> Self {
+        self.timestamps = value;
+        self
+    }
+
+    /// Sets the congestion control algorithm in the target [TcpConfig].
+    pub fn set_congestion_control(mut self, value: CongestionAlgorithm) -> Self {
+        self.congestion_control = value;
+        self
+    }
+
+    /// Sets the explicit congestion notification option in the target [TcpConfig].
+    pub fn set_ecn(mut self, value: bool) -> Self {
+        self.ecn = value;
+        self
+    }
+
+    /// Sets the pacing option in the target [TcpConfig].
+    pub fn set_pacing(mut self, value: bool) -> Self {
+        self.pacing = value;
+        self
+    }
+
+    /// Sets the maximum pacing rate in the target [TcpConfig].
+    pub fn set_max_pacing_rate(mut self, value: Option<u64>) -> Self {
+        assert!(value != Some(0));
+        self.max_pacing_rate = value;
+        self
+    }
 }
 
 //==============================================================================
@@ -184,6 +477,28 @@ impl Default for TcpConfig {
             window_scale: 0,
             rx_checksum_offload: false,
             tx_checksum_offload: false,
+            send_buffer_size: 0x10000,
+            // Nagle's algorithm is opt-in, as most of our workloads are latency-bound request-response traffic.
+            nodelay: true,
+            cork: false,
+            keepalive: false,
+            // Defaults from RFC 1122 Section 4.2.3.6 and most BSD-derived stacks.
+            keepalive_idle: Duration::from_secs(7200),
+            keepalive_interval: Duration::from_secs(75),
+            keepalive_probes: 9,
+            linger: None,
+            // RFC 9293 suggests 2 minutes, which keeps too many connections around at our connection rates.
+            msl: Duration::from_secs(30),
+            reuse_addr: false,
+            sack: true,
+            timestamps: true,
+            // Our links are rarely congested, so this is what connections always used.
+            congestion_control: CongestionAlgorithm::None,
+            // Middleboxes that mangle ECN bits are still around, so it takes switches we know to opt in.
+            ecn: false,
+            // Bursts are harmless on our links, and pacing costs a timer per connection.
+            pacing: false,
+            max_pacing_rate: None,
         }
     }
 }
@@ -197,6 +512,11 @@ mod tests {
     use crate::runtime::network::{
         config::TcpConfig,
         consts::DEFAULT_MSS,
+        socket::{
+            CongestionAlgorithm,
+            SocketOption,
+            SocketOptionName,
+        },
     };
     use ::anyhow::Result;
     use ::std::time::Duration;
@@ -212,6 +532,61 @@ mod tests {
         crate::ensure_eq!(config.get_window_scale(), 0);
         crate::ensure_eq!(config.get_rx_checksum_offload(), false);
         crate::ensure_eq!(config.get_tx_checksum_offload(), false);
+        crate::ensure_eq!(config.get_nodelay(), true);
+        crate::ensure_eq!(config.get_cork(), false);
+        crate::ensure_eq!(config.get_keepalive(), false);
+        crate::ensure_eq!(config.get_keepalive_idle(), Duration::from_secs(7200));
+        crate::ensure_eq!(config.get_keepalive_interval(), Duration::from_secs(75));
+        crate::ensure_eq!(config.get_keepalive_probes(), 9);
+        crate::ensure_eq!(config.get_linger(), None);
+        crate::ensure_eq!(config.get_msl(), Duration::from_secs(30));
+        crate::ensure_eq!(config.get_reuse_addr(), false);
+        crate::ensure_eq!(config.get_sack(), true);
+        crate::ensure_eq!(config.get_timestamps(), true);
+        crate::ensure_eq!(config.get_congestion_control(), CongestionAlgorithm::None);
+        crate::ensure_eq!(config.get_ecn(), false);
+        crate::ensure_eq!(config.get_pacing(), false);
+        crate::ensure_eq!(config.get_max_pacing_rate(), None);
+
+        Ok(())
+    }
+
+    /// Tests if socket options are reflected in [TcpConfig].
+    #[test]
+    fn test_tcp_config_set_option() -> Result<()> {
+        let config: TcpConfig = TcpConfig::default()
+            .set_option(SocketOption::NoDelay(false))?
+            .set_option(SocketOption::Cork(true))?
+            .set_option(SocketOption::Congestion(CongestionAlgorithm::Cubic))?
+            .set_option(SocketOption::MaxSegment(1000))?
+            .set_option(SocketOption::ReceiveBuffer(1 << 20))?
+            .set_option(SocketOption::MaxPacingRate(Some(1 << 30)))?;
+        crate::ensure_eq!(config.get_nodelay(), false);
+        crate::ensure_eq!(config.get_cork(), true);
+        crate::ensure_eq!(config.get_congestion_control(), CongestionAlgorithm::Cubic);
+        crate::ensure_eq!(config.get_advertised_mss(), 1000);
+        crate::ensure_eq!(config.get_window_scale(), 5);
+        crate::ensure_eq!(config.get_receive_window_size(), 0x8000);
+        crate::ensure_eq!(config.get_pacing(), true);
+        crate::ensure_eq!(config.get_max_pacing_rate(), Some(1 << 30));
+        crate::ensure_eq!(
+            config.get_option(SocketOptionName::ReceiveBuffer),
+            SocketOption::ReceiveBuffer(1 << 20)
+        );
+
+        // Out of range values are rejected.
+        crate::ensure_eq!(
+            config.clone().set_option(SocketOption::MaxSegment(1)).map_err(|e| e.errno).err(),
+            Some(libc::EINVAL)
+        );
+        crate::ensure_eq!(
+            config.clone().set_option(SocketOption::MaxPacingRate(Some(0))).map_err(|e| e.errno).err(),
+            Some(libc::EINVAL)
+        );
+        crate::ensure_eq!(
+            config.set_option(SocketOption::KeepCount(0)).map_err(|e| e.errno).err(),
+            Some(libc::EINVAL)
+        );
 
         Ok(())
     }
@@ -19,6 +19,9 @@ pub const MAX_MSS: usize = u16::max_value() as usize;
 /// TODO: Auto-Discovery MTU Size
 pub const DEFAULT_MSS: usize = 1450;
 
+/// Maximum Window Scale Parameter for TCP (RFC 7323)
+pub const MAX_WINDOW_SCALE: u8 = 14;
+
 /// Length of a [crate::memory::DemiBuffer] batch.
 ///
 /// TODO: This Should be Generic
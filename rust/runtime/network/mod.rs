@@ -21,6 +21,7 @@ use self::consts::RECEIVE_BATCH_SIZE;
 
 pub mod config;
 pub mod consts;
+pub mod socket;
 pub mod types;
 
 //==============================================================================
@@ -44,6 +45,9 @@ pub trait NetworkRuntime<const N: usize> {
     /// Transmits a single [PacketBuf].
     fn transmit(&self, pkt: Box<dyn PacketBuf>);
 
+    /// Sends the packets that [transmit](Self::transmit) may have held back to send them in bursts.
+    fn flush(&self) {}
+
     /// Receives a batch of [DemiBuffer].
     fn receive(&self) -> ArrayVec<(*mut rte_mbuf, *mut ControlBlock<RECEIVE_BATCH_SIZE>), RECEIVE_BATCH_SIZE>;
 }
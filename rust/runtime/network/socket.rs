@@ -0,0 +1,370 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use crate::runtime::fail::Fail;
+use ::libc::{
+    c_int,
+    linger,
+};
+use ::std::{
+    mem,
+    ptr,
+    time::Duration,
+};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Socket Option Names
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SocketOptionName {
+    /// SO_RCVBUF
+    ReceiveBuffer,
+    /// SO_SNDBUF
+    SendBuffer,
+    /// SO_KEEPALIVE
+    KeepAlive,
+    /// SO_LINGER
+    Linger,
+    /// SO_REUSEADDR
+    ReuseAddress,
+    /// TCP_NODELAY
+    NoDelay,
+    /// TCP_MAXSEG
+    MaxSegment,
+    /// TCP_KEEPIDLE
+    KeepIdle,
+    /// TCP_KEEPINTVL
+    KeepInterval,
+    /// TCP_KEEPCNT
+    KeepCount,
+    /// TCP_CORK
+    Cork,
+    /// TCP_CONGESTION
+    Congestion,
+    /// SO_MAX_PACING_RATE
+    MaxPacingRate,
+}
+
+/// Socket Options
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SocketOption {
+    /// Size of the receive buffer, in bytes.
+    ReceiveBuffer(usize),
+    /// Size of the send buffer, in bytes.
+    SendBuffer(usize),
+    /// Send keepalive probes on idle connections?
+    KeepAlive(bool),
+    /// How long close waits for unsent data to be acknowledged, if at all.
+    Linger(Option<Duration>),
+    /// Allow binding to an address that is in TIME_WAIT?
+    ReuseAddress(bool),
+    /// Disable Nagle's algorithm?
+    NoDelay(bool),
+    /// Maximum segment size, in bytes.
+    MaxSegment(usize),
+    /// How long a connection stays idle before keepalive probes are sent.
+    KeepIdle(Duration),
+    /// Time between two unanswered keepalive probes.
+    KeepInterval(Duration),
+    /// Number of unanswered keepalive probes before the connection is dropped.
+    KeepCount(usize),
+    /// Only send full-sized segments until uncorked?
+    Cork(bool),
+    /// Congestion control algorithm.
+    Congestion(CongestionAlgorithm),
+    /// Upper bound on the pacing rate, in bytes per second, if any.
+    MaxPacingRate(Option<u64>),
+}
+
+/// Congestion Control Algorithms
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CongestionAlgorithm {
+    /// Send whatever the receive window allows.
+    None,
+    /// NewReno (RFC 5681 and RFC 6582).
+    NewReno,
+    /// CUBIC (RFC 8312).
+    Cubic,
+    /// Data Center TCP (RFC 8257).
+    Dctcp,
+    /// Bottleneck Bandwidth and Round-trip propagation time (BBR).
+    Bbr,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Socket Option Names
+impl SocketOptionName {
+    /// Converts a POSIX option level and name.
+    pub fn from_raw(level: c_int, optname: c_int) -> Result<Self, Fail> {
+        match (level, optname) {
+            (libc::SOL_SOCKET, libc::SO_RCVBUF) => Ok(Self::ReceiveBuffer),
+            (libc::SOL_SOCKET, libc::SO_SNDBUF) => Ok(Self::SendBuffer),
+            (libc::SOL_SOCKET, libc::SO_KEEPALIVE) => Ok(Self::KeepAlive),
+            (libc::SOL_SOCKET, libc::SO_LINGER) => Ok(Self::Linger),
+            (libc::SOL_SOCKET, libc::SO_REUSEADDR) => Ok(Self::ReuseAddress),
+            (libc::SOL_SOCKET, libc::SO_MAX_PACING_RATE) => Ok(Self::MaxPacingRate),
+            (libc::IPPROTO_TCP, libc::TCP_NODELAY) => Ok(Self::NoDelay),
+            (libc::IPPROTO_TCP, libc::TCP_MAXSEG) => Ok(Self::MaxSegment),
+            (libc::IPPROTO_TCP, libc::TCP_KEEPIDLE) => Ok(Self::KeepIdle),
+            (libc::IPPROTO_TCP, libc::TCP_KEEPINTVL) => Ok(Self::KeepInterval),
+            (libc::IPPROTO_TCP, libc::TCP_KEEPCNT) => Ok(Self::KeepCount),
+            (libc::IPPROTO_TCP, libc::TCP_CORK) => Ok(Self::Cork),
+            (libc::IPPROTO_TCP, libc::TCP_CONGESTION) => Ok(Self::Congestion),
+            _ => {
+                let cause: String = format!("unsupported socket option (level={:?}, optname={:?})", level, optname);
+                Err(Fail::new(libc::ENOPROTOOPT, &cause))
+            },
+        }
+    }
+}
+
+/// Associate Functions for Socket Options
+impl SocketOption {
+    /// Returns the name of the target [SocketOption].
+    pub fn name(&self) -> SocketOptionName {
+        match self {
+            Self::ReceiveBuffer(_) => SocketOptionName::ReceiveBuffer,
+            Self::SendBuffer(_) => SocketOptionName::SendBuffer,
+            Self::KeepAlive(_) => SocketOptionName::KeepAlive,
+            Self::Linger(_) => SocketOptionName::Linger,
+            Self::ReuseAddress(_) => SocketOptionName::ReuseAddress,
+            Self::NoDelay(_) => SocketOptionName::NoDelay,
+            Self::MaxSegment(_) => SocketOptionName::MaxSegment,
+            Self::KeepIdle(_) => SocketOptionName::KeepIdle,
+            Self::KeepInterval(_) => SocketOptionName::KeepInterval,
+            Self::KeepCount(_) => SocketOptionName::KeepCount,
+            Self::Cork(_) => SocketOptionName::Cork,
+            Self::Congestion(_) => SocketOptionName::Congestion,
+            Self::MaxPacingRate(_) => SocketOptionName::MaxPacingRate,
+        }
+    }
+
+    /// Parses the value of an option, as passed to setsockopt().
+    pub fn from_raw(name: SocketOptionName, optval: &[u8]) -> Result<Self, Fail> {
+        match name {
+            SocketOptionName::Linger => {
+                let value: linger = read_raw(optval)?;
+                if value.l_onoff == 0 {
+                    Ok(Self::Linger(None))
+                } else if value.l_linger < 0 {
+                    Err(Fail::new(libc::EINVAL, "negative linger timeout"))
+                } else {
+                    Ok(Self::Linger(Some(Duration::from_secs(value.l_linger as u64))))
+                }
+            },
+            SocketOptionName::KeepAlive => Ok(Self::KeepAlive(read_raw::<c_int>(optval)? != 0)),
+            SocketOptionName::ReuseAddress => Ok(Self::ReuseAddress(read_raw::<c_int>(optval)? != 0)),
+            SocketOptionName::NoDelay => Ok(Self::NoDelay(read_raw::<c_int>(optval)? != 0)),
+            SocketOptionName::Cork => Ok(Self::Cork(read_raw::<c_int>(optval)? != 0)),
+            SocketOptionName::ReceiveBuffer => Ok(Self::ReceiveBuffer(read_size(optval)?)),
+            SocketOptionName::SendBuffer => Ok(Self::SendBuffer(read_size(optval)?)),
+            SocketOptionName::MaxSegment => Ok(Self::MaxSegment(read_size(optval)?)),
+            SocketOptionName::KeepIdle => Ok(Self::KeepIdle(Duration::from_secs(read_size(optval)? as u64))),
+            SocketOptionName::KeepInterval => Ok(Self::KeepInterval(Duration::from_secs(read_size(optval)? as u64))),
+            SocketOptionName::KeepCount => Ok(Self::KeepCount(read_size(optval)?)),
+            SocketOptionName::Congestion => {
+                // The name may or may not be NUL-terminated.
+                let len: usize = optval.iter().position(|c| *c == 0).unwrap_or(optval.len());
+                match std::str::from_utf8(&optval[..len]) {
+                    Ok(name) => Ok(Self::Congestion(CongestionAlgorithm::from_name(name)?)),
+                    Err(_) => Err(Fail::new(libc::EINVAL, "congestion control name is not valid UTF-8")),
+                }
+            },
+            SocketOptionName::MaxPacingRate => {
+                // Like Linux, take either a 32-bit or a 64-bit rate.  All ones means unlimited.
+                let rate: u64 = if optval.len() >= mem::size_of::<u64>() {
+                    read_raw::<u64>(optval)?
+                } else {
+                    match read_raw::<u32>(optval)? {
+                        u32::MAX => u64::MAX,
+                        rate => rate as u64,
+                    }
+                };
+                Ok(Self::MaxPacingRate(if rate == u64::MAX { None } else { Some(rate) }))
+            },
+        }
+    }
+
+    /// Writes the value of the target [SocketOption] as returned by getsockopt(), and returns its length.
+    pub fn to_raw(&self, optval: &mut [u8]) -> Result<usize, Fail> {
+        match *self {
+            Self::Linger(timeout) => {
+                let value: linger = linger {
+                    l_onoff: timeout.is_some() as c_int,
+                    l_linger: timeout.map_or(0, |t| t.as_secs().min(c_int::MAX as u64) as c_int),
+                };
+                write_raw(optval, value)
+            },
+            Self::KeepAlive(value) | Self::ReuseAddress(value) | Self::NoDelay(value) | Self::Cork(value) => {
+                write_raw(optval, value as c_int)
+            },
+            Self::ReceiveBuffer(value) | Self::SendBuffer(value) | Self::MaxSegment(value) | Self::KeepCount(value) => {
+                write_raw(optval, value.min(c_int::MAX as usize) as c_int)
+            },
+            Self::KeepIdle(value) | Self::KeepInterval(value) => {
+                write_raw(optval, value.as_secs().min(c_int::MAX as u64) as c_int)
+            },
+            Self::Congestion(algorithm) => {
+                let name: &[u8] = algorithm.name().as_bytes();
+                if optval.len() <= name.len() {
+                    return Err(Fail::new(libc::EINVAL, "option buffer is too short"));
+                }
+                optval[..name.len()].copy_from_slice(name);
+                optval[name.len()] = 0;
+                Ok(name.len() + 1)
+            },
+            Self::MaxPacingRate(rate) => {
+                let rate: u64 = rate.unwrap_or(u64::MAX);
+                if optval.len() >= mem::size_of::<u64>() {
+                    write_raw(optval, rate)
+                } else {
+                    write_raw(optval, rate.min(u32::MAX as u64) as u32)
+                }
+            },
+        }
+    }
+}
+
+/// Associate Functions for Congestion Control Algorithms
+impl CongestionAlgorithm {
+    /// Returns the name of the target [CongestionAlgorithm], as used by TCP_CONGESTION on Linux.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::NewReno => "reno",
+            Self::Cubic => "cubic",
+            Self::Dctcp => "dctcp",
+            Self::Bbr => "bbr",
+        }
+    }
+
+    /// Looks up a congestion control algorithm by name.
+    pub fn from_name(name: &str) -> Result<Self, Fail> {
+        match name {
+            "none" => Ok(Self::None),
+            "reno" | "newreno" => Ok(Self::NewReno),
+            "cubic" => Ok(Self::Cubic),
+            "dctcp" => Ok(Self::Dctcp),
+            "bbr" => Ok(Self::Bbr),
+            _ => {
+                let cause: String = format!("unknown congestion control algorithm (name={:?})", name);
+                Err(Fail::new(libc::ENOENT, &cause))
+            },
+        }
+    }
+}
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Reads a value of type `T` from an option buffer.
+fn read_raw<T: Copy>(optval: &[u8]) -> Result<T, Fail> {
+    if optval.len() < mem::size_of::<T>() {
+        return Err(Fail::new(libc::EINVAL, "option value is too short"));
+    }
+    Ok(unsafe { ptr::read_unaligned(optval.as_ptr() as *const T) })
+}
+
+/// Reads a strictly positive size from an option buffer.
+fn read_size(optval: &[u8]) -> Result<usize, Fail> {
+    match read_raw::<c_int>(optval)? {
+        value if value > 0 => Ok(value as usize),
+        _ => Err(Fail::new(libc::EINVAL, "option value should be positive")),
+    }
+}
+
+/// Writes a value of type `T` to an option buffer.
+fn write_raw<T: Copy>(optval: &mut [u8], value: T) -> Result<usize, Fail> {
+    if optval.len() < mem::size_of::<T>() {
+        return Err(Fail::new(libc::EINVAL, "option buffer is too short"));
+    }
+    unsafe { ptr::write_unaligned(optval.as_mut_ptr() as *mut T, value) };
+    Ok(mem::size_of::<T>())
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::runtime::network::socket::{
+        CongestionAlgorithm,
+        SocketOption,
+        SocketOptionName,
+    };
+    use ::anyhow::Result;
+    use ::libc::c_int;
+    use ::std::{
+        mem,
+        time::Duration,
+    };
+
+    /// Tests if options survive a round trip through their POSIX representation.
+    #[test]
+    fn test_socket_option_raw() -> Result<()> {
+        let options: [SocketOption; 9] = [
+            SocketOption::NoDelay(true),
+            SocketOption::ReceiveBuffer(1 << 20),
+            SocketOption::Linger(Some(Duration::from_secs(5))),
+            SocketOption::Linger(None),
+            SocketOption::KeepIdle(Duration::from_secs(60)),
+            SocketOption::KeepCount(3),
+            SocketOption::Congestion(CongestionAlgorithm::Dctcp),
+            SocketOption::MaxPacingRate(Some(125_000_000)),
+            SocketOption::MaxPacingRate(None),
+        ];
+        for option in options {
+            let mut optval: [u8; 16] = [0; 16];
+            let optlen: usize = option.to_raw(&mut optval)?;
+            crate::ensure_eq!(SocketOption::from_raw(option.name(), &optval[..optlen])?, option);
+        }
+
+        Ok(())
+    }
+
+    /// Tests if bad option names and values are rejected.
+    #[test]
+    fn test_socket_option_bad_raw() -> Result<()> {
+        crate::ensure_eq!(
+            SocketOptionName::from_raw(libc::SOL_SOCKET, libc::SO_BROADCAST).map_err(|e| e.errno),
+            Err(libc::ENOPROTOOPT)
+        );
+        crate::ensure_eq!(
+            SocketOptionName::from_raw(libc::IPPROTO_TCP, libc::TCP_NODELAY).map_err(|e| e.errno),
+            Ok(SocketOptionName::NoDelay)
+        );
+
+        let zero: [u8; mem::size_of::<c_int>()] = 0i32.to_ne_bytes();
+        crate::ensure_eq!(
+            SocketOption::from_raw(SocketOptionName::SendBuffer, &zero).map_err(|e| e.errno),
+            Err(libc::EINVAL)
+        );
+        crate::ensure_eq!(
+            SocketOption::from_raw(SocketOptionName::NoDelay, &zero[..2]).map_err(|e| e.errno),
+            Err(libc::EINVAL)
+        );
+        crate::ensure_eq!(
+            SocketOption::from_raw(SocketOptionName::Congestion, b"vegas\0").map_err(|e| e.errno),
+            Err(libc::ENOENT)
+        );
+
+        // A 32-bit rate of all ones means unlimited too.
+        crate::ensure_eq!(
+            SocketOption::from_raw(SocketOptionName::MaxPacingRate, &u32::MAX.to_ne_bytes())?,
+            SocketOption::MaxPacingRate(None)
+        );
+
+        Ok(())
+    }
+}
@@ -15,6 +15,7 @@ use ::demikernel::{
         CatnipLibOS,
         runtime::{
             DPDKRuntime,
+            WorkerRequest,
             memory::MemoryManager,
         },
     },
@@ -25,19 +26,40 @@ use ::demikernel::{
             rte_eal_mp_wait_lcore,
             rte_eal_remote_launch,
         },
+        fail::Fail,
         memory::DemiBuffer,
         queue::IoQueueTable,
         network::consts::RECEIVE_BATCH_SIZE,
-        network::config::TcpConfig,
+        network::config::NdpConfig,
+        network::socket::{
+            SocketOption,
+            SocketOptionName,
+        },
+        network::NetworkRuntime,
+        timer::{
+            Timer,
+            TimerRc,
+        },
     },
     demikernel::config::Config,
+    scheduler::Scheduler,
     demi_sgarray_t,
     runtime::types::demi_opcode_t,
     LibOS,
     LibOSName,
     QDesc,
     QToken,
-    collections::ring::RingBuffer,
+    collections::{
+        ring::RingBuffer,
+        worker_channel::{
+            Completion,
+            Control,
+            DispatcherEnd,
+            WorkerChannel,
+            WorkerEnd,
+            WorkerState,
+        },
+    },
     inetstack::protocols::{
         queue::InetQueue,
         tcp::{
@@ -46,15 +68,52 @@ use ::demikernel::{
                 Socket,
                 SocketId
             },
-            segment::TcpHeader,
+            segment::{
+                TcpHeader,
+                TcpSegment,
+            },
+            SeqNumber,
             passive_open::PassiveSocket,
+            time_wait::{
+                TimeWaitTable,
+                TimeWaitVerdict,
+            },
             established::{
                 ControlBlock,
-                EstablishedSocket
+                EstablishedSocket,
+                State,
             },
         },
-        ipv4::Ipv4Header,
-        ethernet2::Ethernet2Header,
+        icmpv4::{
+            ErrorRateLimiter,
+            Icmpv4Error,
+            Icmpv4ErrorKind,
+        },
+        icmpv6::Icmpv6Peer,
+        ip::{
+            IpHeader,
+            IpProtocol,
+        },
+        ipv4::{
+            Ipv4Header,
+            ReassemblyTable,
+            RoutingTable,
+            DEFAULT_REASSEMBLY_MEMORY,
+            DEFAULT_REASSEMBLY_TIMEOUT,
+        },
+        ipv6::{
+            skip_extension_headers,
+            Ipv6Header,
+            IPV6_HEADER_SIZE,
+        },
+        ndp::{
+            solicited_node_addr,
+            NdpPeer,
+        },
+        ethernet2::{
+            EtherType2,
+            Ethernet2Header,
+        },
     },
 };
 use rand::{
@@ -67,16 +126,39 @@ use rand::{
 };
 use std::{collections::HashMap, net::Ipv4Addr};
 use ::std::{
+    collections::{
+        hash_map::DefaultHasher,
+        VecDeque,
+    },
     env,
+    hash::{
+        Hash,
+        Hasher,
+    },
+    mem,
+    ops::Range,
+    ptr::NonNull,
+    rc::Rc,
     sync::{
         Arc,
         atomic::{
             Ordering,
             AtomicBool,
+            AtomicU64,
+            AtomicUsize,
         },
     },
-    net::SocketAddrV4,
+    net::{
+        IpAddr,
+        Ipv6Addr,
+        SocketAddr,
+        SocketAddrV4,
+    },
     str::FromStr,
+    time::{
+        Duration,
+        Instant,
+    },
 };
 use log::trace;
 
@@ -100,20 +182,191 @@ use ::demikernel::perftools::profiler;
 //======================================================================================================================
 
 const MAX_WORKERS: usize = 32;
+const MAX_DISPATCHERS: usize = 8;
+/// Default capacity of the dispatcher backlog.
 const MAX_WORK_QUEUE: usize = 1024;
+/// Number of dispatcher loop iterations between two reports of the backlog counters.
+const STATS_REPORT_INTERVAL: u64 = 1 << 24;
+/// Number of dispatcher loop iterations between two sweeps for aborted connections and pending handshakes.
+const REAP_INTERVAL: u64 = 1 << 16;
+/// Number of dispatcher loop iterations between two sweeps for connections whose timers expired.
+const TIMER_INTERVAL: u64 = 1 << 10;
+/// Marks an mbuf whose private area carries request timestamps.
+const TIMESTAMPS_MAGIC: u64 = 0x7473_6361_7473_6d70;
+/// Number of buckets of a latency histogram (one per power of two nanoseconds).
+const NR_HISTOGRAM_BUCKETS: usize = 64;
+/// Stages a request goes through, between two consecutive timestamps.
+const STAGES: [&str; 4] = ["dispatcher (rx -> ring)", "ring (ring -> pop)", "service (pop -> work done)", "tx (work done -> push)"];
+
+/// Control bits of a TCP header.
+const TCP_FLAG_FIN: u8 = 0x01;
+const TCP_FLAG_RST: u8 = 0x04;
+
+/// All-nodes multicast address, which unsolicited Neighbor Advertisements are sent to.
+const IPV6_ALL_NODES: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1);
+
+/// Set when the server is asked to terminate.
+static TERMINATE: AtomicBool = AtomicBool::new(false);
+/// Number of dispatchers that reported their counters after termination was requested.
+static REPORTED: AtomicUsize = AtomicUsize::new(0);
+const WORKER_RING_SIZE: usize = 64;
+/// Number of fake work iterations between two checks of the preemption flag.
+const PREEMPTION_CHECK_INTERVAL: u64 = 256;
 
 //======================================================================================================================
 // Structures
 //======================================================================================================================
 
+/// A request handed from the dispatcher to a worker.
+type Request = WorkerRequest;
+
 pub struct TASLock {
     busy: AtomicBool,
 }
 
-enum WorkerState {
-    IDLE,
-    ALREADYIDLE,
-    BUSY,
+/// Cooperative preemption point shared between the dispatcher and a worker.
+pub struct Preemption {
+    /// Raised by the dispatcher when the worker exceeds its time quantum.
+    flag: AtomicBool,
+    /// TSC at which the worker started its current time slice, or zero if it is not running a request.
+    slice_start: AtomicU64,
+    /// Preempted requests that the worker hands back, so that the dispatcher can migrate them.
+    preempted: RingBuffer<Job>,
+    /// Preempted requests that the dispatcher hands over to the worker.
+    resumed: RingBuffer<Job>,
+}
+
+/// TSC timestamps of a request, carried in the private area of its mbuf, out of reach of the headers of the reply.
+#[repr(C)]
+pub struct Timestamps {
+    magic: u64,
+    /// Returned by `rte_eth_rx_burst()` in the dispatcher.
+    rx: u64,
+    /// Enqueued into the ring of a worker.
+    enqueue: u64,
+    /// Completed `wait_push_pop()` in the worker.
+    pop: u64,
+    /// Fake work done.
+    work: u64,
+    /// Reply pushed.
+    push: u64,
+}
+
+/// Latency histogram with power-of-two buckets, which may be read while another core is recording into it.
+pub struct LatencyHistogram {
+    buckets: [AtomicU64; NR_HISTOGRAM_BUCKETS],
+    count: AtomicU64,
+    sum: AtomicU64,
+    max: AtomicU64,
+}
+
+/// Per-stage latency histograms of a worker.
+pub struct Telemetry {
+    stages: [LatencyHistogram; STAGES.len()],
+}
+
+/// A request that a worker has popped but not yet finished.
+#[derive(Clone, Copy)]
+struct Job {
+    cb: *mut ControlBlock<RECEIVE_BATCH_SIZE>,
+    sga: demi_sgarray_t,
+    remaining: u64,
+    randomness: u64,
+    timestamps: Option<*mut Timestamps>,
+}
+
+/// What the dispatcher does with a request that does not fit in its backlog.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OverflowPolicy {
+    /// Silently drop the packet and let the client retransmit it.
+    Drop,
+    /// Echo the request right away without serving it, so the client learns it was rejected.
+    Nack,
+    /// Reset the connection.
+    Rst,
+}
+
+/// Counters of a dispatcher backlog.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BacklogStats {
+    enqueued: u64,
+    dequeued: u64,
+    rejected: u64,
+    /// Sum of the queueing delays (in TSC ticks) of dequeued requests.
+    total_delay: u64,
+    /// Largest queueing delay (in TSC ticks) of a dequeued request.
+    max_delay: u64,
+}
+
+/// Counters of connection setup and teardown in the dispatcher.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ConnectionStats {
+    accepted: u64,
+    /// Segments on the listening socket that were answered with a RST.
+    refused: u64,
+    fin: u64,
+    rst: u64,
+    /// Connections whose resources were released.
+    closed: u64,
+    /// Connections dropped by the stack, e.g. after unanswered keepalive probes.
+    aborted: u64,
+    /// Handshakes given up on after our SYN+ACKs went unanswered.
+    timed_out: u64,
+    /// Connections released in TIME-WAIT, and tracked in the TIME-WAIT table from then on.
+    time_wait: u64,
+    /// Connections in TIME-WAIT that were taken over by a new SYN for the same 4-tuple.
+    reopened: u64,
+    /// Segments that did not belong to any connection.
+    unknown: u64,
+    /// ICMP errors handed over to the workers of the connections they are about.
+    icmp_errors: u64,
+    /// Datagrams for other transports, answered with an ICMP destination unreachable.
+    unreachable: u64,
+}
+
+/// Bounded FIFO of requests waiting in the dispatcher.
+pub struct Backlog {
+    capacity: usize,
+    queue: VecDeque<(Request, u64)>,
+    stats: BacklogStats,
+}
+
+/// Workers as seen from the dispatcher.
+pub struct WorkerPool {
+    nr_workers: usize,
+    channels: Vec<DispatcherEnd<Request>>,
+    preemptions: *mut ArrayVec<*mut Preemption, MAX_WORKERS>,
+    /// Time quantum in TSC ticks (zero disables preemption).
+    quantum: u64,
+    queue_idle_worker: ArrayVec<usize, MAX_WORKERS>,
+    /// Preempted requests waiting for an idle worker to resume them.
+    queue_preempted: VecDeque<Job>,
+}
+
+/// Centralized first-come first-served: requests only go to idle workers and wait in a single backlog otherwise.
+pub struct Fcfs {
+    queue_work: Backlog,
+}
+
+/// Join-shortest-queue: requests go to the worker with the fewest outstanding requests.
+pub struct JoinShortestQueue {
+    queue_work: Backlog,
+}
+
+/// Power-of-two-choices: requests go to the least loaded of two randomly sampled workers.
+pub struct PowerOfTwoChoices {
+    rng: StdRng,
+    queue_work: Backlog,
+}
+
+/// Per-flow affinity: all requests of a flow go to the same worker.
+pub struct FlowAffinity {
+    queue_work: Vec<Backlog>,
+}
+
+/// Work stealing: requests are queued per worker by flow, and idle workers steal from the longest queue.
+pub struct WorkStealing {
+    queue_work: Vec<Backlog>,
 }
 
 pub enum FakeWorker {
@@ -126,22 +379,49 @@ pub enum FakeWorker {
 }
 
 pub struct DispatcherArg {
-    addr: SocketAddrV4,
+    dispatcher_id: u16,
+    nr_dispatchers: u16,
+    tx_queue_id: u16,
+    addr: SocketAddr,
     nr_workers: u16,
-    rings: *mut ArrayVec<*mut RingBuffer<(*mut rte_mbuf, *mut ControlBlock<RECEIVE_BATCH_SIZE>)>, MAX_WORKERS>,
-    idle_workers_ptr: *mut ArrayVec::<*mut WorkerState, MAX_WORKERS>,
+    policy: Arc<String>,
+    backlog: usize,
+    overflow: OverflowPolicy,
+    ticks_per_ns: f64,
+    telemetry: *mut ArrayVec<*mut Telemetry, MAX_WORKERS>,
+    channels: Vec<DispatcherEnd<Request>>,
+    preemptions: *mut ArrayVec<*mut Preemption, MAX_WORKERS>,
+    quantum: u64,
     spinlock: *mut Arc<TASLock>,
     mm: Arc<MemoryManager>,
 }
 
 pub struct WorkerArg {
     worker_id: u16,
-    idle_worker_ptr: *mut WorkerState,
+    preemption: *mut Preemption,
+    telemetry: *mut Telemetry,
+    ticks_per_ns: f64,
     spec: Arc<String>,
-    ring: *mut RingBuffer<(*mut rte_mbuf, *mut ControlBlock<RECEIVE_BATCH_SIZE>)>,
+    channel: WorkerEnd<Request>,
     mm: Arc<MemoryManager>,
 }
 
+//======================================================================================================================
+// Traits
+//======================================================================================================================
+
+/// Scheduling discipline used by the dispatcher to hand requests over to workers.
+pub trait DispatchPolicy {
+    /// Takes a request that was just received on `flow`, giving it back if there is no room left for it.
+    fn enqueue(&mut self, pool: &mut WorkerPool, flow: u64, request: Request) -> Result<(), Request>;
+
+    /// Hands pending requests over to workers that can take them.
+    fn dispatch(&mut self, pool: &mut WorkerPool);
+
+    /// Returns the counters of the backlogs of this policy.
+    fn stats(&self) -> BacklogStats;
+}
+
 
 //======================================================================================================================
 // TASLock
@@ -166,6 +446,205 @@ impl TASLock {
     }
 }
 
+//======================================================================================================================
+// Preemption
+//======================================================================================================================
+
+impl Preemption {
+    pub fn new() -> Self {
+        Self {
+            flag: AtomicBool::new(false),
+            slice_start: AtomicU64::new(0),
+            preempted: RingBuffer::<Job>::new(WORKER_RING_SIZE).unwrap(),
+            resumed: RingBuffer::<Job>::new(WORKER_RING_SIZE).unwrap(),
+        }
+    }
+
+    /// Called by the worker when it starts running a request.
+    pub fn begin_slice(&self) {
+        self.flag.store(false, Ordering::Relaxed);
+        self.slice_start.store(unsafe { x86::time::rdtsc() }, Ordering::Release);
+    }
+
+    /// Called by the worker when it stops running a request.
+    pub fn end_slice(&self) {
+        self.slice_start.store(0, Ordering::Release);
+    }
+
+    /// Called by the dispatcher to preempt the worker if its time slice is over.
+    pub fn check(&self, now: u64, quantum: u64) -> bool {
+        let start: u64 = self.slice_start.load(Ordering::Acquire);
+        if start != 0 && now.saturating_sub(start) > quantum {
+            self.flag.store(true, Ordering::Relaxed);
+            return true;
+        }
+        false
+    }
+}
+
+/// Measures how many TSC ticks elapse per nanosecond.
+fn tsc_ticks_per_ns() -> f64 {
+    let t0: u64 = unsafe { x86::time::rdtsc() };
+    let start: Instant = Instant::now();
+    while start.elapsed() < Duration::from_millis(10) {}
+    let t1: u64 = unsafe { x86::time::rdtsc() };
+
+    (t1 - t0) as f64 / start.elapsed().as_nanos() as f64
+}
+
+/// Returns the workers owned by dispatcher `d`, so that dispatchers own disjoint sets of workers of about the same size.
+fn worker_range(d: usize, nr_dispatchers: usize, nr_workers: usize) -> Range<usize> {
+    (d * nr_workers / nr_dispatchers)..((d + 1) * nr_workers / nr_dispatchers)
+}
+
+//======================================================================================================================
+// Telemetry
+//======================================================================================================================
+
+impl Timestamps {
+    /// Starts tracking a request that was just received in `pkt`.
+    pub fn stamp_rx(pkt: *mut rte_mbuf, now: u64) {
+        if let Some(timestamps) = unsafe { Timestamps::from_mbuf(pkt) } {
+            unsafe {
+                timestamps.write(Timestamps {
+                    magic: TIMESTAMPS_MAGIC,
+                    rx: now,
+                    enqueue: 0,
+                    pop: 0,
+                    work: 0,
+                    push: 0,
+                })
+            };
+        }
+    }
+
+    /// Records when a request is enqueued into the ring of a worker.
+    pub fn stamp_enqueue(pkt: *mut rte_mbuf) {
+        unsafe {
+            if let Some(timestamps) = Timestamps::from_mbuf(pkt).filter(|t| (**t).magic == TIMESTAMPS_MAGIC) {
+                (*timestamps).enqueue = x86::time::rdtsc();
+            }
+        }
+    }
+
+    /// Retrieves the timestamps carried by a popped scatter-gather array, if any.
+    pub fn from_sgarray(sga: &demi_sgarray_t) -> Option<*mut Timestamps> {
+        let token: NonNull<u8> = NonNull::new(sga.sga_buf as *mut u8)?;
+        let buf: DemiBuffer = unsafe { DemiBuffer::from_raw(token) };
+        if !buf.is_dpdk_allocated() {
+            // Don't drop buf, as the reference belongs to the scatter-gather array.
+            mem::forget(buf);
+            return None;
+        }
+        // Same here, the mbuf still belongs to the scatter-gather array.
+        let pkt: *mut rte_mbuf = buf.into_mbuf()?;
+        unsafe { Timestamps::from_mbuf(pkt) }.filter(|t| unsafe { (**t).magic } == TIMESTAMPS_MAGIC)
+    }
+
+    /// Locates the timestamps in the private area of the mbuf that holds the data of `pkt`. Clones share the private
+    /// area of the original mbuf, which sits right before their common data buffer.
+    unsafe fn from_mbuf(pkt: *mut rte_mbuf) -> Option<*mut Timestamps> {
+        if pkt.is_null() || ((*pkt).priv_size as usize) < mem::size_of::<Timestamps>() {
+            return None;
+        }
+        let private: *mut u8 = ((*pkt).buf_addr as *mut u8).sub((*pkt).priv_size as usize);
+        Some(private as *mut Timestamps)
+    }
+}
+
+impl LatencyHistogram {
+    pub fn new() -> Self {
+        Self {
+            buckets: std::array::from_fn(|_| AtomicU64::new(0)),
+            count: AtomicU64::new(0),
+            sum: AtomicU64::new(0),
+            max: AtomicU64::new(0),
+        }
+    }
+
+    pub fn record(&self, ns: u64) {
+        let bucket: usize = (u64::BITS - ns.leading_zeros()) as usize;
+        self.buckets[std::cmp::min(bucket, NR_HISTOGRAM_BUCKETS - 1)].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(ns, Ordering::Relaxed);
+        self.max.fetch_max(ns, Ordering::Relaxed);
+    }
+
+    /// Adds the samples of `other` to this histogram.
+    pub fn merge(&self, other: &LatencyHistogram) {
+        for (bucket, other_bucket) in self.buckets.iter().zip(other.buckets.iter()) {
+            bucket.fetch_add(other_bucket.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+        self.count.fetch_add(other.count.load(Ordering::Relaxed), Ordering::Relaxed);
+        self.sum.fetch_add(other.sum.load(Ordering::Relaxed), Ordering::Relaxed);
+        self.max.fetch_max(other.max.load(Ordering::Relaxed), Ordering::Relaxed);
+    }
+
+    /// Returns an upper bound of the `p`-th percentile.
+    pub fn percentile(&self, p: f64) -> u64 {
+        let count: u64 = self.count.load(Ordering::Relaxed);
+        let target: u64 = (count as f64 * p / 100.0).ceil() as u64;
+        let mut seen: u64 = 0;
+
+        for (i, bucket) in self.buckets.iter().enumerate() {
+            seen += bucket.load(Ordering::Relaxed);
+            if seen >= target && seen > 0 {
+                return if i == 0 { 0 } else { (1u64 << i) - 1 };
+            }
+        }
+
+        self.max.load(Ordering::Relaxed)
+    }
+
+    pub fn dump(&self, name: &str) {
+        let count: u64 = self.count.load(Ordering::Relaxed);
+        let avg: u64 = if count == 0 { 0 } else { self.sum.load(Ordering::Relaxed) / count };
+        println!(
+            "{:<28} count={:<10} avg={:<8} p50<={:<8} p99<={:<8} p99.9<={:<8} max={} (ns)",
+            name,
+            count,
+            avg,
+            self.percentile(50.0),
+            self.percentile(99.0),
+            self.percentile(99.9),
+            self.max.load(Ordering::Relaxed),
+        );
+    }
+}
+
+impl Telemetry {
+    pub fn new() -> Self {
+        Self {
+            stages: std::array::from_fn(|_| LatencyHistogram::new()),
+        }
+    }
+
+    /// Records the stage latencies of a finished request.
+    pub fn record(&self, timestamps: &Timestamps, ticks_per_ns: f64) {
+        let stamps: [u64; STAGES.len() + 1] = [timestamps.rx, timestamps.enqueue, timestamps.pop, timestamps.work, timestamps.push];
+        for (i, stage) in self.stages.iter().enumerate() {
+            stage.record((stamps[i + 1].saturating_sub(stamps[i]) as f64 / ticks_per_ns) as u64);
+        }
+    }
+
+    /// Prints per-stage histograms aggregated over all workers.
+    pub fn dump_all(telemetry: &[*mut Telemetry]) {
+        let total: Telemetry = Telemetry::new();
+        for t in telemetry {
+            for (stage, other) in total.stages.iter().zip(unsafe { (**t).stages.iter() }) {
+                stage.merge(other);
+            }
+        }
+        for (name, stage) in STAGES.iter().zip(total.stages.iter()) {
+            stage.dump(name);
+        }
+    }
+}
+
+extern "C" fn on_terminate(_signum: libc::c_int) {
+    TERMINATE.store(true, Ordering::SeqCst);
+}
+
 //======================================================================================================================
 // FakeWorker
 //======================================================================================================================
@@ -271,6 +750,24 @@ impl FakeWorker {
         }
     }
 
+    /// Runs up to `iters` iterations, stopping early once `preempt` is raised, and returns the number of iterations
+    /// that were actually run.
+    pub fn work_preemptible(&self, iters: u64, randomness: u64, preempt: &AtomicBool) -> u64 {
+        let mut done: u64 = 0;
+
+        while done < iters {
+            let chunk: u64 = std::cmp::min(PREEMPTION_CHECK_INTERVAL, iters - done);
+            self.work(chunk, randomness.wrapping_add(done));
+            done += chunk;
+
+            if preempt.load(Ordering::Relaxed) {
+                break;
+            }
+        }
+
+        done
+    }
+
     pub fn work(&self, iters: u64, randomness: u64) {
         match *self {
             FakeWorker::Sqrt => {
@@ -320,265 +817,1467 @@ impl FakeWorker {
 }
 
 //======================================================================================================================
-// wrappers
+// OverflowPolicy
 //======================================================================================================================
 
-extern "C" fn dispatcher_wrapper(data: *mut std::os::raw::c_void) -> i32 {
-    let arg: &mut DispatcherArg = unsafe { &mut *(data as *mut DispatcherArg) };
+impl FromStr for OverflowPolicy {
+    type Err = anyhow::Error;
 
-    let mut dispatcher = Dispatcher::new(arg).unwrap();
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "drop" => Ok(OverflowPolicy::Drop),
+            "nack" => Ok(OverflowPolicy::Nack),
+            "rst" => Ok(OverflowPolicy::Rst),
+            _ => anyhow::bail!("bad overflow policy: {}", s),
+        }
+    }
+}
 
-    dispatcher.run();
+//======================================================================================================================
+// Backlog
+//======================================================================================================================
 
-    #[allow(unreachable_code)]
-    0
+impl BacklogStats {
+    fn merge(&mut self, other: &BacklogStats) {
+        self.enqueued += other.enqueued;
+        self.dequeued += other.dequeued;
+        self.rejected += other.rejected;
+        self.total_delay += other.total_delay;
+        self.max_delay = std::cmp::max(self.max_delay, other.max_delay);
+    }
 }
 
-extern "C" fn worker_wrapper(data: *mut std::os::raw::c_void) -> i32 {
-    let arg: &mut WorkerArg = unsafe { &mut *(data as *mut WorkerArg) };
+impl Backlog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            queue: VecDeque::with_capacity(capacity),
+            stats: BacklogStats::default(),
+        }
+    }
 
-    let mut worker = Worker::new(arg).unwrap();
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
 
-    worker.run();
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
 
-    #[allow(unreachable_code)]
-    0
+    /// Appends a request, giving it back if the backlog is full.
+    pub fn push(&mut self, request: Request) -> Result<(), Request> {
+        if self.queue.len() >= self.capacity {
+            self.stats.rejected += 1;
+            return Err(request);
+        }
+        self.queue.push_back((request, unsafe { x86::time::rdtsc() }));
+        self.stats.enqueued += 1;
+        Ok(())
+    }
+
+    pub fn front(&self) -> Option<Request> {
+        self.queue.front().map(|(request, _)| *request)
+    }
+
+    /// Removes the oldest request, accounting for the time it spent in the backlog.
+    pub fn pop_front(&mut self) -> Option<Request> {
+        let (request, enqueued_at): (Request, u64) = self.queue.pop_front()?;
+        let delay: u64 = unsafe { x86::time::rdtsc() }.saturating_sub(enqueued_at);
+        self.stats.dequeued += 1;
+        self.stats.total_delay += delay;
+        self.stats.max_delay = std::cmp::max(self.stats.max_delay, delay);
+        Some(request)
+    }
+
+    /// Hands the oldest request over to worker `w`.
+    fn assign_front(&mut self, pool: &mut WorkerPool, w: usize) -> bool {
+        match self.front() {
+            Some(request) if pool.assign(w, request).is_ok() => {
+                self.pop_front();
+                true
+            },
+            _ => false,
+        }
+    }
+
+    pub fn stats(&self) -> BacklogStats {
+        self.stats
+    }
 }
 
 //======================================================================================================================
-// Dispatcher
+// WorkerPool
 //======================================================================================================================
 
-pub struct Dispatcher {
-    addr: SocketAddrV4,
-    nr_workers: u16,
-    rings: *mut ArrayVec<*mut RingBuffer<(*mut rte_mbuf, *mut ControlBlock<RECEIVE_BATCH_SIZE>)>, MAX_WORKERS>,
-    idle_workers_ptr: *mut ArrayVec::<*mut WorkerState, MAX_WORKERS>,
-    //
-    rt: Arc<DPDKRuntime>,
-    addresses: HashMap::<SocketId, QDesc>,
-    qtable: IoQueueTable::<InetQueue<RECEIVE_BATCH_SIZE>>,
-    queue_idle_worker: ArrayVec<usize, MAX_WORKERS>,
-}   
-
-impl Dispatcher {
-    pub fn new(args: &mut DispatcherArg) -> Result<Self> {
+impl WorkerPool {
+    pub fn new(
+        channels: Vec<DispatcherEnd<Request>>,
+        preemptions: *mut ArrayVec<*mut Preemption, MAX_WORKERS>,
+        quantum: u64,
+    ) -> Self {
+        let nr_workers: usize = channels.len();
         let mut queue_idle_worker: ArrayVec<usize, MAX_WORKERS> = ArrayVec::<usize, MAX_WORKERS>::new();
-
-        let addr: SocketAddrV4 = args.addr;
-        let nr_workers: u16 = args.nr_workers;
-
         for w in 0..nr_workers {
-            queue_idle_worker.push(w as usize);
-        } 
-
-        let port_id: u16 = 0;
-        let queue_id: u16 = 0;
-        let mm: Arc<MemoryManager> = args.mm.clone();
-
-        // Read in configuration file.
-        let config_path: String = std::env::var("CONFIG_PATH").unwrap();
-        let config: Config = Config::new(config_path);
-        let rt: Arc<DPDKRuntime> = Arc::new(DPDKRuntime::new(
-            config.local_ipv4_addr(),
-            config.arp_table(),
-            config.disable_arp(),
-            config.mss(),
-            config.tcp_checksum_offload(),
-            config.udp_checksum_offload(),
-            port_id, 
-            queue_id, 
-            mm,
-            std::ptr::null_mut(),
-        ));
-
-        let addresses: HashMap::<SocketId, QDesc> = HashMap::<SocketId, QDesc>::new();
-        let qtable: IoQueueTable::<InetQueue<RECEIVE_BATCH_SIZE>> = IoQueueTable::<InetQueue<RECEIVE_BATCH_SIZE>>::new();
+            queue_idle_worker.push(w);
+        }
 
-        let mut dispatcher: Dispatcher = Self { 
-            addr,
+        Self {
             nr_workers,
-            rings: args.rings,
-            idle_workers_ptr: args.idle_workers_ptr,
-            rt,
-            addresses,
-            qtable,
+            channels,
+            preemptions,
+            quantum,
             queue_idle_worker,
-        };
+            queue_preempted: VecDeque::new(),
+        }
+    }
 
-        dispatcher.prepare();
+    pub fn nr_workers(&self) -> usize {
+        self.nr_workers
+    }
 
-        let spinlock: *mut Arc<TASLock> = args.spinlock;
-        unsafe { (*spinlock).set() };
+    pub fn has_idle(&self) -> bool {
+        !self.queue_idle_worker.is_empty()
+    }
 
-        Ok(dispatcher)
+    /// Returns the worker that has been idle for the longest time.
+    pub fn peek_idle(&self) -> Option<usize> {
+        self.queue_idle_worker.first().copied()
     }
 
-    fn prepare(&mut self) {
-        let nonce: u32 = 7;
-        let backlog: usize = 128;
+    /// Number of requests the worker `w` still has to serve.
+    pub fn load(&self, w: usize) -> usize {
+        let channel: &DispatcherEnd<Request> = &self.channels[w];
+        let resumed: usize = unsafe { (*(*self.preemptions)[w]).resumed.len() };
+        match channel.state() {
+            WorkerState::Busy => channel.len() + resumed + 1,
+            _ => channel.len() + resumed,
+        }
+    }
 
-        let mut tcp_queue: TcpQueue<RECEIVE_BATCH_SIZE> = TcpQueue::new();
-        let socket = PassiveSocket::new(
-            self.addr,
-            backlog,
-            Some(self.rt.clone()),
-            TcpConfig::default(),
-            nonce,
-        );
-        tcp_queue.set_socket(Socket::Listening(socket));
-        let qd: QDesc = self.qtable.alloc(InetQueue::Tcp(tcp_queue));
-        self.addresses.insert(SocketId::Passive(self.addr), qd);
+    /// Returns the least loaded worker.
+    pub fn least_loaded(&self) -> usize {
+        (0..self.nr_workers).min_by_key(|w| self.load(*w)).expect("there should be at least one worker")
     }
 
-    fn get_5tuple(pkt: *mut rte_mbuf) -> (SocketAddrV4, SocketAddrV4) {
-        //Assuming Ethernet, IP and TCP
-        let header_size = 14+20+20 as usize;
-        let buf: DemiBuffer = unsafe { DemiBuffer::from_mbuf(pkt) };
+    /// Pushes a request into the ring of worker `w`, giving it back if the ring is full.
+    pub fn assign(&mut self, w: usize, request: Request) -> Result<(), Request> {
+        log::debug!("Enqueue to worker {:?}", w);
 
-        let ip_offset: usize = 14 as usize;
-        let hdr_buf: &[u8] = &buf[ip_offset..header_size];
-        // Source address.
-        let src_addr: Ipv4Addr = Ipv4Addr::new(hdr_buf[12], hdr_buf[13], hdr_buf[14], hdr_buf[15]);
-        // Destination address.
-        let dst_addr: Ipv4Addr = Ipv4Addr::new(hdr_buf[16], hdr_buf[17], hdr_buf[18], hdr_buf[19]);
-
-        let tcp_offset: usize = ip_offset+20 as usize;
-        let hdr_buf: &[u8] = &buf[tcp_offset..header_size];
-        // Source port.
-        let src_port: u16 = u16::from_be_bytes([hdr_buf[0], hdr_buf[1]]);
-        // Destination port.
-        let dst_port: u16 = u16::from_be_bytes([hdr_buf[2], hdr_buf[3]]);
+        Timestamps::stamp_enqueue(request.0);
+        self.channels[w].try_request(request)?;
 
-        let local: SocketAddrV4 = SocketAddrV4::new(dst_addr, dst_port);
-        let remote: SocketAddrV4 = SocketAddrV4::new(src_addr, src_port);
+        if let Some(idx) = self.queue_idle_worker.iter().position(|i| *i == w) {
+            self.queue_idle_worker.remove(idx);
+        }
 
-        (local, remote)
+        Ok(())
     }
 
-    fn get_qd(&mut self, local: SocketAddrV4, remote: SocketAddrV4) -> Option<QDesc> {
-        match self.addresses.get(&SocketId::Active(local, remote)) {
-            Some(qdesc) => Some(*qdesc),
-            None => match self.addresses.get(&SocketId::Passive(local)) {
-                Some(qdesc) => Some(*qdesc),
-                None => None
-            },
+    /// Moves workers that finished their requests to the idle list, and preempts workers that exceeded their quantum.
+    pub fn refresh(&mut self) {
+        if self.quantum != 0 {
+            let now: u64 = unsafe { x86::time::rdtsc() };
+            for w in 0..self.nr_workers {
+                if unsafe { (*(*self.preemptions)[w]).check(now, self.quantum) } {
+                    log::debug!("Preempting worker {:?}", w);
+                }
+            }
         }
-    }
 
-    fn run(&mut self) -> ! {
-        let port_id: u16 = 0;
-        let queue_id: u16 = 0;
-        let mut rx_pkts: [*mut rte_mbuf; RECEIVE_BATCH_SIZE] = unsafe { std::mem::zeroed() };
-        let mut queue_work: ArrayVec<(*mut rte_mbuf, *mut ControlBlock<RECEIVE_BATCH_SIZE>), MAX_WORK_QUEUE> = ArrayVec::<(*mut rte_mbuf, *mut ControlBlock<RECEIVE_BATCH_SIZE>), MAX_WORK_QUEUE>::new();
+        for w in 0..self.nr_workers {
+            self.channels[w].poll_completions();
+            let preemption: &Preemption = unsafe { &*(*self.preemptions)[w] };
+            while let Some(job) = preemption.preempted.try_dequeue() {
+                self.queue_preempted.push_back(job);
+            }
+            // The worker still has preempted requests to pick up.
+            if !preemption.resumed.is_empty() {
+                continue;
+            }
+            if self.channels[w].acknowledge_idle() {
+                self.queue_idle_worker.push(w);
+                log::debug!("Inserting the worker {:?} to idle list", w);
+            }
+        }
 
-        loop {
-            if !self.queue_idle_worker.is_empty() {
-                for (pkt, cb) in queue_work.iter_mut() {
-                    let w: usize = self.queue_idle_worker.remove(0);
-                    log::debug!("Enqueuing from queue_work to worker {:?}", w);
-    
+        self.resume();
+    }
+
+    /// Migrates preempted requests to idle workers, which need not be the ones that started them.
+    fn resume(&mut self) {
+        while !self.queue_preempted.is_empty() {
+            let w: usize = match self.peek_idle() {
+                Some(w) => w,
+                None => break,
+            };
+            let job: Job = self.queue_preempted.pop_front().expect("there should be a preempted request");
+            // Mark the worker busy before handing the request over, as for requests sent through its channel.
+            self.channels[w].set_busy();
+            if let Err(job) = unsafe { (*(*self.preemptions)[w]).resumed.try_enqueue(job) } {
+                self.queue_preempted.push_front(job);
+                break;
+            }
+            log::debug!("Resuming a preempted request on worker {:?}", w);
+            self.queue_idle_worker.remove(0);
+        }
+    }
+
+    /// Asks all workers to stop.
+    pub fn terminate(&self) {
+        for channel in self.channels.iter() {
+            channel.control(Control::Terminate);
+        }
+    }
+
+    /// Number of requests served by the workers, and number of times they were preempted.
+    pub fn completions(&self) -> (usize, usize) {
+        self.channels.iter().fold((0, 0), |(done, preempted), channel| {
+            (done + channel.nr_done(), preempted + channel.nr_preempted())
+        })
+    }
+}
+
+//======================================================================================================================
+// Dispatch Policies
+//======================================================================================================================
+
+/// Instantiates the dispatch policy named `name`, with backlogs of `capacity` requests.
+pub fn create_policy(name: &str, nr_workers: usize, capacity: usize) -> Result<Box<dyn DispatchPolicy>, &str> {
+    match name {
+        "fcfs" => Ok(Box::new(Fcfs {
+            queue_work: Backlog::new(capacity),
+        })),
+        "jsq" => Ok(Box::new(JoinShortestQueue {
+            queue_work: Backlog::new(capacity),
+        })),
+        "po2" => Ok(Box::new(PowerOfTwoChoices {
+            rng: StdRng::seed_from_u64(nr_workers as u64),
+            queue_work: Backlog::new(capacity),
+        })),
+        "affinity" => Ok(Box::new(FlowAffinity {
+            queue_work: (0..nr_workers).map(|_| Backlog::new(capacity)).collect(),
+        })),
+        "steal" => Ok(Box::new(WorkStealing {
+            queue_work: (0..nr_workers).map(|_| Backlog::new(capacity)).collect(),
+        })),
+        _ => Err("bad dispatch policy"),
+    }
+}
+
+impl DispatchPolicy for Fcfs {
+    fn enqueue(&mut self, pool: &mut WorkerPool, _flow: u64, request: Request) -> Result<(), Request> {
+        // Keep arrival order: nobody may overtake requests already waiting.
+        if self.queue_work.is_empty() {
+            if let Some(w) = pool.peek_idle() {
+                if pool.assign(w, request).is_ok() {
+                    return Ok(());
+                }
+            }
+        }
+        log::debug!("Enqueue to QUEUE_WORK");
+        self.queue_work.push(request)
+    }
+
+    fn dispatch(&mut self, pool: &mut WorkerPool) {
+        while let Some(w) = pool.peek_idle() {
+            log::debug!("Enqueuing from queue_work to worker {:?}", w);
+            if !self.queue_work.assign_front(pool, w) {
+                break;
+            }
+        }
+    }
+
+    fn stats(&self) -> BacklogStats {
+        self.queue_work.stats()
+    }
+}
+
+impl DispatchPolicy for JoinShortestQueue {
+    fn enqueue(&mut self, pool: &mut WorkerPool, _flow: u64, request: Request) -> Result<(), Request> {
+        if self.queue_work.is_empty() {
+            let w: usize = pool.least_loaded();
+            if pool.assign(w, request).is_ok() {
+                return Ok(());
+            }
+        }
+        self.queue_work.push(request)
+    }
+
+    fn dispatch(&mut self, pool: &mut WorkerPool) {
+        while self.queue_work.assign_front(pool, pool.least_loaded()) {}
+    }
+
+    fn stats(&self) -> BacklogStats {
+        self.queue_work.stats()
+    }
+}
+
+impl PowerOfTwoChoices {
+    fn choose(&mut self, pool: &WorkerPool) -> usize {
+        let nr_workers: usize = pool.nr_workers();
+        if nr_workers == 1 {
+            return 0;
+        }
+        let a: usize = self.rng.gen_range(0..nr_workers);
+        let b: usize = (a + self.rng.gen_range(1..nr_workers)) % nr_workers;
+        if pool.load(a) <= pool.load(b) {
+            a
+        } else {
+            b
+        }
+    }
+}
+
+impl DispatchPolicy for PowerOfTwoChoices {
+    fn enqueue(&mut self, pool: &mut WorkerPool, _flow: u64, request: Request) -> Result<(), Request> {
+        if self.queue_work.is_empty() {
+            let w: usize = self.choose(pool);
+            if pool.assign(w, request).is_ok() {
+                return Ok(());
+            }
+        }
+        self.queue_work.push(request)
+    }
+
+    fn dispatch(&mut self, pool: &mut WorkerPool) {
+        // Sampling may keep hitting full rings, so fall back to the least loaded worker here.
+        while self.queue_work.assign_front(pool, pool.least_loaded()) {}
+    }
+
+    fn stats(&self) -> BacklogStats {
+        self.queue_work.stats()
+    }
+}
+
+impl DispatchPolicy for FlowAffinity {
+    fn enqueue(&mut self, pool: &mut WorkerPool, flow: u64, request: Request) -> Result<(), Request> {
+        let w: usize = (flow % pool.nr_workers() as u64) as usize;
+        // Requests of the same flow must not overtake each other.
+        if self.queue_work[w].is_empty() && pool.assign(w, request).is_ok() {
+            return Ok(());
+        }
+        self.queue_work[w].push(request)
+    }
+
+    fn dispatch(&mut self, pool: &mut WorkerPool) {
+        for w in 0..pool.nr_workers() {
+            while self.queue_work[w].assign_front(pool, w) {}
+        }
+    }
+
+    fn stats(&self) -> BacklogStats {
+        let mut stats: BacklogStats = BacklogStats::default();
+        self.queue_work.iter().for_each(|backlog| stats.merge(&backlog.stats()));
+        stats
+    }
+}
+
+impl DispatchPolicy for WorkStealing {
+    fn enqueue(&mut self, pool: &mut WorkerPool, flow: u64, request: Request) -> Result<(), Request> {
+        let w: usize = (flow % pool.nr_workers() as u64) as usize;
+        self.queue_work[w].push(request)?;
+        self.dispatch(pool);
+        Ok(())
+    }
+
+    fn dispatch(&mut self, pool: &mut WorkerPool) {
+        while let Some(w) = pool.peek_idle() {
+            // Serve the worker's own queue first, otherwise steal from the longest one.
+            let victim: usize = if !self.queue_work[w].is_empty() {
+                w
+            } else {
+                let victim: usize = (0..pool.nr_workers())
+                    .max_by_key(|v| self.queue_work[*v].len())
+                    .expect("there should be at least one worker");
+                if self.queue_work[victim].is_empty() {
+                    break;
+                }
+                log::debug!("Worker {:?} steals from worker {:?}", w, victim);
+                victim
+            };
+            if !self.queue_work[victim].assign_front(pool, w) {
+                break;
+            }
+        }
+    }
+
+    fn stats(&self) -> BacklogStats {
+        let mut stats: BacklogStats = BacklogStats::default();
+        self.queue_work.iter().for_each(|backlog| stats.merge(&backlog.stats()));
+        stats
+    }
+}
+
+//======================================================================================================================
+// wrappers
+//======================================================================================================================
+
+extern "C" fn dispatcher_wrapper(data: *mut std::os::raw::c_void) -> i32 {
+    let arg: &mut DispatcherArg = unsafe { &mut *(data as *mut DispatcherArg) };
+
+    let mut dispatcher = Dispatcher::new(arg).unwrap();
+
+    dispatcher.run();
+
+    #[allow(unreachable_code)]
+    0
+}
+
+extern "C" fn worker_wrapper(data: *mut std::os::raw::c_void) -> i32 {
+    // The worker takes ownership of its arguments, including its end of the channel.
+    let arg: Box<WorkerArg> = unsafe { Box::from_raw(data as *mut WorkerArg) };
+
+    let mut worker = Worker::new(*arg).unwrap();
+
+    worker.run();
+
+    0
+}
+
+//======================================================================================================================
+// Dispatcher
+//======================================================================================================================
+
+pub struct Dispatcher {
+    dispatcher_id: u16,
+    nr_dispatchers: u16,
+    /// RSS queue this dispatcher receives from.
+    queue_id: u16,
+    addr: SocketAddr,
+    pool: WorkerPool,
+    policy: Box<dyn DispatchPolicy>,
+    overflow: OverflowPolicy,
+    ticks_per_ns: f64,
+    telemetry: *mut ArrayVec<*mut Telemetry, MAX_WORKERS>,
+    /// Requests that did not fit in the backlog, per overflow action.
+    nr_dropped: u64,
+    nr_nacked: u64,
+    nr_reset: u64,
+    connections: ConnectionStats,
+    /// Connections that are being torn down by their workers.
+    closing: Vec<QDesc>,
+    /// Control blocks of released connections, which are freed once workers are done with their requests.
+    released: Vec<*mut ControlBlock<RECEIVE_BATCH_SIZE>>,
+    /// Connections that were released in TIME-WAIT.
+    time_wait: TimeWaitTable,
+    //
+    rt: Arc<DPDKRuntime>,
+    /// Pool of the mbufs that reassembled datagrams get copied into.
+    mm: Arc<MemoryManager>,
+    /// Fragmented datagrams being put back together, as only their first fragment tells which flow they belong to.
+    reassembly: ReassemblyTable,
+    /// Clock of the connections that this dispatcher sets up.  Advanced once per loop iteration.
+    clock: TimerRc,
+    /// Tells which datagrams may be answered with an ICMP error.
+    routes: RoutingTable,
+    /// ICMP errors that this dispatcher may still send.  Each dispatcher has its own budget.
+    icmp_errors: ErrorRateLimiter,
+    /// Our IPv6 address, if any, and the peer that answers echo requests and Neighbor Solicitations for it.  Workers
+    /// only get TCP.
+    local_ipv6_addr: Option<Ipv6Addr>,
+    icmpv6: Option<Icmpv6Peer<RECEIVE_BATCH_SIZE>>,
+    /// Runs the background co-routines of the ICMPv6 and NDP peers.
+    scheduler: Scheduler,
+    addresses: HashMap::<SocketId, QDesc>,
+    qtable: IoQueueTable::<InetQueue<RECEIVE_BATCH_SIZE>>,
+}   
+
+impl Dispatcher {
+    pub fn new(args: &mut DispatcherArg) -> Result<Self> {
+        let addr: SocketAddr = args.addr;
+        let nr_workers: usize = args.nr_workers as usize;
+        let pool: WorkerPool = WorkerPool::new(mem::take(&mut args.channels), args.preemptions, args.quantum);
+        let policy: Box<dyn DispatchPolicy> = create_policy(args.policy.as_str(), nr_workers, args.backlog).unwrap();
+
+        let port_id: u16 = 0;
+        let queue_id: u16 = args.tx_queue_id;
+        let mm: Arc<MemoryManager> = args.mm.clone();
+
+        // Read in configuration file.
+        let config_path: String = std::env::var("CONFIG_PATH").unwrap();
+        let config: Config = Config::new(config_path);
+        let rt: Arc<DPDKRuntime> = Arc::new(DPDKRuntime::new(
+            config.local_ipv4_addr(),
+            config.arp_table(),
+            config.disable_arp(),
+            config.mss(),
+            config.tcp_checksum_offload(),
+            config.udp_checksum_offload(),
+            port_id, 
+            queue_id, 
+            mm.clone(),
+            std::ptr::null_mut(),
+        ));
+
+        let clock: TimerRc = TimerRc(Rc::new(Timer::new(Instant::now())));
+        let scheduler: Scheduler = Scheduler::default();
+        let local_ipv6_addr: Option<Ipv6Addr> = config.local_ipv6_addr();
+        let icmpv6: Option<Icmpv6Peer<RECEIVE_BATCH_SIZE>> = match local_ipv6_addr {
+            Some(local_ipv6_addr) => {
+                // Copies of the runtime share its transmit batch, so the peers send as the dispatcher does.
+                let peer_rt: Rc<dyn NetworkRuntime<RECEIVE_BATCH_SIZE>> = Rc::new((*rt).clone());
+                let ndp: NdpPeer<RECEIVE_BATCH_SIZE> = NdpPeer::new(
+                    peer_rt.clone(),
+                    scheduler.clone(),
+                    clock.clone(),
+                    rt.link_addr,
+                    local_ipv6_addr,
+                    NdpConfig::default(),
+                )?;
+                let icmpv6: Icmpv6Peer<RECEIVE_BATCH_SIZE> = Icmpv6Peer::new(
+                    peer_rt,
+                    scheduler.clone(),
+                    clock.clone(),
+                    rt.link_addr,
+                    local_ipv6_addr,
+                    ndp,
+                    [0; 32],
+                )?;
+                Some(icmpv6)
+            },
+            None => None,
+        };
+
+        let addresses: HashMap::<SocketId, QDesc> = HashMap::<SocketId, QDesc>::new();
+        let qtable: IoQueueTable::<InetQueue<RECEIVE_BATCH_SIZE>> = IoQueueTable::<InetQueue<RECEIVE_BATCH_SIZE>>::new();
+
+        let mut dispatcher: Dispatcher = Self { 
+            dispatcher_id: args.dispatcher_id,
+            nr_dispatchers: args.nr_dispatchers,
+            queue_id: args.dispatcher_id,
+            addr,
+            pool,
+            policy,
+            overflow: args.overflow,
+            ticks_per_ns: args.ticks_per_ns,
+            telemetry: args.telemetry,
+            nr_dropped: 0,
+            nr_nacked: 0,
+            nr_reset: 0,
+            connections: ConnectionStats::default(),
+            closing: Vec::new(),
+            released: Vec::new(),
+            time_wait: TimeWaitTable::new(rt.tcp_options.get_msl()),
+            rt,
+            mm,
+            reassembly: ReassemblyTable::new(DEFAULT_REASSEMBLY_TIMEOUT, DEFAULT_REASSEMBLY_MEMORY),
+            clock,
+            routes: config.routing_table(),
+            icmp_errors: ErrorRateLimiter::default(),
+            local_ipv6_addr,
+            icmpv6,
+            scheduler,
+            addresses,
+            qtable,
+        };
+
+        dispatcher.prepare(addr);
+        // Serve the same port on our IPv6 address, if we have one.
+        if let Some(local_ipv6_addr) = local_ipv6_addr {
+            dispatcher.prepare(SocketAddr::new(IpAddr::V6(local_ipv6_addr), addr.port()));
+        }
+
+        let spinlock: *mut Arc<TASLock> = args.spinlock;
+        unsafe { (*spinlock).set() };
+
+        Ok(dispatcher)
+    }
+
+    fn prepare(&mut self, local: SocketAddr) {
+        let nonce: u32 = 7;
+        let backlog: usize = 128;
+
+        let mut tcp_queue: TcpQueue<RECEIVE_BATCH_SIZE> = TcpQueue::new();
+        let socket = PassiveSocket::new(
+            local,
+            backlog,
+            Some(self.rt.clone()),
+            self.clock.clone(),
+            self.rt.tcp_options.clone(),
+            nonce,
+        );
+        tcp_queue.set_socket(Socket::Listening(socket));
+        let qd: QDesc = self.qtable.alloc(InetQueue::Tcp(tcp_queue));
+        self.addresses.insert(SocketId::Passive(local), qd);
+    }
+
+    fn get_5tuple(pkt: *mut rte_mbuf) -> Option<(SocketAddr, SocketAddr)> {
+        let tcp_offset: usize = Dispatcher::get_tcp_offset(pkt)?;
+        // Peek at the headers without taking ownership of the mbuf, which still has to be handed over.
+        let buf: &[u8] = unsafe {
+            let data: *const u8 = ((*pkt).buf_addr as *const u8).offset((*pkt).data_off as isize);
+            std::slice::from_raw_parts(data, tcp_offset + 20)
+        };
+
+        let ip_offset: usize = 14 as usize;
+        let hdr_buf: &[u8] = &buf[ip_offset..tcp_offset];
+        let (src_addr, dst_addr): (IpAddr, IpAddr) = if u16::from_be_bytes([buf[12], buf[13]]) == EtherType2::Ipv6 as u16 {
+            let src_addr: [u8; 16] = hdr_buf[8..24].try_into().unwrap();
+            let dst_addr: [u8; 16] = hdr_buf[24..40].try_into().unwrap();
+            (IpAddr::V6(Ipv6Addr::from(src_addr)), IpAddr::V6(Ipv6Addr::from(dst_addr)))
+        } else {
+            let src_addr: Ipv4Addr = Ipv4Addr::new(hdr_buf[12], hdr_buf[13], hdr_buf[14], hdr_buf[15]);
+            let dst_addr: Ipv4Addr = Ipv4Addr::new(hdr_buf[16], hdr_buf[17], hdr_buf[18], hdr_buf[19]);
+            (IpAddr::V4(src_addr), IpAddr::V4(dst_addr))
+        };
+
+        let hdr_buf: &[u8] = &buf[tcp_offset..];
+        // Source port.
+        let src_port: u16 = u16::from_be_bytes([hdr_buf[0], hdr_buf[1]]);
+        // Destination port.
+        let dst_port: u16 = u16::from_be_bytes([hdr_buf[2], hdr_buf[3]]);
+
+        let local: SocketAddr = SocketAddr::new(dst_addr, dst_port);
+        let remote: SocketAddr = SocketAddr::new(src_addr, src_port);
+
+        Some((local, remote))
+    }
+
+    /// Returns the upper-layer protocol of the IPv4 or IPv6 datagram in `pkt` and the offset of its header, which
+    /// follows IPv4 options or IPv6 extension headers if any.  Returns `None` if `pkt` carries neither or its headers
+    /// are truncated.
+    fn get_transport(pkt: *mut rte_mbuf) -> Option<(u8, usize)> {
+        // Requests that only service timers have no packet.
+        if pkt.is_null() {
+            return None;
+        }
+        let frame: &[u8] = unsafe {
+            let data: *const u8 = ((*pkt).buf_addr as *const u8).offset((*pkt).data_off as isize);
+            std::slice::from_raw_parts(data, (*pkt).data_len as usize)
+        };
+        if frame.len() < 14 {
+            return None;
+        }
+        match u16::from_be_bytes([frame[12], frame[13]]) {
+            ether_type if ether_type == EtherType2::Ipv4 as u16 => {
+                if frame.len() < 14 + 20 {
+                    return None;
+                }
+                let offset: usize = 14 + ((frame[14] & 0x0f) as usize) * 4;
+                if offset < 14 + 20 || frame.len() < offset {
+                    return None;
+                }
+                Some((frame[14 + 9], offset))
+            },
+            ether_type if ether_type == EtherType2::Ipv6 as u16 => {
+                let ip_hdr_size: usize = IPV6_HEADER_SIZE as usize;
+                if frame.len() < 14 + ip_hdr_size {
+                    return None;
+                }
+                let (protocol, ext_size): (u8, usize) =
+                    skip_extension_headers(frame[14 + 6], &frame[(14 + ip_hdr_size)..]).ok()?;
+                Some((protocol, 14 + ip_hdr_size + ext_size))
+            },
+            _ => None,
+        }
+    }
+
+    /// Returns the protocol of the IPv4 or IPv6 datagram in `pkt`, or `None` if `pkt` carries neither.
+    fn get_ip_protocol(pkt: *mut rte_mbuf) -> Option<u8> {
+        Dispatcher::get_transport(pkt).map(|(protocol, _)| protocol)
+    }
+
+    /// Tells whether `pkt` is a fragment of an IPv4 datagram, i.e. whether it has MF set or a non-zero offset.
+    fn is_fragment(pkt: *mut rte_mbuf) -> bool {
+        unsafe {
+            let data: *const u8 = ((*pkt).buf_addr as *const u8).offset((*pkt).data_off as isize);
+            if ((*pkt).data_len as usize) < 14 + 20 {
+                return false;
+            }
+            if u16::from_be_bytes([*data.offset(12), *data.offset(13)]) != EtherType2::Ipv4 as u16 {
+                return false;
+            }
+            u16::from_be_bytes([*data.offset(14 + 6), *data.offset(14 + 7)]) & 0x3fff != 0
+        }
+    }
+
+    /// Puts fragmented IPv4 datagrams back together, as only their first fragment carries the ports of the flow.
+    /// Returns the packet to dispatch, which is `pkt` itself unless it is a fragment, or `None` while fragments of its
+    /// datagram are still missing.
+    fn reassemble(&mut self, pkt: *mut rte_mbuf, now: u64) -> Option<*mut rte_mbuf> {
+        if !Dispatcher::is_fragment(pkt) {
+            return Some(pkt);
+        }
+
+        let buf: DemiBuffer = unsafe { DemiBuffer::from_mbuf(pkt) };
+        let (eth_hdr, payload) = match Ethernet2Header::parse(buf) {
+            Ok(result) => result,
+            Err(e) => {
+                log::warn!("Dropped packet: {:?}", e);
+                return None;
+            },
+        };
+        let (ip_hdr, payload) = match Ipv4Header::parse(payload) {
+            Ok(result) => result,
+            Err(e) => {
+                log::warn!("Dropped packet: {:?}", e);
+                return None;
+            },
+        };
+        let (ip_hdr, payload) = match self.reassembly.insert(ip_hdr, payload, self.clock.now()) {
+            Ok(Some(datagram)) => datagram,
+            Ok(None) => return None,
+            Err(e) => {
+                log::warn!("Dropped fragment: {:?}", e);
+                return None;
+            },
+        };
+
+        // Workers get the whole datagram in a single mbuf, as if it had never been fragmented.
+        let eth_hdr_size: usize = eth_hdr.compute_size();
+        let ip_hdr_size: usize = ip_hdr.compute_size();
+        let size: usize = eth_hdr_size + ip_hdr_size + payload.len();
+        let mut buf: DemiBuffer = match self.mm.alloc_body_mbuf() {
+            Ok(buf) => buf,
+            Err(e) => {
+                log::warn!("Dropped reassembled datagram: {:?}", e);
+                return None;
+            },
+        };
+        if buf.len() < size || buf.trim(buf.len() - size).is_err() {
+            log::warn!("Dropped reassembled datagram: {} bytes do not fit in an mbuf", size);
+            return None;
+        }
+        eth_hdr.serialize(&mut buf[..eth_hdr_size]);
+        ip_hdr.serialize(&mut buf[eth_hdr_size..(eth_hdr_size + ip_hdr_size)], payload.len());
+        buf[(eth_hdr_size + ip_hdr_size)..].copy_from_slice(&payload[..]);
+        log::debug!("Reassembled datagram of {} bytes", payload.len());
+
+        let pkt: *mut rte_mbuf = buf.into_mbuf()?;
+        Timestamps::stamp_rx(pkt, now);
+        Some(pkt)
+    }
+
+    /// Returns the offset of the TCP header in `pkt`, which follows IPv4 options or IPv6 extension headers if any, or
+    /// `None` if its headers are truncated.
+    fn get_tcp_offset(pkt: *mut rte_mbuf) -> Option<usize> {
+        let (_, tcp_offset): (u8, usize) = Dispatcher::get_transport(pkt)?;
+        if unsafe { (*pkt).data_len as usize } < tcp_offset + 20 {
+            return None;
+        }
+        Some(tcp_offset)
+    }
+
+    /// Parses the IPv4 or IPv6 header that follows `eth_hdr`.
+    fn parse_ip(eth_hdr: &Ethernet2Header, payload: DemiBuffer) -> Result<(IpHeader, DemiBuffer), Fail> {
+        match eth_hdr.ether_type() {
+            EtherType2::Ipv4 => Ipv4Header::parse(payload).map(|(ip_hdr, payload)| (IpHeader::V4(ip_hdr), payload)),
+            EtherType2::Ipv6 => Ipv6Header::parse(payload).map(|(ip_hdr, payload)| (IpHeader::V6(ip_hdr), payload)),
+            EtherType2::Arp => Err(Fail::new(libc::ENOTSUP, "not an IP datagram")),
+        }
+    }
+
+    /// Returns the offset of the TCP payload in `pkt`, or `None` if its headers are truncated.
+    fn get_payload_offset(pkt: *mut rte_mbuf) -> Option<usize> {
+        let tcp_offset: usize = Dispatcher::get_tcp_offset(pkt)?;
+        unsafe {
+            let data: *const u8 = ((*pkt).buf_addr as *const u8).offset((*pkt).data_off as isize);
+            Some(tcp_offset + ((*data.offset(tcp_offset as isize + 12) >> 4) as usize) * 4)
+        }
+    }
+
+    /// Returns the control bits of the TCP header in `pkt`, or zero if its headers are truncated.
+    fn get_tcp_flags(pkt: *mut rte_mbuf) -> u8 {
+        match Dispatcher::get_tcp_offset(pkt) {
+            Some(tcp_offset) => unsafe {
+                let data: *const u8 = ((*pkt).buf_addr as *const u8).offset((*pkt).data_off as isize);
+                *data.offset(tcp_offset as isize + 13)
+            },
+            None => 0,
+        }
+    }
+
+    /// Answers a segment that does not belong to any connection with a RST, and releases it.
+    fn reset(&mut self, pkt: *mut rte_mbuf) {
+        let buf: DemiBuffer = unsafe { DemiBuffer::from_mbuf(pkt) };
+        let (eth_hdr, payload) = match Ethernet2Header::parse(buf) {
+            Ok(result) => result,
+            Err(e) => return log::warn!("Dropped packet: {:?}", e),
+        };
+        let (ip_hdr, payload) = match Dispatcher::parse_ip(&eth_hdr, payload) {
+            Ok(result) => result,
+            Err(e) => return log::warn!("Dropped packet: {:?}", e),
+        };
+        let (tcp_hdr, data) = match TcpHeader::parse(&ip_hdr, payload, true) {
+            Ok(result) => result,
+            Err(e) => return log::warn!("Dropped packet: {:?}", e),
+        };
+        // Never answer a RST with a RST.
+        if !tcp_hdr.rst {
+            self.send_rst(&eth_hdr, &ip_hdr, &tcp_hdr, data.len());
+        }
+    }
+
+    /// Answers a datagram for a transport that we do not serve with an ICMP destination unreachable, and releases it.
+    /// Nothing listens for UDP, so all of its ports are closed.  IPv6 datagrams are only dropped, as we do not send
+    /// ICMPv6 errors.
+    fn unreachable(&mut self, pkt: *mut rte_mbuf, protocol: u8) {
+        let buf: DemiBuffer = unsafe { DemiBuffer::from_mbuf(pkt) };
+        let (eth_hdr, datagram) = match Ethernet2Header::parse(buf) {
+            Ok(result) => result,
+            Err(e) => return log::warn!("Dropped packet: {:?}", e),
+        };
+        let kind: Icmpv4ErrorKind = if protocol == IpProtocol::UDP as u8 {
+            Icmpv4ErrorKind::PortUnreachable
+        } else {
+            Icmpv4ErrorKind::ProtocolUnreachable
+        };
+        let message = match Icmpv4Error::reply(kind, &eth_hdr, &self.routes, &datagram[..]) {
+            Some(message) => message,
+            None => return log::trace!("Not answering datagram with protocol {}", protocol),
+        };
+        if !self.icmp_errors.try_acquire(self.clock.now()) {
+            return log::trace!("Not answering datagram with protocol {}: too many ICMP errors", protocol);
+        }
+
+        log::debug!("Sending ICMP error: {:?}", kind);
+        self.connections.unreachable += 1;
+        NetworkRuntime::<RECEIVE_BATCH_SIZE>::transmit(&*self.rt, Box::new(message));
+    }
+
+    /// Hands an ICMP error over to the worker of the connection that it is about, and drops any other ICMP message.
+    fn forward_icmp(&mut self, pkt: *mut rte_mbuf) {
+        // Peek at the message, as the mbuf itself goes to the worker.
+        let frame: &[u8] = unsafe {
+            let data: *const u8 = ((*pkt).buf_addr as *const u8).offset((*pkt).data_off as isize);
+            std::slice::from_raw_parts(data, (*pkt).data_len as usize)
+        };
+        let parsed: Result<Icmpv4Error, Fail> = DemiBuffer::from_slice(frame).and_then(|buf| {
+            let (_, payload) = Ethernet2Header::parse(buf)?;
+            let (_, payload) = Ipv4Header::parse(payload)?;
+            Icmpv4Error::parse(payload)
+        });
+
+        // Errors are about segments that we sent, so they name our end of the connection as the source.
+        let cb: Option<*mut ControlBlock<RECEIVE_BATCH_SIZE>> = match &parsed {
+            Ok(error) if error.get_protocol() == IpProtocol::TCP => {
+                match self.addresses.get(&SocketId::Active(error.get_local(), error.get_remote())) {
+                    Some(qd) => match self.qtable.get(qd) {
+                        Some(InetQueue::Tcp(queue)) => match queue.get_socket() {
+                            Socket::Established(socket) | Socket::Closing(socket) => Some(socket.cb),
+                            _ => None,
+                        },
+                        None => None,
+                    },
+                    None => None,
+                }
+            },
+            _ => None,
+        };
+
+        match (parsed, cb) {
+            (Ok(error), Some(cb)) => {
+                log::debug!("Routing ICMP error to connection: {:?}", error);
+                self.connections.icmp_errors += 1;
+                let flow: u64 = Dispatcher::flow_hash(error.get_local(), error.get_remote());
+                unsafe { (*cb).hold() };
+                if let Err((pkt, cb)) = self.policy.enqueue(&mut self.pool, flow, (pkt, cb)) {
+                    // Errors are only advisory, so they are not worth a NACK or a RST.
+                    let _: DemiBuffer = unsafe { DemiBuffer::from_mbuf(pkt) };
+                    unsafe { (*cb).release() };
+                    self.nr_dropped += 1;
+                }
+            },
+            (parsed, _) => {
+                log::debug!("Dropped ICMP message: {:?}", parsed);
+                let _: DemiBuffer = unsafe { DemiBuffer::from_mbuf(pkt) };
+            },
+        }
+    }
+
+    /// Hands an ICMPv6 message over to the ICMPv6 peer, which answers echo requests and passes Neighbor Discovery on to
+    /// the NDP peer, and releases it.  Solicitations for our address come to its solicited-node multicast address.
+    /// Errors are not handed to connections, as they are over IPv4.
+    fn receive_icmpv6(&mut self, pkt: *mut rte_mbuf) {
+        let buf: DemiBuffer = unsafe { DemiBuffer::from_mbuf(pkt) };
+        let (local_ipv6_addr, icmpv6) = match (self.local_ipv6_addr, self.icmpv6.as_mut()) {
+            (Some(local_ipv6_addr), Some(icmpv6)) => (local_ipv6_addr, icmpv6),
+            _ => return log::debug!("Dropped ICMPv6 message: no IPv6 address"),
+        };
+        let result: Result<(), Fail> = Ethernet2Header::parse(buf).and_then(|(_, payload)| {
+            let (ip_hdr, payload) = Ipv6Header::parse(payload)?;
+            let dst_addr: Ipv6Addr = ip_hdr.get_dest_addr();
+            if dst_addr != local_ipv6_addr && dst_addr != solicited_node_addr(local_ipv6_addr) && dst_addr != IPV6_ALL_NODES {
+                return Err(Fail::new(libc::ENOTCONN, "invalid destination address"));
+            }
+            icmpv6.receive(&ip_hdr, payload)
+        });
+        if let Err(e) = result {
+            log::debug!("Dropped ICMPv6 message: {:?}", e);
+        }
+    }
+
+    /// Releases connections that workers have driven to the CLOSED state, and frees their control blocks once workers
+    /// are done with their requests.
+    fn reclaim(&mut self) {
+        let mut i: usize = 0;
+        while i < self.closing.len() {
+            let qd: QDesc = self.closing[i];
+            let closed: Option<(SocketAddr, SocketAddr, *mut ControlBlock<RECEIVE_BATCH_SIZE>)> = match self.qtable.get(&qd) {
+                Some(InetQueue::Tcp(queue)) => match queue.get_socket() {
+                    Socket::Closing(socket) if socket.state() == State::Closed => {
+                        let (local, remote): (SocketAddr, SocketAddr) = socket.endpoints();
+                        Some((local, remote, socket.cb))
+                    },
+                    Socket::Closing(socket) if socket.state() == State::TimeWait => {
+                        // Nothing is left to do for the worker, so only remember what we need to answer our peer.
+                        let (local, remote): (SocketAddr, SocketAddr) = socket.endpoints();
+                        let (send_next, receive_next): (SeqNumber, SeqNumber) = socket.sequence_numbers();
+                        let expiry: Instant = socket
+                            .time_wait_deadline()
+                            .unwrap_or_else(|| self.clock.now() + 2 * self.rt.tcp_options.get_msl());
+                        self.time_wait.insert(local, remote, send_next, receive_next, expiry);
+                        self.connections.time_wait += 1;
+                        Some((local, remote, socket.cb))
+                    },
+                    Socket::Closing(_) => None,
+                    _ => unreachable!("only closing sockets are tracked"),
+                },
+                None => unreachable!("closing socket without queue descriptor"),
+            };
+
+            match closed {
+                Some((local, remote, cb)) => {
+                    // No new request can point to the control block from now on, but workers may still hold some.
+                    log::debug!("Releasing connection {:?} {:?} -- {:?}", local, remote, qd);
+                    self.addresses.remove(&SocketId::Active(local, remote));
+                    self.qtable.free(&qd);
+                    self.closing.swap_remove(i);
+                    self.released.push(cb);
+                    self.connections.closed += 1;
+                },
+                None => i += 1,
+            }
+        }
+
+        self.released.retain(|&cb| {
+            if unsafe { (*cb).is_held() } {
+                return true;
+            }
+            drop(unsafe { Box::from_raw(cb) });
+            false
+        });
+    }
+
+    /// Starts tracking established connections that the stack aborted, so that they get released, forgets
+    /// connections that are done with TIME-WAIT and datagrams whose fragments did not all arrive, and retransmits or
+    /// gives up on pending handshakes.
+    fn reap(&mut self) {
+        let nr_incomplete: usize = self.reassembly.expire(self.clock.now());
+        if nr_incomplete != 0 {
+            log::debug!("Dropped {} incomplete datagrams ({} remaining)", nr_incomplete, self.reassembly.len());
+        }
+
+        let nr_expired: usize = self.time_wait.expire(self.clock.now());
+        if nr_expired != 0 {
+            log::debug!("{} connections left TIME-WAIT ({} remaining)", nr_expired, self.time_wait.len());
+        }
+
+        for qd in self.addresses.values() {
+            if let Some(InetQueue::Tcp(queue)) = self.qtable.get_mut(qd) {
+                if let Socket::Listening(socket) = queue.get_mut_socket() {
+                    self.connections.timed_out += socket.poll_handshakes() as u64;
+                    continue;
+                }
+                let aborted: Option<EstablishedSocket<RECEIVE_BATCH_SIZE>> = match queue.get_socket() {
+                    Socket::Established(socket) if socket.state() == State::Closed => Some(socket.clone()),
+                    _ => None,
+                };
+                if let Some(socket) = aborted {
+                    log::debug!("Connection aborted: {:?} -- {:?}", socket.endpoints(), qd);
+                    queue.set_socket(Socket::Closing(socket));
+                    self.closing.push(*qd);
+                    self.connections.aborted += 1;
+                }
+            }
+        }
+    }
+
+    /// Sends a request without a packet to connections whose timers expired, so that a worker services them. Nothing
+    /// else would hand an idle connection to a worker.
+    fn tick(&mut self) {
+        let now: Instant = self.clock.now();
+        for (id, qd) in self.addresses.iter() {
+            let (local, remote): (SocketAddr, SocketAddr) = match id {
+                SocketId::Active(local, remote) => (*local, *remote),
+                SocketId::Passive(_) => continue,
+            };
+            let cb: *mut ControlBlock<RECEIVE_BATCH_SIZE> = match self.qtable.get(qd) {
+                Some(InetQueue::Tcp(queue)) => match queue.get_socket() {
+                    Socket::Established(socket) | Socket::Closing(socket) => socket.cb,
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            unsafe {
+                // Workers service the timers with every request, so held connections get theirs serviced anyway.
+                if (*cb).is_held() || !(*cb).has_expired_timers(now) {
+                    continue;
+                }
+                (*cb).hold();
+            }
+            let flow: u64 = Dispatcher::flow_hash(local, remote);
+            if let Err((_, cb)) = self.policy.enqueue(&mut self.pool, flow, (std::ptr::null_mut(), cb)) {
+                // The timers are still due on the next sweep.
+                unsafe { (*cb).release() };
+            }
+        }
+    }
+
+    /// Hashes the 4-tuple of a connection, so that policies can tell flows apart.
+    fn flow_hash(local: SocketAddr, remote: SocketAddr) -> u64 {
+        let mut hasher: DefaultHasher = DefaultHasher::new();
+        local.hash(&mut hasher);
+        remote.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Applies the overflow policy to a request that does not fit in the backlog.
+    fn reject(&mut self, request: Request) {
+        let (pkt, cb): Request = request;
+
+        // Requests that only service timers are sent again on the next sweep.
+        if pkt.is_null() {
+            unsafe { (*cb).release() };
+            return;
+        }
+
+        match self.overflow {
+            // Clear the amount of work, so that the worker echoes the request right away.
+            OverflowPolicy::Nack => match Dispatcher::get_payload_offset(pkt) {
+                Some(offset) if unsafe { (*pkt).data_len } as usize >= offset + 40 => {
                     unsafe {
-                        let ring_ptr: *mut RingBuffer<(*mut rte_mbuf, *mut ControlBlock<RECEIVE_BATCH_SIZE>)> = (*self.rings)[w as usize];
-                        (*(*self.idle_workers_ptr)[w]) = WorkerState::BUSY;
-                        (*ring_ptr).try_enqueue((*pkt, *cb)).ok();
-                    } 
-    
-                    if self.queue_idle_worker.is_empty() {
-                        break;
+                        let data: *mut u8 = ((*pkt).buf_addr as *mut u8).offset((*pkt).data_off as isize);
+                        let iterations: *mut u64 = data.offset(offset as isize + 32) as *mut u64;
+                        iterations.write_unaligned(0);
                     }
+                    let w: usize = self.pool.least_loaded();
+                    if self.pool.assign(w, request).is_ok() {
+                        self.nr_nacked += 1;
+                        return;
+                    }
+                    // No worker can even take the NACK, so fall back to dropping the request.
+                },
+                // There is no request to NACK in this segment.
+                _ => {},
+            },
+            OverflowPolicy::Rst => {
+                self.reset(pkt);
+                // Tear our side down too. Only the worker that serves the connection may touch its state, so leave it
+                // the abort, which it handles on the next timer sweep. The dispatcher that owns the connection then
+                // releases it as it does for connections that the stack aborted.
+                unsafe {
+                    (*cb).request_abort();
+                    (*cb).release();
+                }
+                self.nr_reset += 1;
+                return;
+            },
+            OverflowPolicy::Drop => {},
+        }
+
+        log::debug!("Dropping request: backlog is full");
+        let _: DemiBuffer = unsafe { DemiBuffer::from_mbuf(pkt) };
+        unsafe { (*cb).release() };
+        self.nr_dropped += 1;
+    }
+
+    /// Builds the header of a RST in response to a segment that carries `data_len` bytes, as described in RFC 793.
+    fn rst_header(tcp_hdr: &TcpHeader, data_len: usize) -> TcpHeader {
+        let mut rst_hdr: TcpHeader = TcpHeader::new(tcp_hdr.dst_port, tcp_hdr.src_port);
+        rst_hdr.rst = true;
+        if tcp_hdr.ack {
+            rst_hdr.seq_num = tcp_hdr.ack_num;
+        } else {
+            let mut seg_len: u32 = data_len as u32;
+            if tcp_hdr.syn {
+                seg_len += 1;
+            }
+            if tcp_hdr.fin {
+                seg_len += 1;
+            }
+            rst_hdr.ack = true;
+            rst_hdr.ack_num = tcp_hdr.seq_num + SeqNumber::from(seg_len);
+        }
+        rst_hdr
+    }
+
+    /// Sends a RST in response to a segment, as described in RFC 793.
+    fn send_rst(&self, eth_hdr: &Ethernet2Header, ip_hdr: &IpHeader, tcp_hdr: &TcpHeader, data_len: usize) {
+        let rst_hdr: TcpHeader = Self::rst_header(tcp_hdr, data_len);
+
+        log::debug!("Sending RST: {:?}", rst_hdr);
+        let reply_ip_hdr: IpHeader = IpHeader::new(ip_hdr.get_dest_addr(), ip_hdr.get_src_addr(), IpProtocol::TCP);
+        let segment: TcpSegment = TcpSegment {
+            ethernet2_hdr: Ethernet2Header::new(eth_hdr.src_addr(), eth_hdr.dst_addr(), reply_ip_hdr.get_ether_type()),
+            ip_hdr: reply_ip_hdr,
+            tcp_hdr: rst_hdr,
+            data: None,
+            tx_checksum_offload: true,
+        };
+        NetworkRuntime::<RECEIVE_BATCH_SIZE>::transmit(&*self.rt, Box::new(segment));
+    }
+
+    /// Sends a bare ACK in response to a segment, on behalf of a connection that no longer has a control block.
+    fn send_ack(&self, eth_hdr: &Ethernet2Header, ip_hdr: &IpHeader, tcp_hdr: &TcpHeader, seq_num: SeqNumber, ack_num: SeqNumber) {
+        let mut ack_hdr: TcpHeader = TcpHeader::new(tcp_hdr.dst_port, tcp_hdr.src_port);
+        ack_hdr.seq_num = seq_num;
+        ack_hdr.ack = true;
+        ack_hdr.ack_num = ack_num;
+
+        log::debug!("Sending ACK: {:?}", ack_hdr);
+        let reply_ip_hdr: IpHeader = IpHeader::new(ip_hdr.get_dest_addr(), ip_hdr.get_src_addr(), IpProtocol::TCP);
+        let segment: TcpSegment = TcpSegment {
+            ethernet2_hdr: Ethernet2Header::new(eth_hdr.src_addr(), eth_hdr.dst_addr(), reply_ip_hdr.get_ether_type()),
+            ip_hdr: reply_ip_hdr,
+            tcp_hdr: ack_hdr,
+            data: None,
+            tx_checksum_offload: true,
+        };
+        NetworkRuntime::<RECEIVE_BATCH_SIZE>::transmit(&*self.rt, Box::new(segment));
+    }
+
+    /// Returns whether the listening socket on `local` lets new connections reuse the address of old ones.
+    fn reuse_addr(&self, local: SocketAddr) -> bool {
+        let qd: &QDesc = match self.addresses.get(&SocketId::Passive(local)) {
+            Some(qd) => qd,
+            None => return false,
+        };
+        match self.qtable.get(qd) {
+            Some(InetQueue::Tcp(queue)) => match queue.get_socket() {
+                Socket::Listening(socket) => match socket.get_option(SocketOptionName::ReuseAddress) {
+                    SocketOption::ReuseAddress(reuse_addr) => reuse_addr,
+                    _ => unreachable!("asked for SO_REUSEADDR"),
+                },
+                _ => false,
+            },
+            None => false,
+        }
+    }
+
+    /// Handles a segment for a connection in TIME-WAIT.  Returns false if there is no such connection, in which case
+    /// `pkt` is left untouched.
+    fn time_wait(&mut self, pkt: *mut rte_mbuf, local: SocketAddr, remote: SocketAddr) -> bool {
+        if !self.time_wait.contains(local, remote) {
+            return false;
+        }
+
+        let buf: DemiBuffer = unsafe { DemiBuffer::from_mbuf(pkt) };
+        let (eth_hdr, payload) = match Ethernet2Header::parse(buf) {
+            Ok(result) => result,
+            Err(e) => {
+                log::warn!("Dropped packet: {:?}", e);
+                return true;
+            },
+        };
+        let (ip_hdr, payload) = match Dispatcher::parse_ip(&eth_hdr, payload) {
+            Ok(result) => result,
+            Err(e) => {
+                log::warn!("Dropped packet: {:?}", e);
+                return true;
+            },
+        };
+        let (tcp_hdr, data) = match TcpHeader::parse(&ip_hdr, payload, true) {
+            Ok(result) => result,
+            Err(e) => {
+                log::warn!("Dropped packet: {:?}", e);
+                return true;
+            },
+        };
+
+        let reuse_addr: bool = self.reuse_addr(local);
+        match self.time_wait.receive(local, remote, &tcp_hdr, reuse_addr, self.clock.now()) {
+            Some(TimeWaitVerdict::Ack(seq_num, ack_num)) => self.send_ack(&eth_hdr, &ip_hdr, &tcp_hdr, seq_num, ack_num),
+            Some(TimeWaitVerdict::Reopen) => {
+                log::debug!("Reopening connection in TIME-WAIT: {:?} {:?}", local, remote);
+                self.connections.reopened += 1;
+                match self.addresses.get(&SocketId::Passive(local)) {
+                    Some(&qd) => self.accept(qd, local, remote, &eth_hdr, &ip_hdr, &tcp_hdr, data.len()),
+                    None => self.send_rst(&eth_hdr, &ip_hdr, &tcp_hdr, data.len()),
+                }
+            },
+            Some(TimeWaitVerdict::Drop) => (),
+            // The entry expired just now, so this is a new connection attempt as far as we are concerned.
+            None => match self.addresses.get(&SocketId::Passive(local)) {
+                Some(&qd) => self.accept(qd, local, remote, &eth_hdr, &ip_hdr, &tcp_hdr, data.len()),
+                None if !tcp_hdr.rst => self.send_rst(&eth_hdr, &ip_hdr, &tcp_hdr, data.len()),
+                None => (),
+            },
+        }
+        true
+    }
+
+    /// Feeds a segment to the listening socket `qd`, and sets the connection up once its handshake completes.
+    fn accept(
+        &mut self,
+        qd: QDesc,
+        local: SocketAddr,
+        remote: SocketAddr,
+        eth_hdr: &Ethernet2Header,
+        ip_hdr: &IpHeader,
+        tcp_hdr: &TcpHeader,
+        data_len: usize,
+    ) {
+        let result: Result<Option<*mut ControlBlock<RECEIVE_BATCH_SIZE>>, Fail> = match self.qtable.get_mut(&qd) {
+            Some(InetQueue::Tcp(queue)) => match queue.get_mut_socket() {
+                Socket::Listening(socket) => socket.receive(qd, eth_hdr, ip_hdr, tcp_hdr),
+                _ => unreachable!("only listening sockets accept connections"),
+            },
+            None => unreachable!("listening socket without queue descriptor"),
+        };
+
+        match result {
+            Ok(Some(cb)) => {
+                //Received ACK
+                // let cb = unsafe { *Box::from_raw(cb) };
+                let new_qd: QDesc = self.qtable.alloc(InetQueue::Tcp(TcpQueue::new()));
+                let established: EstablishedSocket<RECEIVE_BATCH_SIZE> = EstablishedSocket::new(cb, new_qd);
+
+                match self.qtable.get_mut(&new_qd) {
+                    Some(InetQueue::Tcp(queue)) => queue.set_socket(Socket::Established(established)),
+                    None => unreachable!("queue descriptor was just allocated"),
+                };
+                log::trace!("{:?} {:?} -- {:?}", local, remote, new_qd);
+
+                if self.addresses.insert(SocketId::Active(local, remote), new_qd).is_some() {
+                    panic!("duplicate queue descriptor in established sockets table");
+                }
+                self.connections.accepted += 1;
+            },
+            Ok(None) => (),
+            Err(e) => {
+                // The segment does not belong to a handshake, nor does it carry a valid SYN cookie.
+                log::debug!("Refusing segment on {:?}: {:?}", local, e);
+                self.connections.refused += 1;
+                if !tcp_hdr.rst {
+                    self.send_rst(eth_hdr, ip_hdr, tcp_hdr, data_len);
                 }
+            },
+        }
+    }
+
+    /// Reports the counters, and exits once all dispatchers did so.
+    fn terminate(&self) -> ! {
+        self.pool.terminate();
+        self.report();
+        REPORTED.fetch_add(1, Ordering::SeqCst);
+
+        if self.dispatcher_id == 0 {
+            while REPORTED.load(Ordering::SeqCst) < self.nr_dispatchers as usize {
+                std::hint::spin_loop();
+            }
+            Telemetry::dump_all(unsafe { &*self.telemetry });
+            std::process::exit(0);
+        }
+
+        loop {
+            std::hint::spin_loop();
+        }
+    }
+
+    /// Logs the backlog counters.
+    fn report(&self) {
+        let stats: BacklogStats = self.policy.stats();
+        let avg_delay_ns: f64 = if stats.dequeued == 0 {
+            0.0
+        } else {
+            stats.total_delay as f64 / stats.dequeued as f64 / self.ticks_per_ns
+        };
+        log::info!(
+            "dispatcher {}: backlog: enqueued={} dequeued={} rejected={} dropped={} nacked={} reset={} avg_delay={:.0}ns max_delay={:.0}ns",
+            self.dispatcher_id,
+            stats.enqueued,
+            stats.dequeued,
+            stats.rejected,
+            self.nr_dropped,
+            self.nr_nacked,
+            self.nr_reset,
+            avg_delay_ns,
+            stats.max_delay as f64 / self.ticks_per_ns,
+        );
+        log::info!("dispatcher {}: connections: {:?}", self.dispatcher_id, self.connections);
+        let (nr_done, nr_preempted): (usize, usize) = self.pool.completions();
+        log::info!("dispatcher {}: workers: done={} preempted={}", self.dispatcher_id, nr_done, nr_preempted);
+        log::info!("dispatcher {}: tx: {:?}", self.dispatcher_id, self.rt.tx_stats());
+    }
+
+    fn get_qd(&mut self, local: SocketAddr, remote: SocketAddr) -> Option<QDesc> {
+        match self.addresses.get(&SocketId::Active(local, remote)) {
+            Some(qdesc) => Some(*qdesc),
+            None => match self.addresses.get(&SocketId::Passive(local)) {
+                Some(qdesc) => Some(*qdesc),
+                None => None
+            },
+        }
+    }
+
+    fn run(&mut self) -> ! {
+        let port_id: u16 = 0;
+        let queue_id: u16 = self.queue_id;
+        let mut rx_pkts: [*mut rte_mbuf; RECEIVE_BATCH_SIZE] = unsafe { std::mem::zeroed() };
+        let mut nr_iterations: u64 = 0;
+
+        loop {
+            nr_iterations += 1;
+            self.clock.advance_clock(Instant::now());
+            if self.icmpv6.is_some() {
+                self.scheduler.poll();
+            }
+            if nr_iterations % STATS_REPORT_INTERVAL == 0 {
+                self.report();
+            }
+
+            if TERMINATE.load(Ordering::Relaxed) {
+                self.terminate();
+            }
+
+            if nr_iterations % REAP_INTERVAL == 0 {
+                self.reap();
+            }
+
+            if nr_iterations % TIMER_INTERVAL == 0 {
+                self.tick();
             }
 
+            self.policy.dispatch(&mut self.pool);
+
             let nr_rx_pkts = unsafe { rte_eth_rx_burst(port_id, queue_id, rx_pkts.as_mut_ptr(), RECEIVE_BATCH_SIZE as u16) };
             if nr_rx_pkts != 0 {
                 log::trace!("Received {:?} packets", nr_rx_pkts);
+                let now: u64 = unsafe { x86::time::rdtsc() };
             
                 for i in 0..nr_rx_pkts {
                     let pkt = rx_pkts[i as usize];
-                    let (local, remote) = Dispatcher::get_5tuple(pkt);
-                    let buf: DemiBuffer = unsafe { DemiBuffer::from_mbuf(pkt) };
-    
+                    Timestamps::stamp_rx(pkt, now);
+                    // Fragments other than the first one carry no ports, so flows can only be told once they are whole.
+                    let pkt: *mut rte_mbuf = match self.reassemble(pkt, now) {
+                        Some(pkt) => pkt,
+                        None => continue,
+                    };
+                    match Dispatcher::get_ip_protocol(pkt) {
+                        Some(protocol) if protocol == IpProtocol::ICMPv4 as u8 => {
+                            self.forward_icmp(pkt);
+                            continue;
+                        },
+                        Some(protocol) if protocol == IpProtocol::ICMPv6 as u8 => {
+                            self.receive_icmpv6(pkt);
+                            continue;
+                        },
+                        Some(protocol) if protocol != IpProtocol::TCP as u8 => {
+                            self.unreachable(pkt, protocol);
+                            continue;
+                        },
+                        _ => {},
+                    }
+                    let (local, remote) = match Dispatcher::get_5tuple(pkt) {
+                        Some(addresses) => addresses,
+                        None => {
+                            log::warn!("Dropped truncated segment");
+                            let _: DemiBuffer = unsafe { DemiBuffer::from_mbuf(pkt) };
+                            continue;
+                        },
+                    };
+
+                    if self.time_wait(pkt, local, remote) {
+                        continue;
+                    }
+
                     let qd: QDesc = match self.get_qd(local, remote) {
                         Some(qd) => qd,
-                        None => panic!("Not be here")
+                        None => {
+                            log::debug!("Segment for unknown connection: {:?} {:?}", local, remote);
+                            self.connections.unknown += 1;
+                            self.reset(pkt);
+                            continue;
+                        },
                     };
-    
+
                     match self.qtable.get_mut(&qd) {
                         Some(InetQueue::Tcp(queue)) => {
                             match queue.get_mut_socket() {
                                 Socket::Established(socket) => {
                                     log::debug!("Routing to established connection: {:?}", socket.endpoints());
-    
-                                    if self.queue_idle_worker.is_empty() == true {
-                                        log::debug!("Enqueue to QUEUE_WORK");
-                                        queue_work.push((pkt, socket.cb));
-                                    } else {
-                                        let w = self.queue_idle_worker.remove(0);
-                                        log::debug!("Enqueue to worker {:?}", w);
-    
-                                        unsafe {
-                                            let ring_ptr = (*self.rings)[w as usize];
-                                            (*(*self.idle_workers_ptr)[w]) = WorkerState::BUSY;
-                                            (*ring_ptr).try_enqueue((pkt, socket.cb)).ok();
-                                        } 
-                                        log::debug!("Enqueue DONE");
+
+                                    let cb: *mut ControlBlock<RECEIVE_BATCH_SIZE> = socket.cb;
+                                    let flags: u8 = Dispatcher::get_tcp_flags(pkt);
+                                    if flags & (TCP_FLAG_FIN | TCP_FLAG_RST) != 0 {
+                                        // The owning worker drives the teardown, we only track it until it is over.
+                                        if flags & TCP_FLAG_RST != 0 {
+                                            self.connections.rst += 1;
+                                        } else {
+                                            self.connections.fin += 1;
+                                        }
+                                        let closing: EstablishedSocket<RECEIVE_BATCH_SIZE> = socket.clone();
+                                        queue.set_socket(Socket::Closing(closing));
+                                        self.closing.push(qd);
+                                    }
+
+                                    let flow: u64 = Dispatcher::flow_hash(local, remote);
+                                    unsafe { (*cb).hold() };
+                                    if let Err(request) = self.policy.enqueue(&mut self.pool, flow, (pkt, cb)) {
+                                        self.reject(request);
+                                    }
+                                },
+                                Socket::Closing(socket) => {
+                                    log::debug!("Routing to closing connection: {:?}", socket.endpoints());
+
+                                    let flow: u64 = Dispatcher::flow_hash(local, remote);
+                                    unsafe { (*socket.cb).hold() };
+                                    if let Err(request) = self.policy.enqueue(&mut self.pool, flow, (pkt, socket.cb)) {
+                                        self.reject(request);
                                     }
                                 },
-                                Socket::Listening(socket) => {
+                                Socket::Listening(_) => {
                                     log::debug!("Routing to passive connection: {:?}", local);
                                     log::trace!("Dispatcher receives a SYN or ACK on LISTEN state");
 
-                                    let (eth_hdr, payload) = Ethernet2Header::parse(buf).unwrap();
-                                    let (ip_hdr, payload) = Ipv4Header::parse(payload).unwrap();
-                                    let (tcp_hdr, _) = TcpHeader::parse(&ip_hdr, payload, true).unwrap();
-    
-                                    match socket.receive(qd, &eth_hdr, &ip_hdr, &tcp_hdr) {
-                                        Ok(opt_cb) => {
-                                            match opt_cb {
-                                                Some(cb) => {
-                                                    //Received ACK
-                                                    // let cb = unsafe { *Box::from_raw(cb) };
-                                                    let new_qd: QDesc = self.qtable.alloc(InetQueue::Tcp(TcpQueue::new()));
-                                                    let established: EstablishedSocket<RECEIVE_BATCH_SIZE> = EstablishedSocket::new(cb, new_qd);
-                                                    
-                                                    match self.qtable.get_mut(&new_qd) {
-                                                        Some(InetQueue::Tcp(queue)) => queue.set_socket(Socket::Established(established)),
-                                                        _ => todo!(),
-                                                    };
-                                                    log::trace!("{:?} {:?} -- {:?}", local, remote, new_qd);
-    
-                                                    if self.addresses.insert(SocketId::Active(local, remote), new_qd).is_some() {
-                                                        panic!("duplicate queue descriptor in established sockets table");
-                                                    }
-                                                },
-                                                None => (),
-                                            }
-                                        }
-                                        Err(_) => todo!(),
-                                    }
-                                }
-                                Socket::Inactive(_) => todo!(),
-                                Socket::Closing(_) => todo!(),
+                                    let buf: DemiBuffer = unsafe { DemiBuffer::from_mbuf(pkt) };
+                                    let (eth_hdr, payload) = match Ethernet2Header::parse(buf) {
+                                        Ok(result) => result,
+                                        Err(e) => {
+                                            log::warn!("Dropped packet: {:?}", e);
+                                            continue;
+                                        },
+                                    };
+                                    let (ip_hdr, payload) = match Dispatcher::parse_ip(&eth_hdr, payload) {
+                                        Ok(result) => result,
+                                        Err(e) => {
+                                            log::warn!("Dropped packet: {:?}", e);
+                                            continue;
+                                        },
+                                    };
+                                    let (tcp_hdr, data) = match TcpHeader::parse(&ip_hdr, payload, true) {
+                                        Ok(result) => result,
+                                        Err(e) => {
+                                            log::warn!("Dropped packet: {:?}", e);
+                                            continue;
+                                        },
+                                    };
+
+                                    self.accept(qd, local, remote, &eth_hdr, &ip_hdr, &tcp_hdr, data.len());
+                                },
+                                Socket::Inactive(_) => {
+                                    log::debug!("Segment for inactive socket: {:?}", qd);
+                                    self.connections.unknown += 1;
+                                    self.reset(pkt);
+                                },
                             }
                         }
-                        _ => todo!(),
-                    }
-                }
-            }
-
-            for w in 0..self.nr_workers {
-                unsafe {
-                    match *(*self.idle_workers_ptr)[w as usize] {
-                        WorkerState::IDLE => {
-                            self.queue_idle_worker.push(w as usize);
-                            log::debug!("Inserting the worker {:?} to idle list", w);
-                            (*(*self.idle_workers_ptr)[w as usize]) = WorkerState::ALREADYIDLE
+                        None => {
+                            log::warn!("Stale queue descriptor for {:?} {:?}: {:?}", local, remote, qd);
+                            self.addresses.remove(&SocketId::Active(local, remote));
+                            self.connections.unknown += 1;
+                            self.reset(pkt);
                         },
-                        _ => {},
                     }
                 }
             }
+
+            self.reclaim();
+            self.pool.refresh();
+
+            // Send the replies and resets of this iteration in a single burst.
+            NetworkRuntime::<RECEIVE_BATCH_SIZE>::flush(&*self.rt);
         }
     }
 }
@@ -590,54 +2289,153 @@ pub struct Worker {
     worker_id: u16,
     libos: LibOS,
     fakework: FakeWorker,
-    idle_ptr: *mut WorkerState,
+    channel: Rc<WorkerEnd<Request>>,
+    preemption: *mut Preemption,
+    telemetry: *mut Telemetry,
+    ticks_per_ns: f64,
+    /// Requests popped by this worker, including the ones that were preempted.
+    run_queue: VecDeque<Job>,
 }
 
 impl Worker {
-    pub fn new(args: &mut WorkerArg) -> Result<Self> {
+    pub fn new(args: WorkerArg) -> Result<Self> {
         let worker_id: u16 = args.worker_id;
         let fakework: FakeWorker = FakeWorker::create(args.spec.as_str()).unwrap();
-        let libos: LibOS = LibOS::new(LibOSName::Catnip, worker_id, args.mm.clone(), args.ring).unwrap();
+        let libos: LibOS = LibOS::new(LibOSName::Catnip, worker_id, args.mm.clone(), args.channel).unwrap();
+        let channel: Rc<WorkerEnd<Request>> = libos.get_channel();
 
         Ok(Self {
             worker_id,
             libos,
             fakework,
-            idle_ptr: args.idle_worker_ptr,
+            channel,
+            preemption: args.preemption,
+            telemetry: args.telemetry,
+            ticks_per_ns: args.ticks_per_ns,
+            run_queue: VecDeque::new(),
         })
     }
 
-    fn run(&mut self) -> ! {
-        let mut pushed: Option<QToken> = None;
-        
+    fn run(&mut self) {
+        // Pushes that did not complete yet.
+        let mut pushed: Vec<QToken> = Vec::new();
+
         loop {
-            let results = self.libos.wait_push_pop(pushed);
-            pushed = None;
+            if let Some(Control::Terminate) = self.channel.take_control() {
+                trace!("Worker {:?}: terminating", self.worker_id);
+                return;
+            }
+
+            // Pick up the preempted requests that the dispatcher migrated to us.
+            let preemption: &Preemption = unsafe { &*self.preemption };
+            while let Some(job) = preemption.resumed.try_dequeue() {
+                self.run_queue.push_back(job);
+            }
+
+            // Do not block, so that control commands and preempted requests are not left waiting. Remember which
+            // pushes were pending, to tell failed pushes from failed pops.
+            let pending: Vec<QToken> = pushed.clone();
+            let results = self.libos.try_push_pop(&mut pushed);
             for (cb, qr) in results {
                 match qr.qr_opcode {
                     // Pop completed
                     demi_opcode_t::DEMI_OPC_POP => {
                         trace!("Worker {:?}: pop completed qd={:?}", self.worker_id, qr.qr_qd);
-                        let mut sga: demi_sgarray_t = unsafe { qr.qr_value.sga };
-                        
-                        unsafe {
-                            let iterations: u64 = *(((sga.sga_segs[0].sgaseg_buf as *mut u8).offset(32)) as *mut u64);
-                            let randomness: u64 = *(((sga.sga_segs[0].sgaseg_buf as *mut u8).offset(40)) as *mut u64);
-                            self.fakework.work(iterations, randomness);
+                        let sga: demi_sgarray_t = unsafe { qr.qr_value.sga };
+                        if sga.sga_segs[0].sgaseg_len == 0 {
+                            // Our peer has closed the connection, so close our side too.
+                            trace!("Worker {:?}: connection closed by peer qd={:?}", self.worker_id, qr.qr_qd);
+                            self.libos.sgafree(sga).unwrap();
+                            if let Err(e) = unsafe { (*cb).close() } {
+                                log::warn!("Worker {:?}: failed to close qd={:?}: {:?}", self.worker_id, qr.qr_qd, e);
+                            }
+                            unsafe { (*cb).release() };
+                            Worker::finish(&self.channel, &self.run_queue, Completion::Done);
+                            continue;
+                        }
+                        let timestamps: Option<*mut Timestamps> = Timestamps::from_sgarray(&sga);
+                        if let Some(timestamps) = timestamps {
+                            unsafe { (*timestamps).pop = x86::time::rdtsc() };
+                        }
+                        let (remaining, randomness): (u64, u64) = unsafe {
+                            (
+                                *(((sga.sga_segs[0].sgaseg_buf as *mut u8).offset(32)) as *mut u64),
+                                *(((sga.sga_segs[0].sgaseg_buf as *mut u8).offset(40)) as *mut u64),
+                            )
+                        };
+                        self.run_queue.push_back(Job { cb, sga, remaining, randomness, timestamps });
+                    },
+                    // Push completed, which is the end of the request.
+                    demi_opcode_t::DEMI_OPC_PUSH => {
+                        trace!("Worker {:?}: push completed", self.worker_id);
+                        unsafe { (*cb).release() };
+                    },
+                    // The connection was reset while popping, or could not take the reply.
+                    demi_opcode_t::DEMI_OPC_FAILED => {
+                        trace!("Worker {:?}: operation failed qd={:?}", self.worker_id, qr.qr_qd);
+                        unsafe { (*cb).release() };
+                        // The request of a failed push was already reported as served.
+                        if !pending.contains(&QToken::from(qr.qr_qt)) {
+                            Worker::finish(&self.channel, &self.run_queue, Completion::Done);
                         }
-                        
-                        let qt: QToken = self.libos.push(cb, &mut sga).unwrap();
-                        pushed = Some(qt);
-                        self.libos.sgafree(sga).unwrap();
-
-                        unsafe { (*self.idle_ptr) = WorkerState::IDLE };
                     },
-                    // Push completed
-                    demi_opcode_t::DEMI_OPC_PUSH => trace!("Worker {:?}: push completed", self.worker_id),
+                    // The request carried nothing to pop, e.g. a pure ACK.
+                    demi_opcode_t::DEMI_OPC_INVALID => {
+                        trace!("Worker {:?}: nothing to pop qd={:?}", self.worker_id, qr.qr_qd);
+                        unsafe { (*cb).release() };
+                        Worker::finish(&self.channel, &self.run_queue, Completion::Done);
+                    },
                     // Any other
                     _ => panic!("Worker {:?}: Not be here", self.worker_id)
                 }
             }
+
+            if let Some(mut job) = self.run_queue.pop_front() {
+                preemption.begin_slice();
+                let done: u64 = self.fakework.work_preemptible(job.remaining, job.randomness, &preemption.flag);
+                preemption.end_slice();
+                job.remaining -= done;
+
+                if job.remaining > 0 {
+                    // Preempted: hand the remaining work back to the dispatcher, which may migrate it to another
+                    // worker, and let it hand us something else. Keep it if the dispatcher cannot take it yet.
+                    trace!("Worker {:?}: preempted with {:?} iterations left", self.worker_id, job.remaining);
+                    job.randomness = job.randomness.wrapping_add(done);
+                    if let Err(job) = preemption.preempted.try_enqueue(job) {
+                        self.run_queue.push_back(job);
+                    }
+                    Worker::finish(&self.channel, &self.run_queue, Completion::Preempted);
+                    continue;
+                }
+
+                if let Some(timestamps) = job.timestamps {
+                    unsafe { (*timestamps).work = x86::time::rdtsc() };
+                }
+
+                let mut sga: demi_sgarray_t = job.sga;
+                let qt: QToken = self.libos.push(job.cb, &mut sga).unwrap();
+                pushed.push(qt);
+
+                if let Some(timestamps) = job.timestamps {
+                    unsafe {
+                        (*timestamps).push = x86::time::rdtsc();
+                        (*self.telemetry).record(&*timestamps, self.ticks_per_ns);
+                        // The mbuf may be recycled, so forget about this request.
+                        (*timestamps).magic = 0;
+                    }
+                }
+                self.libos.sgafree(sga).unwrap();
+                Worker::finish(&self.channel, &self.run_queue, Completion::Done);
+            }
+        }
+    }
+
+    /// Tells the dispatcher that a request is over, and that this worker is idle if it has nothing else to run. Every
+    /// request the worker pops must end here, or the dispatcher keeps seeing it busy.
+    fn finish(channel: &WorkerEnd<Request>, run_queue: &VecDeque<Job>, completion: Completion) {
+        channel.complete(completion);
+        if run_queue.is_empty() {
+            channel.set_idle();
         }
     }
 }
@@ -651,56 +2449,116 @@ pub fn main() -> Result<()> {
 
     if args.len() >= 5 {
         if args[1] == "--server" {
-            let addr: SocketAddrV4 = SocketAddrV4::from_str(&args[2])?;
+            // Dispatchers listen on this IPv4 address, and on the IPv6 address of the configuration if it has one.
+            let addr: SocketAddr = SocketAddr::V4(SocketAddrV4::from_str(&args[2])?);
             let lcores: Vec<&str> = args[3].split(":").collect();
             let spec: Arc<String> = Arc::new(args[5].clone());
             let nr_workers: u16 = u16::from_str(&args[4])?;
+            let policy: Arc<String> = Arc::new(args.get(6).cloned().unwrap_or(String::from("fcfs")));
+            if let Err(e) = create_policy(policy.as_str(), nr_workers as usize, 0) {
+                anyhow::bail!("{}: {}", e, policy);
+            }
+            // Time quantum in microseconds (zero disables preemption).
+            let quantum_us: u64 = match args.get(7) {
+                Some(quantum) => u64::from_str(quantum)?,
+                None => 0,
+            };
+            let ticks_per_ns: f64 = tsc_ticks_per_ns();
+            let quantum: u64 = (quantum_us as f64 * 1000.0 * ticks_per_ns) as u64;
+            let backlog: usize = match args.get(8) {
+                Some(backlog) => usize::from_str(backlog)?,
+                None => MAX_WORK_QUEUE,
+            };
+            let overflow: OverflowPolicy = match args.get(9) {
+                Some(overflow) => OverflowPolicy::from_str(overflow)?,
+                None => OverflowPolicy::Drop,
+            };
+
+            let nr_dispatchers: u16 = match args.get(10) {
+                Some(nr_dispatchers) => u16::from_str(nr_dispatchers)?,
+                None => 1,
+            };
+            if nr_dispatchers == 0 || nr_dispatchers as usize > MAX_DISPATCHERS || nr_dispatchers > nr_workers {
+                anyhow::bail!("bad number of dispatchers: {}", nr_dispatchers);
+            }
 
-            let mm: Arc<MemoryManager> = Arc::new(CatnipLibOS::start(nr_workers).unwrap());
-            let mut idle_workers = ArrayVec::<*mut WorkerState, MAX_WORKERS>::new();
-            let mut rings: ArrayVec<*mut RingBuffer<(*mut rte_mbuf, *mut ControlBlock<RECEIVE_BATCH_SIZE>)>, MAX_WORKERS> = ArrayVec::<*mut RingBuffer<(*mut rte_mbuf, *mut ControlBlock<RECEIVE_BATCH_SIZE>)>, MAX_WORKERS>::new();
+            // One RSS queue per dispatcher, and one transmit queue per worker and per dispatcher.
+            let mm: Arc<MemoryManager> = Arc::new(CatnipLibOS::start(nr_dispatchers, nr_workers + nr_dispatchers).unwrap());
+            let mut dispatcher_ends: Vec<DispatcherEnd<Request>> = Vec::new();
+            let mut worker_ends: Vec<WorkerEnd<Request>> = Vec::new();
+            let mut preemptions: ArrayVec<*mut Preemption, MAX_WORKERS> = ArrayVec::<*mut Preemption, MAX_WORKERS>::new();
+            let mut telemetry: ArrayVec<*mut Telemetry, MAX_WORKERS> = ArrayVec::<*mut Telemetry, MAX_WORKERS>::new();
 
             for _ in 0..nr_workers {
-                let ring = Box::new(RingBuffer::<(*mut rte_mbuf, *mut ControlBlock<RECEIVE_BATCH_SIZE>)>::new(2).unwrap());
-                let ring_ptr = Box::into_raw(ring);
+                let (dispatcher_end, worker_end) = WorkerChannel::<Request>::new(WORKER_RING_SIZE).unwrap();
 
-                rings.push(ring_ptr);
-                idle_workers.push(Box::into_raw(Box::new(WorkerState::ALREADYIDLE)));
+                dispatcher_ends.push(dispatcher_end);
+                worker_ends.push(worker_end);
+                preemptions.push(Box::into_raw(Box::new(Preemption::new())));
+                telemetry.push(Box::into_raw(Box::new(Telemetry::new())));
             }
 
-            let rings_ptr = Box::into_raw(Box::new(rings));
-            let idle_workers_ptr = Box::into_raw(Box::new(idle_workers));
+            let telemetry_ptr = Box::into_raw(Box::new(telemetry));
+
+            // Dump the telemetry on exit.
+            unsafe {
+                libc::signal(libc::SIGINT, on_terminate as libc::sighandler_t);
+                libc::signal(libc::SIGTERM, on_terminate as libc::sighandler_t);
+            }
 
-            // Creating the Dispatcher
+            // Creating the Dispatchers, each one owning a disjoint set of workers.
             let mut lcore_idx: usize = 1;
-            let spinlock: Box<Arc<TASLock>> = Box::new(Arc::new(TASLock::new()));
-            let mut arg: DispatcherArg = DispatcherArg {
-                addr,
-                nr_workers,
-                rings: rings_ptr,
-                idle_workers_ptr,
-                spinlock: Box::into_raw(spinlock.clone()),
-                mm: mm.clone(),
-            };
-            let lcore_id: u32 = u32::from_str(lcores[lcore_idx])?;
-            lcore_idx += 1;
-            let arg_ptr: *mut std::os::raw::c_void = &mut arg as *mut _ as *mut std::os::raw::c_void;
-            unsafe { rte_eal_remote_launch(Some(dispatcher_wrapper), arg_ptr, lcore_id) };
-            while !spinlock.test() { }
+            for d in 0..nr_dispatchers {
+                let Range { start: first, end: last } =
+                    worker_range(d as usize, nr_dispatchers as usize, nr_workers as usize);
+
+                // Workers are handed out in order, so this dispatcher takes the first ends that are left.
+                let own_channels: Vec<DispatcherEnd<Request>> = dispatcher_ends.drain(..last - first).collect();
+                let mut own_preemptions: ArrayVec<*mut Preemption, MAX_WORKERS> = ArrayVec::new();
+                for w in first..last {
+                    own_preemptions.push(preemptions[w]);
+                }
+
+                let spinlock: Box<Arc<TASLock>> = Box::new(Arc::new(TASLock::new()));
+                let mut arg: DispatcherArg = DispatcherArg {
+                    dispatcher_id: d,
+                    nr_dispatchers,
+                    tx_queue_id: nr_workers + d,
+                    addr,
+                    nr_workers: (last - first) as u16,
+                    policy: policy.clone(),
+                    backlog,
+                    overflow,
+                    ticks_per_ns,
+                    telemetry: telemetry_ptr,
+                    channels: own_channels,
+                    preemptions: Box::into_raw(Box::new(own_preemptions)),
+                    quantum,
+                    spinlock: Box::into_raw(spinlock.clone()),
+                    mm: mm.clone(),
+                };
+                let lcore_id: u32 = u32::from_str(lcores[lcore_idx])?;
+                lcore_idx += 1;
+                let arg_ptr: *mut std::os::raw::c_void = &mut arg as *mut _ as *mut std::os::raw::c_void;
+                unsafe { rte_eal_remote_launch(Some(dispatcher_wrapper), arg_ptr, lcore_id) };
+                while !spinlock.test() { }
+            }
 
             // Creating the Worker
-            for i in 0..nr_workers {
-                let mut arg: WorkerArg = WorkerArg {
+            for (i, channel) in (0..nr_workers).zip(worker_ends.into_iter()) {
+                let arg: WorkerArg = WorkerArg {
                     worker_id: i,
-                    idle_worker_ptr: unsafe { (*idle_workers_ptr)[i as usize] },
+                    preemption: preemptions[i as usize],
+                    telemetry: unsafe { (*telemetry_ptr)[i as usize] },
+                    ticks_per_ns,
                     spec: Arc::clone(&spec),
                     mm: mm.clone(),
-                    ring: unsafe { (*rings_ptr)[i as usize] },
+                    channel,
                 };
 
                 let lcore_id = u32::from_str(lcores[lcore_idx])?;
                 lcore_idx += 1;
-                let arg_ptr: *mut std::os::raw::c_void = &mut arg as *mut _ as *mut std::os::raw::c_void;
+                let arg_ptr: *mut std::os::raw::c_void = Box::into_raw(Box::new(arg)) as *mut std::os::raw::c_void;
                 unsafe { rte_eal_remote_launch(Some(worker_wrapper), arg_ptr, lcore_id) };
             }
 
@@ -711,4 +2569,423 @@ pub fn main() -> Result<()> {
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds an mbuf whose data is `frame`, preceded by a private area of `priv_size` bytes. The storage is made of
+    /// words, so that the private area is aligned as in a real mbuf.
+    fn fake_mbuf(storage: &mut Vec<u64>, priv_size: usize, frame: &[u8]) -> rte_mbuf {
+        storage.clear();
+        storage.resize((priv_size + frame.len() + 7) / 8, 0);
+        let data: *mut u8 = unsafe { (storage.as_mut_ptr() as *mut u8).add(priv_size) };
+        unsafe { std::ptr::copy_nonoverlapping(frame.as_ptr(), data, frame.len()) };
+        let mut mbuf: rte_mbuf = unsafe { mem::zeroed() };
+        mbuf.buf_addr = data as *mut std::os::raw::c_void;
+        mbuf.priv_size = priv_size as u16;
+        mbuf.data_len = frame.len() as u16;
+        mbuf
+    }
+
+    /// Builds an Ethernet frame that carries a TCP segment with the given control bits, in an IPv4 datagram with
+    /// `nr_options` words of IP options.
+    fn tcp_frame(nr_options: usize, flags: u8) -> Vec<u8> {
+        let mut frame: Vec<u8> = vec![0; 14];
+        frame[12..14].copy_from_slice(&(EtherType2::Ipv4 as u16).to_be_bytes());
+        let mut ip_hdr: Vec<u8> = vec![0; 20 + 4 * nr_options];
+        ip_hdr[0] = 0x40 | (5 + nr_options as u8);
+        ip_hdr[9] = IpProtocol::TCP as u8;
+        ip_hdr[12..16].copy_from_slice(&[10, 0, 0, 2]);
+        ip_hdr[16..20].copy_from_slice(&[10, 0, 0, 1]);
+        frame.extend_from_slice(&ip_hdr);
+        let mut tcp_hdr: Vec<u8> = vec![0; 20];
+        tcp_hdr[0..2].copy_from_slice(&49152u16.to_be_bytes());
+        tcp_hdr[2..4].copy_from_slice(&80u16.to_be_bytes());
+        tcp_hdr[12] = 5 << 4;
+        tcp_hdr[13] = flags;
+        frame.extend_from_slice(&tcp_hdr);
+        frame
+    }
+
+    /// Builds a pool of `nr_workers` workers, and returns it along with the worker ends of their channels.
+    fn fake_pool(nr_workers: usize) -> Result<(WorkerPool, Vec<WorkerEnd<Request>>)> {
+        let mut channels: Vec<DispatcherEnd<Request>> = Vec::new();
+        let mut workers: Vec<WorkerEnd<Request>> = Vec::new();
+        let mut preemptions: ArrayVec<*mut Preemption, MAX_WORKERS> = ArrayVec::new();
+        for _ in 0..nr_workers {
+            let (dispatcher_end, worker_end) = WorkerChannel::<Request>::new(WORKER_RING_SIZE)?;
+            channels.push(dispatcher_end);
+            workers.push(worker_end);
+            preemptions.push(Box::into_raw(Box::new(Preemption::new())));
+        }
+        let pool: WorkerPool = WorkerPool::new(channels, Box::into_raw(Box::new(preemptions)), 0);
+        Ok((pool, workers))
+    }
+
+    /// Builds requests that carry the given (empty) mbufs.
+    fn fake_requests(mbufs: &mut [rte_mbuf]) -> Vec<Request> {
+        mbufs.iter_mut().map(|mbuf| (mbuf as *mut rte_mbuf, std::ptr::null_mut())).collect()
+    }
+
+    /// Tests if policies are picked by name, and unknown names are refused.
+    #[test]
+    fn policy_selection() -> Result<()> {
+        for name in ["fcfs", "jsq", "po2", "affinity", "steal"] {
+            ::demikernel::ensure_eq!(create_policy(name, 2, 4).is_ok(), true);
+        }
+        ::demikernel::ensure_eq!(create_policy("lifo", 2, 4).is_err(), true);
+
+        Ok(())
+    }
+
+    /// Tests if FCFS only hands requests over to idle workers, and keeps the others in arrival order.
+    #[test]
+    fn policy_fcfs() -> Result<()> {
+        let mut mbufs: Vec<rte_mbuf> = (0..3).map(|_| unsafe { mem::zeroed() }).collect();
+        let requests: Vec<Request> = fake_requests(&mut mbufs);
+        let (mut pool, workers) = fake_pool(2)?;
+        let mut policy: Box<dyn DispatchPolicy> = create_policy("fcfs", 2, 4).unwrap();
+
+        for request in requests.iter() {
+            ::demikernel::ensure_eq!(policy.enqueue(&mut pool, 0, *request).is_ok(), true);
+        }
+        ::demikernel::ensure_eq!(workers[0].try_recv_request(), Some(requests[0]));
+        ::demikernel::ensure_eq!(workers[1].try_recv_request(), Some(requests[1]));
+        ::demikernel::ensure_eq!(policy.stats().enqueued, 1);
+
+        // The last request waits for a worker to be done.
+        policy.dispatch(&mut pool);
+        ::demikernel::ensure_eq!(workers[0].try_recv_request(), None);
+        Worker::finish(&workers[1], &VecDeque::new(), Completion::Done);
+        pool.refresh();
+        policy.dispatch(&mut pool);
+        ::demikernel::ensure_eq!(workers[1].try_recv_request(), Some(requests[2]));
+        ::demikernel::ensure_eq!(policy.stats().dequeued, 1);
+
+        Ok(())
+    }
+
+    /// Tests if JSQ hands requests over to the worker with the fewest outstanding requests.
+    #[test]
+    fn policy_jsq() -> Result<()> {
+        let mut mbufs: Vec<rte_mbuf> = (0..3).map(|_| unsafe { mem::zeroed() }).collect();
+        let requests: Vec<Request> = fake_requests(&mut mbufs);
+        let (mut pool, _workers) = fake_pool(2)?;
+        let mut policy: Box<dyn DispatchPolicy> = create_policy("jsq", 2, 4).unwrap();
+
+        for request in requests.iter() {
+            ::demikernel::ensure_eq!(policy.enqueue(&mut pool, 0, *request).is_ok(), true);
+        }
+        // Both workers are busy with one request, so the last one goes to the first of them.
+        ::demikernel::ensure_eq!(pool.load(0), 3);
+        ::demikernel::ensure_eq!(pool.load(1), 2);
+        ::demikernel::ensure_eq!(policy.stats().enqueued, 0);
+
+        Ok(())
+    }
+
+    /// Tests if flow affinity keeps all requests of a flow on the same worker, in order.
+    #[test]
+    fn policy_flow_affinity() -> Result<()> {
+        let mut mbufs: Vec<rte_mbuf> = (0..3).map(|_| unsafe { mem::zeroed() }).collect();
+        let requests: Vec<Request> = fake_requests(&mut mbufs);
+        let (mut pool, workers) = fake_pool(2)?;
+        let mut policy: Box<dyn DispatchPolicy> = create_policy("affinity", 2, 4).unwrap();
+
+        ::demikernel::ensure_eq!(policy.enqueue(&mut pool, 3, requests[0]).is_ok(), true);
+        ::demikernel::ensure_eq!(policy.enqueue(&mut pool, 4, requests[1]).is_ok(), true);
+        ::demikernel::ensure_eq!(policy.enqueue(&mut pool, 3, requests[2]).is_ok(), true);
+        ::demikernel::ensure_eq!(workers[0].try_recv_request(), Some(requests[1]));
+        ::demikernel::ensure_eq!(workers[0].try_recv_request(), None);
+        ::demikernel::ensure_eq!(workers[1].try_recv_request(), Some(requests[0]));
+        ::demikernel::ensure_eq!(workers[1].try_recv_request(), Some(requests[2]));
+
+        Ok(())
+    }
+
+    /// Tests if an idle worker steals the requests queued for a busy one.
+    #[test]
+    fn policy_work_stealing() -> Result<()> {
+        let mut mbufs: Vec<rte_mbuf> = (0..2).map(|_| unsafe { mem::zeroed() }).collect();
+        let requests: Vec<Request> = fake_requests(&mut mbufs);
+        let (mut pool, workers) = fake_pool(2)?;
+        let mut policy: Box<dyn DispatchPolicy> = create_policy("steal", 2, 4).unwrap();
+
+        // Both requests belong to the flow of the first worker, which only takes the first one.
+        ::demikernel::ensure_eq!(policy.enqueue(&mut pool, 0, requests[0]).is_ok(), true);
+        ::demikernel::ensure_eq!(policy.enqueue(&mut pool, 0, requests[1]).is_ok(), true);
+        ::demikernel::ensure_eq!(workers[0].try_recv_request(), Some(requests[0]));
+        ::demikernel::ensure_eq!(workers[0].try_recv_request(), None);
+        ::demikernel::ensure_eq!(workers[1].try_recv_request(), Some(requests[1]));
+        ::demikernel::ensure_eq!(pool.has_idle(), false);
+
+        Ok(())
+    }
+
+    /// Tests if a worker gets preempted only once it runs a request for longer than the quantum.
+    #[test]
+    fn preemption_quantum() -> Result<()> {
+        let preemption: Preemption = Preemption::new();
+
+        // A worker that is not running a request is never preempted.
+        ::demikernel::ensure_eq!(preemption.check(u64::MAX, 0), false);
+
+        preemption.begin_slice();
+        let start: u64 = preemption.slice_start.load(Ordering::Acquire);
+        ::demikernel::ensure_eq!(preemption.check(start + 1000, 1000), false);
+        ::demikernel::ensure_eq!(preemption.flag.load(Ordering::Relaxed), false);
+        ::demikernel::ensure_eq!(preemption.check(start + 1001, 1000), true);
+        ::demikernel::ensure_eq!(preemption.flag.load(Ordering::Relaxed), true);
+
+        // A new slice lowers the flag, and the end of a slice stops the clock.
+        preemption.begin_slice();
+        ::demikernel::ensure_eq!(preemption.flag.load(Ordering::Relaxed), false);
+        preemption.end_slice();
+        ::demikernel::ensure_eq!(preemption.check(u64::MAX, 0), false);
+
+        Ok(())
+    }
+
+    /// Tests if fake work stops at the first check of the preemption flag once it is raised.
+    #[test]
+    fn preemptible_work() -> Result<()> {
+        let worker: FakeWorker = FakeWorker::Sqrt;
+        let preempt: AtomicBool = AtomicBool::new(false);
+        ::demikernel::ensure_eq!(worker.work_preemptible(1000, 0, &preempt), 1000);
+
+        preempt.store(true, Ordering::Relaxed);
+        ::demikernel::ensure_eq!(worker.work_preemptible(1000, 0, &preempt), PREEMPTION_CHECK_INTERVAL);
+        ::demikernel::ensure_eq!(worker.work_preemptible(100, 0, &preempt), 100);
+
+        Ok(())
+    }
+
+    /// Tests if a preempted request is migrated to the worker that has been idle for the longest time.
+    #[test]
+    fn preemption_migrates_requests() -> Result<()> {
+        let mut mbufs: Vec<rte_mbuf> = (0..1).map(|_| unsafe { mem::zeroed() }).collect();
+        let requests: Vec<Request> = fake_requests(&mut mbufs);
+        let (mut pool, workers) = fake_pool(2)?;
+
+        ::demikernel::ensure_eq!(pool.assign(0, requests[0]).is_ok(), true);
+        ::demikernel::ensure_eq!(workers[0].try_recv_request(), Some(requests[0]));
+
+        // The first worker runs out of its quantum and hands the request back.
+        let job: Job = Job {
+            cb: std::ptr::null_mut(),
+            sga: unsafe { mem::zeroed() },
+            remaining: 1000,
+            randomness: 0,
+            timestamps: None,
+        };
+        let preemptions: &ArrayVec<*mut Preemption, MAX_WORKERS> = unsafe { &*pool.preemptions };
+        ::demikernel::ensure_eq!(unsafe { (*preemptions[0]).preempted.try_enqueue(job).is_ok() }, true);
+        Worker::finish(&workers[0], &VecDeque::new(), Completion::Preempted);
+
+        pool.refresh();
+        ::demikernel::ensure_eq!(unsafe { (*preemptions[0]).resumed.len() }, 0);
+        ::demikernel::ensure_eq!(unsafe { (*preemptions[1]).resumed.len() }, 1);
+        ::demikernel::ensure_eq!(pool.peek_idle(), Some(0));
+        ::demikernel::ensure_eq!(pool.load(1), 2);
+        ::demikernel::ensure_eq!(pool.completions(), (0, 1));
+
+        Ok(())
+    }
+
+    /// Tests if a full backlog refuses requests, and accounts for them.
+    #[test]
+    fn backlog_overflow() -> Result<()> {
+        let mut mbufs: Vec<rte_mbuf> = (0..3).map(|_| unsafe { mem::zeroed() }).collect();
+        let requests: Vec<Request> = fake_requests(&mut mbufs);
+        let mut backlog: Backlog = Backlog::new(2);
+
+        ::demikernel::ensure_eq!(backlog.push(requests[0]), Ok(()));
+        ::demikernel::ensure_eq!(backlog.push(requests[1]), Ok(()));
+        ::demikernel::ensure_eq!(backlog.push(requests[2]), Err(requests[2]));
+        ::demikernel::ensure_eq!(backlog.len(), 2);
+        ::demikernel::ensure_eq!(backlog.stats().enqueued, 2);
+        ::demikernel::ensure_eq!(backlog.stats().rejected, 1);
+
+        // Room is made in arrival order.
+        ::demikernel::ensure_eq!(backlog.pop_front(), Some(requests[0]));
+        ::demikernel::ensure_eq!(backlog.push(requests[2]), Ok(()));
+        ::demikernel::ensure_eq!(backlog.front(), Some(requests[1]));
+        let stats: BacklogStats = backlog.stats();
+        ::demikernel::ensure_eq!(stats.dequeued, 1);
+        ::demikernel::ensure_eq!(stats.total_delay, stats.max_delay);
+
+        Ok(())
+    }
+
+    /// Tests if a policy gives requests back once both the workers and its backlog are full.
+    #[test]
+    fn policy_overflow() -> Result<()> {
+        let mut mbufs: Vec<rte_mbuf> = (0..3).map(|_| unsafe { mem::zeroed() }).collect();
+        let requests: Vec<Request> = fake_requests(&mut mbufs);
+        let (mut pool, _workers) = fake_pool(1)?;
+        let mut policy: Box<dyn DispatchPolicy> = create_policy("fcfs", 1, 1).unwrap();
+
+        ::demikernel::ensure_eq!(policy.enqueue(&mut pool, 0, requests[0]), Ok(()));
+        ::demikernel::ensure_eq!(policy.enqueue(&mut pool, 0, requests[1]), Ok(()));
+        ::demikernel::ensure_eq!(policy.enqueue(&mut pool, 0, requests[2]), Err(requests[2]));
+        ::demikernel::ensure_eq!(policy.stats().enqueued, 1);
+        ::demikernel::ensure_eq!(policy.stats().rejected, 1);
+
+        Ok(())
+    }
+
+    /// Tests if overflow policies are parsed from the command line.
+    #[test]
+    fn overflow_policy_from_str() -> Result<()> {
+        ::demikernel::ensure_eq!(OverflowPolicy::from_str("drop")?, OverflowPolicy::Drop);
+        ::demikernel::ensure_eq!(OverflowPolicy::from_str("nack")?, OverflowPolicy::Nack);
+        ::demikernel::ensure_eq!(OverflowPolicy::from_str("rst")?, OverflowPolicy::Rst);
+        ::demikernel::ensure_eq!(OverflowPolicy::from_str("block").is_err(), true);
+
+        Ok(())
+    }
+
+    /// Tests if a RST acknowledges segments that carry no ACK, and takes its sequence number from the others.
+    #[test]
+    fn rst_header() -> Result<()> {
+        let mut syn: TcpHeader = TcpHeader::new(49152, 80);
+        syn.syn = true;
+        syn.seq_num = SeqNumber::from(1000);
+        let rst: TcpHeader = Dispatcher::rst_header(&syn, 0);
+        ::demikernel::ensure_eq!((rst.src_port, rst.dst_port), (80, 49152));
+        ::demikernel::ensure_eq!((rst.rst, rst.ack), (true, true));
+        ::demikernel::ensure_eq!(rst.seq_num, SeqNumber::from(0));
+        ::demikernel::ensure_eq!(rst.ack_num, SeqNumber::from(1001));
+
+        // Both data and FIN take up sequence space.
+        let mut fin: TcpHeader = TcpHeader::new(49152, 80);
+        fin.fin = true;
+        fin.seq_num = SeqNumber::from(1000);
+        ::demikernel::ensure_eq!(Dispatcher::rst_header(&fin, 10).ack_num, SeqNumber::from(1011));
+
+        let mut ack: TcpHeader = TcpHeader::new(49152, 80);
+        ack.ack = true;
+        ack.seq_num = SeqNumber::from(1000);
+        ack.ack_num = SeqNumber::from(5000);
+        let rst: TcpHeader = Dispatcher::rst_header(&ack, 10);
+        ::demikernel::ensure_eq!((rst.rst, rst.ack), (true, false));
+        ::demikernel::ensure_eq!(rst.seq_num, SeqNumber::from(5000));
+
+        Ok(())
+    }
+
+    /// Tests if the control bits of a segment are read whatever the IP options.
+    #[test]
+    fn classify_tcp_flags() -> Result<()> {
+        let mut storage: Vec<u64> = Vec::new();
+        for nr_options in [0, 1, 10] {
+            let mut mbuf: rte_mbuf = fake_mbuf(&mut storage, 0, &tcp_frame(nr_options, TCP_FLAG_RST));
+            ::demikernel::ensure_eq!(Dispatcher::get_tcp_flags(&mut mbuf), TCP_FLAG_RST);
+            let mut mbuf: rte_mbuf = fake_mbuf(&mut storage, 0, &tcp_frame(nr_options, TCP_FLAG_FIN | TCP_FLAG_RST));
+            ::demikernel::ensure_eq!(Dispatcher::get_tcp_flags(&mut mbuf), TCP_FLAG_FIN | TCP_FLAG_RST);
+        }
+
+        // A truncated segment carries no control bits.
+        let frame: Vec<u8> = tcp_frame(0, TCP_FLAG_RST);
+        let mut mbuf: rte_mbuf = fake_mbuf(&mut storage, 0, &frame[..14 + 20 + 10]);
+        ::demikernel::ensure_eq!(Dispatcher::get_tcp_flags(&mut mbuf), 0);
+
+        Ok(())
+    }
+
+    /// Tests if dispatchers split the workers into disjoint sets that cover all of them.
+    #[test]
+    fn worker_partition() -> Result<()> {
+        for nr_dispatchers in 1..=4 {
+            for nr_workers in nr_dispatchers..=MAX_WORKERS {
+                let mut next: usize = 0;
+                for d in 0..nr_dispatchers {
+                    let range: Range<usize> = worker_range(d, nr_dispatchers, nr_workers);
+                    ::demikernel::ensure_eq!(range.start, next);
+                    // Shares differ by at most one worker.
+                    ::demikernel::ensure_eq!(range.len() >= nr_workers / nr_dispatchers, true);
+                    ::demikernel::ensure_eq!(range.len() <= (nr_workers + nr_dispatchers - 1) / nr_dispatchers, true);
+                    next = range.end;
+                }
+                ::demikernel::ensure_eq!(next, nr_workers);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Tests if a worker that only tears a connection down goes back to the idle list.
+    #[test]
+    fn worker_teardown() -> Result<()> {
+        let mut mbufs: Vec<rte_mbuf> = (0..2).map(|_| unsafe { mem::zeroed() }).collect();
+        let requests: Vec<Request> = fake_requests(&mut mbufs);
+        let (mut pool, workers) = fake_pool(1)?;
+
+        // The first request carries the FIN of the peer, the second one fails because the connection was reset.
+        for request in requests.iter() {
+            ::demikernel::ensure_eq!(pool.assign(0, *request).is_ok(), true);
+            ::demikernel::ensure_eq!(pool.has_idle(), false);
+            ::demikernel::ensure_eq!(workers[0].try_recv_request(), Some(*request));
+            Worker::finish(&workers[0], &VecDeque::new(), Completion::Done);
+
+            pool.refresh();
+            ::demikernel::ensure_eq!(pool.peek_idle(), Some(0));
+        }
+        ::demikernel::ensure_eq!(pool.completions(), (2, 0));
+
+        Ok(())
+    }
+
+    /// Tests if the headers of a segment are found after the IP options.
+    #[test]
+    fn classify_ip_options() -> Result<()> {
+        let mut storage: Vec<u64> = Vec::new();
+        let mut mbuf: rte_mbuf = fake_mbuf(&mut storage, 0, &tcp_frame(2, TCP_FLAG_FIN));
+        let pkt: *mut rte_mbuf = &mut mbuf;
+
+        let local: SocketAddr = SocketAddr::from_str("10.0.0.1:80")?;
+        let remote: SocketAddr = SocketAddr::from_str("10.0.0.2:49152")?;
+        ::demikernel::ensure_eq!(Dispatcher::get_5tuple(pkt), Some((local, remote)));
+        ::demikernel::ensure_eq!(Dispatcher::get_tcp_flags(pkt), TCP_FLAG_FIN);
+        ::demikernel::ensure_eq!(Dispatcher::get_payload_offset(pkt), Some(14 + 28 + 20));
+
+        // Options that run past the end of the frame.
+        let mut frame: Vec<u8> = tcp_frame(0, 0);
+        frame[14] = 0x4f;
+        let mut mbuf: rte_mbuf = fake_mbuf(&mut storage, 0, &frame);
+        ::demikernel::ensure_eq!(Dispatcher::get_5tuple(&mut mbuf), None);
+
+        Ok(())
+    }
+
+    /// Tests if timestamps live in the private area of the mbuf, and are left alone by the headers of the reply.
+    #[test]
+    fn timestamps_private_area() -> Result<()> {
+        let mut storage: Vec<u64> = Vec::new();
+        let mut mbuf: rte_mbuf = fake_mbuf(&mut storage, mem::size_of::<Timestamps>(), &tcp_frame(0, 0));
+        let pkt: *mut rte_mbuf = &mut mbuf;
+
+        Timestamps::stamp_rx(pkt, 42);
+        Timestamps::stamp_enqueue(pkt);
+        let timestamps: *mut Timestamps = unsafe { Timestamps::from_mbuf(pkt) }.expect("should have a private area");
+        ::demikernel::ensure_eq!(unsafe { (*timestamps).magic }, TIMESTAMPS_MAGIC);
+        ::demikernel::ensure_eq!(unsafe { (*timestamps).rx }, 42);
+        ::demikernel::ensure_eq!(unsafe { (*timestamps).enqueue } != 0, true);
+        // The whole buffer, headroom included, is left to the packet.
+        let frame: Vec<u8> = tcp_frame(0, 0);
+        let data: &[u8] = unsafe { std::slice::from_raw_parts(mbuf.buf_addr as *const u8, frame.len()) };
+        ::demikernel::ensure_eq!(data, &frame[..]);
+
+        // Without a private area, requests are simply not tracked.
+        let mut mbuf: rte_mbuf = fake_mbuf(&mut storage, 0, &tcp_frame(0, 0));
+        Timestamps::stamp_rx(&mut mbuf, 42);
+        ::demikernel::ensure_eq!(unsafe { Timestamps::from_mbuf(&mut mbuf) }.is_none(), true);
+
+        Ok(())
+    }
+}